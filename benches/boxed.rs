@@ -0,0 +1,81 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use btree::btree::{BoxedBtree, Btree};
+
+/*
+** what storing large keys boxed saves: every split, merge and borrow shifts
+** keys around inside the nodes' Vecs, a 512 byte key moves all 512 bytes and
+** a boxed one moves its pointer. the boxed tree pays for that with an
+** allocation per insert and a pointer chase per comparison, so lookups get
+** slower while inserts and deletes (which move keys) get faster
+**
+** run with cargo bench --bench boxed, it prints the time per operation of
+** both representations side by side
+*/
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Large([u64; 64]);
+
+const KEYS: u64 = 20_000;
+const DEGREE: usize = 6;
+
+// the keys in a scattered order, so inserts land all over the tree
+fn keys() -> Vec<Large> {
+    (0..KEYS)
+        .map(|i| Large([i.wrapping_mul(0x9e3779b97f4a7c15) % KEYS; 64]))
+        .collect()
+}
+
+fn per_op(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / KEYS as f64
+}
+
+fn report(what: &str, plain: Duration, boxed: Duration) {
+    println!(
+        "{:<8} plain {:>8.1} ns/op   boxed {:>8.1} ns/op",
+        what,
+        per_op(plain),
+        per_op(boxed)
+    );
+}
+
+fn main() {
+    let keys = keys();
+
+    let start = Instant::now();
+    let mut plain = Btree::new(DEGREE);
+    for key in &keys {
+        plain.insert(key.clone());
+    }
+    let plain_insert = start.elapsed();
+
+    let start = Instant::now();
+    let mut boxed = BoxedBtree::new(DEGREE);
+    for key in &keys {
+        boxed.insert(key.clone());
+    }
+    let boxed_insert = start.elapsed();
+    report("insert", plain_insert, boxed_insert);
+
+    let start = Instant::now();
+    for key in &keys {
+        black_box(plain.search(key));
+    }
+    let plain_search = start.elapsed();
+    let start = Instant::now();
+    for key in &keys {
+        black_box(boxed.contains(key));
+    }
+    report("search", plain_search, start.elapsed());
+
+    let start = Instant::now();
+    for key in &keys {
+        black_box(plain.take(key));
+    }
+    let plain_take = start.elapsed();
+    let start = Instant::now();
+    for key in &keys {
+        black_box(boxed.take(key));
+    }
+    report("take", plain_take, start.elapsed());
+}
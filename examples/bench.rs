@@ -0,0 +1,227 @@
+use btree::btree::Btree;
+use std::collections::BTreeSet;
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/*
+** rough wall clock comparison against std's BTreeSet, every operation on the
+** same keys for every degree. not a statistics harness, each cell is the best
+** of a few runs, which is enough to see which way a change moves things
+**
+**   BENCH_KEYS    keys per workload (default 20000)
+**   BENCH_RUNS    runs per cell, the fastest one is shown (default 3)
+**
+**   cargo run --release --example bench
+*/
+
+const DEGREES: [usize; 4] = [2, 8, 32, 128];
+
+// small xorshift generator so every run measures the same keys
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn sequential(n: usize) -> Vec<u64> {
+    (0..n as u64).collect()
+}
+
+fn random(n: usize) -> Vec<u64> {
+    let mut rng = Rng(0x9e3779b97f4a7c15);
+    (0..n).map(|_| rng.next()).collect()
+}
+
+// zipf with s = 1 over n distinct keys, so a few keys come up over and over
+// the ranks are scattered over the key space so the hot keys aren't neighbours
+fn zipfian(n: usize) -> Vec<u64> {
+    let mut cumulative = Vec::with_capacity(n);
+    let mut total = 0.0;
+    for rank in 1..=n {
+        total += 1.0 / rank as f64;
+        cumulative.push(total);
+    }
+    let mut rng = Rng(0xbf58476d1ce4e5b9);
+    (0..n)
+        .map(|_| {
+            let rank = cumulative.partition_point(|&c| c < rng.unit() * total);
+            (rank as u64).wrapping_mul(0x94d049bb133111eb)
+        })
+        .collect()
+}
+
+// the operations, each one gets a tree already holding the keys (but insert)
+trait Set {
+    fn name(&self) -> String;
+    fn fresh(&self) -> Self;
+    fn insert(&mut self, key: u64);
+    fn contains(&self, key: &u64) -> bool;
+    fn remove(&mut self, key: &u64);
+    fn iter_sum(&self) -> u64;
+    fn range_count(&self, from: u64, to: u64) -> usize;
+}
+
+impl Set for BTreeSet<u64> {
+    fn name(&self) -> String {
+        "std".to_string()
+    }
+
+    fn fresh(&self) -> Self {
+        BTreeSet::new()
+    }
+
+    fn insert(&mut self, key: u64) {
+        BTreeSet::insert(self, key);
+    }
+
+    fn contains(&self, key: &u64) -> bool {
+        BTreeSet::contains(self, key)
+    }
+
+    fn remove(&mut self, key: &u64) {
+        BTreeSet::remove(self, key);
+    }
+
+    fn iter_sum(&self) -> u64 {
+        self.iter().fold(0, |sum, key| sum.wrapping_add(*key))
+    }
+
+    fn range_count(&self, from: u64, to: u64) -> usize {
+        self.range(from..to).count()
+    }
+}
+
+impl Set for Btree<u64> {
+    fn name(&self) -> String {
+        format!("t={}", self.degree())
+    }
+
+    fn fresh(&self) -> Self {
+        Btree::new(self.degree())
+    }
+
+    fn insert(&mut self, key: u64) {
+        Btree::insert(self, key);
+    }
+
+    fn contains(&self, key: &u64) -> bool {
+        self.search(key)
+    }
+
+    fn remove(&mut self, key: &u64) {
+        self.delete(key);
+    }
+
+    fn iter_sum(&self) -> u64 {
+        self.iter().fold(0, |sum, key| sum.wrapping_add(*key))
+    }
+
+    fn range_count(&self, from: u64, to: u64) -> usize {
+        self.range(from..to).count()
+    }
+}
+
+const OPERATIONS: [&str; 5] = ["insert", "search", "delete", "iterate", "range"];
+
+// the best of runs timings of every operation on one set
+fn measure<S: Set>(empty: &S, keys: &[u64], runs: usize) -> [Duration; 5] {
+    let mut sorted = keys.to_vec();
+    sorted.sort_unstable();
+    let mut best = [Duration::MAX; 5];
+    for _ in 0..runs {
+        let mut set = empty.fresh();
+        let start = Instant::now();
+        for &key in keys {
+            set.insert(key);
+        }
+        let insert = start.elapsed();
+
+        let start = Instant::now();
+        for key in keys {
+            black_box(set.contains(key));
+        }
+        let search = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..10 {
+            black_box(set.iter_sum());
+        }
+        let iterate = start.elapsed();
+
+        // a hundred scans over roughly a hundredth of the keys each
+        let start = Instant::now();
+        let step = (sorted.len() / 100).max(1);
+        for window in sorted.windows(step + 1).step_by(step) {
+            black_box(set.range_count(window[0], window[step]));
+        }
+        let range = start.elapsed();
+
+        let start = Instant::now();
+        for key in keys {
+            set.remove(key);
+        }
+        let delete = start.elapsed();
+
+        let timings = [insert, search, delete, iterate, range];
+        for (best, timing) in best.iter_mut().zip(timings) {
+            *best = (*best).min(timing);
+        }
+    }
+    best
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn main() {
+    let n = env_usize("BENCH_KEYS", 20_000);
+    let runs = env_usize("BENCH_RUNS", 3).max(1);
+    let workloads: [(&str, Vec<u64>); 3] = [
+        ("sequential", sequential(n)),
+        ("random", random(n)),
+        ("zipfian", zipfian(n)),
+    ];
+
+    println!(
+        "{} keys per workload, best of {} runs, times in ms\n",
+        n, runs
+    );
+    for (workload, keys) in &workloads {
+        let mut columns = vec![(
+            BTreeSet::new().name(),
+            measure(&BTreeSet::new(), keys, runs),
+        )];
+        for degree in DEGREES {
+            let tree = Btree::new(degree);
+            columns.push((tree.name(), measure(&tree, keys, runs)));
+        }
+
+        print!("{:<12}", workload);
+        for (name, _) in &columns {
+            print!("{:>10}", name);
+        }
+        println!();
+        for (op, operation) in OPERATIONS.iter().enumerate() {
+            print!("  {:<10}", operation);
+            for (_, timings) in &columns {
+                print!("{:>10.3}", timings[op].as_secs_f64() * 1000.0);
+            }
+            println!();
+        }
+        println!();
+    }
+}
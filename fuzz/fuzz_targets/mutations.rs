@@ -0,0 +1,54 @@
+#![no_main]
+
+use btree::btree::Btree;
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeSet;
+
+/*
+** the input is a program for the tree: the first byte picks the degree
+** (2 to 9), then every two bytes are one operation. the high bits of the
+** first byte say which, the second byte is the key, taken from a domain of
+** 64 keys so inserts collide and deletes hit, which is what walks the
+** deletion code through its merges and borrows
+**
+**   0..=3  insert key      (twice as likely as the rest)
+**   4..=5  delete key
+**   6      search key
+**   7      range between key and the low bits of the first byte
+**
+** the low bits of the first byte of every insert or delete also ask for a
+** validate now and then, validating after every step would slow the fuzzer
+** down to a crawl on large trees
+**
+** run from the repository root with: cargo fuzz run mutations
+** corpus/mutations holds seeds for the known tricky deletion sequences
+*/
+const KEYS: u8 = 64;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&degree, program)) = data.split_first() else {
+        return;
+    };
+    let mut tree = Btree::new(2 + usize::from(degree % 8));
+    let mut model = BTreeSet::new();
+
+    for op in program.chunks_exact(2) {
+        let (code, key) = (op[0], op[1] % KEYS);
+        match code >> 5 {
+            0..=3 => assert_eq!(tree.insert(key), model.insert(key)),
+            4..=5 => assert_eq!(tree.delete(&key), model.remove(&key)),
+            6 => assert_eq!(tree.search(&key), model.contains(&key)),
+            _ => {
+                let (start, end) = (key.min(code % KEYS), key.max(code % KEYS));
+                assert!(tree.range(start..end).eq(model.range(start..end)));
+                assert!(tree.range(start..=end).rev().eq(model.range(start..=end).rev()));
+            }
+        }
+        if code & 0x1f == 0 {
+            assert_eq!(tree.validate(), Ok(()));
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+    assert_eq!(tree.validate(), Ok(()));
+    assert!(tree.iter().eq(model.iter()));
+});
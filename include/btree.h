@@ -0,0 +1,24 @@
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * a b-tree of int64_t keys, see src/ffi.rs for what every call returns for
+ * a null handle. a handle from btree_new goes back to btree_free exactly once
+ */
+typedef struct BtreeHandle BtreeHandle;
+
+BtreeHandle *btree_new(uintptr_t degree);
+
+bool btree_insert(BtreeHandle *handle, int64_t key);
+
+bool btree_delete(BtreeHandle *handle, int64_t key);
+
+bool btree_contains(const BtreeHandle *handle, int64_t key);
+
+uintptr_t btree_len(const BtreeHandle *handle);
+
+uintptr_t btree_iter_collect(const BtreeHandle *handle, int64_t *out, uintptr_t cap);
+
+void btree_free(BtreeHandle *handle);
@@ -0,0 +1,26 @@
+use btree::repl::Session;
+use std::io;
+
+// interactive shell over a tree of integers, the minimum degree can be given
+// as the only argument (3 if it isn't). type help for the commands
+fn main() {
+    let degree = match std::env::args().nth(1).map(|arg| arg.parse()) {
+        None => 3,
+        Some(Ok(degree)) => degree,
+        Some(Err(_)) => {
+            eprintln!("usage: repl [degree]");
+            std::process::exit(2);
+        }
+    };
+    let mut session = match Session::new(degree) {
+        Ok(session) => session,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    };
+    if let Err(error) = session.run(io::stdin().lock(), io::stdout().lock(), true) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
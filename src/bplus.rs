@@ -0,0 +1,702 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+use crate::compare::{Compare, Natural};
+
+mod iter;
+
+pub use iter::{Iter, Range};
+
+/*
+** b+ tree: every key lives in a leaf, internal nodes only hold separators
+** used for routing, so a scan never has to go back up into internal nodes
+** to read keys
+**
+** the size rules are the same as for the btree with minimum degree t:
+** every node except the root holds between t - 1 and 2t - 1 keys (separators
+** for internal nodes) and an internal node with k separators has k + 1 children
+**
+** separator i sits between child i and child i + 1:
+** every key in child i is less than it, every key in child i + 1 is greater or equal
+** a separator is a copy of a key (hence T: Clone for anything that modifies
+** the tree), and deletion is relaxed: removing a key from a leaf leaves any
+** separator copying it in place, it keeps routing correctly
+**
+** nodes live in an arena and refer to each other by index, freed slots are
+** recycled by later splits
+**
+** the leaves form a doubly linked list in key order, so once a scan has
+** found its first leaf it follows the links and never touches an internal
+** node again. the links are arena indexes too, not pointers: a leaf can be
+** referenced from its parent and both neighbours without any unsafe code
+** and nothing dangles when slots get reused
+*/
+pub(crate) enum Node<T> {
+    Leaf {
+        keys: Vec<T>,
+        prev: Option<usize>,
+        next: Option<usize>,
+    },
+    Internal {
+        keys: Vec<T>,
+        children: Vec<usize>,
+    },
+}
+
+impl<T> Node<T> {
+    pub(crate) fn keys(&self) -> &Vec<T> {
+        match self {
+            Node::Leaf { keys, .. } | Node::Internal { keys, .. } => keys,
+        }
+    }
+
+    fn keys_mut(&mut self) -> &mut Vec<T> {
+        match self {
+            Node::Leaf { keys, .. } | Node::Internal { keys, .. } => keys,
+        }
+    }
+
+    // the children of an internal node, None for a leaf
+    pub(crate) fn children(&self) -> Option<&Vec<usize>> {
+        match self {
+            Node::Leaf { .. } => None,
+            Node::Internal { children, .. } => Some(children),
+        }
+    }
+
+    // the leaves right before and after this one, None at the ends (and for internal nodes)
+    pub(crate) fn prev_leaf(&self) -> Option<usize> {
+        match self {
+            Node::Leaf { prev, .. } => *prev,
+            Node::Internal { .. } => None,
+        }
+    }
+
+    pub(crate) fn next_leaf(&self) -> Option<usize> {
+        match self {
+            Node::Leaf { next, .. } => *next,
+            Node::Internal { .. } => None,
+        }
+    }
+
+    fn new_leaf(keys: Vec<T>) -> Self {
+        Node::Leaf {
+            keys,
+            prev: None,
+            next: None,
+        }
+    }
+
+    fn internal_parts(&mut self) -> (&mut Vec<T>, &mut Vec<usize>) {
+        match self {
+            Node::Internal { keys, children } => (keys, children),
+            Node::Leaf { .. } => unreachable!("leaves have no children to rebalance"),
+        }
+    }
+
+    fn is_full(&self, degree: usize) -> bool {
+        self.keys().len() == 2 * degree - 1
+    }
+}
+
+// the child of an internal node the probe leads to
+// keys equal to a separator live to its right
+fn child_index<T, F: FnMut(&T) -> Ordering>(keys: &[T], probe: &mut F) -> usize {
+    match keys.binary_search_by(probe) {
+        Ok(i) => i + 1,
+        Err(i) => i,
+    }
+}
+
+pub struct BPlusTree<T, C = Natural> {
+    nodes: Vec<Node<T>>,
+    // slots of nodes that were merged away, reused before the arena grows
+    free: Vec<usize>,
+    root: Option<usize>,
+    len: usize,
+    degree: usize,
+    cmp: C,
+}
+
+impl<T: Ord> BPlusTree<T> {
+    pub fn new(degree: usize) -> Self {
+        BPlusTree::with_comparator(degree, Natural)
+    }
+}
+
+impl<T, C: Compare<T>> BPlusTree<T, C> {
+    // create a tree ordered by cmp instead of the keys' natural ordering
+    pub fn with_comparator(degree: usize, cmp: C) -> Self {
+        assert!(degree >= 2, "degree must be atleast 2!");
+        BPlusTree {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            len: 0,
+            degree,
+            cmp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    // number of levels, counting the leaves (0 for an empty tree)
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut node = self.root;
+        while let Some(current) = node {
+            height += 1;
+            node = self.nodes[current].children().map(|children| children[0]);
+        }
+        height
+    }
+
+    // search for a key in the tree
+    pub fn search<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // the stored key equal to key, if any
+    // always a walk from the root down to a leaf, separators are never returned
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut probe = |stored: &T| self.cmp.compare(stored.borrow(), key);
+        let mut node = self.root?;
+        loop {
+            match &self.nodes[node] {
+                Node::Leaf { keys, .. } => {
+                    return keys.binary_search_by(&mut probe).ok().map(|i| &keys[i]);
+                }
+                Node::Internal { keys, children } => {
+                    node = children[child_index(keys, &mut probe)];
+                }
+            }
+        }
+    }
+
+    // iterate over all keys in ascending order
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(&self.nodes, self.root)
+    }
+
+    // iterate over the keys inside the range in ascending order
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let compare = |key: &T, bound: &Q| self.cmp.compare(key.borrow(), bound);
+        let after_start = |key: &T| match range.start_bound() {
+            Bound::Included(start) => compare(key, start) != Ordering::Less,
+            Bound::Excluded(start) => compare(key, start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        };
+        let before_end = |key: &T| match range.end_bound() {
+            Bound::Included(end) => compare(key, end) != Ordering::Greater,
+            Bound::Excluded(end) => compare(key, end) == Ordering::Less,
+            Bound::Unbounded => true,
+        };
+        Range::new(&self.nodes, self.root, after_start, before_end)
+    }
+
+    // check that the tree satisfies every b+ tree invariant
+    // returns a description of the first violation found
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(root) = self.root else {
+            return if self.len == 0 {
+                Ok(())
+            } else {
+                Err(format!("empty tree claims {} keys", self.len))
+            };
+        };
+        let mut reachable = 0;
+        let mut leaves = Vec::new();
+        let count = self.validate_node(root, 0, None, None, &mut reachable, &mut leaves)?;
+        if count != self.len {
+            return Err(format!("tree holds {} keys but len is {}", count, self.len));
+        }
+        if reachable + self.free.len() != self.nodes.len() {
+            return Err(format!(
+                "{} nodes reachable and {} free, but the arena has {}",
+                reachable,
+                self.free.len(),
+                self.nodes.len()
+            ));
+        }
+
+        if let Some(&(_, depth)) = leaves.iter().find(|(_, depth)| *depth != leaves[0].1) {
+            return Err(format!(
+                "leaf at depth {} but other leaves are at depth {}",
+                depth, leaves[0].1
+            ));
+        }
+
+        // the leaf chain has to list the leaves in the same order as the tree does
+        for (i, &(leaf, _)) in leaves.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| leaves[i].0);
+            let next = leaves.get(i + 1).map(|&(leaf, _)| leaf);
+            let node = &self.nodes[leaf];
+            if node.prev_leaf() != prev || node.next_leaf() != next {
+                return Err(format!(
+                    "leaf {} of {} is linked to {:?} and {:?} instead of {:?} and {:?}",
+                    i,
+                    leaves.len(),
+                    node.prev_leaf(),
+                    node.next_leaf(),
+                    prev,
+                    next
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // checks the subtree at node and returns the number of keys in its leaves
+    // keys have to satisfy lower <= key < upper
+    // the leaves are collected in order, along with their depth
+    fn validate_node(
+        &self,
+        node: usize,
+        depth: usize,
+        lower: Option<&T>,
+        upper: Option<&T>,
+        reachable: &mut usize,
+        leaves: &mut Vec<(usize, usize)>,
+    ) -> Result<usize, String> {
+        *reachable += 1;
+        let degree = self.degree;
+        let keys = self.nodes[node].keys();
+        if keys.len() > 2 * degree - 1 {
+            return Err(format!(
+                "node at depth {} has {} keys, at most {} allowed",
+                depth,
+                keys.len(),
+                2 * degree - 1
+            ));
+        }
+        if depth > 0 && keys.len() < degree - 1 {
+            return Err(format!(
+                "node at depth {} has {} keys, at least {} required",
+                depth,
+                keys.len(),
+                degree - 1
+            ));
+        }
+        if keys.is_empty() {
+            return Err(format!("node at depth {} has no keys", depth));
+        }
+        if keys
+            .windows(2)
+            .any(|pair| self.cmp.compare(&pair[0], &pair[1]) != Ordering::Less)
+        {
+            return Err(format!("keys at depth {} are not strictly sorted", depth));
+        }
+        if lower.is_some_and(|lower| self.cmp.compare(&keys[0], lower) == Ordering::Less)
+            || upper.is_some_and(|upper| {
+                self.cmp.compare(&keys[keys.len() - 1], upper) != Ordering::Less
+            })
+        {
+            return Err(format!(
+                "keys at depth {} fall outside their parent's separators",
+                depth
+            ));
+        }
+
+        let Some(children) = self.nodes[node].children() else {
+            leaves.push((node, depth));
+            return Ok(keys.len());
+        };
+        if children.len() != keys.len() + 1 {
+            return Err(format!(
+                "internal node at depth {} has {} keys but {} children",
+                depth,
+                keys.len(),
+                children.len()
+            ));
+        }
+        let mut count = 0;
+        for (i, &child) in children.iter().enumerate() {
+            let child_lower = if i == 0 { lower } else { Some(&keys[i - 1]) };
+            let child_upper = keys.get(i).or(upper);
+            count += self.validate_node(
+                child,
+                depth + 1,
+                child_lower,
+                child_upper,
+                reachable,
+                leaves,
+            )?;
+        }
+        Ok(count)
+    }
+
+    fn key_buffer(&self) -> Vec<T> {
+        Vec::with_capacity(2 * self.degree - 1)
+    }
+
+    fn child_buffer(&self) -> Vec<usize> {
+        Vec::with_capacity(2 * self.degree)
+    }
+
+    // put a node into the arena, in a freed slot if there is one
+    fn alloc(&mut self, node: Node<T>) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                slot
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // hand a node's slot back to the arena, returning what it held
+    fn release(&mut self, slot: usize) -> Node<T> {
+        self.free.push(slot);
+        std::mem::replace(&mut self.nodes[slot], Node::new_leaf(Vec::new()))
+    }
+
+    // two different nodes borrowed at the same time
+    fn pair_mut(&mut self, a: usize, b: usize) -> (&mut Node<T>, &mut Node<T>) {
+        debug_assert_ne!(a, b);
+        if a < b {
+            let (left, right) = self.nodes.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = self.nodes.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+
+    // make right follow left in the leaf chain, either one can be missing at the ends
+    fn link(&mut self, left: Option<usize>, right: Option<usize>) {
+        if let Some(Node::Leaf { next, .. }) = left.map(|left| &mut self.nodes[left]) {
+            *next = right;
+        }
+        if let Some(Node::Leaf { prev, .. }) = right.map(|right| &mut self.nodes[right]) {
+            *prev = left;
+        }
+    }
+
+    fn child(&self, parent: usize, i: usize) -> usize {
+        self.nodes[parent].children().unwrap()[i]
+    }
+}
+
+impl<T: Clone, C: Compare<T>> BPlusTree<T, C> {
+    // insert a key into the tree
+    // returns false if an equal key is already stored (the stored key is kept)
+    pub fn insert(&mut self, key: T) -> bool {
+        let root = match self.root {
+            None => {
+                let mut keys = self.key_buffer();
+                keys.push(key);
+                let leaf = self.alloc(Node::new_leaf(keys));
+                self.root = Some(leaf);
+                self.len = 1;
+                return true;
+            }
+            Some(root) => root,
+        };
+
+        // a full root is split under a new root, the only way the tree grows taller
+        if self.nodes[root].is_full(self.degree) {
+            let mut children = self.child_buffer();
+            children.push(root);
+            let keys = self.key_buffer();
+            let new_root = self.alloc(Node::Internal { keys, children });
+            self.split_child(new_root, 0);
+            self.root = Some(new_root);
+        }
+
+        // going down, every full child is split before we enter it, so the leaf
+        // we end up in has room for the key
+        let mut node = self.root.unwrap();
+        loop {
+            let mut probe = |stored: &T| self.cmp.compare(stored, &key);
+            match &self.nodes[node] {
+                Node::Leaf { keys, .. } => {
+                    return match keys.binary_search_by(probe) {
+                        Ok(_) => false,
+                        Err(i) => {
+                            self.nodes[node].keys_mut().insert(i, key);
+                            self.len += 1;
+                            true
+                        }
+                    };
+                }
+                Node::Internal { keys, children } => {
+                    let mut i = child_index(keys, &mut probe);
+                    if self.nodes[children[i]].is_full(self.degree) {
+                        self.split_child(node, i);
+                        // the new separator decides which half the key goes to
+                        if self.cmp.compare(&key, &self.nodes[node].keys()[i]) != Ordering::Less {
+                            i += 1;
+                        }
+                    }
+                    node = self.child(node, i);
+                }
+            }
+        }
+    }
+
+    /*
+     ** split the full child i of parent
+     ** a leaf keeps its first t - 1 keys, the other t move to the new right leaf
+     ** and a copy of the first of them becomes the separator
+     ** an internal node splits like a btree node: t - 1 separators stay, the
+     ** median moves up and the last t - 1 go right, along with the last t children
+     */
+    fn split_child(&mut self, parent: usize, i: usize) {
+        let degree = self.degree;
+        let child = self.child(parent, i);
+        let mut right_keys = self.key_buffer();
+        let mut right_children = self.child_buffer();
+        let (separator, sibling) = match &mut self.nodes[child] {
+            Node::Leaf { keys, .. } => {
+                right_keys.extend(keys.drain(degree - 1..));
+                (right_keys[0].clone(), Node::new_leaf(right_keys))
+            }
+            Node::Internal { keys, children } => {
+                right_keys.extend(keys.drain(degree..));
+                right_children.extend(children.drain(degree..));
+                let median = keys.pop().unwrap();
+                let sibling = Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                };
+                (median, sibling)
+            }
+        };
+        let sibling = self.alloc(sibling);
+        if self.nodes[sibling].children().is_none() {
+            // the new leaf goes into the chain right after the one it came from
+            let after = self.nodes[child].next_leaf();
+            self.link(Some(child), Some(sibling));
+            self.link(Some(sibling), after);
+        }
+        let (keys, children) = self.nodes[parent].internal_parts();
+        keys.insert(i, separator);
+        children.insert(i + 1, sibling);
+    }
+
+    // delete a key from the tree
+    pub fn delete<Q: ?Sized>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.take(key).is_some()
+    }
+
+    // remove the key equal to key and hand the stored one back
+    // single pass down: every child gets at least t keys before we enter it
+    pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut node = self.root?;
+        let removed = loop {
+            let mut probe = |stored: &T| self.cmp.compare(stored.borrow(), key);
+            match &self.nodes[node] {
+                Node::Leaf { keys, .. } => {
+                    break match keys.binary_search_by(probe) {
+                        Ok(i) => Some(self.nodes[node].keys_mut().remove(i)),
+                        Err(_) => None,
+                    };
+                }
+                Node::Internal { keys, .. } => {
+                    let i = child_index(keys, &mut probe);
+                    let i = self.ensure_child_has_spare(node, i);
+                    node = self.child(node, i);
+                }
+            }
+        };
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        self.shrink_root();
+        removed
+    }
+
+    // make sure the child at idx has at least t keys so we can safely descend into it
+    // returns the index of that child afterwards (it moves left when merged with its left sibling)
+    fn ensure_child_has_spare(&mut self, parent: usize, idx: usize) -> usize {
+        let degree = self.degree;
+        let children = self.nodes[parent].children().unwrap();
+        let count = |i: usize| self.nodes[children[i]].keys().len();
+        if count(idx) >= degree {
+            idx
+        } else if idx > 0 && count(idx - 1) >= degree {
+            self.borrow_from_left_sibling(parent, idx);
+            idx
+        } else if idx + 1 < children.len() && count(idx + 1) >= degree {
+            self.borrow_from_right_sibling(parent, idx);
+            idx
+        } else if idx > 0 {
+            self.merge_children(parent, idx - 1);
+            idx - 1
+        } else {
+            self.merge_children(parent, idx);
+            idx
+        }
+    }
+
+    // move one key from the left sibling into the child at idx
+    fn borrow_from_left_sibling(&mut self, parent: usize, idx: usize) {
+        let (sibling, child) = (self.child(parent, idx - 1), self.child(parent, idx));
+        let (from, to) = self.pair_mut(sibling, child);
+        let separator = match (from, to) {
+            // the sibling's last key moves over and becomes the new separator
+            (Node::Leaf { keys: from, .. }, Node::Leaf { keys: to, .. }) => {
+                to.insert(0, from.pop().unwrap());
+                to[0].clone()
+            }
+            // rotate through the parent, the separator comes down in front of
+            // the child's keys, together with the sibling's last child
+            (
+                Node::Internal {
+                    keys: from,
+                    children: from_children,
+                },
+                Node::Internal {
+                    children: to_children,
+                    ..
+                },
+            ) => {
+                to_children.insert(0, from_children.pop().unwrap());
+                let up = from.pop().unwrap();
+                let down = std::mem::replace(&mut self.nodes[parent].keys_mut()[idx - 1], up);
+                self.nodes[child].keys_mut().insert(0, down);
+                return;
+            }
+            _ => unreachable!("siblings are always at the same level"),
+        };
+        self.nodes[parent].keys_mut()[idx - 1] = separator;
+    }
+
+    // move one key from the right sibling into the child at idx
+    fn borrow_from_right_sibling(&mut self, parent: usize, idx: usize) {
+        let (child, sibling) = (self.child(parent, idx), self.child(parent, idx + 1));
+        let (to, from) = self.pair_mut(child, sibling);
+        let separator = match (to, from) {
+            // the sibling's first key moves over, its new first key is the separator
+            (Node::Leaf { keys: to, .. }, Node::Leaf { keys: from, .. }) => {
+                to.push(from.remove(0));
+                from[0].clone()
+            }
+            (
+                Node::Internal {
+                    children: to_children,
+                    ..
+                },
+                Node::Internal {
+                    keys: from,
+                    children: from_children,
+                },
+            ) => {
+                to_children.push(from_children.remove(0));
+                let up = from.remove(0);
+                let down = std::mem::replace(&mut self.nodes[parent].keys_mut()[idx], up);
+                self.nodes[child].keys_mut().push(down);
+                return;
+            }
+            _ => unreachable!("siblings are always at the same level"),
+        };
+        self.nodes[parent].keys_mut()[idx] = separator;
+    }
+
+    // merge the children at idx and idx + 1 into the left one
+    // leaves simply concatenate (the separator between them is dropped), internal
+    // nodes pull the separator down between their keys like in a btree
+    fn merge_children(&mut self, parent: usize, idx: usize) {
+        let (keys, children) = self.nodes[parent].internal_parts();
+        let separator = keys.remove(idx);
+        let right = children.remove(idx + 1);
+        let left = children[idx];
+        let right = self.release(right);
+        match (&mut self.nodes[left], right) {
+            (
+                Node::Leaf {
+                    keys: left_keys, ..
+                },
+                Node::Leaf {
+                    keys: right_keys,
+                    next: after,
+                    ..
+                },
+            ) => {
+                left_keys.extend(right_keys);
+                // the right leaf drops out of the chain
+                self.link(Some(left), after);
+            }
+            (
+                Node::Internal {
+                    keys: left_keys,
+                    children: left_children,
+                },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                left_keys.push(separator);
+                left_keys.extend(right_keys);
+                left_children.extend(right_children);
+            }
+            _ => unreachable!("siblings are always at the same level"),
+        }
+    }
+
+    // an empty internal root is replaced by its only child, an empty leaf root
+    // means the tree is empty again
+    fn shrink_root(&mut self) {
+        let Some(root) = self.root else {
+            return;
+        };
+        if !self.nodes[root].keys().is_empty() {
+            return;
+        }
+        match &self.nodes[root] {
+            Node::Leaf { .. } => {
+                self.root = None;
+                self.nodes.clear();
+                self.free.clear();
+            }
+            Node::Internal { children, .. } => {
+                self.root = Some(children[0]);
+                self.release(root);
+            }
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a BPlusTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        Iter::new(&self.nodes, self.root)
+    }
+}
@@ -0,0 +1,215 @@
+use super::Node;
+
+/*
+** a position on a key of a leaf: (leaf, index of the key in it)
+** moving past either end of a leaf follows the leaf chain to its neighbour,
+** so after the initial seek no internal node is ever looked at again
+** None means the position is past the end (or before the start)
+*/
+struct Path<'a, T> {
+    nodes: &'a [Node<T>],
+    position: Option<(usize, usize)>,
+}
+
+impl<'a, T> Path<'a, T> {
+    fn empty(nodes: &'a [Node<T>]) -> Self {
+        Path {
+            nodes,
+            position: None,
+        }
+    }
+
+    // the key at this position
+    fn current(&self) -> Option<&'a T> {
+        let nodes = self.nodes;
+        let (leaf, i) = self.position?;
+        Some(&nodes[leaf].keys()[i])
+    }
+
+    fn same_position(&self, other: &Path<'a, T>) -> bool {
+        self.position.is_some() && self.position == other.position
+    }
+
+    // move to the next key (or past the end)
+    fn next(&mut self) {
+        let Some((leaf, i)) = self.position else {
+            return;
+        };
+        self.position = if i + 1 < self.nodes[leaf].keys().len() {
+            Some((leaf, i + 1))
+        } else {
+            // leaves are never empty, the next one starts at key 0
+            self.nodes[leaf].next_leaf().map(|next| (next, 0))
+        };
+    }
+
+    // move to the previous key (or before the start)
+    fn prev(&mut self) {
+        let Some((leaf, i)) = self.position else {
+            return;
+        };
+        self.position = if i > 0 {
+            Some((leaf, i - 1))
+        } else {
+            let nodes = self.nodes;
+            nodes[leaf]
+                .prev_leaf()
+                .map(|prev| (prev, nodes[prev].keys().len() - 1))
+        };
+    }
+
+    // the leaf a seek ends up in, picking children with choose
+    fn leaf_where<F: FnMut(&[T]) -> usize>(
+        nodes: &'a [Node<T>],
+        mut node: usize,
+        mut choose: F,
+    ) -> usize {
+        while let Some(children) = nodes[node].children() {
+            node = children[choose(nodes[node].keys())];
+        }
+        node
+    }
+
+    // position at the first key for which after_start is true
+    // after_start has to be false for a prefix of the keys and true for the rest
+    fn first_where<F: FnMut(&T) -> bool>(
+        nodes: &'a [Node<T>],
+        root: Option<usize>,
+        mut after_start: F,
+    ) -> Self {
+        let mut path = Path::empty(nodes);
+        let Some(root) = root else {
+            return path;
+        };
+        // keys right of a separator that fails are still worth checking,
+        // so go into the child left of the first separator that passes
+        let leaf = Path::leaf_where(nodes, root, |keys| {
+            keys.partition_point(|key| !after_start(key))
+        });
+        let keys = nodes[leaf].keys();
+        let i = keys.partition_point(|key| !after_start(key));
+        path.position = Some((leaf, i.min(keys.len() - 1)));
+        if i == keys.len() {
+            // nothing in this leaf, the answer is the next leaf's first key
+            path.next();
+        }
+        path
+    }
+
+    // position at the last key for which before_end is true
+    // before_end has to be true for a prefix of the keys and false for the rest
+    fn last_where<F: FnMut(&T) -> bool>(
+        nodes: &'a [Node<T>],
+        root: Option<usize>,
+        mut before_end: F,
+    ) -> Self {
+        let mut path = Path::empty(nodes);
+        let Some(root) = root else {
+            return path;
+        };
+        let leaf = Path::leaf_where(nodes, root, |keys| {
+            keys.partition_point(|key| before_end(key))
+        });
+        let i = nodes[leaf].keys().partition_point(|key| before_end(key));
+        path.position = Some((leaf, i.saturating_sub(1)));
+        if i == 0 {
+            // nothing in this leaf, the answer is the previous leaf's last key
+            path.prev();
+        }
+        path
+    }
+}
+
+/*
+** double ended iterator over the keys between two positions (both inclusive)
+** front and back walk towards each other, once they meet the range is done
+*/
+pub struct Range<'a, T> {
+    front: Path<'a, T>,
+    back: Path<'a, T>,
+}
+
+impl<'a, T> Range<'a, T> {
+    // keys for which after_start and before_end are both true
+    pub(crate) fn new<S, E>(
+        nodes: &'a [Node<T>],
+        root: Option<usize>,
+        after_start: S,
+        mut before_end: E,
+    ) -> Self
+    where
+        S: FnMut(&T) -> bool,
+        E: FnMut(&T) -> bool,
+    {
+        let front = Path::first_where(nodes, root, after_start);
+        match front.current() {
+            // the first key after the start is already past the end: empty range
+            Some(first) if before_end(first) => Range {
+                front,
+                back: Path::last_where(nodes, root, before_end),
+            },
+            _ => Range {
+                front: Path::empty(nodes),
+                back: Path::empty(nodes),
+            },
+        }
+    }
+
+    fn finish(&mut self) {
+        self.front.position = None;
+        self.back.position = None;
+    }
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let key = self.front.current()?;
+        if self.front.same_position(&self.back) {
+            self.finish();
+        } else {
+            self.front.next();
+        }
+        Some(key)
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let key = self.back.current()?;
+        if self.back.same_position(&self.front) {
+            self.finish();
+        } else {
+            self.back.prev();
+        }
+        Some(key)
+    }
+}
+
+// in-order iterator over all keys of the tree
+pub struct Iter<'a, T> {
+    range: Range<'a, T>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    pub(crate) fn new(nodes: &'a [Node<T>], root: Option<usize>) -> Self {
+        Iter {
+            range: Range::new(nodes, root, |_| true, |_| true),
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.range.next()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.range.next_back()
+    }
+}
@@ -1,4 +1,80 @@
-use std::fmt::Debug;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::ops::{Bound, ControlFlow, RangeBounds};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::compare::{ByKey, Compare, Desc, Natural, TotalOrder};
+use crate::error::{InvalidDegree, InvalidKey, InvalidQuantile, NotSorted};
+
+mod batch;
+mod bounded;
+mod boxed;
+mod bulk;
+mod change;
+#[cfg(feature = "check_ord")]
+mod check_ord;
+mod compressed;
+mod cursor;
+mod diff;
+mod encoded;
+mod explain;
+mod finger;
+mod interned;
+mod iter;
+mod join;
+mod key_check;
+mod lazy;
+mod lines;
+mod map;
+mod merge;
+mod metrics;
+mod observer;
+mod ops;
+mod packed;
+mod page;
+mod print;
+#[cfg(feature = "rand")]
+mod sample;
+mod shared;
+mod trace;
+mod ttl;
+mod view;
+mod visit;
+
+pub use batch::{BatchSummary, Op};
+pub use bounded::{BoundedBtree, EvictPolicy, Insertion};
+pub use boxed::{BoxedBtree, BoxedIter};
+pub use change::Change;
+use change::{OnChange, notify};
+pub use compressed::{CompressKey, CompressedBtree, CompressedIter};
+pub use cursor::{Cursor, CursorMut};
+pub use diff::{Diff, DiffEntry};
+pub use explain::{SearchExplanation, SearchLevel};
+pub use finger::Finger;
+pub use interned::{InternedBtree, Interner};
+use iter::Path;
+use iter::RangeMut;
+pub use iter::{Chunks, Iter, NodeSlices, Range};
+pub use join::{JoinItem, MergeJoin};
+use key_check::{KeyCheck, admit};
+pub use lazy::LazyBtree;
+pub use map::{BtreeMap, ValueMut};
+pub use merge::{MergeIter, merge_iter};
+pub use metrics::Metrics;
+use metrics::{Counter, Counters, observe, trace_event, trace_span};
+pub use observer::TreeEvent;
+pub use packed::{PackKey, PackedBtree, PackedIter};
+pub use page::PageToken;
+pub use print::PrintOptions;
+pub use shared::SharedBtree;
+pub use trace::TraceStep;
+use trace::{NoTrace, Tracer};
+pub use ttl::TtlMap;
+pub use view::BtreeView;
+pub use visit::{NodeInfo, Order, PathStep, Visit};
 /*
 ** every btree has minimum degree where degree >= 2
 ** every node except the root must contain at least degree - 1 keys
@@ -8,61 +84,318 @@ use std::fmt::Debug;
 ** all keys within the node are stored in (ascending) order Ex. 1, 2, 3, 4,...
 ** for any key "k" in the node, all keys in the left subtree are less than "k",
 ** and all keys in the right subtree is greater than "k"
+** "ascending", "less" and "greater" are all decided by the tree's comparator
+** (the natural Ord of the keys unless the tree was built with another one)
+** and every key is stored at most once: keys comparing equal collide
+**
+** a comparator that panics (a user's closure, or a key's Ord) leaves a tree
+** that still validates, with every key it held: a split, merge or borrow is
+** over before the next comparison, subtree sizes only change once a key is
+** known to be inserted or removed, and the bulk paths compare the keys where
+** they are before any are drained out of the tree
 */
 
-#[derive(Debug, Clone)]
-pub struct BtreeNode<T: Ord + Clone + Debug> {
-    keys: Vec<T>,
-    children: Vec<Box<BtreeNode<T>>>,
-    is_leaf: bool,
-    degree: usize,
+/*
+** leaves and internal nodes are different variants: leaves are the majority
+** of the nodes and don't need a children vector at all, and there's no way to
+** reach into the children of a leaf by accident
+**
+** the degree isn't stored in the nodes, it lives in the tree and is passed
+** down to every method that needs it
+**
+** internal nodes keep the number of keys in their subtree (a leaf's is just
+** its key count), which is what turns "the k-th key" or "how many keys come
+** before this one" into a single descent. every insert and removal adjusts it
+** on the way through, splits, merges and borrows move it between siblings
+*/
+#[derive(Clone)]
+pub(crate) enum BtreeNode<T> {
+    Leaf {
+        keys: Vec<T>,
+    },
+    Internal {
+        keys: Vec<T>,
+        children: Vec<BtreeNode<T>>,
+        len: usize,
+    },
 }
 
-impl<T: Ord + Clone + Debug> BtreeNode<T> {
-    fn new(degree: usize, is_leaf: bool) -> Self {
-        assert!(degree >= 2, "degree must be getter than 2");
-        BtreeNode {
-            keys: Vec::new(),
-            children: Vec::new(),
-            is_leaf,
-            degree,
+// a node is nothing more than its key (and children) vectors plus the count
+const _: () =
+    assert!(size_of::<BtreeNode<u64>>() == 2 * size_of::<Vec<u64>>() + size_of::<usize>());
+
+impl<T> BtreeNode<T> {
+    // a node never holds more than 2t - 1 keys and 2t children, so the vectors
+    // are allocated at full size once and never have to grow (or move) later
+    fn key_buffer(degree: usize) -> Vec<T> {
+        Vec::with_capacity(2 * degree - 1)
+    }
+
+    fn child_buffer(degree: usize) -> Vec<BtreeNode<T>> {
+        Vec::with_capacity(2 * degree)
+    }
+
+    fn new_leaf(degree: usize) -> Self {
+        BtreeNode::Leaf {
+            keys: Self::key_buffer(degree),
+        }
+    }
+
+    // a new root on top of the old one (which is about to be split)
+    fn new_root(old_root: BtreeNode<T>, degree: usize) -> Self {
+        let len = old_root.len();
+        let mut children = Self::child_buffer(degree);
+        children.push(old_root);
+        BtreeNode::Internal {
+            keys: Self::key_buffer(degree),
+            children,
+            len,
+        }
+    }
+
+    // an internal node over these keys and children, counting their keys
+    fn new_internal(keys: Vec<T>, children: Vec<BtreeNode<T>>) -> Self {
+        let len = keys.len() + children.iter().map(|child| child.len()).sum::<usize>();
+        BtreeNode::Internal {
+            keys,
+            children,
+            len,
+        }
+    }
+
+    // number of keys in this subtree
+    fn len(&self) -> usize {
+        match self {
+            BtreeNode::Leaf { keys } => keys.len(),
+            BtreeNode::Internal { len, .. } => *len,
+        }
+    }
+
+    // the subtree key count of an internal node, for keeping it up to date
+    fn len_mut(&mut self) -> &mut usize {
+        match self {
+            BtreeNode::Internal { len, .. } => len,
+            BtreeNode::Leaf { .. } => unreachable!("leaves count their keys directly"),
+        }
+    }
+
+    fn keys(&self) -> &Vec<T> {
+        match self {
+            BtreeNode::Leaf { keys, .. } | BtreeNode::Internal { keys, .. } => keys,
+        }
+    }
+
+    fn keys_mut(&mut self) -> &mut Vec<T> {
+        match self {
+            BtreeNode::Leaf { keys, .. } | BtreeNode::Internal { keys, .. } => keys,
+        }
+    }
+
+    // the children of an internal node, None for a leaf
+    fn children(&self) -> Option<&Vec<BtreeNode<T>>> {
+        match self {
+            BtreeNode::Leaf { .. } => None,
+            BtreeNode::Internal { children, .. } => Some(children),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(self, BtreeNode::Leaf { .. })
+    }
+
+    // keys and children of an internal node
+    // only used by the rebalancing helpers, which are only ever called on parents
+    fn internal_parts(&mut self) -> (&mut Vec<T>, &mut Vec<BtreeNode<T>>) {
+        match self {
+            BtreeNode::Internal { keys, children, .. } => (keys, children),
+            BtreeNode::Leaf { .. } => unreachable!("leaves have no children to rebalance"),
         }
     }
 
     // check if the BtreeNode is full (contains 2t - 1)
-    fn is_full(&self) -> bool {
-        self.keys.len() == 2 * self.degree - 1
+    fn is_full(&self, degree: usize) -> bool {
+        self.keys().len() == 2 * degree - 1
     }
 
     // lower bound would be the index where key would be inserted to maintain the sorted array
     // or where the key should be located
-    fn lower_bound(&self, key: &T) -> usize {
-        match self.keys.binary_search(key) {
-            Ok(i) | Err(i) => i,
+    // the probe compares a stored key against the key we are looking for,
+    // Ok(i) means keys[i] is the key, Err(i) means it belongs at (or below) index i
+    // with check_ord, every key of the node is checked against the answer
+    fn lower_bound<F: FnMut(&T) -> Ordering>(&self, probe: &mut F) -> Result<usize, usize> {
+        let found = self.keys().binary_search_by(&mut *probe);
+        #[cfg(feature = "check_ord")]
+        check_ord::probe_results(self.keys(), probe, found);
+        found
+    }
+
+    fn search<F: FnMut(&T) -> Ordering>(&self, probe: &mut F, counters: &Counters) -> Option<&T> {
+        counters.bump(Counter::NodeVisit);
+        match (self.lower_bound(probe), self) {
+            (Ok(i), _) => Some(&self.keys()[i]),
+            (Err(_), BtreeNode::Leaf { .. }) => None,
+            (Err(i), BtreeNode::Internal { children, .. }) => children[i].search(probe, counters),
         }
     }
 
-    fn search(&self, key: &T) -> bool {
-        let i = self.lower_bound(key);
-        if i < self.keys.len() && &self.keys[i] == key {
-            return true;
+    fn search_mut<F: FnMut(&T) -> Ordering>(
+        &mut self,
+        probe: &mut F,
+        counters: &Counters,
+    ) -> Option<&mut T> {
+        counters.bump(Counter::NodeVisit);
+        match self.lower_bound(probe) {
+            Ok(i) => Some(&mut self.keys_mut()[i]),
+            Err(i) => match self {
+                BtreeNode::Leaf { .. } => None,
+                BtreeNode::Internal { children, .. } => children[i].search_mut(probe, counters),
+            },
         }
-        if self.is_leaf {
-            false
-        } else {
-            self.children[i].search(key)
+    }
+
+    // the key with the given rank (0 is the first) in this subtree
+    // subtrees that end before the rank are skipped by their size alone
+    fn select(&self, mut rank: usize) -> &T {
+        let mut node = self;
+        loop {
+            let (keys, children) = match node {
+                BtreeNode::Leaf { keys } => return &keys[rank],
+                BtreeNode::Internal { keys, children, .. } => (keys, children),
+            };
+            let mut next = &children[keys.len()];
+            for (child, key) in children.iter().zip(keys) {
+                if rank < child.len() {
+                    next = child;
+                    break;
+                }
+                rank -= child.len();
+                if rank == 0 {
+                    return key;
+                }
+                rank -= 1;
+            }
+            node = next;
+        }
+    }
+
+    // the keys with the given ranks, in one sweep like multi_search
+    // ranks are sorted and counted from offset, the rank of this subtree's first key
+    fn multi_select<'a>(&'a self, ranks: &[usize], offset: usize, found: &mut Vec<&'a T>) {
+        let (keys, children) = match self {
+            BtreeNode::Leaf { keys } => {
+                found.extend(ranks.iter().map(|rank| &keys[rank - offset]));
+                return;
+            }
+            BtreeNode::Internal { keys, children, .. } => (keys, children),
+        };
+        let mut rest = ranks;
+        let mut offset = offset;
+        for (child, key) in children.iter().zip(keys) {
+            // the key right after the child has the rank end
+            let end = offset + child.len();
+            let below = rest.partition_point(|rank| *rank < end);
+            if below > 0 {
+                child.multi_select(&rest[..below], offset, found);
+            }
+            rest = &rest[below..];
+            let equal = rest.partition_point(|rank| *rank == end);
+            found.extend(std::iter::repeat_n(key, equal));
+            rest = &rest[equal..];
+            offset = end + 1;
+        }
+        if !rest.is_empty() {
+            children[keys.len()].multi_select(rest, offset, found);
+        }
+    }
+
+    // look up a sorted run of probes in one sweep over this subtree
+    // the probes are split between the children by the keys of this node, so
+    // a node is visited once no matter how many probes pass through it
+    // results are pushed in probe order
+    fn multi_search<'a, C: Compare<T>>(
+        &'a self,
+        probes: &[T],
+        cmp: &C,
+        found: &mut Vec<Option<&'a T>>,
+    ) {
+        let mut rest = probes;
+        for (i, key) in self.keys().iter().enumerate() {
+            let below = rest.partition_point(|probe| cmp.compare(probe, key) == Ordering::Less);
+            self.multi_search_child(i, &rest[..below], cmp, found);
+            rest = &rest[below..];
+            let equal = rest.partition_point(|probe| cmp.compare(probe, key) == Ordering::Equal);
+            found.extend(std::iter::repeat_n(Some(key), equal));
+            rest = &rest[equal..];
+        }
+        self.multi_search_child(self.keys().len(), rest, cmp, found);
+    }
+
+    // the probes that belong below child i, none of them are here if this is a leaf
+    fn multi_search_child<'a, C: Compare<T>>(
+        &'a self,
+        i: usize,
+        probes: &[T],
+        cmp: &C,
+        found: &mut Vec<Option<&'a T>>,
+    ) {
+        if probes.is_empty() {
+            return;
+        }
+        match self {
+            BtreeNode::Leaf { .. } => found.extend(std::iter::repeat_n(None, probes.len())),
+            BtreeNode::Internal { children, .. } => children[i].multi_search(probes, cmp, found),
+        }
+    }
+
+    // call f on every key of this subtree in order, child i comes before key i
+    // a break from f unwinds straight out through every level with ?
+    fn try_for_each<E, F: FnMut(&T) -> ControlFlow<E>>(&self, f: &mut F) -> ControlFlow<E> {
+        match self {
+            BtreeNode::Leaf { keys } => keys.iter().try_for_each(f),
+            BtreeNode::Internal { keys, children, .. } => {
+                for (child, key) in children.iter().zip(keys) {
+                    child.try_for_each(f)?;
+                    f(key)?;
+                }
+                children[keys.len()].try_for_each(f)
+            }
+        }
+    }
+
+    // the same, from the last key to the first
+    fn for_each_rev<F: FnMut(&T)>(&self, f: &mut F) {
+        match self {
+            BtreeNode::Leaf { keys } => keys.iter().rev().for_each(f),
+            BtreeNode::Internal { keys, children, .. } => {
+                children[keys.len()].for_each_rev(f);
+                for (child, key) in children.iter().zip(keys).rev() {
+                    f(key);
+                    child.for_each_rev(f);
+                }
+            }
         }
     }
 
     // search for a key in this sub tree (will implement later)
 
     // insert a key into non full node
-    fn insert_non_full(&mut self, key: T) {
+    // returns false (and drops the key) if an equal key is already stored
+    // when a path is given, the indexes leading to the new key are pushed onto it
+    fn insert_non_full<C: Compare<T>>(
+        &mut self,
+        key: T,
+        cmp: &C,
+        degree: usize,
+        counters: &Counters,
+        mut path: Option<&mut Vec<usize>>,
+        trace: &mut impl Tracer<T>,
+    ) -> bool {
+        counters.bump(Counter::NodeVisit);
         // getting the last key's index in a non full node
         // let mut i = self.keys.len() as i32 - 1;
 
         // if it's leaf node then we insert the key and then sort the keys of the node
-        if self.is_leaf {
+        if self.is_leaf() {
             /*
              ** let's say the degree is 3
              ** so the leaf node may contain at most 5 keys
@@ -99,9 +432,37 @@ impl<T: Ord + Clone + Debug> BtreeNode<T> {
              */
 
             // get the position where the key could be inserted in sorted array
-            let pos = self.keys.binary_search(&key).unwrap_or_else(|e| e);
-            // insert the new key
-            self.keys.insert(pos, key);
+            match self.lower_bound(&mut |stored| cmp.compare(stored, &key)) {
+                Ok(index) => {
+                    trace.record(|depth| TraceStep::Found {
+                        depth,
+                        node: self.keys().iter().collect(),
+                        index,
+                    });
+                    false
+                }
+                Err(pos) => {
+                    #[cfg(feature = "check_ord")]
+                    check_ord::fits_between(
+                        cmp,
+                        &key,
+                        pos.checked_sub(1).map(|before| &self.keys()[before]),
+                        self.keys().get(pos),
+                        "spot",
+                    );
+                    // insert the new key
+                    self.keys_mut().insert(pos, key);
+                    trace.record(|depth| TraceStep::Inserted {
+                        depth,
+                        node: self.keys().iter().collect(),
+                        index: pos,
+                    });
+                    if let Some(path) = path {
+                        path.push(pos);
+                    }
+                    true
+                }
+            }
         } else {
             /*
              ** internal node: choose child and ensure it's not full before descending
@@ -112,23 +473,116 @@ impl<T: Ord + Clone + Debug> BtreeNode<T> {
              ** move to correct child index
              ** i += 1;
              */
-            let mut i = self.lower_bound(&key);
+            let mut i = match self.lower_bound(&mut |stored| cmp.compare(stored, &key)) {
+                Ok(index) => {
+                    trace.record(|depth| TraceStep::Found {
+                        depth,
+                        node: self.keys().iter().collect(),
+                        index,
+                    });
+                    return false;
+                }
+                Err(i) => i,
+            };
 
             // if the child is full, we need to split it first
-            if self.children[i as usize].is_full() {
-                self.split_child(i as usize);
+            if self.internal_parts().1[i].is_full(degree) {
+                let child = &self.internal_parts().1[i];
+                trace.record(|depth| TraceStep::Split {
+                    depth: depth + 1,
+                    node: child.keys().iter().collect(),
+                    median: &child.keys()[degree - 1],
+                });
+                self.split_child(i, degree, counters);
 
                 // after split decide which side to insert to
-                if key > self.keys[i as usize] {
-                    i += 1;
+                match cmp.compare(&key, &self.keys()[i]) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        trace.record(|depth| TraceStep::Found {
+                            depth,
+                            node: self.keys().iter().collect(),
+                            index: i,
+                        });
+                        return false;
+                    }
+                    Ordering::Greater => i += 1,
                 }
             }
+            trace.record(|depth| TraceStep::Descend {
+                depth,
+                node: self.keys().iter().collect(),
+                child: i,
+            });
 
+            #[cfg(feature = "check_ord")]
+            check_ord::fits_between(
+                cmp,
+                &key,
+                i.checked_sub(1).map(|before| &self.keys()[before]),
+                self.keys().get(i),
+                "child",
+            );
             // recursively insert into the appropriate children
-            self.children[i as usize].insert_non_full(key);
+            if let Some(path) = path.as_deref_mut() {
+                path.push(i);
+            }
+            let inserted =
+                self.internal_parts().1[i].insert_non_full(key, cmp, degree, counters, path, trace);
+            if inserted {
+                *self.len_mut() += 1;
+            }
+            inserted
         }
     }
 
+    // find the key probe matches against wanted in this non full subtree, or
+    // insert the key make builds out of wanted where it belongs. either way the
+    // indexes leading to the key are pushed onto path, returns true if make
+    // was called
+    fn find_or_insert<K, F: FnMut(&T, &K) -> Ordering, M: FnOnce(K) -> T>(
+        &mut self,
+        wanted: K,
+        probe: &mut F,
+        make: M,
+        degree: usize,
+        counters: &Counters,
+        path: &mut Vec<usize>,
+    ) -> bool {
+        counters.bump(Counter::NodeVisit);
+        let mut i = match self.lower_bound(&mut |stored| probe(stored, &wanted)) {
+            Ok(i) => {
+                path.push(i);
+                return false;
+            }
+            Err(i) => i,
+        };
+        if self.is_leaf() {
+            self.keys_mut().insert(i, make(wanted));
+            path.push(i);
+            return true;
+        }
+        // split a full child before going down, like insert_non_full
+        if self.internal_parts().1[i].is_full(degree) {
+            self.split_child(i, degree, counters);
+            match probe(&self.keys()[i], &wanted) {
+                Ordering::Greater => {}
+                Ordering::Equal => {
+                    path.push(i);
+                    return false;
+                }
+                Ordering::Less => i += 1,
+            }
+        }
+        path.push(i);
+        let inserted =
+            self.internal_parts().1[i].find_or_insert(wanted, probe, make, degree, counters, path);
+        if inserted {
+            *self.len_mut() += 1;
+        }
+        inserted
+    }
+
     /*
      ** split a child at index i (child has 2t - 1 keys)
      ** after the split:
@@ -136,63 +590,70 @@ impl<T: Ord + Clone + Debug> BtreeNode<T> {
      ** median key index "t - 1" moves up into this node
      ** right child gets the last t - 1 keys
      */
-    fn split_child(&mut self, i: usize) {
-        let degree = self.degree;
+    fn split_child(&mut self, i: usize, degree: usize, counters: &Counters) {
+        counters.bump(Counter::Split);
+        let (keys, children) = self.internal_parts();
+        observe!(
+            counters,
+            TreeEvent::Split {
+                depth: counters.depth(),
+                parent_key_count: keys.len(),
+            }
+        );
+        trace_event!(
+            depth = counters.depth(),
+            index = i,
+            parent_keys = keys.len(),
+            "split"
+        );
 
         // safety: caller guarantees child i exists and is full
-        let full_child = &mut self.children[i];
-        debug_assert_eq!(full_child.keys.len(), 2 * degree - 1);
+        let full_child = &mut children[i];
+        debug_assert_eq!(full_child.keys().len(), 2 * degree - 1);
 
         // prepare the new right sibling
-        // both siblings share the same degree & leaf flag
+        // both siblings share the same leaf/internal variant
         // this new node holds the second half of keys
-        let mut new_child = BtreeNode::new(degree, full_child.is_leaf);
-
-        // move the second half of the keys to new node
-        // keys at position [t, 2t - 1] move to new node
-        for j in 0..degree - 1 {
-            new_child.keys.push(full_child.keys[j + degree].clone());
-        }
-
-        // if not leaf, move the second half of the children too
-        // underlying method removes the children one by one, shifting the vector repeatedly (O(n^2))
-        // if !full_child.is_leaf {
-        //     for _ in 0..degree {
-        //         new_child.children.push(full_child.children.remove(degree));
-        //     }
-        // }
-        // better to drain like
-        if !full_child.is_leaf {
-            new_child
-                .children
-                .extend(full_child.children.drain(degree..));
-        }
-
-        // remove the moved keys from the original child
-        full_child.keys.truncate(degree);
+        let mut new_keys = Self::key_buffer(degree);
+        let new_child = match full_child {
+            // move the second half of the keys to new node
+            // keys at position [t, 2t - 1] move to new node
+            BtreeNode::Leaf { keys } => {
+                new_keys.extend(keys.drain(degree..));
+                BtreeNode::Leaf { keys: new_keys }
+            }
+            // if not leaf, move the second half of the children too
+            // underlying method removes the children one by one, shifting the vector repeatedly (O(n^2))
+            // for _ in 0..degree {
+            //     new_child.children.push(full_child.children.remove(degree));
+            // }
+            // better to drain like
+            BtreeNode::Internal {
+                keys,
+                children: full_children,
+                len,
+            } => {
+                new_keys.extend(keys.drain(degree..));
+                let mut new_children = Self::child_buffer(degree);
+                new_children.extend(full_children.drain(degree..));
+                let new_child = Self::new_internal(new_keys, new_children);
+                // whatever went right (and the middle key going up) leaves the count
+                *len -= new_child.len() + 1;
+                new_child
+            }
+        };
 
         // let middle key at position (t - 1) moves up to parent
-        let middle_key = full_child.keys.remove(degree - 1);
+        // it is the last key left in the original child after the drain
+        let middle_key = full_child.keys_mut().pop().unwrap();
 
         // insert the new child in to parent's array
-        self.children.insert(i + 1, Box::new(new_child));
+        children.insert(i + 1, new_child);
 
         // insert middle key into parent's keys array
-        self.keys.insert(i, middle_key);
+        keys.insert(i, middle_key);
     }
 
-    // Helper method to print the tree structure
-    fn print_tree(&self, level: usize) {
-        println!(
-            "{}Keys: {:?} (leaf: {})",
-            " ".repeat(level),
-            self.keys,
-            self.is_leaf
-        );
-        for child in &self.children {
-            child.print_tree(level + 1);
-        }
-    }
     /*
      ** deletation in btrees is significantly more complex than insertion.
      ** let's break down all the edge cases and decision making process first.
@@ -201,261 +662,2040 @@ impl<T: Ord + Clone + Debug> BtreeNode<T> {
      ** 2. does the node have enough keys? (more than minimum)
      ** 3. do siblings have spare keys? (for borrowing)
      ** 4. can we merge with sibling node? (when borrowing isn't possible)
+     **
+     ** the whole deletion is a single top-down pass: before descending into a
+     ** child we make sure it has at least t keys, so whatever we remove further
+     ** down never leaves a node below t - 1 keys and nothing has to be fixed on
+     ** the way back up. the removed key is moved out and returned, never cloned.
      */
-    fn delete(&mut self, key: &T) -> bool {
+    fn delete<F: FnMut(&T) -> Ordering>(
+        &mut self,
+        probe: &mut F,
+        degree: usize,
+        counters: &Counters,
+        trace: &mut impl Tracer<T>,
+    ) -> Option<T> {
+        counters.bump(Counter::NodeVisit);
         // get the index (i) where the key should be or should be inserted
-        let i = self.lower_bound(key);
-
-        // - this line might be unnecessary
-        if i < self.keys.len() && &self.keys[i] == key {
+        let i = match self.lower_bound(probe) {
             // key found in this node
-            if self.is_leaf {
-                // key is in leaf node
-                self.delete_from_leaf(i)
-            } else {
-                self.delete_from_internal(i)
+            Ok(index) => {
+                trace.record(|depth| TraceStep::Found {
+                    depth,
+                    node: self.keys().iter().collect(),
+                    index,
+                });
+                return Some(self.delete_at(index, degree, counters, trace));
             }
-        } else {
-            // key not in this node, must be in child (if exists)
+            Err(i) => i,
+        };
 
-            if self.is_leaf {
-                return false;
-            }
-            let is_last_child = i == self.keys.len();
+        // key not in this node, must be in child (if exists)
+        if self.is_leaf() {
+            trace.record(|depth| TraceStep::NotFound {
+                depth,
+                node: self.keys().iter().collect(),
+            });
+            return None;
+        }
 
-            // Ensure child has enough keys before recursing
-            if self.children[i].keys.len() < self.degree {
-                self.fix_child_underflow(i);
-            }
+        // ensure child has enough keys before recursing
+        // after fixing, the child might have moved one slot to the left
+        let i = self.ensure_child_has_spare(i, degree, counters, trace);
+        self.trace_descend(i, trace);
+        let removed = self.internal_parts().1[i].delete(probe, degree, counters, trace);
+        if removed.is_some() {
+            *self.len_mut() -= 1;
+        }
+        removed
+    }
 
-            // After fixing, the child might have moved
-            let child_idx = if is_last_child && i > self.keys.len() {
-                i - 1
-            } else {
-                i
-            };
+    // remove and return the key path leads to: child indexes from this node
+    // down, then the index of the key. same top-down pass as delete, except the
+    // way down is given, and the index into each child is corrected whenever
+    // rebalancing shifts that child's keys and children
+    fn delete_path(&mut self, path: &mut [usize], degree: usize, counters: &Counters) -> T {
+        counters.bump(Counter::NodeVisit);
+        let (i, rest) = match path {
+            [i] => return self.delete_at(*i, degree, counters, &mut NoTrace),
+            [i, rest @ ..] => (*i, rest),
+            [] => unreachable!("a path always ends at a key"),
+        };
 
-            self.children[child_idx].delete(key)
+        let left_len = i
+            .checked_sub(1)
+            .map(|left| self.internal_parts().1[left].keys().len());
+        let child = self.ensure_child_has_spare(i, degree, counters, &mut NoTrace);
+        let children = self.internal_parts().1;
+        if child < i {
+            // merged into the left sibling, behind its keys and the separator
+            rest[0] += left_len.unwrap() + 1;
+        } else if left_len.is_some_and(|len| children[i - 1].keys().len() < len) {
+            // took the left sibling's last key (and child), everything moved up by one
+            rest[0] += 1;
         }
+        let removed = children[child].delete_path(rest, degree, counters);
+        *self.len_mut() -= 1;
+        removed
     }
 
-    fn delete_from_leaf(&mut self, i: usize) -> bool {
-        self.keys.remove(i);
-        true
-    }
+    // remove and return the key at index i of this node
+    fn delete_at(
+        &mut self,
+        i: usize,
+        degree: usize,
+        counters: &Counters,
+        trace: &mut impl Tracer<T>,
+    ) -> T {
+        if self.is_leaf() {
+            // key is in leaf node
+            trace.record(|depth| TraceStep::Removed {
+                depth,
+                node: self.keys().iter().collect(),
+                index: i,
+            });
+            return self.keys_mut().remove(i);
+        }
+        let children = self.internal_parts().1;
 
-    // delete from the internal node
-    fn delete_from_internal(&mut self, i: usize) -> bool {
-        let key = &self.keys[i].clone();
+        let removed = if children[i].keys().len() >= degree {
+            // left child node has >= degree keys
+            // pull the predecessor (largest key in left subtree) out of its leaf
+            // and swap it into the slot of the deleted key
+            trace.record(|depth| TraceStep::ReplaceWithPredecessor {
+                depth,
+                node: self.keys().iter().collect(),
+                index: i,
+            });
+            self.trace_descend(i, trace);
+            let (keys, children) = self.internal_parts();
+            let predecessor = children[i].delete_max(degree, counters, trace);
+            std::mem::replace(&mut keys[i], predecessor)
+        } else if children[i + 1].keys().len() >= degree {
+            // right child node has >= degree keys
+            // same as above with the successor (smallest key in right subtree)
+            trace.record(|depth| TraceStep::ReplaceWithSuccessor {
+                depth,
+                node: self.keys().iter().collect(),
+                index: i,
+            });
+            self.trace_descend(i + 1, trace);
+            let (keys, children) = self.internal_parts();
+            let successor = children[i + 1].delete_min(degree, counters, trace);
+            std::mem::replace(&mut keys[i], successor)
+        } else {
+            // both children have exactly t - 1 keys
+            // merge key with both children, the key ends up in the middle of
+            // the merged child (index t - 1) so we keep descending from there
+            trace.record(|depth| TraceStep::Merge {
+                depth: depth + 1,
+                left: i,
+                separator: &self.keys()[i],
+            });
+            self.merge_children(i, counters);
+            counters.bump(Counter::NodeVisit);
+            self.trace_descend(i, trace);
+            let child = &mut self.internal_parts().1[i];
+            trace.record(|depth| TraceStep::Found {
+                depth,
+                node: child.keys().iter().collect(),
+                index: degree - 1,
+            });
+            child.delete_at(degree - 1, degree, counters, trace)
+        };
+        *self.len_mut() -= 1;
+        removed
+    }
 
-        // check if left child node has >= degree keys
-        // find predecessor (largest key in left subtree)
-        if self.children[i].keys.len() >= self.degree {
-            let predecessor = self.get_predecessor(i);
-            self.keys[i] = predecessor.clone();
-            self.children[i].delete(&predecessor)
+    // remove and return the largest key in this subtree
+    fn delete_max(&mut self, degree: usize, counters: &Counters, trace: &mut impl Tracer<T>) -> T {
+        counters.bump(Counter::NodeVisit);
+        let last = match self {
+            BtreeNode::Leaf { keys, .. } => {
+                trace.record(|depth| TraceStep::Removed {
+                    depth,
+                    node: keys.iter().collect(),
+                    index: keys.len() - 1,
+                });
+                return keys.pop().unwrap();
+            }
+            BtreeNode::Internal { children, .. } => children.len() - 1,
+        };
+        let last = self.ensure_child_has_spare(last, degree, counters, trace);
+        self.trace_descend(last, trace);
+        let removed = self.internal_parts().1[last].delete_max(degree, counters, trace);
+        *self.len_mut() -= 1;
+        removed
+    }
 
-            // check if right child node has >= degree keys
-            // find successor (smallest key in the right subtree)
-        } else if self.children[i + 1].keys.len() >= self.degree {
-            let successor = self.get_successor(i);
-            self.keys[i] = successor.clone();
-            self.children[i + 1].delete(&successor)
-            // both children has exactly t - 1 keys
-            // merge key with both children
-        } else {
-            // need to fix this
-            self.merge_children(i);
-            self.children[i].delete(key)
+    // remove and return the smallest key in this subtree
+    fn delete_min(&mut self, degree: usize, counters: &Counters, trace: &mut impl Tracer<T>) -> T {
+        counters.bump(Counter::NodeVisit);
+        if let BtreeNode::Leaf { keys, .. } = self {
+            trace.record(|depth| TraceStep::Removed {
+                depth,
+                node: keys.iter().collect(),
+                index: 0,
+            });
+            return keys.remove(0);
         }
+        let first = self.ensure_child_has_spare(0, degree, counters, trace);
+        self.trace_descend(first, trace);
+        let removed = self.internal_parts().1[first].delete_min(degree, counters, trace);
+        *self.len_mut() -= 1;
+        removed
     }
-    // get predecessor of key at index idx (largest key in left subtree)
-    fn get_predecessor(&self, idx: usize) -> T {
-        let mut current = &self.children[idx];
-        while !current.is_leaf {
-            current = &current.children[current.children.len() - 1];
+
+    // make sure the child at idx has at least t keys so we can safely descend into it
+    // returns the index of that child afterwards (it moves left when merged with its left sibling)
+    fn ensure_child_has_spare(
+        &mut self,
+        idx: usize,
+        degree: usize,
+        counters: &Counters,
+        trace: &mut impl Tracer<T>,
+    ) -> usize {
+        if self.internal_parts().1[idx].keys().len() >= degree {
+            return idx;
         }
-        current.keys[current.keys.len() - 1].clone()
+        self.fix_child_underflow(idx, degree, counters, trace)
     }
 
-    // get successor of key at index idx (smallest key in right subtree)
-    fn get_successor(&self, idx: usize) -> T {
-        let mut current = &self.children[idx + 1];
-        while !current.is_leaf {
-            current = &current.children[0];
-        }
-        current.keys[0].clone()
+    // going down into child i, for a traced operation
+    fn trace_descend(&self, child: usize, trace: &mut impl Tracer<T>) {
+        trace.record(|depth| TraceStep::Descend {
+            depth,
+            node: self.keys().iter().collect(),
+            child,
+        });
     }
 
     // merge key at idx with its left and right children
-    fn merge_children(&mut self, idx: usize) {
-        let key = self.keys.remove(idx);
-        let right_child = self.children.remove(idx + 1);
-        let left_child = &mut self.children[idx];
-
-        // add the key to left child
-        left_child.keys.push(key);
-
-        // add all keys from right child
-        left_child.keys.extend(right_child.keys);
+    fn merge_children(&mut self, idx: usize, counters: &Counters) {
+        counters.bump(Counter::Merge);
+        observe!(
+            counters,
+            TreeEvent::Merge {
+                depth: counters.depth(),
+            }
+        );
+        let (keys, children) = self.internal_parts();
+        trace_event!(
+            depth = counters.depth(),
+            left = idx,
+            right = idx + 1,
+            keys = children[idx].keys().len() + children[idx + 1].keys().len() + 1,
+            "merge"
+        );
+        let key = keys.remove(idx);
+        let right_child = children.remove(idx + 1);
+        let left_child = &mut children[idx];
 
-        // add all children from right child (if not leaf)
-        if !left_child.is_leaf {
-            left_child.children.extend(right_child.children);
+        // the merged node keeps the left child's buffers (already allocated at
+        // full size), the right child's buffers are freed
+        match (left_child, right_child) {
+            (BtreeNode::Leaf { keys: left_keys }, BtreeNode::Leaf { keys: right_keys }) => {
+                left_keys.reserve(right_keys.len() + 1);
+                // add the key to left child
+                left_keys.push(key);
+                // add all keys from right child
+                left_keys.extend(right_keys);
+            }
+            (
+                BtreeNode::Internal {
+                    keys: left_keys,
+                    children: left_children,
+                    len: left_len,
+                },
+                BtreeNode::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                    len: right_len,
+                },
+            ) => {
+                *left_len += right_len + 1;
+                left_keys.reserve(right_keys.len() + 1);
+                left_keys.push(key);
+                left_keys.extend(right_keys);
+                // add all children from right child (if not leaf)
+                left_children.reserve(right_children.len());
+                left_children.extend(right_children);
+            }
+            _ => unreachable!("siblings are always at the same level"),
         }
     }
 
     // fix underflow in child at index idx
-    fn fix_child_underflow(&mut self, idx: usize) {
+    // returns the index the child ends up at
+    fn fix_child_underflow(
+        &mut self,
+        idx: usize,
+        degree: usize,
+        counters: &Counters,
+        trace: &mut impl Tracer<T>,
+    ) -> usize {
+        let children = self.internal_parts().1;
         // try to borrow from left sibling
-        if idx > 0 && self.children[idx - 1].keys.len() >= self.degree {
-            self.borrow_from_left_sibling(idx);
+        if idx > 0 && children[idx - 1].keys().len() >= degree {
+            trace.record(|depth| TraceStep::BorrowLeft {
+                depth: depth + 1,
+                child: idx,
+                separator: &self.keys()[idx - 1],
+            });
+            self.borrow_from_left_sibling(idx, counters);
+            idx
         }
         // try to borrow from right sibling
-        else if idx < self.children.len() - 1 && self.children[idx + 1].keys.len() >= self.degree
-        {
-            self.borrow_from_right_sibling(idx);
+        else if idx < children.len() - 1 && children[idx + 1].keys().len() >= degree {
+            trace.record(|depth| TraceStep::BorrowRight {
+                depth: depth + 1,
+                child: idx,
+                separator: &self.keys()[idx],
+            });
+            self.borrow_from_right_sibling(idx, counters);
+            idx
         }
         // merge with sibling
         else {
-            if idx > 0 {
-                // merge with left sibling
-                self.merge_children(idx - 1);
-            } else {
-                // merge with right sibling
-                self.merge_children(idx);
+            // merge with the left sibling, or the right one for the first child
+            let left = idx.saturating_sub(1);
+            trace.record(|depth| TraceStep::Merge {
+                depth: depth + 1,
+                left,
+                separator: &self.keys()[left],
+            });
+            self.merge_children(left, counters);
+            left
+        }
+    }
+
+    // heap bytes held by the vectors of this subtree (capacity, not length)
+    // the nodes themselves live inline in their parent's children vector
+    fn memory_usage(&self) -> usize {
+        let keys = self.keys().capacity() * size_of::<T>();
+        match self {
+            BtreeNode::Leaf { .. } => keys,
+            BtreeNode::Internal { children, .. } => {
+                keys + children.capacity() * size_of::<BtreeNode<T>>()
+                    + children
+                        .iter()
+                        .map(|child| child.memory_usage())
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    // same kind of node holding equal keys, with children of the same shape
+    fn same_shape(&self, other: &Self, eq: &impl Fn(&T, &T) -> bool) -> bool {
+        let keys_eq = self.keys().len() == other.keys().len()
+            && self.keys().iter().zip(other.keys()).all(|(a, b)| eq(a, b));
+        match (self, other) {
+            (BtreeNode::Leaf { .. }, BtreeNode::Leaf { .. }) => keys_eq,
+            (
+                BtreeNode::Internal { children, .. },
+                BtreeNode::Internal {
+                    children: others, ..
+                },
+            ) => {
+                keys_eq
+                    && children
+                        .iter()
+                        .zip(others)
+                        .all(|(child, other)| child.same_shape(other, eq))
             }
+            _ => false,
         }
     }
 
+    // drop the spare capacity of every vector in this subtree
+    // returns the number of bytes given back
+    fn shrink_to_fit(&mut self) -> usize {
+        let keys = self.keys_mut();
+        let mut reclaimed = (keys.capacity() - keys.len()) * size_of::<T>();
+        keys.shrink_to_fit();
+        if let BtreeNode::Internal { children, .. } = self {
+            reclaimed += (children.capacity() - children.len()) * size_of::<BtreeNode<T>>();
+            children.shrink_to_fit();
+            for child in children {
+                reclaimed += child.shrink_to_fit();
+            }
+        }
+        reclaimed
+    }
+
+    /*
+     ** check every b-tree property for this subtree
+     ** lower/upper are the separators from the parent this subtree must stay within
+     ** leaf_depth remembers the depth of the first leaf we saw, all others must match it
+     */
+    fn validate<C: Compare<T>>(
+        &self,
+        cmp: &C,
+        degree: usize,
+        depth: usize,
+        lower: Option<&T>,
+        upper: Option<&T>,
+        leaf_depth: &mut Option<usize>,
+    ) -> Result<(), String> {
+        let keys = self.keys();
+        if keys.len() > 2 * degree - 1 {
+            return Err(format!(
+                "node at depth {} has {} keys, at most {} allowed",
+                depth,
+                keys.len(),
+                2 * degree - 1
+            ));
+        }
+        // the root is the only node allowed to go below t - 1 keys
+        if depth > 0 && keys.len() < degree - 1 {
+            return Err(format!(
+                "node at depth {} has {} keys, at least {} required",
+                depth,
+                keys.len(),
+                degree - 1
+            ));
+        }
+        if keys.is_empty() {
+            return Err(format!("node at depth {} has no keys", depth));
+        }
+        if keys
+            .windows(2)
+            .any(|pair| cmp.compare(&pair[0], &pair[1]) != Ordering::Less)
+        {
+            return Err(format!("keys at depth {} are not strictly sorted", depth));
+        }
+        if lower.is_some_and(|lower| cmp.compare(&keys[0], lower) != Ordering::Greater)
+            || upper
+                .is_some_and(|upper| cmp.compare(&keys[keys.len() - 1], upper) != Ordering::Less)
+        {
+            return Err(format!(
+                "keys at depth {} fall outside their parent's separators",
+                depth
+            ));
+        }
+
+        let children = match self {
+            BtreeNode::Leaf { .. } => {
+                return match *leaf_depth {
+                    None => {
+                        *leaf_depth = Some(depth);
+                        Ok(())
+                    }
+                    Some(expected) if expected == depth => Ok(()),
+                    Some(expected) => Err(format!(
+                        "leaf at depth {} but other leaves are at depth {}",
+                        depth, expected
+                    )),
+                };
+            }
+            BtreeNode::Internal { children, .. } => children,
+        };
+
+        if children.len() != keys.len() + 1 {
+            return Err(format!(
+                "internal node at depth {} has {} keys but {} children",
+                depth,
+                keys.len(),
+                children.len()
+            ));
+        }
+        let counted = keys.len() + children.iter().map(|child| child.len()).sum::<usize>();
+        if counted != self.len() {
+            return Err(format!(
+                "internal node at depth {} counts {} keys in its subtree but holds {}",
+                depth,
+                self.len(),
+                counted
+            ));
+        }
+        for (i, child) in children.iter().enumerate() {
+            let child_lower = if i == 0 { lower } else { Some(&keys[i - 1]) };
+            let child_upper = keys.get(i).or(upper);
+            child.validate(cmp, degree, depth + 1, child_lower, child_upper, leaf_depth)?;
+        }
+        Ok(())
+    }
+
     // borrow a key from left sibling
-    fn borrow_from_left_sibling(&mut self, idx: usize) {
+    fn borrow_from_left_sibling(&mut self, idx: usize, counters: &Counters) {
+        counters.bump(Counter::BorrowLeft);
+        observe!(
+            counters,
+            TreeEvent::BorrowLeft {
+                depth: counters.depth(),
+            }
+        );
+        let (keys, children) = self.internal_parts();
+        trace_event!(
+            depth = counters.depth(),
+            index = idx,
+            sibling_keys = children[idx - 1].keys().len(),
+            "borrow left"
+        );
         // used split_at_mut to get mutable references to both children
-        let (left, right) = self.children.split_at_mut(idx);
+        let (left, right) = children.split_at_mut(idx);
         let sibling = &mut left[idx - 1]; // left sibling
         let child = &mut right[0]; // the child that needs a key
 
-        // move parent key down to child
-        child.keys.insert(0, self.keys[idx - 1].clone());
-
         // move sibling's last key up to parent
-        self.keys[idx - 1] = sibling.keys.pop().unwrap();
+        // and the parent key it replaces down to child
+        let parent_key = std::mem::replace(&mut keys[idx - 1], sibling.keys_mut().pop().unwrap());
+        child.keys_mut().insert(0, parent_key);
 
         // if not leaf, move sibling's last child to child's first
-        if !child.is_leaf {
-            child.children.insert(0, sibling.children.pop().unwrap());
+        // the key and the moved subtree change sides in the counts too
+        if let (
+            BtreeNode::Internal { children, len, .. },
+            BtreeNode::Internal {
+                children: sibling_children,
+                len: sibling_len,
+                ..
+            },
+        ) = (child, sibling)
+        {
+            let moved = sibling_children.pop().unwrap();
+            *len += moved.len() + 1;
+            *sibling_len -= moved.len() + 1;
+            children.insert(0, moved);
         }
     }
 
     // borrow a key from right sibling
-    fn borrow_from_right_sibling(&mut self, idx: usize) {
+    fn borrow_from_right_sibling(&mut self, idx: usize, counters: &Counters) {
+        counters.bump(Counter::BorrowRight);
+        observe!(
+            counters,
+            TreeEvent::BorrowRight {
+                depth: counters.depth(),
+            }
+        );
+        let (keys, children) = self.internal_parts();
+        trace_event!(
+            depth = counters.depth(),
+            index = idx,
+            sibling_keys = children[idx + 1].keys().len(),
+            "borrow right"
+        );
         // use split_at_mut to get mutable references to both children
-        let (left, right) = self.children.split_at_mut(idx + 1);
+        let (left, right) = children.split_at_mut(idx + 1);
         let child = &mut left[idx]; // the child that needs a key
         let sibling = &mut right[0]; // right sibling
 
-        // move parent key down to child
-        child.keys.push(self.keys[idx].clone());
-
         // move sibling's first key up to parent
-        self.keys[idx] = sibling.keys.remove(0);
+        // and the parent key it replaces down to child
+        let parent_key = std::mem::replace(&mut keys[idx], sibling.keys_mut().remove(0));
+        child.keys_mut().push(parent_key);
 
         // if not leaf, move sibling's first child to child's last
-        if !child.is_leaf {
-            child.children.push(sibling.children.remove(0));
+        if let (
+            BtreeNode::Internal { children, len, .. },
+            BtreeNode::Internal {
+                children: sibling_children,
+                len: sibling_len,
+                ..
+            },
+        ) = (child, sibling)
+        {
+            let moved = sibling_children.remove(0);
+            *len += moved.len() + 1;
+            *sibling_len -= moved.len() + 1;
+            children.push(moved);
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Btree<T: Ord + Debug + Clone> {
+pub struct Btree<T, C = Natural> {
     root: Option<Box<BtreeNode<T>>>,
+    // number of keys stored
+    len: usize,
     degree: usize,
+    cmp: C,
+    counters: Counters,
+    // changes whenever the tree does, see generation
+    generation: u64,
+    // told about every key going in or out, see set_on_change
+    on_change: Option<OnChange<T>>,
+    // every key going in has to pass it, see validate_keys
+    key_check: Option<KeyCheck<T>>,
 }
 
-impl<T: Ord + Clone + Debug> Btree<T> {
+// degree of the trees built by conversions, which have no way to be told one
+// (the same node size std's BTreeSet uses)
+pub const DEFAULT_DEGREE: usize = 6;
+
+// bytes of keys a full node of Btree::new_auto holds, about four cache lines
+pub const AUTO_NODE_BYTES: usize = 256;
+
+// the degree whose full nodes (2t - 1 keys) hold about node_bytes of keys of
+// type T, at least 2 (for keys too big to fit more) and at most 256 (for tiny
+// keys and a large budget, zero sized keys count as a byte). a full node's
+// keys are one run of memory,
+// a search through them touches as many cache lines as the run is long, so
+// the budget decides how much of a node a lookup reads
+pub fn auto_degree<T>(node_bytes: usize) -> usize {
+    let keys = node_bytes / size_of::<T>().max(1);
+    keys.div_ceil(2).clamp(2, 256)
+}
+
+// the batch operations (delete_many, drain_range, apply_batch,
+// extend_unsorted) drain and rebuild the tree once the batch is at least
+// 1 / REBUILD_BATCH_FRACTION of the tree's size, and go key by key below that
+// a rebuild is O(n + k) however small the batch, k single steps O(k log n)
+const REBUILD_BATCH_FRACTION: usize = 8;
+
+// how full rebuild() packs the nodes: a quarter of every node is left free so
+// the inserts that follow a rebuild don't start splitting right away
+const REBUILD_FILL: f64 = 0.75;
+
+// a tree ordered by a custom comparator closure
+pub type BtreeBy<T, F> = Btree<T, F>;
+
+// binary keys, looked up, deleted, ranged over and prefix scanned with a plain
+// &[u8] so a query never allocates. [u8] compares with a single memcmp, which
+// makes every probe inside a node one call however long the keys are
+pub type BytesBtree = Btree<Box<[u8]>>;
+
+impl<T: Ord> Btree<T> {
     pub fn new(degree: usize) -> Self {
+        Btree::with_comparator(degree, Natural)
+    }
+
+    // a tree with the degree picked for the size of T, see auto_degree
+    // degree() tells which one it got
+    pub fn new_auto() -> Self {
+        Btree::with_node_bytes(AUTO_NODE_BYTES)
+    }
+
+    // a tree whose full nodes hold about node_bytes of keys, see auto_degree
+    pub fn with_node_bytes(node_bytes: usize) -> Self {
+        Btree::new(auto_degree::<T>(node_bytes))
+    }
+}
+
+impl<T: Ord + AsRef<[u8]>> Btree<T> {
+    // every key starting with prefix, in order. for keys ordered by their
+    // bytes (String, Vec<u8>, ..), where the keys sharing a prefix are one run
+    // the run ends before the prefix with its last byte bumped up, the bound is
+    // compared as bytes so it doesn't have to be valid utf-8
+    // trailing 0xff bytes can't be bumped and are dropped first, a prefix with
+    // nothing else (or an empty one) runs to the end of the tree
+    pub fn prefix_range<P: AsRef<[u8]> + ?Sized>(&self, prefix: &P) -> Range<'_, T> {
+        let prefix = prefix.as_ref();
+        let mut end = prefix.to_vec();
+        while end.pop_if(|byte| *byte == u8::MAX).is_some() {}
+        let end = match end.last_mut() {
+            Some(byte) => {
+                *byte += 1;
+                Bound::Excluded(&end[..])
+            }
+            None => Bound::Unbounded,
+        };
+        self.range_with((Bound::Included(prefix), end), |stored, bound: &[u8]| {
+            stored.as_ref().cmp(bound)
+        })
+    }
+}
+
+impl BytesBtree {
+    // store a copy of key, the copy is only made when the key is new. one
+    // descent finds the key or the spot for the copy
+    pub fn insert_bytes(&mut self, key: &[u8]) -> bool {
+        self.find_or_insert_with(key, || key.into()).1
+    }
+}
+
+impl<T: Ord> Btree<T, Desc> {
+    // a tree ordered from the largest key to the smallest one
+    // iteration, ranges, first/last, pops and floor/ceiling all follow that order
+    pub fn new_reversed(degree: usize) -> Self {
+        Btree::with_comparator(degree, Desc)
+    }
+}
+
+impl Btree<f64, TotalOrder> {
+    // f64 isn't Ord, a tree of floats is ordered by f64::total_cmp instead
+    // (see TotalOrder for where -0.0 and NaN end up)
+    pub fn new_f64(degree: usize) -> Self {
+        Btree::with_comparator(degree, TotalOrder)
+    }
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> Btree<T, ByKey<F, K>> {
+    // a tree ordered by the key f derives from every stored value
+    // lookups can then use either a whole value or just the derived key
+    pub fn new_by_key(degree: usize, f: F) -> Self {
+        Btree::with_comparator(degree, ByKey::new(f))
+    }
+
+    // the stored value whose derived key equals key
+    pub fn get_by(&self, key: &K) -> Option<&T> {
+        self.get_with(|stored| self.cmp.compare_key(stored, key))
+    }
+
+    // remove and return the stored value whose derived key equals key
+    pub fn remove_by(&mut self, key: &K) -> Option<T> {
+        self.remove_with(|cmp, stored| cmp.compare_key(stored, key))
+    }
+
+    // values whose derived keys fall inside the range
+    pub fn range_by<R: RangeBounds<K>>(&self, range: R) -> Range<'_, T, ByKey<F, K>> {
+        self.range_with(range, |stored, bound| self.cmp.compare_key(stored, bound))
+    }
+}
+
+impl<T, C> Btree<T, C> {
+    // the descent a lookup makes, node by node from the root: each node's keys
+    // searched the way search does it (counted, and checked with check_ord)
+    // visit is handed every node passed, where probe led in it and how many
+    // comparisons that took. the descent stops at a match or in a leaf
+    fn descend<F, V>(&self, mut probe: F, mut visit: V)
+    where
+        F: FnMut(&T) -> Ordering,
+        V: FnMut(&BtreeNode<T>, Result<usize, usize>, usize),
+    {
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            self.counters.bump(Counter::NodeVisit);
+            let mut comparisons = 0;
+            let at = current.lower_bound(&mut |stored| {
+                self.counters.bump(Counter::Comparison);
+                comparisons += 1;
+                probe(stored)
+            });
+            // check_ord compares every key once more after the binary search,
+            // which isn't what the lookup itself costs
+            #[cfg(feature = "check_ord")]
+            let comparisons = comparisons - current.keys().len();
+            visit(current, at, comparisons);
+            node = match (at, current.children()) {
+                (Err(i), Some(children)) => Some(&children[i]),
+                _ => None,
+            };
+        }
+    }
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // create a tree ordered by cmp instead of the keys' natural ordering
+    // the comparator is used for every comparison for as long as the tree lives
+    pub fn with_comparator(degree: usize, cmp: C) -> Self {
         assert!(degree >= 2, "degree must be atleast 2!");
-        Btree { root: None, degree }
+        Btree::empty(degree, cmp, None)
+    }
+
+    // every tree starts out here, trees made out of another one pass on its
+    // key check (a subscriber isn't passed on, it's told about one tree)
+    fn empty(degree: usize, cmp: C, key_check: Option<KeyCheck<T>>) -> Self {
+        Btree {
+            root: None,
+            len: 0,
+            degree,
+            cmp,
+            counters: Counters::new(),
+            generation: finger::next_generation(),
+            on_change: None,
+            key_check,
+        }
     }
 
     // search for a key in the tree
-    pub fn search(&self, key: &T) -> bool {
+    pub fn search<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // the stored key equal to key, if any
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_with(|stored| self.cmp.compare(stored.borrow(), key))
+    }
+
+    // the level the key is stored at (the root is level 0), None if it's absent
+    // keys found in an internal node stop the descent there, so the result is
+    // anywhere from 0 to height - 1
+    pub fn depth_of<Q: ?Sized>(&self, key: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut depth = 0;
+        let mut found = false;
+        self.descend(
+            |stored| self.cmp.compare(stored.borrow(), key),
+            |_, at, _| {
+                found = at.is_ok();
+                depth += usize::from(!found);
+            },
+        );
+        found.then_some(depth)
+    }
+
+    // every node the search for key goes through, from the root down
+    // the last step matched if the key is stored, otherwise it's a leaf where
+    // the key would have been. an empty tree gives an empty path
+    pub fn path_to<Q: ?Sized>(&self, key: &Q) -> Vec<PathStep>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut steps = Vec::new();
+        self.descend(
+            |stored| self.cmp.compare(stored.borrow(), key),
+            |node, at, _| {
+                let (i, matched) = match at {
+                    Ok(i) => (i, true),
+                    Err(i) => (i, false),
+                };
+                steps.push(PathStep::new(i, node.keys().len(), matched));
+            },
+        );
+        steps
+    }
+
+    // look up many keys at once, sharing the way down between them
+    // the probes get sorted (by the tree's comparator) and the results line up
+    // with the sorted order: the i-th result answers keys[i]
+    // close probes share most of their path, so many lookups cost about as much
+    // as one sweep over the part of the tree they touch
+    pub fn multi_get<'a>(&'a self, keys: &mut [T]) -> Vec<Option<&'a T>> {
+        keys.sort_by(|a, b| self.cmp.compare(a, b));
+        let mut found = Vec::with_capacity(keys.len());
         match &self.root {
-            None => false,
-            Some(root) => root.search(key),
+            None => found.resize(keys.len(), None),
+            Some(root) => root.multi_search(keys, &self.cmp, &mut found),
         }
+        found
     }
 
-    pub fn insert(&mut self, key: T) {
-        match self.root.as_mut() {
+    // true if every one of keys is stored, stops at the first one that isn't
+    // an empty set of keys is trivially all there
+    pub fn contains_all<'a, Q, I>(&self, keys: I) -> bool
+    where
+        Q: ?Sized + 'a,
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        !self.any_probe(keys, false)
+    }
+
+    // true if at least one of keys is stored, stops at the first one that is
+    pub fn contains_any<'a, Q, I>(&self, keys: I) -> bool
+    where
+        Q: ?Sized + 'a,
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.any_probe(keys, true)
+    }
+
+    // look keys up in turn until one of them is (or isn't) stored, as wanted
+    // while the keys come in ascending order the position of the last lookup is
+    // kept and the next one moves forward from there, so a sorted run of keys
+    // costs one forward sweep instead of a walk from the root per key
+    fn any_probe<'a, Q, I>(&self, keys: I, wanted: bool) -> bool
+    where
+        Q: ?Sized + 'a,
+        I: IntoIterator<Item = &'a Q>,
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut path = Path::empty();
+        let mut previous: Option<&Q> = None;
+        for key in keys {
+            let reached = |stored: &T| self.cmp.compare(stored.borrow(), key) != Ordering::Less;
+            match previous {
+                Some(previous) if self.cmp.compare(previous, key) != Ordering::Greater => {
+                    path.seek_forward(reached)
+                }
+                _ => path = Path::first_where(self.root.as_deref(), reached),
+            }
+            let stored = path
+                .current()
+                .is_some_and(|stored| self.cmp.compare(stored.borrow(), key) == Ordering::Equal);
+            if stored == wanted {
+                return true;
+            }
+            previous = Some(key);
+        }
+        false
+    }
+
+    // descend with a probe comparing stored keys against whatever we look for
+    fn get_with<F: FnMut(&T) -> Ordering>(&self, mut probe: F) -> Option<&T> {
+        let mut counted = |stored: &T| {
+            self.counters.bump(Counter::Comparison);
+            probe(stored)
+        };
+        self.root.as_ref()?.search(&mut counted, &self.counters)
+    }
+
+    // get_with handing out the key mutably, for the parts of it the
+    // comparator never looks at (a map's values)
+    fn get_mut_with<F: FnMut(&C, &T) -> Ordering>(&mut self, mut probe: F) -> Option<&mut T> {
+        self.bump_generation();
+        let (cmp, counters) = (&self.cmp, &self.counters);
+        let mut counted = |stored: &T| {
+            counters.bump(Counter::Comparison);
+            probe(cmp, stored)
+        };
+        self.root.as_mut()?.search_mut(&mut counted, counters)
+    }
+
+    // insert a key into the tree
+    // returns false if an equal key (according to the comparator) is already
+    // stored, in which case the stored key is kept and the new one is dropped
+    // panics if the key check turns key down, see try_insert
+    pub fn insert(&mut self, key: T) -> bool {
+        admit(self.key_check.as_ref(), &key);
+        self.insert_tracked(key, None, &mut NoTrace)
+    }
+
+    // insert, handing back why the key check (see validate_keys) turned key
+    // down instead of panicking
+    pub fn try_insert(&mut self, key: T) -> Result<bool, InvalidKey> {
+        self.check_keys([&key])?;
+        Ok(self.insert_tracked(key, None, &mut NoTrace))
+    }
+
+    // insert key, and if an equal key is stored already put key in its place
+    // and hand the stored one back (insert keeps the stored one instead)
+    pub fn replace(&mut self, key: T) -> Option<T> {
+        admit(self.key_check.as_ref(), &key);
+        self.bump_generation();
+        let (cmp, counters) = (&self.cmp, &self.counters);
+        let mut counted = |stored: &T| {
+            counters.bump(Counter::Comparison);
+            cmp.compare(stored, &key)
+        };
+        let stored = match self.root.as_mut() {
+            Some(root) => root.search_mut(&mut counted, counters),
+            None => None,
+        };
+        let Some(stored) = stored else {
+            self.insert_tracked(key, None, &mut NoTrace);
+            return None;
+        };
+        let old = mem::replace(stored, key);
+        notify(
+            &mut self.on_change,
+            Change::Replaced {
+                old: &old,
+                new: stored,
+            },
+        );
+        Some(old)
+    }
+
+    // insert, pushing the indexes that lead from the root to the new key onto path
+    fn insert_tracked(
+        &mut self,
+        key: T,
+        path: Option<&mut Vec<usize>>,
+        trace: &mut impl Tracer<T>,
+    ) -> bool {
+        self.bump_generation();
+        // the way to the new key is needed to report it as well
+        let watched = self.watched();
+        let mut at = Vec::new();
+        let tracked = (path.is_some() || watched).then_some(&mut at);
+        let (cmp, counters) = (&self.cmp, &self.counters);
+        trace_span!(counters, "insert", len = self.len);
+        let counted = |a: &T, b: &T| {
+            counters.bump(Counter::Comparison);
+            cmp.compare(a, b)
+        };
+        let inserted = match self.root.as_mut() {
             None => {
                 // we create a 1 key leaf root
-                let mut root = BtreeNode::new(self.degree, true);
-                root.keys.push(key);
+                let mut root = BtreeNode::new_leaf(self.degree);
+                root.keys_mut().push(key);
+                trace.record(|depth| TraceStep::Inserted {
+                    depth,
+                    node: root.keys().iter().collect(),
+                    index: 0,
+                });
                 self.root = Some(Box::new(root));
+                if let Some(at) = tracked {
+                    at.push(0);
+                }
+                true
             }
 
-            Some(root) if root.is_full() => {
+            Some(root) if root.is_full(self.degree) => {
                 // if the root is full we allocate a new root
                 // make old root its child, split, and then insert
                 // this is the only case where the tree height increases
-                let mut new_root = BtreeNode::new(self.degree, false);
-                new_root.children.push(self.root.take().unwrap());
+                trace.record(|_| TraceStep::RootGrew);
+                trace.record(|depth| TraceStep::Split {
+                    depth: depth + 1,
+                    node: root.keys().iter().collect(),
+                    median: &root.keys()[self.degree - 1],
+                });
+                let old_root = mem::replace(root.as_mut(), BtreeNode::Leaf { keys: Vec::new() });
+                **root = BtreeNode::new_root(old_root, self.degree);
+                observe!(counters, TreeEvent::RootGrew);
                 // pplit the old root
-                new_root.split_child(0);
+                root.split_child(0, self.degree, counters);
 
                 // after split the appropriate child is guaranteed not full
-                new_root.insert_non_full(key);
-                self.root = Some(Box::new(new_root));
+                root.insert_non_full(key, &counted, self.degree, counters, tracked, trace)
             }
 
             // insert into possibly new root
             Some(root) => {
-                root.insert_non_full(key);
+                root.insert_non_full(key, &counted, self.degree, counters, tracked, trace)
             }
+        };
+        self.len += usize::from(inserted);
+        if inserted && watched {
+            self.notify_inserted_at(&at);
         }
+        if let Some(path) = path {
+            path.append(&mut at);
+        }
+        inserted
+    }
+
+    // the stored key equal to probe, or the key make builds if there is none
+    // (inserted and then handed out). make only runs for a missing key and has
+    // to build one equal to probe. a single descent finds the key or the spot
+    // for the new one, splitting full nodes on the way down like insert does
+    pub fn get_or_insert_with<Q: ?Sized, F: FnOnce() -> T>(&mut self, probe: &Q, make: F) -> &T
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let (path, _) = self.find_or_insert_with(probe, make);
+        Path::from_indices(self.root.as_deref(), &path)
+            .current()
+            .expect("the path leads to the key")
+    }
+
+    // the descent of get_or_insert_with, handing back the indexes leading
+    // from the root to the key and whether it was inserted
+    fn find_or_insert_with<Q: ?Sized, F: FnOnce() -> T>(
+        &mut self,
+        probe: &Q,
+        make: F,
+    ) -> (Vec<usize>, bool)
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.find_or_insert_by(
+            probe,
+            |cmp, stored, probe| cmp.compare(stored.borrow(), probe),
+            |cmp, probe| {
+                let key = make();
+                debug_assert!(
+                    cmp.compare(key.borrow(), probe) == Ordering::Equal,
+                    "make built a key that doesn't match the probe"
+                );
+                key
+            },
+        )
+    }
+
+    // find_or_insert_with for a wanted value that make takes over, for when
+    // the key to insert can only be built by giving up what it's looked for
+    // with. probe and make get the tree's comparator along
+    fn find_or_insert_by<K, F, M>(&mut self, wanted: K, mut probe: F, make: M) -> (Vec<usize>, bool)
+    where
+        F: FnMut(&C, &T, &K) -> Ordering,
+        M: FnOnce(&C, K) -> T,
+    {
+        self.bump_generation();
+        trace_span!(self.counters, "get_or_insert_with", len = self.len);
+        // the descent can't hand out a reference to the key, the nodes above
+        // still have their sizes adjusted once it's back. it leaves the way
+        // to the key behind instead, which is followed again without comparing
+        let mut path = Vec::new();
+        let key_check = self.key_check.clone();
+        // a probe or make panicking in an empty tree leaves the new root empty
+        let inserted = self.shrinking_root(|tree| {
+            let (cmp, counters, degree) = (&tree.cmp, &tree.counters, tree.degree);
+            let make = |wanted| {
+                let key = make(cmp, wanted);
+                admit(key_check.as_ref(), &key);
+                key
+            };
+            let mut probe = |stored: &T, wanted: &K| {
+                counters.bump(Counter::Comparison);
+                probe(cmp, stored, wanted)
+            };
+            let root = tree
+                .root
+                .get_or_insert_with(|| Box::new(BtreeNode::new_leaf(degree)));
+            if root.is_full(degree) {
+                let old_root = mem::replace(root.as_mut(), BtreeNode::Leaf { keys: Vec::new() });
+                **root = BtreeNode::new_root(old_root, degree);
+                observe!(counters, TreeEvent::RootGrew);
+                root.split_child(0, degree, counters);
+            }
+            root.find_or_insert(wanted, &mut probe, make, degree, counters, &mut path)
+        });
+        self.len += usize::from(inserted);
+        if inserted {
+            self.notify_inserted_at(&path);
+        }
+        (path, inserted)
+    }
+
+    // the key the indexes from the root lead to, for the parts of it the
+    // comparator never looks at. path comes from find_or_insert_by
+    fn key_at_mut(&mut self, path: &[usize]) -> &mut T {
+        self.bump_generation();
+        let (last, above) = path.split_last().expect("a path leads somewhere");
+        let mut node = self
+            .root
+            .as_deref_mut()
+            .expect("the path leads into the tree");
+        for &i in above {
+            node = &mut node.internal_parts().1[i];
+        }
+        &mut node.keys_mut()[*last]
+    }
+
+    // iterate over all keys in ascending order
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        Iter::new(self.root.as_deref(), &self.cmp)
+    }
+
+    // the keys in order as the slices of the nodes they sit in, see NodeSlices
+    pub fn iter_node_slices(&self) -> NodeSlices<'_, T> {
+        NodeSlices::new(self.root.as_deref())
+    }
+
+    // the keys in order, in batches of size of them (the last one may be
+    // smaller). size has to be at least 1
+    pub fn iter_chunks(&self, size: usize) -> Chunks<'_, T> {
+        assert!(size > 0, "chunks must hold at least one key");
+        Chunks::new(self.iter_node_slices(), size)
+    }
+
+    // the k smallest keys, smallest first
+    // only the left fringe is walked, no back end is set up like for iter(),
+    // and nothing at all happens for k = 0
+    pub fn smallest(&self, k: usize) -> impl Iterator<Item = &T> {
+        let mut path = match k {
+            0 => Path::empty(),
+            _ => Path::first_where(self.root.as_deref(), |_| true),
+        };
+        std::iter::from_fn(move || {
+            let key = path.current()?;
+            path.next();
+            Some(key)
+        })
+        .take(k)
+    }
+
+    // the k largest keys, largest first, walking in from the right fringe
+    pub fn largest(&self, k: usize) -> impl Iterator<Item = &T> {
+        let mut path = match k {
+            0 => Path::empty(),
+            _ => Path::last_where(self.root.as_deref(), |_| true),
+        };
+        std::iter::from_fn(move || {
+            let key = path.current()?;
+            path.prev();
+            Some(key)
+        })
+        .take(k)
+    }
+
+    // call f on every key in order
+    // plain recursion over the nodes: unlike iter() it keeps no path and
+    // allocates nothing, which makes it the cheapest way to visit everything
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        self.try_for_each(|key| {
+            f(key);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    // call f on every key in order until it breaks, the subtrees not visited by
+    // then are skipped entirely. returns the value f broke with, None if it never did
+    pub fn try_for_each<E, F: FnMut(&T) -> ControlFlow<E>>(&self, mut f: F) -> Option<E> {
+        match self.root.as_ref()?.try_for_each(&mut f) {
+            ControlFlow::Break(value) => Some(value),
+            ControlFlow::Continue(()) => None,
+        }
+    }
+
+    // call f on every key in reverse order, also without allocating
+    pub fn for_each_rev<F: FnMut(&T)>(&self, mut f: F) {
+        if let Some(root) = &self.root {
+            root.for_each_rev(&mut f);
+        }
+    }
+
+    // a cursor on the first key, to be moved around with seek, next and prev
+    pub fn cursor(&self) -> Cursor<'_, T, C> {
+        Cursor::new(self)
+    }
+
+    // a cursor on the first key that can also remove and insert keys where it stands
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T, C> {
+        CursorMut::new(self)
+    }
+
+    // iterate over the keys inside the range in ascending order
+    // bounds are compared with the tree's comparator, a range whose start lies
+    // after its end is simply empty
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T, C>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.range_with(range, |stored, bound| {
+            self.cmp.compare(stored.borrow(), bound)
+        })
+    }
+
+    // one page of at most limit keys from start on, plus the key to pass as
+    // Bound::Excluded start of the next page, None once nothing comes after the page
+    // seeks to start in O(log n) and never walks past the page
+    // a zero limit gives an empty page and no continuation
+    pub fn scan<Q: ?Sized>(&self, start: Bound<&Q>, limit: usize) -> (Vec<&T>, Option<&T>)
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        if limit == 0 {
+            return (Vec::new(), None);
+        }
+        let mut path = Path::first_where(self.root.as_deref(), |stored: &T| match start {
+            Bound::Included(start) => self.cmp.compare(stored.borrow(), start) != Ordering::Less,
+            Bound::Excluded(start) => self.cmp.compare(stored.borrow(), start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        });
+        let mut page = Vec::with_capacity(limit);
+        while let Some(key) = path.current() {
+            page.push(key);
+            path.next();
+            if page.len() == limit {
+                break;
+            }
+        }
+        // only hand out a continuation if the next page won't be empty
+        let next = path.current().and(page.last().copied());
+        (page, next)
+    }
+
+    // range where compare tells how a stored key relates to a bound
+    fn range_with<B: ?Sized, R, F>(&self, range: R, compare: F) -> Range<'_, T, C>
+    where
+        R: RangeBounds<B>,
+        F: Fn(&T, &B) -> Ordering,
+    {
+        let after_start = |key: &T| match range.start_bound() {
+            Bound::Included(start) => compare(key, start) != Ordering::Less,
+            Bound::Excluded(start) => compare(key, start) == Ordering::Greater,
+            Bound::Unbounded => true,
+        };
+        let before_end = |key: &T| match range.end_bound() {
+            Bound::Included(end) => compare(key, end) != Ordering::Greater,
+            Bound::Excluded(end) => compare(key, end) == Ordering::Less,
+            Bound::Unbounded => true,
+        };
+        Range::new(self.root.as_deref(), &self.cmp, after_start, before_end)
+    }
+
+    // range_with handing out the keys mutably, for the parts of them the
+    // comparator never looks at (a map's values)
+    fn range_mut_with<B: ?Sized, R, F>(
+        &mut self,
+        range: R,
+        compare: F,
+    ) -> RangeMut<'_, T, impl FnMut(&T) -> Ordering>
+    where
+        R: RangeBounds<B>,
+        F: Fn(&C, &T, &B) -> Ordering,
+    {
+        self.bump_generation();
+        let cmp = &self.cmp;
+        let locate = move |key: &T| {
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => compare(cmp, key, start) != Ordering::Less,
+                Bound::Excluded(start) => compare(cmp, key, start) == Ordering::Greater,
+                Bound::Unbounded => true,
+            };
+            let before_end = match range.end_bound() {
+                Bound::Included(end) => compare(cmp, key, end) != Ordering::Greater,
+                Bound::Excluded(end) => compare(cmp, key, end) == Ordering::Less,
+                Bound::Unbounded => true,
+            };
+            match (after_start, before_end) {
+                (false, _) => Ordering::Less,
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+            }
+        };
+        RangeMut::new(self.root.as_deref_mut(), locate)
+    }
+
+    // the minimum degree the tree was built with
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    // number of keys stored
+    pub fn len(&self) -> usize {
+        self.len
     }
 
     // check if the tree is empty
-    // used in the tests
-    #[allow(dead_code)]
     pub fn is_empty(&self) -> bool {
         self.root.is_none()
     }
 
-    // print the entire tree structure
-    pub fn print_tree(&self) {
+    // check that the tree satisfies every b-tree invariant
+    // returns a description of the first violation found
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(root) = &self.root {
+            root.validate(&self.cmp, self.degree, 0, None, None, &mut None)?;
+        }
+        let count = self.iter().count();
+        if count != self.len {
+            return Err(format!("tree holds {} keys but len is {}", count, self.len));
+        }
+        Ok(())
+    }
+
+    // heap bytes used by the tree's nodes, counting the full capacity of every
+    // key and children vector (whatever the keys themselves own is not included)
+    pub fn memory_usage(&self) -> usize {
         match &self.root {
-            None => println!("Empty tree"),
-            Some(root) => {
-                println!("B-tree (degree {}):", self.degree);
-                root.print_tree(0);
+            None => 0,
+            Some(root) => size_of::<BtreeNode<T>>() + root.memory_usage(),
+        }
+    }
+
+    // give back the spare capacity nodes keep around after heavy deletion
+    // keys and structure stay exactly as they are, nodes that grow again later
+    // simply reallocate. returns the number of bytes reclaimed
+    pub fn shrink_to_fit(&mut self) -> usize {
+        match &mut self.root {
+            None => 0,
+            Some(root) => root.shrink_to_fit(),
+        }
+    }
+
+    // number of levels, all leaves sit at the same depth (0 for an empty tree)
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            height += 1;
+            node = current.children().map(|children| &children[0]);
+        }
+        height
+    }
+
+    // number of nodes in the tree
+    pub fn node_count(&self) -> usize {
+        struct Count(usize);
+        impl<T> Visit<T> for Count {
+            fn enter_node(&mut self, _info: &NodeInfo) {
+                self.0 += 1;
+            }
+        }
+        let mut count = Count(0);
+        self.walk(&mut count, Order::PreOrder);
+        count.0
+    }
+
+    // rebuild the tree from scratch with every node three quarters full
+    // after lots of deletions nodes hover around half full, this gives close
+    // to the smallest height and node count for the same keys and degree
+    // while leaving room for new keys, see rebuild_with_fill
+    // runs in O(n), keys are moved and never cloned or compared
+    pub fn rebuild(&mut self) {
+        self.rebuild_with_fill(REBUILD_FILL);
+    }
+
+    // rebuild with nodes filled to fill of the keys they can hold, fill in
+    // (0, 1]. 1 packs them full for the smallest tree, which then splits on
+    // the first insert into any node. nodes never drop under the minimum the
+    // degree sets, so a fill under 1 / 2 is the same as 1 / 2
+    pub fn rebuild_with_fill(&mut self, fill: f64) {
+        assert!(fill > 0.0 && fill <= 1.0, "fill must be in (0, 1]!");
+        let keys = self.take_sorted();
+        self.fill_sorted_to(keys, fill);
+    }
+
+    // rebuild the tree into the one shape the bulk loader gives its keys and
+    // degree, however they got there. two trees holding the same keys with the
+    // same degree are structurally_eq once both are canonicalized
+    pub fn canonicalize(&mut self) {
+        self.rebuild_with_fill(1.0);
+    }
+
+    // node by node the same shape holding the same keys, where two keys are
+    // the same if the comparator says so. trees with equal contents usually
+    // aren't, unless both were built the same way (or canonicalized)
+    pub fn structurally_eq(&self, other: &Btree<T, C>) -> bool {
+        let eq = |a: &T, b: &T| self.cmp.compare(a, b) == Ordering::Equal;
+        match (&self.root, &other.root) {
+            (Some(ours), Some(theirs)) => ours.same_shape(theirs, &eq),
+            (ours, theirs) => ours.is_none() && theirs.is_none(),
+        }
+    }
+
+    // a copy of the tree with a different degree, built bottom-up from the
+    // keys in order (so in O(n), with every node packed full)
+    pub fn with_degree(&self, new_degree: usize) -> Result<Btree<T, C>, InvalidDegree>
+    where
+        T: Clone,
+        C: Clone,
+    {
+        if new_degree < 2 {
+            return Err(InvalidDegree(new_degree));
+        }
+        let mut copy = Btree::empty(new_degree, self.cmp.clone(), self.key_check.clone());
+        copy.fill_sorted(self.iter().cloned().collect());
+        Ok(copy)
+    }
+
+    // same as with_degree, but the keys are moved over instead of cloned
+    pub fn into_degree(mut self, new_degree: usize) -> Result<Btree<T, C>, InvalidDegree> {
+        if new_degree < 2 {
+            return Err(InvalidDegree(new_degree));
+        }
+        let keys = self.take_sorted();
+        self.degree = new_degree;
+        self.fill_sorted(keys);
+        Ok(self)
+    }
+
+    // break the tree into n trees holding consecutive runs of its keys, sizes
+    // differing by at most one (the first ones get the extra keys), every key
+    // of piece i comes before every key of piece i + 1. keys are moved, never
+    // cloned, and each piece is bulk built. n has to be at least 1
+    pub fn split_evenly(mut self, n: usize) -> Vec<Btree<T, C>>
+    where
+        C: Clone,
+    {
+        assert!(n > 0, "can't split a tree into 0 pieces");
+        let len = self.len;
+        let mut keys = self.take_sorted().into_iter();
+        (0..n)
+            .map(|i| {
+                let mut piece = Btree::empty(self.degree, self.cmp.clone(), self.key_check.clone());
+                let size = len / n + usize::from(i < len % n);
+                piece.fill_sorted(keys.by_ref().take(size).collect());
+                piece
+            })
+            .collect()
+    }
+
+    // move every key of other into this tree in O(n + m): both trees are
+    // drained in order, the two runs merged and the tree bulk built again,
+    // however the key ranges overlap. a key stored in both keeps this tree's
+    // copy, like insert does. other is ordered by this tree's comparator
+    // panics if the key check turns one of other's keys down, see
+    // try_extend_from_tree
+    pub fn extend_from_tree(&mut self, other: Btree<T, C>) {
+        if let Err(invalid) = self.try_extend_from_tree(other) {
+            panic!("{}", invalid);
+        }
+    }
+
+    // extend_from_tree, handing back the first of other's keys (in order) the
+    // key check turns down instead of panicking. nothing is moved then
+    pub fn try_extend_from_tree(&mut self, mut other: Btree<T, C>) -> Result<(), InvalidKey> {
+        self.check_keys(other.iter())?;
+        self.merge_sorted(other.take_sorted());
+        Ok(())
+    }
+
+    // move every key of data into the tree, in any order and with repeats
+    // (the first of equal keys is the one stored, and a key the tree holds
+    // already keeps the stored copy, like insert). a batch of at least
+    // 1 / REBUILD_BATCH_FRACTION of the tree's size is sorted and merged
+    // with the stored keys in one pass, O((n + k) + k log k) for n keys and
+    // a batch of k, anything smaller is inserted key by key in O(k log n)
+    // returns how many keys were added. panics if the key check turns one of
+    // them down, before any goes in
+    pub fn extend_unsorted(&mut self, mut data: Vec<T>) -> usize {
+        if let Err(invalid) = self.check_keys(&data) {
+            panic!("{}", invalid);
+        }
+        if data.len() < self.len / REBUILD_BATCH_FRACTION {
+            return data
+                .into_iter()
+                .map(|key| self.insert_tracked(key, None, &mut NoTrace))
+                .filter(|added| *added)
+                .count();
+        }
+        let cmp = &self.cmp;
+        // stable, so of equal keys the first one stays in front and is kept
+        data.sort_by(|a, b| cmp.compare(a, b));
+        data.dedup_by(|later, earlier| cmp.compare(later, earlier) == Ordering::Equal);
+        self.merge_sorted(data)
+    }
+
+    // merge keys (strictly sorted) with the stored ones in one pass and bulk
+    // build the tree again, returning how many of them were new
+    fn merge_sorted(&mut self, keys: Vec<T>) -> usize {
+        // where every key goes is settled before any leave the tree, the
+        // comparator may panic
+        enum Source {
+            Ours,
+            Theirs,
+            // one of keys that this tree has as well
+            Dropped,
+        }
+        let cmp = &self.cmp;
+        let mut order = Vec::with_capacity(self.len + keys.len());
+        let mut theirs = keys.iter().peekable();
+        for key in self.iter() {
+            while theirs
+                .next_if(|other| cmp.compare(other, key) == Ordering::Less)
+                .is_some()
+            {
+                order.push(Source::Theirs);
             }
+            if theirs
+                .next_if(|other| cmp.compare(other, key) == Ordering::Equal)
+                .is_some()
+            {
+                order.push(Source::Dropped);
+            }
+            order.push(Source::Ours);
+        }
+        order.extend(theirs.map(|_| Source::Theirs));
+
+        let mut ours = self.take_sorted().into_iter();
+        let mut theirs = keys.into_iter();
+        let mut merged = Vec::with_capacity(order.len());
+        // which of the merged keys are new, for the subscriber
+        let mut added = Vec::new();
+        let mut count = 0;
+        for from in order {
+            match from {
+                Source::Ours => merged.extend(ours.next()),
+                Source::Theirs => {
+                    merged.extend(theirs.next());
+                    count += 1;
+                }
+                Source::Dropped => drop(theirs.next()),
+            }
+            if self.watched() && !matches!(from, Source::Dropped) {
+                added.push(matches!(from, Source::Theirs));
+            }
+        }
+        self.fill_sorted(merged);
+        self.notify_rebuilt(&[], &added);
+        count
+    }
+
+    // empty the tree, handing back all keys in order
+    fn take_sorted(&mut self) -> Vec<T> {
+        self.bump_generation();
+        let mut keys = Vec::with_capacity(self.len);
+        if let Some(root) = self.root.take() {
+            root.drain_into(&mut keys);
+        }
+        self.len = 0;
+        keys
+    }
+
+    // bulk build an (empty) tree out of strictly sorted keys
+    fn fill_sorted(&mut self, keys: Vec<T>) {
+        self.fill_sorted_to(keys, 1.0);
+    }
+
+    // fill_sorted with nodes filled to fill, see rebuild_with_fill
+    fn fill_sorted_to(&mut self, keys: Vec<T>, fill: f64) {
+        self.bump_generation();
+        debug_assert!(self.root.is_none());
+        self.len = keys.len();
+        self.root = BtreeNode::bulk_build(keys, self.degree, fill).map(Box::new);
+    }
+
+    // remove every stored key that appears in keys, returns how many were removed
+    // keys gets sorted, the rest is delete_sorted
+    pub fn delete_many(&mut self, keys: &mut [T]) -> usize {
+        keys.sort_by(|a, b| self.cmp.compare(a, b));
+        self.delete_batch(keys)
+    }
+
+    // remove every stored key that appears in keys, which have to be sorted by
+    // the tree's comparator already (repeats are fine). returns how many were
+    // removed, or which key comes before the one ahead of it, nothing is
+    // removed then. a batch that is large next to the tree is handled in one
+    // sweep: the tree and the batch are walked side by side in order, marking
+    // the keys that go, then the tree is drained and the rest bulk built again
+    // in O(n + k). a small batch is cheaper to delete key by key
+    pub fn delete_sorted(&mut self, keys: &[T]) -> Result<usize, NotSorted> {
+        let out_of_order = (1..keys.len())
+            .find(|&i| self.cmp.compare(&keys[i - 1], &keys[i]) == Ordering::Greater);
+        match out_of_order {
+            Some(i) => Err(NotSorted(i)),
+            None => Ok(self.delete_batch(keys)),
+        }
+    }
+
+    fn delete_batch(&mut self, keys: &[T]) -> usize {
+        let before = self.len;
+        if keys.len() < self.len / REBUILD_BATCH_FRACTION {
+            for key in keys.iter() {
+                self.remove_with(|cmp, stored| cmp.compare(stored, key));
+            }
+        } else {
+            // which keys stay is settled before any leave the tree, the
+            // comparator may panic
+            let mut batch = keys.iter().peekable();
+            let cmp = &self.cmp;
+            let keep: Vec<bool> = self
+                .iter()
+                .map(|key| {
+                    while batch
+                        .next_if(|probe| cmp.compare(probe, key) == Ordering::Less)
+                        .is_some()
+                    {}
+                    batch
+                        .peek()
+                        .is_none_or(|probe| cmp.compare(probe, key) != Ordering::Equal)
+                })
+                .collect();
+            self.keep_marked(keep);
         }
+        before - self.len
+    }
+
+    // drain the tree and bulk build it again out of the keys whose mark (one
+    // per key, in order) is true
+    fn keep_marked(&mut self, keep: Vec<bool>) {
+        let watched = self.watched();
+        let (mut kept, mut removed) = (Vec::new(), Vec::new());
+        for (key, keep) in self.take_sorted().into_iter().zip(keep) {
+            match keep {
+                true => kept.push(key),
+                false if watched => removed.push(key),
+                false => {}
+            }
+        }
+        self.fill_sorted(kept);
+        self.notify_rebuilt(&removed, &[]);
+    }
+
+    // remove every key inside the range, handing them back in order
+    // the keys are taken out before the first one is handed back, so the whole
+    // range is gone even if the iterator is dropped early. how many keys that
+    // is comes from their ranks, a run that is large next to the tree is cut out
+    // of the drained keys and the rest bulk built again (like delete_many),
+    // a small one is removed key by key
+    pub fn drain_range<Q: ?Sized, R: RangeBounds<Q>>(&mut self, range: R) -> impl Iterator<Item = T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let (start, end) = {
+            let cmp = &self.cmp;
+            let start = self.partition_point(|key| match range.start_bound() {
+                Bound::Included(start) => cmp.compare(key.borrow(), start) == Ordering::Less,
+                Bound::Excluded(start) => cmp.compare(key.borrow(), start) != Ordering::Greater,
+                Bound::Unbounded => false,
+            });
+            let end = self.partition_point(|key| match range.end_bound() {
+                Bound::Included(end) => cmp.compare(key.borrow(), end) != Ordering::Greater,
+                Bound::Excluded(end) => cmp.compare(key.borrow(), end) == Ordering::Less,
+                Bound::Unbounded => true,
+            });
+            // a range starting after its end is empty
+            (start, end.max(start))
+        };
+        let drained = if end - start < self.len / REBUILD_BATCH_FRACTION {
+            // the key after a removed one takes over its rank
+            (start..end)
+                .map(|_| {
+                    let mut path = self.rank_path(start);
+                    self.remove_at(&mut path)
+                })
+                .collect()
+        } else {
+            let mut keys = self.take_sorted();
+            let after = keys.split_off(end);
+            let drained = keys.split_off(start);
+            keys.extend(after);
+            self.fill_sorted(keys);
+            self.notify_rebuilt(&drained, &[]);
+            drained
+        };
+        drained.into_iter()
+    }
+
+    // the indexes leading from the root to the key of the given rank
+    // (0 is the first key), rank has to be below len
+    fn rank_path(&self, mut rank: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            let Some(children) = current.children() else {
+                path.push(rank);
+                break;
+            };
+            // skip whole subtrees (and the key after each) before the rank
+            let mut i = 0;
+            while rank > children[i].len() {
+                rank -= children[i].len() + 1;
+                i += 1;
+            }
+            path.push(i);
+            if rank == children[i].len() {
+                // it's key i of this node
+                break;
+            }
+            node = Some(&children[i]);
+        }
+        path
     }
 
     // Delete a key from the tree
-    pub fn delete(&mut self, key: &T) -> bool {
-        match &mut self.root {
-            None => false, // tree is empty
+    pub fn delete<Q: ?Sized>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.take(key).is_some()
+    }
+
+    // remove the key equal to key and hand the stored one back
+    pub fn take<Q: ?Sized>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.remove_with(|cmp, stored| cmp.compare(stored.borrow(), key))
+    }
+
+    // delete with a probe, the comparator is handed to the probe since the
+    // tree itself is mutably borrowed for the whole deletion
+    fn remove_with<F: FnMut(&C, &T) -> Ordering>(&mut self, probe: F) -> Option<T> {
+        self.remove_traced_with(probe, &mut NoTrace)
+    }
+
+    // remove_with, telling trace every step
+    fn remove_traced_with<F: FnMut(&C, &T) -> Ordering>(
+        &mut self,
+        mut probe: F,
+        trace: &mut impl Tracer<T>,
+    ) -> Option<T> {
+        self.bump_generation();
+        trace_span!(self.counters, "delete", len = self.len);
+        // merging the root's last key away on the way down and panicking
+        // further down leaves the root empty
+        let removed = self.shrinking_root(|tree| match &mut tree.root {
+            None => None, // tree is empty
             Some(root) => {
-                let found = root.delete(key);
-
-                // special case: if root becomes empty after deletion
-                if root.keys.is_empty() {
-                    if root.is_leaf {
-                        // tree becomes empty
-                        self.root = None;
-                    } else {
-                        // root had only one key, and it was deleted
-                        // the first child becomes the new root (tree height decreases)
-                        self.root = Some(root.children.remove(0));
-                    }
-                }
+                let mut counted = |stored: &T| {
+                    tree.counters.bump(Counter::Comparison);
+                    probe(&tree.cmp, stored)
+                };
+                root.delete(&mut counted, tree.degree, &tree.counters, trace)
+            }
+        });
+        self.len -= usize::from(removed.is_some());
+        if let Some(removed) = &removed {
+            self.notify_removed(removed);
+        }
+        removed
+    }
 
-                found
+    // remove the key an index path from the root leads to
+    fn remove_at(&mut self, path: &mut [usize]) -> T {
+        self.bump_generation();
+        trace_span!(self.counters, "delete", len = self.len);
+        let removed = self
+            .root
+            .as_mut()
+            .unwrap()
+            .delete_path(path, self.degree, &self.counters);
+        self.len -= 1;
+        self.shrink_root();
+        self.notify_removed(&removed);
+        removed
+    }
+
+    // the first key in the tree's order (the smallest one for natural ordering)
+    pub fn first(&self) -> Option<&T> {
+        Path::first_where(self.root.as_deref(), |_| true).current()
+    }
+
+    // the last key in the tree's order (the largest one for natural ordering)
+    pub fn last(&self) -> Option<&T> {
+        Path::last_where(self.root.as_deref(), |_| true).current()
+    }
+
+    // remove and return the first key
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.bump_generation();
+        trace_span!(self.counters, "pop_first", len = self.len);
+        let first = self
+            .root
+            .as_mut()
+            .map(|root| root.delete_min(self.degree, &self.counters, &mut NoTrace));
+        self.len -= usize::from(first.is_some());
+        self.shrink_root();
+        if let Some(first) = &first {
+            self.notify_removed(first);
+        }
+        first
+    }
+
+    // remove and return the last key
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.bump_generation();
+        trace_span!(self.counters, "pop_last", len = self.len);
+        let last = self
+            .root
+            .as_mut()
+            .map(|root| root.delete_max(self.degree, &self.counters, &mut NoTrace));
+        self.len -= usize::from(last.is_some());
+        self.shrink_root();
+        if let Some(last) = &last {
+            self.notify_removed(last);
+        }
+        last
+    }
+
+    // the last key that does not come after key in the tree's order
+    // (the largest key <= key for natural ordering, the smallest key >= key when reversed)
+    pub fn floor<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        Path::last_where(self.root.as_deref(), |stored: &T| {
+            self.cmp.compare(stored.borrow(), key) != Ordering::Greater
+        })
+        .current()
+    }
+
+    // the first key that does not come before key in the tree's order
+    // (the smallest key >= key for natural ordering, the largest key <= key when reversed)
+    pub fn ceiling<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        Path::first_where(self.root.as_deref(), |stored: &T| {
+            self.cmp.compare(stored.borrow(), key) != Ordering::Less
+        })
+        .current()
+    }
+
+    // the first key (in the tree's order) for which pred is true
+    // pred has to be monotone over the keys: false for a prefix, true for the
+    // rest. each node is binary searched and only the child on the boundary is
+    // entered, so it's O(log n * log t) calls to pred. for a predicate that isn't
+    // monotone the result is unspecified (but always some key or None)
+    pub fn find_first_true<F: Fn(&T) -> bool>(&self, pred: F) -> Option<&T> {
+        Path::first_where(self.root.as_deref(), pred).current()
+    }
+
+    // the number of keys pred is true for, with pred monotone like for
+    // find_first_true (true for a prefix of the keys here, the way
+    // slice::partition_point wants it). one descent: in every node the keys
+    // before the boundary count along with the subtrees left of them, and only
+    // the child on the boundary is entered
+    pub fn partition_point<F: Fn(&T) -> bool>(&self, pred: F) -> usize {
+        let mut count = 0;
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            let i = current.keys().partition_point(&pred);
+            count += i;
+            node = current.children().map(|children| {
+                count += children[..i].iter().map(|child| child.len()).sum::<usize>();
+                &children[i]
+            });
+        }
+        count
+    }
+
+    // where key is in the order of the keys, like slice::binary_search on them:
+    // Ok(rank) if it's stored, Err(rank) with the rank it would get if inserted
+    // one descent, the keys and subtrees left of the path are counted by size
+    pub fn locate<Q: ?Sized>(&self, key: &Q) -> Result<usize, usize>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut probe = |stored: &T| {
+            self.counters.bump(Counter::Comparison);
+            self.cmp.compare(stored.borrow(), key)
+        };
+        let mut rank = 0;
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            self.counters.bump(Counter::NodeVisit);
+            let found = current.lower_bound(&mut probe);
+            let i = found.unwrap_or_else(|i| i);
+            let children = current.children();
+            // the keys and subtrees before key i (or child i)
+            rank += i + children.map_or(0, |children| {
+                children[..i].iter().map(|child| child.len()).sum::<usize>()
+            });
+            if found.is_ok() {
+                return Ok(rank + children.map_or(0, |children| children[i].len()));
+            }
+            node = children.map(|children| &children[i]);
+        }
+        Err(rank)
+    }
+
+    // the keys whose ranks (0 is the first key) fall inside ranks, in order
+    // the first one is found by its rank in a single descent, nothing before
+    // it is walked. ranks past the end are cut off, an empty range gives nothing
+    pub fn range_by_rank(&self, ranks: std::ops::Range<usize>) -> impl Iterator<Item = &T> {
+        let end = ranks.end.min(self.len);
+        let start = ranks.start.min(end);
+        let mut path = match start < end {
+            true => Path::from_indices(self.root.as_deref(), &self.rank_path(start)),
+            false => Path::empty(),
+        };
+        (start..end).map(move |_| {
+            let key = path.current().expect("the rank is below len");
+            path.next();
+            key
+        })
+    }
+
+    // the key at the q-th quantile, q from 0 (the first key) to 1 (the last)
+    // exact, it's the key of rank floor(q * (len - 1)) found by a single
+    // descent. None for an empty tree, an error for q outside [0, 1] (or NaN)
+    pub fn quantile(&self, q: f64) -> Result<Option<&T>, InvalidQuantile> {
+        let rank = self.quantile_rank(q)?;
+        Ok(self.root.as_deref().map(|root| root.select(rank)))
+    }
+
+    // the keys at every quantile in qs, lined up with qs, found in one sweep
+    // over the tree instead of one descent each. empty for an empty tree
+    pub fn quantiles(&self, qs: &[f64]) -> Result<Vec<&T>, InvalidQuantile> {
+        let mut ranks = qs
+            .iter()
+            .enumerate()
+            .map(|(i, q)| Ok((self.quantile_rank(*q)?, i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(root) = self.root.as_deref() else {
+            return Ok(Vec::new());
+        };
+        ranks.sort_unstable();
+        let sorted: Vec<usize> = ranks.iter().map(|(rank, _)| *rank).collect();
+        let mut found = Vec::with_capacity(ranks.len());
+        root.multi_select(&sorted, 0, &mut found);
+
+        // back to the order the quantiles were asked in
+        let mut answers = found.clone();
+        for ((_, i), key) in ranks.into_iter().zip(found) {
+            answers[i] = key;
+        }
+        Ok(answers)
+    }
+
+    // parts - 1 splitter keys dividing the keys into parts buckets whose sizes
+    // differ by at most one, each splitter is the first key of the bucket after it
+    // with parts >= len every key but the first is a splitter (one key per
+    // bucket), parts <= 1 needs no splitters. one sweep over the tree
+    pub fn partition_points(&self, parts: usize) -> Vec<&T> {
+        let parts = parts.min(self.len);
+        let Some(root) = self.root.as_deref().filter(|_| parts > 1) else {
+            return Vec::new();
+        };
+        let ranks: Vec<usize> = (1..parts).map(|i| i * self.len / parts).collect();
+        let mut splitters = Vec::with_capacity(ranks.len());
+        root.multi_select(&ranks, 0, &mut splitters);
+        splitters
+    }
+
+    fn quantile_rank(&self, q: f64) -> Result<usize, InvalidQuantile> {
+        if !(0.0..=1.0).contains(&q) {
+            return Err(InvalidQuantile(q));
+        }
+        Ok((q * self.len.saturating_sub(1) as f64).floor() as usize)
+    }
+
+    // special case: if root becomes empty after deletion
+    // run f on the tree and shrink the root after it, even if f panics (in a
+    // comparison), the panic goes on once the root is fixed
+    fn shrinking_root<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.shrink_root();
+        result.unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+
+    fn shrink_root(&mut self) {
+        if let Some(root) = &mut self.root
+            && root.keys().is_empty()
+        {
+            match root.as_mut() {
+                // tree becomes empty
+                BtreeNode::Leaf { .. } => self.root = None,
+                // root had only one key, and it was deleted
+                // the first child becomes the new root (tree height decreases)
+                BtreeNode::Internal { children, .. } => {
+                    self.root = Some(Box::new(children.remove(0)));
+                    observe!(self.counters, TreeEvent::RootShrank);
+                }
             }
         }
     }
 }
+
+// std's set hands its keys out in order, so they're bulk loaded in O(n)
+// into a tree of DEFAULT_DEGREE, without a single comparison
+impl<T: Ord> From<BTreeSet<T>> for Btree<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        let mut tree = Btree::new(DEFAULT_DEGREE);
+        tree.fill_sorted(set.into_iter().collect());
+        tree
+    }
+}
+
+impl<T: Ord> From<Btree<T>> for BTreeSet<T> {
+    fn from(mut tree: Btree<T>) -> Self {
+        tree.take_sorted().into_iter().collect()
+    }
+}
+
+// printing and formatting only need T: Debug, the tree itself doesn't
+impl<T: Debug> Debug for BtreeNode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BtreeNode::Leaf { keys } => f.debug_struct("Leaf").field("keys", keys).finish(),
+            BtreeNode::Internal { keys, children, .. } => f
+                .debug_struct("Internal")
+                .field("keys", keys)
+                .field("children", children)
+                .finish(),
+        }
+    }
+}
+
+impl<T: Debug, C> Debug for Btree<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Btree")
+            .field("root", &self.root)
+            .field("degree", &self.degree)
+            .finish()
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a Btree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C>;
+
+    fn into_iter(self) -> Iter<'a, T, C> {
+        Iter::new(self.root.as_deref(), &self.cmp)
+    }
+}
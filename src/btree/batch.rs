@@ -0,0 +1,171 @@
+use std::cmp::Ordering;
+
+use super::key_check::check_key;
+use super::trace::NoTrace;
+use super::{Btree, REBUILD_BATCH_FRACTION};
+use crate::compare::Compare;
+use crate::error::{BatchError, NotSorted};
+
+/*
+** a script of inserts and deletes in key order applied in one go, the way a
+** replication log is replayed. the whole script is checked for order before
+** anything happens, so a script out of order changes nothing
+**
+** ops on the same key run in the order they come in. each op does what insert
+** or delete would have: an insert of a key the tree holds (by then) keeps the
+** stored one and is ignored, so is a delete of a key it doesn't hold
+**
+** a script that is large next to the tree is merged with it: the stored keys
+** and the script are walked side by side in order, working out for every key
+** whether it's kept, then the tree is drained and bulk built again out of the
+** merged run, O(n + k) for a tree of n keys and k ops. a small script is
+** cheaper to apply op by op
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<T> {
+    Insert(T),
+    Delete(T),
+}
+
+impl<T> Op<T> {
+    pub fn key(&self) -> &T {
+        match self {
+            Op::Insert(key) | Op::Delete(key) => key,
+        }
+    }
+
+    fn into_key(self) -> T {
+        match self {
+            Op::Insert(key) | Op::Delete(key) => key,
+        }
+    }
+}
+
+// what a batch did, every op is counted once
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub inserted: usize,
+    pub deleted: usize,
+    // inserts of keys already there and deletes of keys that weren't
+    pub ignored: usize,
+}
+
+// where the next key of the merged run comes from
+enum Step {
+    // the next stored key, kept or dropped
+    Stored(bool),
+    // the key of the next op, kept or dropped
+    Op(bool),
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // apply ops, sorted by key in the tree's order (several ops on one key are
+    // fine). a script out of order is an error naming the first op out of
+    // place, and so is an insert of a key the key check (see validate_keys)
+    // turns down. either way nothing is applied
+    pub fn apply_batch(
+        &mut self,
+        ops: impl IntoIterator<Item = Op<T>>,
+    ) -> Result<BatchSummary, BatchError> {
+        let ops: Vec<Op<T>> = ops.into_iter().collect();
+        let out_of_order = (1..ops.len())
+            .find(|&i| self.cmp.compare(ops[i - 1].key(), ops[i].key()) == Ordering::Greater);
+        if let Some(i) = out_of_order {
+            return Err(NotSorted(i).into());
+        }
+        for (i, op) in ops.iter().enumerate() {
+            if let Op::Insert(key) = op {
+                check_key(self.key_check.as_ref(), i, key)?;
+            }
+        }
+
+        let mut summary = BatchSummary::default();
+        if ops.len() < self.len / REBUILD_BATCH_FRACTION {
+            for op in ops {
+                let (done, count) = match op {
+                    Op::Insert(key) => (
+                        self.insert_tracked(key, None, &mut NoTrace),
+                        &mut summary.inserted,
+                    ),
+                    Op::Delete(key) => (self.delete(&key), &mut summary.deleted),
+                };
+                match done {
+                    true => *count += 1,
+                    false => summary.ignored += 1,
+                }
+            }
+            return Ok(summary);
+        }
+
+        // the merge is worked out before any key leaves the tree, the
+        // comparator may panic
+        let cmp = &self.cmp;
+        let mut steps = Vec::with_capacity(self.len + ops.len());
+        let mut stored = self.iter().peekable();
+        let mut i = 0;
+        while i < ops.len() {
+            let key = ops[i].key();
+            while stored
+                .next_if(|stored| cmp.compare(stored, key) == Ordering::Less)
+                .is_some()
+            {
+                steps.push(Step::Stored(true));
+            }
+            let in_tree = stored
+                .next_if(|stored| cmp.compare(stored, key) == Ordering::Equal)
+                .is_some();
+            let group = i + ops[i..]
+                .iter()
+                .take_while(|op| cmp.compare(op.key(), key) == Ordering::Equal)
+                .count();
+
+            // which copy of the key is held after each op: None for none,
+            // Some(None) for the stored one, Some(Some(j)) for op j's
+            let mut held = in_tree.then_some(None);
+            for (j, op) in ops.iter().enumerate().take(group).skip(i) {
+                match (op, held) {
+                    (Op::Insert(_), None) => {
+                        held = Some(Some(j));
+                        summary.inserted += 1;
+                    }
+                    (Op::Delete(_), Some(_)) => {
+                        held = None;
+                        summary.deleted += 1;
+                    }
+                    _ => summary.ignored += 1,
+                }
+            }
+            if in_tree {
+                steps.push(Step::Stored(held == Some(None)));
+            }
+            steps.extend((i..group).map(|j| Step::Op(held == Some(Some(j)))));
+            i = group;
+        }
+
+        let watched = self.watched();
+        let mut stored = self.take_sorted().into_iter();
+        let mut ops = ops.into_iter();
+        let mut merged = Vec::with_capacity(steps.len());
+        // what changed, for the subscriber: the stored keys that went, and
+        // which of the merged keys are new
+        let (mut removed, mut added) = (Vec::new(), Vec::new());
+        for step in steps {
+            let (key, kept) = match step {
+                Step::Stored(kept) => (stored.next().unwrap(), kept),
+                Step::Op(kept) => (ops.next().unwrap().into_key(), kept),
+            };
+            if kept {
+                merged.push(key);
+                if watched {
+                    added.push(matches!(step, Step::Op(_)));
+                }
+            } else if watched && matches!(step, Step::Stored(_)) {
+                removed.push(key);
+            }
+        }
+        merged.extend(stored);
+        self.fill_sorted(merged);
+        self.notify_rebuilt(&removed, &added);
+        Ok(summary)
+    }
+}
@@ -0,0 +1,180 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::RangeBounds;
+
+use super::{Btree, Range};
+use crate::compare::{Compare, Natural};
+
+/*
+** a btree holding at most capacity keys. an insert into a full tree makes
+** room by evicting the smallest or the largest key, or is turned down, as the
+** policy says. evicting one end keeps the keys at the other end, so a stream
+** through a tree dropping its smallest key leaves the largest capacity keys
+** of the stream behind (a top-k)
+**
+** a key that would be evicted right away (smaller than the smallest key of a
+** full tree dropping its smallest) is rejected instead, the tree stays as it
+** was. that's told by the key at the evicting end alone, so a rejected key
+** costs a walk down one side of the tree and a single comparison, and any
+** other insert one descent plus, if it adds a key to a full tree, the pop at
+** the end
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictPolicy {
+    DropSmallest,
+    DropLargest,
+    Reject,
+}
+
+// what became of a key handed to BoundedBtree::insert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Insertion<T> {
+    // stored, there was room for it
+    Added,
+    // already stored, the tree is unchanged
+    Present,
+    // stored, the key given back had to go to make room
+    Evicted(T),
+    // not stored, here it is back
+    Rejected(T),
+}
+
+pub struct BoundedBtree<T, C = Natural> {
+    tree: Btree<T, C>,
+    capacity: usize,
+    policy: EvictPolicy,
+}
+
+impl<T: Ord> BoundedBtree<T> {
+    pub fn with_capacity_limit(degree: usize, capacity: usize, policy: EvictPolicy) -> Self {
+        BoundedBtree::with_comparator(degree, capacity, policy, Natural)
+    }
+}
+
+impl<T, C: Compare<T>> BoundedBtree<T, C> {
+    // a bounded tree ordering its keys by cmp, smallest and largest going by it
+    pub fn with_comparator(degree: usize, capacity: usize, policy: EvictPolicy, cmp: C) -> Self {
+        BoundedBtree {
+            tree: Btree::with_comparator(degree, cmp),
+            capacity,
+            policy,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> EvictPolicy {
+        self.policy
+    }
+
+    pub fn search<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.search(key)
+    }
+
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.first()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.last()
+    }
+
+    // store key, evicting the key at the policy's end if the tree is full
+    pub fn insert(&mut self, key: T) -> Insertion<T> {
+        if self.tree.len() >= self.capacity {
+            // full: the key has to beat the one it would push out, and one
+            // that doesn't can only be stored already if it is that one
+            let (end, beats) = match self.policy {
+                EvictPolicy::DropSmallest => (self.tree.first(), Ordering::Greater),
+                EvictPolicy::DropLargest => (self.tree.last(), Ordering::Less),
+                EvictPolicy::Reject => match self.tree.search(&key) {
+                    true => return Insertion::Present,
+                    false => return Insertion::Rejected(key),
+                },
+            };
+            match end.map(|end| self.tree.cmp.compare(&key, end)) {
+                Some(order) if order == beats => {}
+                Some(Ordering::Equal) => return Insertion::Present,
+                // a zero capacity tree has no end to compare with
+                _ => return Insertion::Rejected(key),
+            }
+        }
+        let (_, inserted) = self.tree.find_or_insert_by(
+            key,
+            |cmp, stored, key| cmp.compare(stored, key),
+            |_, key| key,
+        );
+        if !inserted {
+            return Insertion::Present;
+        }
+        if self.tree.len() <= self.capacity {
+            return Insertion::Added;
+        }
+        let evicted = match self.policy {
+            EvictPolicy::DropSmallest => self.tree.pop_first(),
+            _ => self.tree.pop_last(),
+        };
+        Insertion::Evicted(evicted.expect("a full tree has keys"))
+    }
+
+    pub fn delete<Q: ?Sized>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.delete(key)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.tree.pop_first()
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.tree.pop_last()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.tree.iter()
+    }
+
+    // the keys inside the range in ascending order
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T, C>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree.range(range)
+    }
+
+    // the keys kept, as a tree without a limit
+    pub fn into_tree(self) -> Btree<T, C> {
+        self.tree
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()
+    }
+}
@@ -0,0 +1,112 @@
+use std::ops::RangeBounds;
+
+use super::{Btree, Range};
+
+/*
+** large keys stored boxed, so the nodes hold pointers and splits, merges and
+** borrows move 8 bytes per key instead of the whole key. the boxes stay on
+** the inside: keys go in and come out as T, and every lookup, delete and
+** range takes a plain &T that the comparisons look through the box with
+**
+** the price is an allocation per key and a pointer chase per comparison, so
+** it only pays off for keys many times the size of a pointer
+*/
+pub struct BoxedBtree<T: Ord> {
+    tree: Btree<Box<T>>,
+}
+
+impl<T: Ord> BoxedBtree<T> {
+    pub fn new(degree: usize) -> Self {
+        BoxedBtree {
+            tree: Btree::new(degree),
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.tree.degree()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.tree.search(key)
+    }
+
+    pub fn get(&self, key: &T) -> Option<&T> {
+        self.tree.get(key).map(|stored| &**stored)
+    }
+
+    // false if an equal key was already stored, which is kept. the box is
+    // made up front so one descent both looks and inserts
+    pub fn insert(&mut self, key: T) -> bool {
+        self.tree.insert(Box::new(key))
+    }
+
+    // false if the key wasn't stored
+    pub fn delete(&mut self, key: &T) -> bool {
+        self.tree.delete(key)
+    }
+
+    // remove the stored key equal to key and hand it back out of its box
+    pub fn take(&mut self, key: &T) -> Option<T> {
+        self.tree.take(key).map(|stored| *stored)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.first().map(|stored| &**stored)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.last().map(|stored| &**stored)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.tree.pop_first().map(|stored| *stored)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.tree.pop_last().map(|stored| *stored)
+    }
+
+    // every key in order, seen through its box
+    pub fn iter(&self) -> BoxedIter<'_, T> {
+        self.range(..)
+    }
+
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> BoxedIter<'_, T> {
+        BoxedIter(self.tree.range::<T, _>(range))
+    }
+
+    // heap bytes of the nodes, which hold the pointers. like
+    // Btree::memory_usage the keys' own allocations aren't counted
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()
+    }
+}
+
+// the keys of a BoxedBtree in order, see BoxedBtree::range
+pub struct BoxedIter<'a, T>(Range<'a, Box<T>>);
+
+impl<'a, T: Ord> Iterator for BoxedIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next().map(|stored| &**stored)
+    }
+}
+
+impl<'a, T: Ord> DoubleEndedIterator for BoxedIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.0.next_back().map(|stored| &**stored)
+    }
+}
@@ -0,0 +1,89 @@
+use super::BtreeNode;
+
+/*
+** bottom-up construction from keys that are already strictly sorted
+**
+** the leaves are built first, left to right, and the key after each leaf is
+** kept aside as a separator. then the same thing happens one level up: the
+** leaves are grouped under internal nodes, with the separators between the
+** groups kept aside for the level above, until a single node is left
+**
+** a node with c children holds c - 1 keys, and a leaf with k keys is followed
+** by one separator, so both levels are the same problem: split a row of
+** "units" into groups of at most 2t units, each with at least t units
+** unless there is only one group (the root)
+** nodes are packed up to a fill factor: at 1 as full as possible, which gives
+** the smallest height, below that with room left for later inserts
+*/
+
+// how many units go into each group, spread as evenly as possible
+// a group takes at most m = fill * 2t units (but no fewer than t), so there
+// are g = ceil(total / m) groups, unless that many would leave some under t:
+// with at most total / t groups the average never falls under t, and with at
+// least ceil(total / 2t) it never goes over 2t
+fn group_sizes(total: usize, degree: usize, fill: f64) -> impl Iterator<Item = usize> {
+    let most = ((2 * degree) as f64 * fill).round() as usize;
+    let most = most.clamp(degree, 2 * degree);
+    let groups = total.div_ceil(most).min(total / degree).max(1);
+    (0..groups).map(move |i| total / groups + usize::from(i < total % groups))
+}
+
+impl<T> BtreeNode<T> {
+    // build a tree out of strictly sorted keys, moving them into the nodes
+    // fill is how full the nodes get, in (0, 1], see group_sizes
+    pub(crate) fn bulk_build(sorted: Vec<T>, degree: usize, fill: f64) -> Option<BtreeNode<T>> {
+        if sorted.is_empty() {
+            return None;
+        }
+
+        // n keys: every leaf plus the separator after it is a unit, the last
+        // leaf has no separator, hence n + 1 units
+        let units = sorted.len() + 1;
+        let mut keys = sorted.into_iter();
+        let mut level = Vec::new();
+        let mut separators = Vec::new();
+        for (i, size) in group_sizes(units, degree, fill).enumerate() {
+            if i > 0 {
+                separators.push(keys.next().unwrap());
+            }
+            let mut leaf_keys = Self::key_buffer(degree);
+            leaf_keys.extend(keys.by_ref().take(size - 1));
+            level.push(BtreeNode::Leaf { keys: leaf_keys });
+        }
+
+        // group every level under new parents until only the root is left
+        while level.len() > 1 {
+            let groups = group_sizes(level.len(), degree, fill);
+            let mut nodes = level.into_iter();
+            let mut keys = separators.into_iter();
+            level = Vec::new();
+            separators = Vec::new();
+            for (i, size) in groups.enumerate() {
+                if i > 0 {
+                    separators.push(keys.next().unwrap());
+                }
+                let mut children = Self::child_buffer(degree);
+                children.extend(nodes.by_ref().take(size));
+                let mut node_keys = Self::key_buffer(degree);
+                node_keys.extend(keys.by_ref().take(size - 1));
+                level.push(BtreeNode::new_internal(node_keys, children));
+            }
+        }
+        level.pop()
+    }
+
+    // move every key of this subtree into out, in order
+    pub(crate) fn drain_into(self, out: &mut Vec<T>) {
+        match self {
+            BtreeNode::Leaf { keys } => out.extend(keys),
+            BtreeNode::Internal { keys, children, .. } => {
+                let mut children = children.into_iter();
+                for key in keys {
+                    children.next().unwrap().drain_into(out);
+                    out.push(key);
+                }
+                children.next().unwrap().drain_into(out);
+            }
+        }
+    }
+}
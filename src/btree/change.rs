@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+
+use super::Btree;
+use super::iter::{Iter, Path};
+use crate::compare::Compare;
+
+/*
+** a change to what the tree holds, handed to the subscriber set with
+** set_on_change once per key that went in or out. it's told after the
+** operation is done and the tree whole again, and never about lookups, about
+** inserts of keys already stored or deletes of keys that aren't, or about
+** anything that only moves keys around (splits, merges, rebuild, ..)
+**
+** the batch operations that rebuild the tree in one sweep (delete_many,
+** extend_from_tree, apply_batch, drain_range, ..) report what they changed
+** once the rebuild is done: the keys that left first, then the ones that came
+** in, in order. a key inserted and deleted again within one batch is reported
+** as neither
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<T> {
+    Inserted(T),
+    Removed(T),
+    // an equal key took the stored one's place, see Btree::replace
+    Replaced { old: T, new: T },
+}
+
+// what set_on_change keeps, locked only so the tree stays Sync, every call
+// comes through a mutable reference
+pub(crate) type OnChange<T> = Mutex<Box<dyn FnMut(Change<&T>) + Send>>;
+
+// hand a change to the subscriber, if there is one
+pub(crate) fn notify<T>(on_change: &mut Option<OnChange<T>>, change: Change<&T>) {
+    if let Some(on_change) = on_change {
+        let on_change = on_change
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        on_change(change);
+    }
+}
+
+impl<T, C> Btree<T, C> {
+    // call on_change with every key inserted or removed from now on, replacing
+    // any subscriber set before
+    pub fn set_on_change<F: FnMut(Change<&T>) + Send + 'static>(&mut self, on_change: F) {
+        self.on_change = Some(Mutex::new(Box::new(on_change)));
+    }
+
+    pub fn clear_on_change(&mut self) {
+        self.on_change = None;
+    }
+
+    // whether changes have anywhere to go, so the work of reporting them can
+    // be skipped when they don't
+    pub(crate) fn watched(&self) -> bool {
+        self.on_change.is_some()
+    }
+
+    // report the key an index path from the root leads to as inserted
+    pub(crate) fn notify_inserted_at(&mut self, path: &[usize]) {
+        let key = Path::from_indices(self.root.as_deref(), path)
+            .current()
+            .expect("the path leads to the key");
+        notify(&mut self.on_change, Change::Inserted(key));
+    }
+
+    pub(crate) fn notify_removed(&mut self, key: &T) {
+        notify(&mut self.on_change, Change::Removed(key));
+    }
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // report what a rebuild changed: removed are the keys that left the tree,
+    // added marks (in order) which keys of the rebuilt tree are new
+    pub(crate) fn notify_rebuilt(&mut self, removed: &[T], added: &[bool]) {
+        for key in removed {
+            notify(&mut self.on_change, Change::Removed(key));
+        }
+        let keys = Iter::new(self.root.as_deref(), &self.cmp);
+        for (key, _) in keys.zip(added).filter(|(_, added)| **added) {
+            notify(&mut self.on_change, Change::Inserted(key));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::cmp::Ordering;
+
+use crate::compare::Compare;
+
+/*
+** checks for an ordering that contradicts itself (only with the "check_ord"
+** feature): a comparator that isn't transitive, isn't antisymmetric, or
+** answers differently from one call to the next. a tree trusting one files
+** keys where later searches don't look, this catches it where it shows, on
+** the way through the nodes, and panics instead
+**
+** every node a descent passes has all its keys compared with the key looked
+** for, the answers have to go from Less to Greater in key order with at most
+** one Equal in between. an insert compares the new key once more with the
+** keys it ends up between, in the other direction, at every level
+**
+** keys don't have to be Debug, so the messages name them by position. every
+** check is extra comparisons, a Metrics count includes them
+*/
+
+// the probe's answer for every key of a node, given where the binary search
+// found the key (or the spot for it)
+pub(super) fn probe_results<T, F: FnMut(&T) -> Ordering>(
+    keys: &[T],
+    probe: &mut F,
+    found: Result<usize, usize>,
+) {
+    let split = found.unwrap_or_else(|i| i);
+    for (j, key) in keys.iter().enumerate() {
+        let expected = match (j.cmp(&split), found) {
+            (Ordering::Less, _) => Ordering::Less,
+            (Ordering::Equal, Ok(_)) => Ordering::Equal,
+            _ => Ordering::Greater,
+        };
+        let answer = probe(key);
+        assert!(
+            answer == expected,
+            "inconsistent ordering: the key looked for belongs at {:?} of a node of {} keys, \
+             but key {} of it compares {:?} to it instead of {:?}",
+            found,
+            keys.len(),
+            j,
+            answer,
+            expected
+        );
+    }
+}
+
+// key is about to go between lower and upper (the keys around its spot in a
+// leaf, or the separators around the child it goes down into)
+pub(super) fn fits_between<T, C: Compare<T>>(
+    cmp: &C,
+    key: &T,
+    lower: Option<&T>,
+    upper: Option<&T>,
+    place: &str,
+) {
+    if let Some(lower) = lower {
+        let answer = cmp.compare(key, lower);
+        assert!(
+            answer == Ordering::Greater,
+            "inconsistent ordering: the new key went after the key before its {}, \
+             but compares {:?} to it",
+            place,
+            answer
+        );
+    }
+    if let Some(upper) = upper {
+        let answer = cmp.compare(key, upper);
+        assert!(
+            answer == Ordering::Less,
+            "inconsistent ordering: the new key went before the key after its {}, \
+             but compares {:?} to it",
+            place,
+            answer
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use super::encoded::{Encoding, Iter, Tree};
+
+/*
+** a b-tree of byte string keys where every node stores the prefix all of its
+** keys share once, and of every key only the bytes after it (its suffix)
+** nodes full of urls or file paths mostly differ in their last few bytes, so
+** a key shrinks to those bytes and a boxed slice pointing at them
+**
+** a probe is compared with the prefix first and with the suffixes after that,
+** keys are only put back together when they're handed out. every change to a
+** node's keys (inserts, removals, splits, merges, borrows) recomputes its
+** prefix, which is always the longest one the keys have in common
+**
+** the keys have to be ordered by their bytes, which String and Vec<u8> are
+*/
+pub trait CompressKey: Ord + Clone + AsRef<[u8]> {
+    // the key made of these bytes, they always come from a key of this type
+    fn from_bytes(bytes: Vec<u8>) -> Self;
+}
+
+impl CompressKey for Vec<u8> {
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        bytes
+    }
+}
+
+impl CompressKey for String {
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        String::from_utf8(bytes).expect("stored keys are valid utf-8")
+    }
+}
+
+// number of bytes a and b start with in common
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+// a node's keys, the longest prefix they share and what's left of each
+pub(crate) struct Prefixed<K> {
+    prefix: Vec<u8>,
+    suffixes: Vec<Box<[u8]>>,
+    marker: PhantomData<K>,
+}
+
+impl<K> Default for Prefixed<K> {
+    fn default() -> Self {
+        Prefixed {
+            prefix: Vec::new(),
+            suffixes: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K> Prefixed<K> {
+    fn bytes(&self, i: usize) -> Vec<u8> {
+        [&self.prefix[..], &self.suffixes[i]].concat()
+    }
+}
+
+impl<K: CompressKey> Encoding for Prefixed<K> {
+    type Key = K;
+    type Probe = [u8];
+
+    fn probe(key: &K) -> &[u8] {
+        key.as_ref()
+    }
+
+    fn len(&self) -> usize {
+        self.suffixes.len()
+    }
+
+    fn key(&self, i: usize) -> K {
+        K::from_bytes(self.bytes(i))
+    }
+
+    // the prefix first, and the suffixes only if the probe starts with it
+    fn find(&self, probe: &[u8]) -> Result<usize, usize> {
+        let shared = self.prefix.len().min(probe.len());
+        match probe[..shared].cmp(&self.prefix[..shared]) {
+            Ordering::Less => Err(0),
+            Ordering::Greater => Err(self.len()),
+            // a probe that is a proper prefix of the prefix comes before every key
+            Ordering::Equal if probe.len() < self.prefix.len() => Err(0),
+            Ordering::Equal => {
+                let rest = &probe[self.prefix.len()..];
+                self.suffixes
+                    .binary_search_by(|suffix| suffix[..].cmp(rest))
+            }
+        }
+    }
+
+    fn insert(&mut self, i: usize, key: K) {
+        let key = key.as_ref();
+        if self.suffixes.is_empty() {
+            self.prefix = key.to_vec();
+            self.suffixes.push(Box::default());
+            return;
+        }
+        // a key that doesn't share the whole prefix shortens it, the bytes
+        // given up move to the front of every suffix
+        let shared = common_prefix_len(&self.prefix, key);
+        if shared < self.prefix.len() {
+            let given_up = self.prefix.split_off(shared);
+            for suffix in &mut self.suffixes {
+                *suffix = [&given_up[..], suffix].concat().into();
+            }
+        }
+        self.suffixes.insert(i, key[shared..].into());
+    }
+
+    fn remove(&mut self, i: usize) -> K {
+        let key = self.bytes(i);
+        self.suffixes.remove(i);
+        // the keys left may share more than before
+        match (self.suffixes.first(), self.suffixes.last()) {
+            (Some(first), Some(last)) => {
+                let extra = common_prefix_len(first, last);
+                if extra > 0 {
+                    self.prefix.extend_from_slice(&first[..extra]);
+                    for suffix in &mut self.suffixes {
+                        *suffix = suffix[extra..].into();
+                    }
+                }
+            }
+            _ => self.prefix.clear(),
+        }
+        K::from_bytes(key)
+    }
+
+    fn set(&mut self, keys: Vec<K>) {
+        // the keys are sorted, whatever the first and last share all of them do
+        let shared = match (keys.first(), keys.last()) {
+            (Some(first), Some(last)) => common_prefix_len(first.as_ref(), last.as_ref()),
+            _ => 0,
+        };
+        self.prefix = keys
+            .first()
+            .map_or(Vec::new(), |first| first.as_ref()[..shared].to_vec());
+        self.suffixes = keys
+            .iter()
+            .map(|key| key.as_ref()[shared..].into())
+            .collect();
+    }
+
+    // every prefix and suffix byte is counted, there's no key to own them
+    fn memory_usage(&self) -> usize {
+        self.prefix.capacity()
+            + self.suffixes.capacity() * size_of::<Box<[u8]>>()
+            + self
+                .suffixes
+                .iter()
+                .map(|suffix| suffix.len())
+                .sum::<usize>()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let (Some(first), Some(last)) = (self.suffixes.first(), self.suffixes.last()) else {
+            return Ok(());
+        };
+        match common_prefix_len(first, last) > 0 || (self.len() == 1 && !first.is_empty()) {
+            true => Err("prefix is not the longest common one".to_string()),
+            false => Ok(()),
+        }
+    }
+}
+
+pub struct CompressedBtree<K: CompressKey> {
+    tree: Tree<Prefixed<K>>,
+}
+
+impl<K: CompressKey> CompressedBtree<K> {
+    pub fn new(degree: usize) -> Self {
+        CompressedBtree {
+            tree: Tree::new(degree),
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.tree.degree()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains<Q: AsRef<[u8]> + ?Sized>(&self, key: &Q) -> bool {
+        self.tree.contains(key.as_ref())
+    }
+
+    // false if the key was already stored
+    pub fn insert(&mut self, key: K) -> bool {
+        self.tree.insert(key)
+    }
+
+    // false if the key wasn't stored
+    pub fn delete<Q: AsRef<[u8]> + ?Sized>(&mut self, key: &Q) -> bool {
+        self.tree.delete(key.as_ref())
+    }
+
+    pub fn first(&self) -> Option<K> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<K> {
+        self.tree.last()
+    }
+
+    // every key in order, each one put back together as it's handed out
+    pub fn iter(&self) -> CompressedIter<'_, K> {
+        self.range(..)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> CompressedIter<'_, K> {
+        let start = range.start_bound().map(|start| start.as_ref());
+        CompressedIter(self.tree.range(start, range.end_bound().cloned()))
+    }
+
+    // heap bytes the tree owns, keys included: unlike Btree::memory_usage the
+    // bytes of the keys are counted too, since there is no key to own them
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()
+    }
+}
+
+// the keys of a CompressedBtree in order, see CompressedBtree::range
+pub struct CompressedIter<'a, K: CompressKey>(Iter<'a, Prefixed<K>>);
+
+impl<K: CompressKey> Iterator for CompressedIter<'_, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.0.next()
+    }
+}
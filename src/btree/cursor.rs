@@ -0,0 +1,238 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+use super::key_check::admit;
+use super::trace::NoTrace;
+use super::{Btree, Path};
+use crate::compare::{Compare, Natural};
+
+/*
+** a read only position in the tree that can be moved in both directions and
+** repositioned at any time, unlike an iterator which only ever moves inwards
+**
+** when it isn't on a key the cursor is either past the end or before the
+** start, moving back from there lands on the last (or first) key again
+** an empty tree has no keys, so the cursor never lands anywhere
+*/
+pub struct Cursor<'a, T, C = Natural> {
+    tree: &'a Btree<T, C>,
+    path: Path<'a, T>,
+    // which side the cursor fell off when it's not on a key
+    past_end: bool,
+}
+
+impl<'a, T, C: Compare<T>> Cursor<'a, T, C> {
+    // a cursor on the first key of the tree
+    pub(crate) fn new(tree: &'a Btree<T, C>) -> Self {
+        let mut cursor = Cursor {
+            tree,
+            path: Path::empty(),
+            past_end: false,
+        };
+        cursor.seek_to_first();
+        cursor
+    }
+
+    // the key the cursor is on, None past the end or before the start
+    pub fn current(&self) -> Option<&'a T> {
+        self.path.current()
+    }
+
+    // move to the next key, stepping past the end after the last one
+    pub fn next(&mut self) {
+        if self.path.current().is_some() {
+            self.path.next();
+            self.past_end = true;
+        } else if !self.past_end {
+            self.seek_to_first();
+        }
+    }
+
+    // move to the previous key, stepping before the start after the first one
+    pub fn prev(&mut self) {
+        if self.path.current().is_some() {
+            self.path.prev();
+            self.past_end = false;
+        } else if self.past_end {
+            self.seek_to_last();
+        }
+    }
+
+    pub fn seek_to_first(&mut self) {
+        self.path = Path::first_where(self.tree.root.as_deref(), |_| true);
+        self.past_end = false;
+    }
+
+    pub fn seek_to_last(&mut self) {
+        self.path = Path::last_where(self.tree.root.as_deref(), |_| true);
+        self.past_end = true;
+    }
+
+    // move to the first key that does not come before key in the tree's order
+    // (the first key >= key for natural ordering), or past the end if there's none
+    pub fn seek<Q: ?Sized>(&mut self, key: &Q)
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let cmp = &self.tree.cmp;
+        self.path = Path::first_where(self.tree.root.as_deref(), |stored: &T| {
+            cmp.compare(stored.borrow(), key) != Ordering::Less
+        });
+        self.past_end = true;
+    }
+}
+
+/*
+** a cursor that can also remove and insert keys where it stands
+**
+** removing or inserting rebalances nodes and moves keys around, so the
+** position isn't kept as references but as the list of indexes leading to
+** the current key (see Path::indices). after every change it is derived
+** again from the key that was just removed or inserted
+*/
+pub struct CursorMut<'a, T, C = Natural> {
+    tree: &'a mut Btree<T, C>,
+    // empty when the cursor is not on a key
+    indices: Vec<usize>,
+    // which side the cursor fell off when it's not on a key
+    past_end: bool,
+}
+
+impl<'a, T, C: Compare<T>> CursorMut<'a, T, C> {
+    // a cursor on the first key of the tree
+    pub(crate) fn new(tree: &'a mut Btree<T, C>) -> Self {
+        let mut cursor = CursorMut {
+            tree,
+            indices: Vec::new(),
+            past_end: false,
+        };
+        cursor.seek_to_first();
+        cursor
+    }
+
+    fn path(&self) -> Path<'_, T> {
+        Path::from_indices(self.tree.root.as_deref(), &self.indices)
+    }
+
+    // the key the cursor is on, None past the end or before the start
+    pub fn current(&self) -> Option<&T> {
+        self.path().current()
+    }
+
+    // move to the next key, stepping past the end after the last one
+    pub fn next(&mut self) {
+        if !self.indices.is_empty() {
+            let mut path = self.path();
+            path.next();
+            self.indices = path.indices();
+            self.past_end = true;
+        } else if !self.past_end {
+            self.seek_to_first();
+        }
+    }
+
+    // move to the previous key, stepping before the start after the first one
+    pub fn prev(&mut self) {
+        if !self.indices.is_empty() {
+            let mut path = self.path();
+            path.prev();
+            self.indices = path.indices();
+            self.past_end = false;
+        } else if self.past_end {
+            self.seek_to_last();
+        }
+    }
+
+    pub fn seek_to_first(&mut self) {
+        self.indices = Path::first_where(self.tree.root.as_deref(), |_| true).indices();
+        self.past_end = false;
+    }
+
+    pub fn seek_to_last(&mut self) {
+        self.indices = Path::last_where(self.tree.root.as_deref(), |_| true).indices();
+        self.past_end = true;
+    }
+
+    // move to the first key that does not come before key in the tree's order
+    // (the first key >= key for natural ordering), or past the end if there's none
+    pub fn seek<Q: ?Sized>(&mut self, key: &Q)
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let cmp = &self.tree.cmp;
+        self.indices = Path::first_where(self.tree.root.as_deref(), |stored: &T| {
+            cmp.compare(stored.borrow(), key) != Ordering::Less
+        })
+        .indices();
+        self.past_end = true;
+    }
+
+    // remove the key the cursor is on and move on to the key after it
+    // returns None (and removes nothing) when the cursor is not on a key
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.indices.is_empty() {
+            return None;
+        }
+        let removed = self.tree.remove_at(&mut self.indices);
+        let cmp = &self.tree.cmp;
+        self.indices = Path::first_where(self.tree.root.as_deref(), |stored| {
+            cmp.compare(stored, &removed) == Ordering::Greater
+        })
+        .indices();
+        self.past_end = true;
+        Some(removed)
+    }
+
+    // insert key right before the cursor position, the cursor stays where it is
+    // panics if key doesn't come strictly between the keys around that spot
+    pub fn insert_before(&mut self, key: T) {
+        self.insert_next_to(key, false);
+    }
+
+    // insert key right after the cursor position, the cursor stays where it is
+    // panics if key doesn't come strictly between the keys around that spot
+    pub fn insert_after(&mut self, key: T) {
+        self.insert_next_to(key, true);
+    }
+
+    fn insert_next_to(&mut self, key: T, after: bool) {
+        admit(self.tree.key_check.as_ref(), &key);
+        // the keys the new one has to fit between, past the end or before the
+        // start there is nothing on the far side
+        let path = self.path();
+        let (lower, upper) = match path.current() {
+            Some(current) if after => {
+                let mut next = path.clone();
+                next.next();
+                (Some(current), next.current())
+            }
+            Some(current) => {
+                let mut prev = path.clone();
+                prev.prev();
+                (prev.current(), Some(current))
+            }
+            None if self.past_end => (self.tree.last(), None),
+            None => (None, self.tree.first()),
+        };
+        let cmp = &self.tree.cmp;
+        let fits = lower.is_none_or(|lower| cmp.compare(lower, &key) == Ordering::Less)
+            && upper.is_none_or(|upper| cmp.compare(&key, upper) == Ordering::Less);
+        assert!(fits, "key does not fit at the cursor position");
+
+        let mut inserted = Vec::new();
+        self.tree
+            .insert_tracked(key, Some(&mut inserted), &mut NoTrace);
+        if !self.indices.is_empty() {
+            // the key the cursor was on is now the new key's neighbour
+            let mut path = Path::from_indices(self.tree.root.as_deref(), &inserted);
+            if after {
+                path.prev();
+            } else {
+                path.next();
+            }
+            self.indices = path.indices();
+        }
+    }
+}
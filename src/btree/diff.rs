@@ -0,0 +1,58 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+use super::{Btree, Iter};
+use crate::compare::{Compare, Natural};
+
+// one difference between two trees, see Btree::diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffEntry<'a, T> {
+    OnlyInSelf(&'a T),
+    OnlyInOther(&'a T),
+}
+
+/*
+** both trees walked side by side in key order: keys in both are skipped,
+** the smaller head is only in its own tree. one pass over each tree, and the
+** entries come out in key order, so they can be applied as a sorted script
+*/
+pub struct Diff<'a, T, C = Natural> {
+    ours: Peekable<Iter<'a, T, C>>,
+    theirs: Peekable<Iter<'a, T, C>>,
+    cmp: &'a C,
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // the keys only one of the two trees holds, in key order
+    // other has to be ordered the same way, this tree's comparator decides
+    pub fn diff<'a>(&'a self, other: &'a Btree<T, C>) -> Diff<'a, T, C> {
+        Diff {
+            ours: self.iter().peekable(),
+            theirs: other.iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+}
+
+impl<'a, T, C: Compare<T>> Iterator for Diff<'a, T, C> {
+    type Item = DiffEntry<'a, T>;
+
+    fn next(&mut self) -> Option<DiffEntry<'a, T>> {
+        loop {
+            let order = match (self.ours.peek(), self.theirs.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(ours), Some(theirs)) => self.cmp.compare(ours, theirs),
+            };
+            match order {
+                Ordering::Less => return self.ours.next().map(DiffEntry::OnlyInSelf),
+                Ordering::Greater => return self.theirs.next().map(DiffEntry::OnlyInOther),
+                Ordering::Equal => {
+                    self.ours.next();
+                    self.theirs.next();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+/*
+** the b-tree behind the trees that don't keep their keys as they are
+** (CompressedBtree, PackedBtree). the shape, splits, merges and borrows are
+** the same as for Btree, only how a node lays out its keys is left to an
+** Encoding, which hands keys out rebuilt and finds probes in its own terms
+**
+** a node's encoding is only ever changed through insert, remove and set, so
+** it can recompute whatever it shares between keys right there
+*/
+pub(crate) trait Encoding: Default {
+    // what goes in and comes out, rebuilt from the encoding
+    type Key: Ord + Clone;
+    // what lookups take
+    type Probe: ?Sized;
+
+    fn probe(key: &Self::Key) -> &Self::Probe;
+    fn len(&self) -> usize;
+    fn key(&self, i: usize) -> Self::Key;
+    // where probe is, or where it would go
+    fn find(&self, probe: &Self::Probe) -> Result<usize, usize>;
+    fn insert(&mut self, i: usize, key: Self::Key);
+    fn remove(&mut self, i: usize) -> Self::Key;
+    // replace every key with these, in order
+    fn set(&mut self, keys: Vec<Self::Key>);
+    // heap bytes the encoding owns
+    fn memory_usage(&self) -> usize;
+    // the encoding is the one set would have picked for these keys
+    fn check(&self) -> Result<(), String>;
+
+    fn take(&mut self) -> Vec<Self::Key> {
+        let keys = (0..self.len()).map(|i| self.key(i)).collect();
+        self.set(Vec::new());
+        keys
+    }
+}
+
+// a leaf has no children
+pub(crate) struct Node<E> {
+    keys: E,
+    children: Vec<Node<E>>,
+}
+
+impl<E: Encoding> Node<E> {
+    fn new(keys: Vec<E::Key>, children: Vec<Node<E>>) -> Self {
+        let mut encoding = E::default();
+        encoding.set(keys);
+        Node {
+            keys: encoding,
+            children,
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn replace_key(&mut self, i: usize, key: E::Key) -> E::Key {
+        let old = self.keys.remove(i);
+        self.keys.insert(i, key);
+        old
+    }
+
+    // split the full child at i around its median, which moves up here
+    fn split_child(&mut self, i: usize, degree: usize) {
+        let child = &mut self.children[i];
+        let mut keys = child.keys.take();
+        let right_keys = keys.split_off(degree);
+        let median = keys.pop().expect("a full node has a median");
+        let right_children = match child.is_leaf() {
+            true => Vec::new(),
+            false => child.children.split_off(degree),
+        };
+        child.keys.set(keys);
+        self.children
+            .insert(i + 1, Node::new(right_keys, right_children));
+        self.keys.insert(i, median);
+    }
+
+    fn insert_non_full(&mut self, key: E::Key, degree: usize) -> bool {
+        let Err(mut i) = self.keys.find(E::probe(&key)) else {
+            return false;
+        };
+        if self.is_leaf() {
+            self.keys.insert(i, key);
+            return true;
+        }
+        if self.children[i].len() == 2 * degree - 1 {
+            self.split_child(i, degree);
+            // the median that came up may be the key itself
+            match self.keys.find(E::probe(&key)) {
+                Ok(_) => return false,
+                Err(j) => i = j,
+            }
+        }
+        self.children[i].insert_non_full(key, degree)
+    }
+
+    // the separator at i and everything of the child right of it go into the
+    // child left of it
+    fn merge_children(&mut self, i: usize) {
+        let separator = self.keys.remove(i);
+        let mut right = self.children.remove(i + 1);
+        let left = &mut self.children[i];
+        let mut keys = left.keys.take();
+        keys.push(separator);
+        keys.extend(right.keys.take());
+        left.keys.set(keys);
+        left.children.append(&mut right.children);
+    }
+
+    fn borrow_from_left(&mut self, i: usize) {
+        let left = &mut self.children[i - 1];
+        let last = left.keys.remove(left.len() - 1);
+        let last_child = left.children.pop();
+        let separator = self.replace_key(i - 1, last);
+        let child = &mut self.children[i];
+        child.keys.insert(0, separator);
+        if let Some(last_child) = last_child {
+            child.children.insert(0, last_child);
+        }
+    }
+
+    fn borrow_from_right(&mut self, i: usize) {
+        let right = &mut self.children[i + 1];
+        let first = right.keys.remove(0);
+        let first_child = (!right.is_leaf()).then(|| right.children.remove(0));
+        let separator = self.replace_key(i, first);
+        let child = &mut self.children[i];
+        child.keys.insert(child.len(), separator);
+        child.children.extend(first_child);
+    }
+
+    // make sure the child at i has a key to spare before going down into it
+    // returns the index the child ends up at
+    fn ensure_child_has_spare(&mut self, i: usize, degree: usize) -> usize {
+        if self.children[i].len() >= degree {
+            i
+        } else if i > 0 && self.children[i - 1].len() >= degree {
+            self.borrow_from_left(i);
+            i
+        } else if i + 1 < self.children.len() && self.children[i + 1].len() >= degree {
+            self.borrow_from_right(i);
+            i
+        } else if i + 1 < self.children.len() {
+            self.merge_children(i);
+            i
+        } else {
+            self.merge_children(i - 1);
+            i - 1
+        }
+    }
+
+    fn delete(&mut self, probe: &E::Probe, degree: usize) -> bool {
+        match self.keys.find(probe) {
+            Ok(i) if self.is_leaf() => {
+                self.keys.remove(i);
+                true
+            }
+            // the key is replaced by its predecessor or successor, whichever
+            // child can give one up, or sinks into the merge of both children
+            Ok(i) => {
+                if self.children[i].len() >= degree {
+                    let predecessor = self.children[i].delete_max(degree);
+                    self.replace_key(i, predecessor);
+                } else if self.children[i + 1].len() >= degree {
+                    let successor = self.children[i + 1].delete_min(degree);
+                    self.replace_key(i, successor);
+                } else {
+                    self.merge_children(i);
+                    return self.children[i].delete(probe, degree);
+                }
+                true
+            }
+            Err(_) if self.is_leaf() => false,
+            Err(i) => {
+                let i = self.ensure_child_has_spare(i, degree);
+                self.children[i].delete(probe, degree)
+            }
+        }
+    }
+
+    fn delete_min(&mut self, degree: usize) -> E::Key {
+        if self.is_leaf() {
+            return self.keys.remove(0);
+        }
+        let i = self.ensure_child_has_spare(0, degree);
+        self.children[i].delete_min(degree)
+    }
+
+    fn delete_max(&mut self, degree: usize) -> E::Key {
+        if self.is_leaf() {
+            return self.keys.remove(self.len() - 1);
+        }
+        let i = self.ensure_child_has_spare(self.children.len() - 1, degree);
+        self.children[i].delete_max(degree)
+    }
+
+    // what the encodings own plus the children vectors' capacity, the node
+    // itself lives in its parent's children
+    fn memory_usage(&self) -> usize {
+        self.keys.memory_usage()
+            + self.children.capacity() * size_of::<Node<E>>()
+            + self.children.iter().map(Node::memory_usage).sum::<usize>()
+    }
+
+    // the size and encoding of every node, and that all leaves are at one
+    // depth. the key order is left to the in-order walk
+    fn validate(
+        &self,
+        degree: usize,
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+    ) -> Result<(), String> {
+        if depth > 0 && self.len() < degree - 1 {
+            return Err(format!(
+                "node at depth {} has {} keys, less than {}",
+                depth,
+                self.len(),
+                degree - 1
+            ));
+        }
+        if self.len() > 2 * degree - 1 || self.len() == 0 {
+            return Err(format!("node at depth {} has {} keys", depth, self.len()));
+        }
+        self.keys
+            .check()
+            .map_err(|problem| format!("node at depth {}: {}", depth, problem))?;
+        if self.is_leaf() {
+            return match *leaf_depth.get_or_insert(depth) == depth {
+                true => Ok(()),
+                false => Err(format!(
+                    "leaf at depth {} while others are deeper or shallower",
+                    depth
+                )),
+            };
+        }
+        if self.children.len() != self.len() + 1 {
+            return Err(format!(
+                "node at depth {} has {} keys but {} children",
+                depth,
+                self.len(),
+                self.children.len()
+            ));
+        }
+        self.children
+            .iter()
+            .try_for_each(|child| child.validate(degree, depth + 1, leaf_depth))
+    }
+}
+
+pub(crate) struct Tree<E> {
+    root: Option<Box<Node<E>>>,
+    len: usize,
+    degree: usize,
+}
+
+impl<E: Encoding> Tree<E> {
+    pub(crate) fn new(degree: usize) -> Self {
+        assert!(degree >= 2, "degree must be atleast 2!");
+        Tree {
+            root: None,
+            len: 0,
+            degree,
+        }
+    }
+
+    pub(crate) fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub(crate) fn contains(&self, probe: &E::Probe) -> bool {
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            match current.keys.find(probe) {
+                Ok(_) => return true,
+                Err(i) => node = current.children.get(i),
+            }
+        }
+        false
+    }
+
+    pub(crate) fn insert(&mut self, key: E::Key) -> bool {
+        let degree = self.degree;
+        let root = self
+            .root
+            .get_or_insert_with(|| Box::new(Node::new(Vec::new(), Vec::new())));
+        if root.len() == 2 * degree - 1 {
+            let old_root = std::mem::replace(root, Box::new(Node::new(Vec::new(), Vec::new())));
+            root.children.push(*old_root);
+            root.split_child(0, degree);
+        }
+        let inserted = root.insert_non_full(key, degree);
+        self.len += inserted as usize;
+        inserted
+    }
+
+    pub(crate) fn delete(&mut self, probe: &E::Probe) -> bool {
+        let Some(root) = &mut self.root else {
+            return false;
+        };
+        let deleted = root.delete(probe, self.degree);
+        // a root left without keys gives way to its only child, or to nothing
+        if root.len() == 0 {
+            self.root = match root.is_leaf() {
+                true => None,
+                false => Some(Box::new(root.children.remove(0))),
+            };
+        }
+        self.len -= deleted as usize;
+        deleted
+    }
+
+    pub(crate) fn last(&self) -> Option<E::Key> {
+        let mut node = self.root.as_deref()?;
+        while let Some(child) = node.children.last() {
+            node = child;
+        }
+        Some(node.keys.key(node.len() - 1))
+    }
+
+    pub(crate) fn range(&self, start: Bound<&E::Probe>, end: Bound<E::Key>) -> Iter<'_, E> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            end,
+        };
+        let mut node = self.root.as_deref();
+        while let Some(current) = node {
+            // an included start found here is where the walk begins, an
+            // excluded one has the walk go on right of it
+            let (i, found) = match start {
+                Bound::Unbounded => (0, false),
+                Bound::Included(start) => match current.keys.find(start) {
+                    Ok(i) => (i, true),
+                    Err(i) => (i, false),
+                },
+                Bound::Excluded(start) => match current.keys.find(start) {
+                    Ok(i) => (i + 1, false),
+                    Err(i) => (i, false),
+                },
+            };
+            iter.stack.push((current, i));
+            node = match found {
+                true => None,
+                false => current.children.get(i),
+            };
+        }
+        iter
+    }
+
+    pub(crate) fn memory_usage(&self) -> usize {
+        match &self.root {
+            None => 0,
+            Some(root) => size_of::<Node<E>>() + root.memory_usage(),
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(root) = &self.root {
+            root.validate(self.degree, 0, &mut None)?;
+        }
+        let mut count = 0;
+        let mut previous = None;
+        for key in self.range(Bound::Unbounded, Bound::Unbounded) {
+            if previous.as_ref().is_some_and(|previous| *previous >= key) {
+                return Err(format!("keys out of order at position {}", count));
+            }
+            previous = Some(key);
+            count += 1;
+        }
+        match count == self.len {
+            true => Ok(()),
+            false => Err(format!("{} keys stored but len says {}", count, self.len)),
+        }
+    }
+}
+
+// the in-order walk, the stack holds (node, index of the next key to hand out
+// of it), the nodes below the top are ancestors waiting for their child
+pub(crate) struct Iter<'a, E: Encoding> {
+    stack: Vec<(&'a Node<E>, usize)>,
+    end: Bound<E::Key>,
+}
+
+impl<E: Encoding> Iterator for Iter<'_, E> {
+    type Item = E::Key;
+
+    fn next(&mut self) -> Option<E::Key> {
+        loop {
+            let (node, i) = self.stack.pop()?;
+            if i == node.len() {
+                continue;
+            }
+            self.stack.push((node, i + 1));
+            let key = node.keys.key(i);
+            let past_end = match &self.end {
+                Bound::Included(end) => key.cmp(end) == Ordering::Greater,
+                Bound::Excluded(end) => key.cmp(end) != Ordering::Less,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                self.stack.clear();
+                return None;
+            }
+            // the subtree right of the key comes next, from its smallest key
+            let mut child = node.children.get(i + 1);
+            while let Some(current) = child {
+                self.stack.push((current, 0));
+                child = current.children.first();
+            }
+            return Some(key);
+        }
+    }
+}
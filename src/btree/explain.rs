@@ -0,0 +1,114 @@
+use std::borrow::Borrow;
+use std::fmt;
+
+use super::Btree;
+use crate::compare::Compare;
+
+/*
+** what a lookup costs, level by level, see Btree::explain_search
+** the same descent as search and path_to, with the comparisons each node's
+** binary search made counted along the way (the extra ones check_ord makes
+** are left out). explaining a search costs what doing it does
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchExplanation {
+    levels: Vec<SearchLevel>,
+}
+
+// one node of an explained search, index as in PathStep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLevel {
+    key_count: usize,
+    index: usize,
+    comparisons: usize,
+    matched: bool,
+}
+
+impl SearchLevel {
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn comparisons(&self) -> usize {
+        self.comparisons
+    }
+
+    pub fn matched(&self) -> bool {
+        self.matched
+    }
+}
+
+impl SearchExplanation {
+    // the visited nodes, from the root down
+    pub fn levels(&self) -> &[SearchLevel] {
+        &self.levels
+    }
+
+    // how many levels the search went through, 0 for an empty tree
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn comparisons(&self) -> usize {
+        self.levels.iter().map(SearchLevel::comparisons).sum()
+    }
+
+    pub fn found(&self) -> bool {
+        self.levels.last().is_some_and(SearchLevel::matched)
+    }
+}
+
+impl fmt::Display for SearchExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (depth, level) in self.levels.iter().enumerate() {
+            let to = match level.matched {
+                true => "match",
+                false if depth + 1 == self.levels.len() => "miss",
+                false => "down",
+            };
+            writeln!(
+                f,
+                "{}: {} keys, {} at {}, {} cmp",
+                depth, level.key_count, to, level.index, level.comparisons
+            )?;
+        }
+        write!(
+            f,
+            "{} after {} levels, {} cmp",
+            if self.found() { "found" } else { "not found" },
+            self.depth(),
+            self.comparisons()
+        )
+    }
+}
+
+impl<T, C> Btree<T, C> {
+    // the path a lookup of key takes, with what it cost at every level
+    pub fn explain_search<Q: ?Sized>(&self, key: &Q) -> SearchExplanation
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let mut levels = Vec::new();
+        self.descend(
+            |stored| self.cmp.compare(stored.borrow(), key),
+            |node, at, comparisons| {
+                let (index, matched) = match at {
+                    Ok(i) => (i, true),
+                    Err(i) => (i, false),
+                };
+                levels.push(SearchLevel {
+                    key_count: node.keys().len(),
+                    index,
+                    comparisons,
+                    matched,
+                });
+            },
+        );
+        SearchExplanation { levels }
+    }
+}
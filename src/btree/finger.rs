@@ -0,0 +1,130 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+use super::Btree;
+use super::metrics::Counter;
+use crate::compare::Compare;
+
+/*
+** finger search: a Finger remembers where the last lookup through it ended
+** (the child taken at every level) and the next lookup starts from there. it
+** climbs only as long as the key lies outside the subtree it's in and goes
+** down again from the first one holding it, so for a key d ranks away from
+** the last one only the O(log d) levels around both are visited
+**
+** every change to a tree gives it a new generation, drawn from a counter all
+** trees share. a finger remembers the generation its path was taken in and
+** starts over from the root once that's not the tree's anymore (after a
+** mutation, or when the finger is used on another tree). page tokens are
+** stamped the same way, see Btree::page
+*/
+
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+// a generation no tree has had before, never 0
+pub(super) fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Relaxed) + 1
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Finger {
+    // the child taken at every level on the way to where the last lookup ended
+    path: Vec<usize>,
+    // generation of the tree the path was taken in, 0 for none
+    generation: u64,
+}
+
+impl Finger {
+    // a finger that hasn't been anywhere yet, its first lookup starts at the root
+    pub fn new() -> Self {
+        Finger::default()
+    }
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // the stored key equal to key, looked up from where finger was left
+    // finger is left where this lookup ends
+    pub fn get_with_finger<Q: ?Sized>(&self, finger: &mut Finger, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        if finger.generation != self.generation {
+            finger.path.clear();
+            finger.generation = self.generation;
+        }
+        let root = self.root.as_deref()?;
+        let mut probe = |stored: &T| {
+            self.counters.bump(Counter::Comparison);
+            self.cmp.compare(stored.borrow(), key)
+        };
+
+        // the nodes along the path and the keys around each one's subtree,
+        // picked up without a single comparison
+        let mut nodes = vec![root];
+        let mut bounds: Vec<(Option<&T>, Option<&T>)> = vec![(None, None)];
+        for &i in &finger.path {
+            let node = nodes[nodes.len() - 1];
+            let (lower, upper) = bounds[bounds.len() - 1];
+            let children = node
+                .children()
+                .expect("a finger only passes internal nodes");
+            let lower = i
+                .checked_sub(1)
+                .map_or(lower, |left| Some(&node.keys()[left]));
+            bounds.push((lower, node.keys().get(i).or(upper)));
+            nodes.push(&children[i]);
+        }
+
+        // climb until the subtree holds the key, the root holds every key
+        let mut level = nodes.len() - 1;
+        while level > 0 {
+            let (lower, upper) = bounds[level];
+            let inside = lower.is_none_or(|lower| probe(lower) == Ordering::Less)
+                && upper.is_none_or(|upper| probe(upper) == Ordering::Greater);
+            if inside {
+                break;
+            }
+            level -= 1;
+        }
+        finger.path.truncate(level);
+
+        // and go down from there
+        let mut node = nodes[level];
+        loop {
+            self.counters.bump(Counter::NodeVisit);
+            match (node.lower_bound(&mut probe), node.children()) {
+                (Ok(i), _) => return Some(&node.keys()[i]),
+                (Err(_), None) => return None,
+                (Err(i), Some(children)) => {
+                    finger.path.push(i);
+                    node = &children[i];
+                }
+            }
+        }
+    }
+
+    pub fn search_with_finger<Q: ?Sized>(&self, finger: &mut Finger, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_with_finger(finger, key).is_some()
+    }
+
+    // a number that changes whenever the tree might have: every insert and
+    // removal (through the tree, a cursor or a map), every rebuild, and every
+    // handing out of keys mutably. an insert of a key already stored and a
+    // removal of one that isn't count too, they may split or merge nodes on
+    // the way down. no two trees ever have the same generation, so an equal
+    // one means the same tree, unchanged
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // the tree changed, fingers and page tokens into it are stale
+    pub(super) fn bump_generation(&mut self) {
+        self.generation = next_generation();
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use super::Btree;
+
+/*
+** string keys shared instead of copied: an Interner hands out one Arc<str>
+** per distinct string, so trees (and results cloned out of them) holding the
+** same string all point at a single allocation, and cloning a key is a
+** reference count bump. the trees are plain Btree<Arc<str>>, looked up,
+** deleted and ranged over with a &str
+**
+** the interner keeps every string it has handed out alive for as long as it
+** lives itself
+*/
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: Mutex<HashSet<Arc<str>>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    // the shared copy of s, made the first time s comes up
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        let mut strings = self.strings.lock().expect("interner lock poisoned");
+        match strings.get(s) {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared: Arc<str> = Arc::from(s);
+                strings.insert(Arc::clone(&shared));
+                shared
+            }
+        }
+    }
+
+    // number of distinct strings interned
+    pub fn len(&self) -> usize {
+        self.strings.lock().expect("interner lock poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub type InternedBtree = Btree<Arc<str>>;
+
+impl InternedBtree {
+    // store the interned copy of key, nothing is interned when it's already
+    // here. one descent finds the key or the spot for it
+    pub fn insert_interned(&mut self, interner: &Interner, key: &str) -> bool {
+        self.find_or_insert_with(key, || interner.intern(key)).1
+    }
+
+    // memory_usage plus the allocations behind the keys (string bytes and
+    // reference counts). an allocation shared with other trees is counted in
+    // full by each of them, so the sum over several trees overstates the total
+    pub fn memory_usage_with_keys(&self) -> usize {
+        self.memory_usage()
+            + self
+                .iter()
+                .map(|key| 2 * size_of::<usize>() + key.len())
+                .sum::<usize>()
+    }
+}
@@ -0,0 +1,573 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+use super::BtreeNode;
+use crate::compare::{Compare, Natural};
+
+/*
+** a position in the tree, used by the iterators to walk in both directions
+** the stack holds the path from the root down to the node with the current key
+** the top entry is (node, index of the current key in that node)
+** every entry below it is (ancestor, index of the child we went down into)
+**
+** the nice thing about remembering child indexes for the ancestors is that key i
+** of an ancestor is exactly the key that comes right after its child i, and
+** key i - 1 is the one right before it, so walking back up is cheap
+** an empty stack means the position is past the end (or before the start)
+*/
+pub(crate) struct Path<'a, T> {
+    stack: Vec<(&'a BtreeNode<T>, usize)>,
+}
+
+// cloning a path only copies references, the keys don't have to be Clone
+impl<T> Clone for Path<'_, T> {
+    fn clone(&self) -> Self {
+        Path {
+            stack: self.stack.clone(),
+        }
+    }
+}
+
+impl<'a, T> Path<'a, T> {
+    pub(crate) fn empty() -> Self {
+        Path { stack: Vec::new() }
+    }
+
+    // the same position as a list of indexes: the child taken at every level,
+    // then the index of the key. only valid until the tree changes
+    pub(crate) fn indices(&self) -> Vec<usize> {
+        self.stack.iter().map(|&(_, i)| i).collect()
+    }
+
+    // the position a list of indexes from the root leads to
+    pub(crate) fn from_indices(root: Option<&'a BtreeNode<T>>, indices: &[usize]) -> Self {
+        let mut path = Path::empty();
+        let mut node = root;
+        for &i in indices {
+            let current = node.expect("index path leads out of the tree");
+            path.stack.push((current, i));
+            node = current.children().and_then(|children| children.get(i));
+        }
+        path
+    }
+
+    // the key at this position
+    pub(crate) fn current(&self) -> Option<&'a T> {
+        let (node, i) = self.stack.last()?;
+        Some(&node.keys()[*i])
+    }
+
+    // true if both paths point at the same key of the same node
+    fn same_position(&self, other: &Path<'a, T>) -> bool {
+        match (self.stack.last(), other.stack.last()) {
+            (Some((a, i)), Some((b, j))) => std::ptr::eq(*a, *b) && i == j,
+            _ => false,
+        }
+    }
+
+    // go down from node along the leftmost children, ending at the first key of a leaf
+    fn push_leftmost(&mut self, mut node: &'a BtreeNode<T>) {
+        loop {
+            self.stack.push((node, 0));
+            match node.children() {
+                None => break,
+                Some(children) => node = &children[0],
+            }
+        }
+    }
+
+    // go down from node along the rightmost children, ending at the last key of a leaf
+    fn push_rightmost(&mut self, mut node: &'a BtreeNode<T>) {
+        loop {
+            match node.children() {
+                None => {
+                    self.stack.push((node, node.keys().len() - 1));
+                    break;
+                }
+                Some(children) => {
+                    self.stack.push((node, children.len() - 1));
+                    node = &children[children.len() - 1];
+                }
+            }
+        }
+    }
+
+    // after popping a finished node: the first ancestor whose child index still
+    // has a key after it holds the next key
+    fn ascend_next(&mut self) {
+        while let Some((node, i)) = self.stack.last() {
+            if *i < node.keys().len() {
+                return;
+            }
+            self.stack.pop();
+        }
+    }
+
+    // the mirror image: the first ancestor whose child index has a key before it
+    fn ascend_prev(&mut self) {
+        while let Some((_, i)) = self.stack.last_mut() {
+            if *i > 0 {
+                *i -= 1;
+                return;
+            }
+            self.stack.pop();
+        }
+    }
+
+    // position at the first key for which after_start is true
+    // after_start has to be false for a prefix of the keys and true for the rest
+    pub(crate) fn first_where<F: FnMut(&T) -> bool>(
+        root: Option<&'a BtreeNode<T>>,
+        after_start: F,
+    ) -> Self {
+        let mut path = Path::empty();
+        if let Some(root) = root {
+            path.descend_where(root, after_start);
+        }
+        path
+    }
+
+    // go down from node to the first key of its subtree for which after_start
+    // is true, or to the ancestor key right after the subtree if there's none
+    fn descend_where<F: FnMut(&T) -> bool>(
+        &mut self,
+        mut node: &'a BtreeNode<T>,
+        mut after_start: F,
+    ) {
+        loop {
+            let i = node.keys().partition_point(|key| !after_start(key));
+            self.stack.push((node, i));
+            match node.children() {
+                Some(children) => node = &children[i],
+                None => {
+                    if i == node.keys().len() {
+                        // nothing in this leaf, the answer (if any) is an ancestor's key
+                        self.stack.pop();
+                        self.ascend_next();
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    // move forward to the first key for which after_start is true, which must
+    // not come before the current position
+    // instead of starting over from the root this only climbs as far as the
+    // target is out of reach: once the separator right above the current
+    // subtree passes, the target is somewhere in that subtree (or is the
+    // separator itself), so nearby targets cost only a few levels
+    pub(crate) fn seek_forward<F: FnMut(&T) -> bool>(&mut self, mut after_start: F) {
+        let Some(current) = self.current() else {
+            return;
+        };
+        if after_start(current) {
+            return;
+        }
+        let mut node = self.stack.pop().unwrap().0;
+        while let Some(&(parent, i)) = self.stack.last() {
+            if parent.keys().get(i).is_some_and(&mut after_start) {
+                break;
+            }
+            node = parent;
+            self.stack.pop();
+        }
+        self.descend_where(node, after_start);
+    }
+
+    // position at the last key for which before_end is true
+    // before_end has to be true for a prefix of the keys and false for the rest
+    pub(crate) fn last_where<F: FnMut(&T) -> bool>(
+        root: Option<&'a BtreeNode<T>>,
+        mut before_end: F,
+    ) -> Self {
+        let mut path = Path::empty();
+        let mut node = match root {
+            Some(root) => root,
+            None => return path,
+        };
+        loop {
+            let i = node.keys().partition_point(|key| before_end(key));
+            match node.children() {
+                Some(children) => {
+                    path.stack.push((node, i));
+                    node = &children[i];
+                }
+                None => {
+                    if i == 0 {
+                        // nothing in this leaf, the answer (if any) is an ancestor's key
+                        path.ascend_prev();
+                    } else {
+                        path.stack.push((node, i - 1));
+                    }
+                    return path;
+                }
+            }
+        }
+    }
+
+    // move to the next key in order (or past the end)
+    pub(crate) fn next(&mut self) {
+        let Some((node, i)) = self.stack.last_mut() else {
+            return;
+        };
+        let node: &'a BtreeNode<T> = node;
+        if let Some(children) = node.children() {
+            // the next key is the smallest one in the subtree right of key i
+            *i += 1;
+            let child = *i;
+            self.push_leftmost(&children[child]);
+        } else if *i + 1 < node.keys().len() {
+            *i += 1;
+        } else {
+            self.stack.pop();
+            self.ascend_next();
+        }
+    }
+
+    // move to the previous key in order (or before the start)
+    pub(crate) fn prev(&mut self) {
+        let Some((node, i)) = self.stack.last() else {
+            return;
+        };
+        let (node, i): (&'a BtreeNode<T>, usize) = (node, *i);
+        if let Some(children) = node.children() {
+            // the previous key is the largest one in the subtree left of key i
+            // which is child i, so the index stays as it is
+            self.push_rightmost(&children[i]);
+        } else if i > 0 {
+            self.stack.last_mut().unwrap().1 -= 1;
+        } else {
+            self.stack.pop();
+            self.ascend_prev();
+        }
+    }
+}
+
+/*
+** double ended iterator over the keys between two positions (both inclusive)
+** front and back walk towards each other, once they meet the range is done
+** the root and comparator are kept around so the front can seek ahead
+*/
+pub struct Range<'a, T, C = Natural> {
+    front: Path<'a, T>,
+    back: Path<'a, T>,
+    root: Option<&'a BtreeNode<T>>,
+    cmp: &'a C,
+}
+
+impl<'a, T, C> Range<'a, T, C> {
+    // keys for which after_start and before_end are both true
+    pub(crate) fn new<S, E>(
+        root: Option<&'a BtreeNode<T>>,
+        cmp: &'a C,
+        after_start: S,
+        mut before_end: E,
+    ) -> Self
+    where
+        S: FnMut(&T) -> bool,
+        E: FnMut(&T) -> bool,
+    {
+        let front = Path::first_where(root, after_start);
+        match front.current() {
+            // the first key after the start is already past the end: empty range
+            Some(first) if before_end(first) => Range {
+                front,
+                back: Path::last_where(root, before_end),
+                root,
+                cmp,
+            },
+            _ => Range {
+                front: Path::empty(),
+                back: Path::empty(),
+                root,
+                cmp,
+            },
+        }
+    }
+
+    fn finish(&mut self) {
+        self.front.stack.clear();
+        self.back.stack.clear();
+    }
+
+    // skip ahead to the first remaining key that does not come before key
+    // (the first key >= key for natural ordering) in O(log n)
+    // seeking only ever moves forward: a key at or before the front is a no-op,
+    // and the range still ends where it did, keys already taken from the back
+    // are not seen again
+    pub fn seek<Q: ?Sized>(&mut self, key: &Q)
+    where
+        T: Borrow<Q>,
+        C: Compare<T> + Compare<Q>,
+    {
+        let cmp = self.cmp;
+        self.seek_where(
+            |stored| cmp.compare(stored.borrow(), key) != Ordering::Less,
+            |a, b| cmp.compare(a, b),
+        );
+    }
+
+    // seek, for the first remaining key reached says yes to. reached has to
+    // be false for a run of keys and then true for the rest, like for
+    // Path::first_where. order compares two keys the way the tree does
+    pub(crate) fn seek_where<P, O>(&mut self, mut reached: P, order: O)
+    where
+        P: FnMut(&T) -> bool,
+        O: Fn(&T, &T) -> Ordering,
+    {
+        match self.front.current() {
+            Some(front) if !reached(front) => {}
+            _ => return,
+        }
+        let target = Path::first_where(self.root, reached);
+        match (target.current(), self.back.current()) {
+            (Some(target_key), Some(back)) if order(target_key, back) != Ordering::Greater => {
+                self.front = target;
+            }
+            _ => self.finish(),
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Range<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let key = self.front.current()?;
+        if self.front.same_position(&self.back) {
+            self.finish();
+        } else {
+            self.front.next();
+        }
+        Some(key)
+    }
+}
+
+impl<'a, T, C> DoubleEndedIterator for Range<'a, T, C> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let key = self.back.current()?;
+        if self.back.same_position(&self.front) {
+            self.finish();
+        } else {
+            self.back.prev();
+        }
+        Some(key)
+    }
+}
+
+// in-order iterator over all keys of the tree
+pub struct Iter<'a, T, C = Natural> {
+    range: Range<'a, T, C>,
+}
+
+impl<'a, T, C> Iter<'a, T, C> {
+    pub(crate) fn new(root: Option<&'a BtreeNode<T>>, cmp: &'a C) -> Self {
+        Iter {
+            range: Range::new(root, cmp, |_| true, |_| true),
+        }
+    }
+
+    // skip ahead to the first remaining key that does not come before key,
+    // see Range::seek
+    pub fn seek<Q: ?Sized>(&mut self, key: &Q)
+    where
+        T: Borrow<Q>,
+        C: Compare<T> + Compare<Q>,
+    {
+        self.range.seek(key);
+    }
+
+    // seek, for the first remaining key reached says yes to, see
+    // Range::seek_where
+    pub(crate) fn seek_where<P: FnMut(&T) -> bool>(&mut self, reached: P)
+    where
+        C: Compare<T>,
+    {
+        let cmp = self.range.cmp;
+        self.range.seek_where(reached, |a, b| cmp.compare(a, b));
+    }
+}
+
+impl<'a, T, C> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.range.next()
+    }
+}
+
+impl<'a, T, C> DoubleEndedIterator for Iter<'a, T, C> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.range.next_back()
+    }
+}
+
+/*
+** forward iterator handing out the keys between two bounds mutably, for the
+** parts of them the comparator never looks at (a map's values)
+** a Path can't do that, it would hold on to every node it passes through. so
+** every node on the way down keeps mutable iterators over its keys and its
+** children instead, and each key and subtree is handed out exactly once
+*/
+struct Frame<'a, T> {
+    keys: std::slice::IterMut<'a, T>,
+    // None for a leaf
+    children: Option<std::slice::IterMut<'a, BtreeNode<T>>>,
+}
+
+// locate tells whether a key comes before the range (Less), inside it
+// (Equal) or after it (Greater)
+pub(crate) struct RangeMut<'a, T, L> {
+    stack: Vec<Frame<'a, T>>,
+    locate: L,
+}
+
+impl<'a, T, L: FnMut(&T) -> Ordering> RangeMut<'a, T, L> {
+    pub(crate) fn new(root: Option<&'a mut BtreeNode<T>>, locate: L) -> Self {
+        let mut range = RangeMut {
+            stack: Vec::new(),
+            locate,
+        };
+        // the same descent as Path::first_where, leaving out everything before
+        // the start at every level
+        let mut node = root;
+        while let Some(current) = node {
+            let i = current
+                .keys()
+                .partition_point(|key| (range.locate)(key) == Ordering::Less);
+            node = range.push(current, i);
+        }
+        range
+    }
+
+    // the keys and children of node from i on, handing back child i (the
+    // subtree in front of key i) for the caller to go down into
+    fn push(&mut self, node: &'a mut BtreeNode<T>, i: usize) -> Option<&'a mut BtreeNode<T>> {
+        let (keys, mut children) = match node {
+            BtreeNode::Leaf { keys } => (keys, None),
+            BtreeNode::Internal { keys, children, .. } => (keys, Some(children[i..].iter_mut())),
+        };
+        let child = children.as_mut().and_then(|children| children.next());
+        self.stack.push(Frame {
+            keys: keys[i..].iter_mut(),
+            children,
+        });
+        child
+    }
+}
+
+impl<'a, T, L: FnMut(&T) -> Ordering> Iterator for RangeMut<'a, T, L> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            let Some(key) = frame.keys.next() else {
+                self.stack.pop();
+                continue;
+            };
+            if (self.locate)(key) == Ordering::Greater {
+                self.stack.clear();
+                return None;
+            }
+            // the subtree right of the key comes next
+            let mut node = frame.children.as_mut().and_then(|children| children.next());
+            while let Some(current) = node {
+                node = self.push(current, 0);
+            }
+            return Some(key);
+        }
+    }
+}
+
+/*
+** in-order iterator over the keys as slices of the nodes holding them: a
+** leaf's keys come as one slice, an internal node's keys one at a time
+** between the slices of the subtrees around them. concatenated the slices are
+** the keys in order, and a scan gets to loop over whole slices instead of
+** paying for one next() per key
+**
+** every stack entry is a node and how far along it is: child i / 2 is next
+** when that's even, key i / 2 when it's odd
+*/
+pub struct NodeSlices<'a, T> {
+    stack: Vec<(&'a BtreeNode<T>, usize)>,
+}
+
+impl<'a, T> NodeSlices<'a, T> {
+    pub(crate) fn new(root: Option<&'a BtreeNode<T>>) -> Self {
+        NodeSlices {
+            stack: root.into_iter().map(|root| (root, 0)).collect(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for NodeSlices<'a, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<&'a [T]> {
+        loop {
+            let (node, step) = self.stack.last_mut()?;
+            let node: &'a BtreeNode<T> = node;
+            let Some(children) = node.children() else {
+                self.stack.pop();
+                return Some(node.keys());
+            };
+            let (i, key_next) = (*step / 2, *step % 2 == 1);
+            *step += 1;
+            if !key_next {
+                self.stack.push((&children[i], 0));
+                continue;
+            }
+            // there's one key less than there are children, the node is done
+            // once the last child is
+            match node.keys().get(i) {
+                Some(key) => return Some(std::slice::from_ref(key)),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+// in-order iterator over the keys in batches of a fixed size (the last one
+// may come up short), filled from node slices a whole slice at a time
+pub struct Chunks<'a, T> {
+    slices: NodeSlices<'a, T>,
+    // the rest of the slice the last batch stopped in
+    rest: &'a [T],
+    size: usize,
+}
+
+impl<'a, T> Chunks<'a, T> {
+    pub(crate) fn new(slices: NodeSlices<'a, T>, size: usize) -> Self {
+        Chunks {
+            slices,
+            rest: &[],
+            size,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Chunks<'a, T> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Vec<&'a T>> {
+        let mut chunk = Vec::with_capacity(self.size);
+        while chunk.len() < self.size {
+            if self.rest.is_empty() {
+                match self.slices.next() {
+                    Some(slice) => self.rest = slice,
+                    None => break,
+                }
+            }
+            let take = self.rest.len().min(self.size - chunk.len());
+            let (head, rest) = self.rest.split_at(take);
+            chunk.extend(head);
+            self.rest = rest;
+        }
+        (!chunk.is_empty()).then_some(chunk)
+    }
+}
@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+use super::{Btree, Iter};
+use crate::compare::{Compare, Natural};
+
+// one step of a merge join, see Btree::merge_join
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinItem<'a, T, U> {
+    Both(&'a T, U),
+    LeftOnly(&'a T),
+    RightOnly(U),
+}
+
+/*
+** the tree and a sorted stream walked side by side in key order, holding at
+** most one item of lookahead on each side. every stream item equal to a tree
+** key pairs up with it, so a run of equal stream items joins the same tree
+** key once each. a tree key nothing pairs up with comes out once as LeftOnly
+**
+** with matches_only the unmatched items are dropped. the tree side then skips
+** a gap by seeking once a single step did not catch up with the stream, so a
+** sparse stream costs O(log n) per gap instead of a walk over it
+*/
+pub struct MergeJoin<'a, T, I: Iterator, F, C = Natural> {
+    ours: Iter<'a, T, C>,
+    head: Option<&'a T>,
+    matched: bool,
+    theirs: Peekable<I>,
+    cmp: F,
+    matches_only: bool,
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // join the keys with other in key order, cmp puts a key and an item of
+    // other in order. other has to be sorted the way the tree is, by cmp
+    pub fn merge_join<'a, I, U, F>(
+        &'a self,
+        other: I,
+        cmp: F,
+    ) -> MergeJoin<'a, T, I::IntoIter, F, C>
+    where
+        I: IntoIterator<Item = U>,
+        F: Fn(&T, &U) -> Ordering,
+    {
+        let mut ours = self.iter();
+        MergeJoin {
+            head: ours.next(),
+            ours,
+            matched: false,
+            theirs: other.into_iter().peekable(),
+            cmp,
+            matches_only: false,
+        }
+    }
+}
+
+impl<'a, T, U, I, F, C> MergeJoin<'a, T, I, F, C>
+where
+    I: Iterator<Item = U>,
+    F: Fn(&T, &U) -> Ordering,
+    C: Compare<T>,
+{
+    // hand out only the pairs, skipping over gaps in the tree
+    pub fn matches_only(mut self) -> Self {
+        self.matches_only = true;
+        self
+    }
+
+    // move past the head key, Some(key) if it never paired up
+    fn advance(&mut self) -> Option<&'a T> {
+        let head = self.head.take()?;
+        self.head = self.ours.next();
+        let unmatched = !self.matched;
+        self.matched = false;
+        unmatched.then_some(head)
+    }
+}
+
+impl<'a, T, U, I, F, C> Iterator for MergeJoin<'a, T, I, F, C>
+where
+    I: Iterator<Item = U>,
+    F: Fn(&T, &U) -> Ordering,
+    C: Compare<T>,
+{
+    type Item = JoinItem<'a, T, U>;
+
+    fn next(&mut self) -> Option<JoinItem<'a, T, U>> {
+        loop {
+            let order = match (self.head, self.theirs.peek()) {
+                (None, None) => return None,
+                (Some(_), None) if self.matches_only => return None,
+                (None, Some(_)) if self.matches_only => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(head), Some(item)) => (self.cmp)(head, item),
+            };
+            match order {
+                Ordering::Less if self.matches_only => {
+                    self.advance();
+                    let item = self.theirs.peek()?;
+                    let cmp = &self.cmp;
+                    if self
+                        .head
+                        .is_some_and(|head| cmp(head, item) == Ordering::Less)
+                    {
+                        self.ours.seek_where(|key| cmp(key, item) != Ordering::Less);
+                        self.head = self.ours.next();
+                    }
+                }
+                Ordering::Less => {
+                    if let Some(key) = self.advance() {
+                        return Some(JoinItem::LeftOnly(key));
+                    }
+                }
+                Ordering::Greater => {
+                    let item = self.theirs.next()?;
+                    if !self.matches_only {
+                        return Some(JoinItem::RightOnly(item));
+                    }
+                }
+                Ordering::Equal => {
+                    let head = self.head?;
+                    self.matched = true;
+                    return self.theirs.next().map(|item| JoinItem::Both(head, item));
+                }
+            }
+        }
+    }
+}
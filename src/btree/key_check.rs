@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use super::Btree;
+use crate::error::InvalidKey;
+
+/*
+** a check every key has to pass on its way into the tree, so constraints of
+** the domain (non-empty names, ids within a range, ..) are kept by the tree
+** instead of at every call site. it runs before the tree is touched, a key it
+** turns down changes nothing, and neither does a batch holding one
+**
+** the calls that can fail hand the reason back as an InvalidKey (try_insert,
+** try_extend_from_tree, apply_batch), the ones that can't panic with it
+** (insert, replace, get_or_insert_with, insert_traced, inserts through a
+** cursor, extend_from_tree). lookups and deletes never run it, and neither
+** are the keys stored before it was set checked
+*/
+pub(crate) type KeyCheck<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+// key, the one at index among the keys of a call, checked against check
+pub(crate) fn check_key<T>(
+    check: Option<&KeyCheck<T>>,
+    index: usize,
+    key: &T,
+) -> Result<(), InvalidKey> {
+    match check {
+        Some(check) => check(key).map_err(|reason| InvalidKey { index, reason }),
+        None => Ok(()),
+    }
+}
+
+// check_key for the calls that can't hand the reason back
+pub(crate) fn admit<T>(check: Option<&KeyCheck<T>>, key: &T) {
+    if let Err(invalid) = check_key(check, 0, key) {
+        panic!("{}", invalid);
+    }
+}
+
+impl<T, C> Btree<T, C> {
+    // turn down every key check rejects from now on, with the reason it gives
+    // trees made out of this one (with_degree, split_evenly, ..) keep it
+    pub fn validate_keys<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.key_check = Some(Arc::new(check));
+        self
+    }
+
+    // the first of keys the check turns down, at its index among them
+    pub(crate) fn check_keys<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a T>,
+    ) -> Result<(), InvalidKey>
+    where
+        T: 'a,
+    {
+        keys.into_iter()
+            .enumerate()
+            .try_for_each(|(index, key)| check_key(self.key_check.as_ref(), index, key))
+    }
+}
@@ -0,0 +1,226 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::RangeBounds;
+
+use super::Btree;
+use crate::compare::{Compare, Natural};
+
+/*
+** a btree where delete only marks the key dead (a tombstone) and leaves it
+** where it is. nothing is moved, merged or borrowed, and inserting the key
+** again just brings it back to life in place, which makes bursts of deletes
+** followed by reinserts cheap
+**
+** lookups and iteration skip dead keys, len counts live ones only. purge
+** drops every tombstone and bulk builds the tree again from the live keys,
+** either when asked to or, with a purge threshold, as soon as dead keys make
+** up that share of the stored ones
+*/
+
+// a stored key and whether it has been deleted
+struct Slot<T> {
+    key: T,
+    dead: bool,
+}
+
+// orders slots by their keys with the tree's comparator
+struct Slots<C>(C);
+
+impl<T, C: Compare<T>> Compare<Slot<T>> for Slots<C> {
+    fn compare(&self, a: &Slot<T>, b: &Slot<T>) -> Ordering {
+        self.0.compare(&a.key, &b.key)
+    }
+}
+
+pub struct LazyBtree<T, C = Natural> {
+    tree: Btree<Slot<T>, Slots<C>>,
+    // number of live keys
+    len: usize,
+    tombstones: usize,
+    // share of dead keys that triggers a purge, None to only purge when asked
+    threshold: Option<f64>,
+}
+
+impl<T: Ord> LazyBtree<T> {
+    pub fn new(degree: usize) -> Self {
+        LazyBtree::with_comparator(degree, Natural)
+    }
+
+    // purge on its own once tombstones make up threshold of the stored keys
+    // threshold has to be in (0, 1]
+    pub fn with_purge_threshold(degree: usize, threshold: f64) -> Self {
+        assert!(
+            threshold > 0.0 && threshold <= 1.0,
+            "purge threshold must be in (0, 1]!"
+        );
+        LazyBtree {
+            threshold: Some(threshold),
+            ..LazyBtree::new(degree)
+        }
+    }
+}
+
+impl<T, C: Compare<T>> LazyBtree<T, C> {
+    pub fn with_comparator(degree: usize, cmp: C) -> Self {
+        LazyBtree {
+            tree: Btree::with_comparator(degree, Slots(cmp)),
+            len: 0,
+            tombstones: 0,
+            threshold: None,
+        }
+    }
+
+    // number of live keys
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // number of deleted keys still taking up their slot
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    // the slot of the key equal to key, dead or alive
+    fn slot<Q: ?Sized>(&self, key: &Q) -> Option<&Slot<T>>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let cmp = &self.tree.cmp.0;
+        self.tree
+            .get_with(|slot| cmp.compare(slot.key.borrow(), key))
+    }
+
+    pub fn search<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.slot(key)
+            .filter(|slot| !slot.dead)
+            .map(|slot| &slot.key)
+    }
+
+    // false if an equal key is already live. a dead one is brought back in
+    // place, keeping the stored key and dropping the new one
+    // a single descent either finds the slot or makes one for key
+    pub fn insert(&mut self, key: T) -> bool {
+        let (path, made) = self.tree.find_or_insert_by(
+            key,
+            |cmp, slot, key| cmp.0.compare(&slot.key, key),
+            |_, key| Slot { key, dead: false },
+        );
+        let inserted = made || {
+            let slot = self.tree.key_at_mut(&path);
+            let revived = mem::replace(&mut slot.dead, false);
+            self.tombstones -= usize::from(revived);
+            revived
+        };
+        self.len += usize::from(inserted);
+        inserted
+    }
+
+    // mark the key dead, false if there was no live key to delete
+    pub fn delete<Q: ?Sized>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let Some(slot) = self
+            .tree
+            .get_mut_with(|cmp, slot| cmp.0.compare(slot.key.borrow(), key))
+            .filter(|slot| !slot.dead)
+        else {
+            return false;
+        };
+        slot.dead = true;
+        self.len -= 1;
+        self.tombstones += 1;
+        if self
+            .threshold
+            .is_some_and(|threshold| self.tombstones as f64 >= threshold * self.tree.len() as f64)
+        {
+            self.purge();
+        }
+        true
+    }
+
+    // remove every tombstone for good and rebuild the tree, packed full, out
+    // of the live keys. returns the number of tombstones dropped
+    pub fn purge(&mut self) -> usize {
+        let purged = self.tombstones;
+        let live = self
+            .tree
+            .take_sorted()
+            .into_iter()
+            .filter(|slot| !slot.dead)
+            .collect();
+        self.tree.fill_sorted(live);
+        self.tombstones = 0;
+        purged
+    }
+
+    // every live key in ascending order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.tree.iter().filter_map(live)
+    }
+
+    // the live keys inside the range in ascending order
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(
+        &self,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = &T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let cmp = &self.tree.cmp.0;
+        self.tree
+            .range_with(range, move |slot, bound| {
+                cmp.compare(slot.key.borrow(), bound)
+            })
+            .filter_map(live)
+    }
+
+    // heap bytes used by the tree's nodes, tombstones included until purged
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    // check the b-tree invariants and that the live and dead keys add up
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()?;
+        let dead = self.tree.iter().filter(|slot| slot.dead).count();
+        if dead != self.tombstones {
+            return Err(format!(
+                "tree holds {} tombstones but {} are counted",
+                dead, self.tombstones
+            ));
+        }
+        match self.tree.len() - dead == self.len {
+            true => Ok(()),
+            false => Err(format!(
+                "tree holds {} live keys but len is {}",
+                self.tree.len() - dead,
+                self.len
+            )),
+        }
+    }
+}
+
+fn live<T>(slot: &Slot<T>) -> Option<&T> {
+    (!slot.dead).then_some(&slot.key)
+}
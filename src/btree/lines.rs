@@ -0,0 +1,52 @@
+use std::io::{self, BufRead, Write};
+
+use super::Btree;
+use crate::compare::Compare;
+use crate::error::ImportError;
+
+/*
+** trees to and from text with one key per line, for loading data into tools
+** and tests. export writes the keys in order, so an exported tree imports
+** again without any sorting
+**
+** import streams the lines and checks whether each key comes after the one
+** before it. keys that arrive strictly ascending are bulk built as they are,
+** anything else is sorted (and duplicates dropped, keeping the first) before
+** the tree is built, either way in one bottom-up pass. blank lines are parse
+** input like any other, parse decides what they mean
+*/
+impl<T, C: Compare<T>> Btree<T, C> {
+    // every key in order, formatted by fmt and followed by a newline
+    pub fn export_lines<W: Write>(&self, mut w: W, fmt: impl Fn(&T) -> String) -> io::Result<()> {
+        for key in self.iter() {
+            writeln!(w, "{}", fmt(key))?;
+        }
+        w.flush()
+    }
+}
+
+impl<T: Ord> Btree<T> {
+    // a tree of the keys parse makes of every line of r. the first line that
+    // doesn't parse ends the import with its number
+    pub fn import_lines<R: BufRead, E>(
+        degree: usize,
+        r: R,
+        parse: impl Fn(&str) -> Result<T, E>,
+    ) -> Result<Btree<T>, ImportError<E>> {
+        let mut keys: Vec<T> = Vec::new();
+        let mut sorted = true;
+        for (i, line) in r.lines().enumerate() {
+            let key = parse(&line?).map_err(|error| ImportError::Parse { line: i + 1, error })?;
+            sorted = sorted && keys.last().is_none_or(|last| *last < key);
+            keys.push(key);
+        }
+        if !sorted {
+            // stable, so of equal keys the first one read is kept
+            keys.sort();
+            keys.dedup();
+        }
+        let mut tree = Btree::new(degree);
+        tree.fill_sorted(keys);
+        Ok(tree)
+    }
+}
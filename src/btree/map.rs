@@ -0,0 +1,433 @@
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::ops::{Deref, DerefMut, RangeBounds};
+
+use super::{Btree, DEFAULT_DEGREE};
+use crate::compare::{Compare, Natural};
+
+/*
+** a map on top of the tree: every stored key carries its value along, and the
+** comparator only ever looks at the keys. so a key and its value move as one
+** through every split, merge, borrow and predecessor swap the tree does, and
+** the value can be changed in place without the tree noticing
+**
+** every entry has a weight, 1 unless a weigher says otherwise, and the map
+** keeps the total of them up to date as entries come and go, for caches
+** bounded by bytes rather than entries. an entry is weighed when it goes in
+** and again when it comes out. a value changed in place is handed out by
+** get_mut and range_mut behind a ValueMut, which weighs it again once it's
+** dropped
+*/
+
+struct Entry<K, V> {
+    key: K,
+    value: V,
+}
+
+// orders entries by their keys with the map's comparator
+struct Keys<C>(C);
+
+impl<K, V, C: Compare<K>> Compare<Entry<K, V>> for Keys<C> {
+    fn compare(&self, a: &Entry<K, V>, b: &Entry<K, V>) -> Ordering {
+        self.0.compare(&a.key, &b.key)
+    }
+}
+
+// what an entry weighs, see with_weigher
+type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+// total with an entry weighing old in it replaced by one weighing new
+// a weigher that doesn't give the same entry the same weight every time can
+// take out more than it put in, which stops at 0 rather than wrapping around
+fn reweighed(total: usize, old: usize, new: usize) -> usize {
+    let rest = total.checked_sub(old);
+    debug_assert!(
+        rest.is_some(),
+        "an entry weighs more than all of them together"
+    );
+    rest.unwrap_or(0) + new
+}
+
+// a value of the map to change in place, weighed again when this is dropped
+// so the map's total_weight follows whatever was done to it
+pub struct ValueMut<'a, K, V> {
+    key: &'a K,
+    value: &'a mut V,
+    // what the entry weighed when it was handed out
+    weight: usize,
+    total_weight: &'a Cell<usize>,
+    weigher: Option<&'a Weigher<K, V>>,
+}
+
+impl<K, V> Deref for ValueMut<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.value
+    }
+}
+
+impl<K, V> DerefMut for ValueMut<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.value
+    }
+}
+
+impl<K, V> Drop for ValueMut<'_, K, V> {
+    fn drop(&mut self) {
+        // without a weigher every entry weighs 1 whatever its value
+        if let Some(weigher) = self.weigher {
+            let weight = weigher(self.key, self.value);
+            self.total_weight
+                .set(reweighed(self.total_weight.get(), self.weight, weight));
+        }
+    }
+}
+
+impl<K, V: Debug> Debug for ValueMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+pub struct BtreeMap<K, V, C = Natural> {
+    tree: Btree<Entry<K, V>, Keys<C>>,
+    weigher: Option<Weigher<K, V>>,
+    // sum of the weights of every entry
+    total_weight: usize,
+}
+
+impl<K: Ord, V> BtreeMap<K, V> {
+    pub fn new(degree: usize) -> Self {
+        BtreeMap::with_comparator(degree, Natural)
+    }
+
+    // a map weighing its entries with weigher instead of counting them
+    pub fn with_weigher<F>(degree: usize, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        let mut map = BtreeMap::new(degree);
+        map.set_weigher(weigher);
+        map
+    }
+}
+
+impl<K, V, C: Compare<K>> BtreeMap<K, V, C> {
+    // a map ordering its keys by cmp
+    pub fn with_comparator(degree: usize, cmp: C) -> Self {
+        BtreeMap {
+            tree: Btree::with_comparator(degree, Keys(cmp)),
+            weigher: None,
+            total_weight: 0,
+        }
+    }
+
+    // weigh entries with weigher from now on, the ones stored already included
+    // (they're all weighed again, in O(n))
+    pub fn set_weigher<F>(&mut self, weigher: F)
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        self.weigher = Some(Box::new(weigher));
+        self.total_weight = self.iter().map(|(key, value)| self.weigh(key, value)).sum();
+    }
+
+    fn weigh(&self, key: &K, value: &V) -> usize {
+        self.weigher
+            .as_ref()
+            .map_or(1, |weigher| weigher(key, value))
+    }
+
+    // the weights of all entries added up, len without a weigher
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
+    pub fn degree(&self) -> usize {
+        self.tree.degree()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    // what the entry of key weighs, 1 without a weigher
+    pub fn weight<Q: ?Sized>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_key_value(key)
+            .map(|(key, value)| self.weigh(key, value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    // changes whenever the map might have, values changed in place included
+    // (see Btree::generation)
+    pub fn generation(&self) -> u64 {
+        self.tree.generation()
+    }
+
+    // how a stored entry relates to the key looked for
+    fn probe<'a, Q: ?Sized>(&'a self, key: &'a Q) -> impl Fn(&Entry<K, V>) -> Ordering + 'a
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        move |entry| self.tree.cmp.0.compare(entry.key.borrow(), key)
+    }
+
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    // the stored key along with its value, which may be told apart from the
+    // key looked up by whatever the comparator ignores
+    pub fn get_key_value<Q: ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.tree
+            .get_with(self.probe(key))
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    // the value of key to change in place, reweighed once the ValueMut is dropped
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<ValueMut<'_, K, V>>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let entry = self
+            .tree
+            .get_mut_with(|cmp, entry| cmp.0.compare(entry.key.borrow(), key))?;
+        let weigher = self.weigher.as_ref();
+        Some(value_mut(
+            entry,
+            Cell::from_mut(&mut self.total_weight),
+            weigher,
+        ))
+    }
+
+    // store value under key, handing back the value it replaces
+    // a key already stored is kept and the new one dropped
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let added = self.weigh(&key, &value);
+        let stored = self
+            .tree
+            .get_mut_with(|cmp, entry| cmp.0.compare(&entry.key, &key));
+        if let Some(stored) = stored {
+            let old = std::mem::replace(&mut stored.value, value);
+            let weigh = |value| {
+                self.weigher
+                    .as_ref()
+                    .map_or(1, |weigher| weigher(&key, value))
+            };
+            self.total_weight = reweighed(self.total_weight, weigh(&old), added);
+            return Some(old);
+        }
+        self.tree.insert(Entry { key, value });
+        self.total_weight += added;
+        None
+    }
+
+    // an entry taken out of the tree, its weight no longer counted
+    fn took(&mut self, entry: Entry<K, V>) -> (K, V) {
+        self.total_weight = reweighed(self.total_weight, self.weigh(&entry.key, &entry.value), 0);
+        (entry.key, entry.value)
+    }
+
+    // remove key, handing back its value
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    // remove key, handing back the stored key and its value
+    pub fn remove_entry<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let entry = self
+            .tree
+            .remove_with(|cmp, entry| cmp.0.compare(entry.key.borrow(), key))?;
+        Some(self.took(entry))
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.first().map(|entry| (&entry.key, &entry.value))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.tree.last().map(|entry| (&entry.key, &entry.value))
+    }
+
+    // remove the first key, handing it back with its value
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let entry = self.tree.pop_first()?;
+        Some(self.took(entry))
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let entry = self.tree.pop_last()?;
+        Some(self.took(entry))
+    }
+
+    // every key and value, in key order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> {
+        self.tree.iter().map(|entry| (&entry.key, &entry.value))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // the keys and values inside the range, in key order
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(
+        &self,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = (&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let cmp = &self.tree.cmp.0;
+        self.tree
+            .range_with(range, move |entry, bound| {
+                cmp.compare(entry.key.borrow(), bound)
+            })
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    // the keys inside the range with their values to change, in key order
+    // nothing is collected up front, the range is walked as it's consumed
+    // every value is reweighed once its ValueMut is dropped
+    pub fn range_mut<Q: ?Sized, R: RangeBounds<Q>>(
+        &mut self,
+        range: R,
+    ) -> impl Iterator<Item = (&K, ValueMut<'_, K, V>)>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let total_weight = Cell::from_mut(&mut self.total_weight);
+        let weigher = self.weigher.as_ref();
+        self.tree
+            .range_mut_with(range, |cmp, entry, bound| {
+                cmp.0.compare(entry.key.borrow(), bound)
+            })
+            .map(move |entry| {
+                let value = value_mut(entry, total_weight, weigher);
+                (value.key, value)
+            })
+    }
+
+    // keep only the entries f says yes to
+    // f sees every entry (in key order) before any is removed, the ones that go
+    // are then dropped in a single rebuild of the tree rather than one by one
+    pub fn retain<F: FnMut(&K, &V) -> bool>(&mut self, mut f: F) {
+        let mut removed = 0;
+        let keep: Vec<bool> = self
+            .iter()
+            .map(|(key, value)| {
+                let keep = f(key, value);
+                if !keep {
+                    removed += self.weigh(key, value);
+                }
+                keep
+            })
+            .collect();
+        self.total_weight = reweighed(self.total_weight, removed, 0);
+        if keep.iter().any(|&keep| !keep) {
+            self.tree.keep_marked(keep);
+        }
+    }
+
+    // heap bytes used by the tree's nodes, see Btree::memory_usage
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()
+    }
+}
+
+// an entry's value handed out to be changed, weighed as it is now
+fn value_mut<'a, K, V>(
+    entry: &'a mut Entry<K, V>,
+    total_weight: &'a Cell<usize>,
+    weigher: Option<&'a Weigher<K, V>>,
+) -> ValueMut<'a, K, V> {
+    let weight = weigher.map_or(1, |weigher| weigher(&entry.key, &entry.value));
+    ValueMut {
+        key: &entry.key,
+        value: &mut entry.value,
+        weight,
+        total_weight,
+        weigher,
+    }
+}
+
+impl<K: Debug, V: Debug, C: Compare<K>> Debug for BtreeMap<K, V, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// bulk loaded in O(n) from the entries std hands out in order, see the
+// conversions of the set
+impl<K: Ord, V> From<BTreeMap<K, V>> for BtreeMap<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        let mut tree = Btree::with_comparator(DEFAULT_DEGREE, Keys(Natural));
+        tree.fill_sorted(
+            map.into_iter()
+                .map(|(key, value)| Entry { key, value })
+                .collect(),
+        );
+        let total_weight = tree.len();
+        BtreeMap {
+            tree,
+            weigher: None,
+            total_weight,
+        }
+    }
+}
+
+impl<K: Ord, V> From<BtreeMap<K, V>> for BTreeMap<K, V> {
+    fn from(mut map: BtreeMap<K, V>) -> Self {
+        map.tree
+            .take_sorted()
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect()
+    }
+}
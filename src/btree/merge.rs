@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::{Btree, Iter};
+use crate::compare::{Compare, Natural};
+
+// the next key of one of the trees, ordered so the max-heap hands out the
+// smallest key first (and the earlier tree first among equal keys)
+struct Head<'a, T, C> {
+    key: &'a T,
+    source: usize,
+    cmp: &'a C,
+}
+
+impl<T, C: Compare<T>> Ord for Head<'_, T, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp
+            .compare(other.key, self.key)
+            .then(other.source.cmp(&self.source))
+    }
+}
+
+impl<T, C: Compare<T>> PartialOrd for Head<'_, T, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, C: Compare<T>> PartialEq for Head<'_, T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T, C: Compare<T>> Eq for Head<'_, T, C> {}
+
+/*
+** lazy k-way merge over the keys of several trees, in order
+** every tree contributes its next key to a heap, the smallest one is handed
+** out and replaced by the next key of the same tree. so a step is O(log k)
+** and nothing is collected up front
+**
+** all the trees have to be ordered the same way, the first tree's comparator
+** decides. keys stored in several trees come out once per tree unless dedup
+** is asked for
+*/
+pub struct MergeIter<'a, T, C = Natural> {
+    iters: Vec<Iter<'a, T, C>>,
+    heap: BinaryHeap<Head<'a, T, C>>,
+    dedup: bool,
+    last: Option<&'a T>,
+}
+
+// merge the keys of every tree into one ascending sequence
+pub fn merge_iter<'a, T, C: Compare<T>>(trees: &'a [Btree<T, C>]) -> MergeIter<'a, T, C> {
+    let mut iters: Vec<Iter<'a, T, C>> = trees.iter().map(|tree| tree.iter()).collect();
+    let mut heap = BinaryHeap::with_capacity(trees.len());
+    if let Some(first) = trees.first() {
+        for (source, iter) in iters.iter_mut().enumerate() {
+            if let Some(key) = iter.next() {
+                heap.push(Head {
+                    key,
+                    source,
+                    cmp: &first.cmp,
+                });
+            }
+        }
+    }
+    MergeIter {
+        iters,
+        heap,
+        dedup: false,
+        last: None,
+    }
+}
+
+impl<'a, T, C: Compare<T>> MergeIter<'a, T, C> {
+    // hand out keys stored in several trees only once
+    pub fn dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+}
+
+impl<'a, T, C: Compare<T>> Iterator for MergeIter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let head = self.heap.pop()?;
+            if let Some(key) = self.iters[head.source].next() {
+                self.heap.push(Head { key, ..head });
+            }
+            let repeated = self.dedup
+                && self
+                    .last
+                    .is_some_and(|last| head.cmp.compare(last, head.key) == Ordering::Equal);
+            if !repeated {
+                self.last = Some(head.key);
+                return Some(head.key);
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(feature = "metrics")]
+use std::sync::atomic::AtomicU64;
+#[cfg(any(feature = "tracing", feature = "observer"))]
+use std::sync::atomic::AtomicUsize;
+#[cfg(any(feature = "metrics", feature = "tracing", feature = "observer"))]
+use std::sync::atomic::Ordering::Relaxed;
+
+#[cfg(feature = "observer")]
+use super::observer::{Observer, TreeEvent};
+
+#[cfg(feature = "metrics")]
+use super::Btree;
+
+/*
+** counts of what the tree did to its nodes, for tuning the degree
+** only kept with the "metrics" feature: without it the counters are a zero
+** sized type and every bump compiles to nothing
+**
+** comparisons and visited nodes are those of lookups, inserts and deletes
+** (the descents), iteration and range scans aren't counted
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub splits: u64,
+    pub merges: u64,
+    pub borrows_left: u64,
+    pub borrows_right: u64,
+    pub comparisons: u64,
+    pub nodes_visited: u64,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Counter {
+    Split,
+    Merge,
+    BorrowLeft,
+    BorrowRight,
+    Comparison,
+    NodeVisit,
+}
+
+/*
+** what gets threaded through every descent for instrumentation: the counters
+** for metrics, for tracing and observers the depth the current operation has
+** reached (every visited node is one level further down, so the depth of a
+** child being split or fixed is the number of nodes visited so far), and the
+** observer to tell about structural changes
+** atomics so lookups can count through a shared reference
+*/
+pub(crate) struct Counters {
+    #[cfg(feature = "metrics")]
+    counts: [AtomicU64; 6],
+    #[cfg(any(feature = "tracing", feature = "observer"))]
+    depth: AtomicUsize,
+    #[cfg(feature = "observer")]
+    pub(crate) observer: Option<Observer>,
+}
+
+impl Counters {
+    pub(crate) fn new() -> Self {
+        Counters {
+            #[cfg(feature = "metrics")]
+            counts: Default::default(),
+            #[cfg(any(feature = "tracing", feature = "observer"))]
+            depth: AtomicUsize::new(0),
+            #[cfg(feature = "observer")]
+            observer: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn bump(&self, counter: Counter) {
+        #[cfg(feature = "metrics")]
+        self.counts[counter as usize].fetch_add(1, Relaxed);
+        #[cfg(any(feature = "tracing", feature = "observer"))]
+        if let Counter::NodeVisit = counter {
+            self.depth.fetch_add(1, Relaxed);
+        }
+        let _ = counter;
+    }
+
+    // a mutation starts again from the root
+    #[cfg(any(feature = "tracing", feature = "observer"))]
+    pub(crate) fn start(&self) {
+        self.depth.store(0, Relaxed);
+    }
+
+    #[cfg(any(feature = "tracing", feature = "observer"))]
+    pub(crate) fn depth(&self) -> usize {
+        self.depth.load(Relaxed)
+    }
+
+    // hand the event to the observer, if there is one
+    #[cfg(feature = "observer")]
+    pub(crate) fn observe(&self, event: TreeEvent) {
+        if let Some(observer) = &self.observer {
+            (observer.lock().expect("observer panicked"))(event);
+        }
+    }
+}
+
+// a tracing event that isn't even compiled without the tracing feature
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+// enter a span for a whole operation, and start counting depth from the root
+macro_rules! trace_span {
+    ($counters:expr, $name:literal $(, $($field:tt)*)?) => {
+        #[cfg(any(feature = "tracing", feature = "observer"))]
+        $counters.start();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $($field)*)?).entered();
+    };
+}
+
+// tell the observer about a structural change, the event isn't even built
+// without the observer feature
+macro_rules! observe {
+    ($counters:expr, $event:expr) => {
+        #[cfg(feature = "observer")]
+        $counters.observe($event);
+    };
+}
+
+pub(crate) use {observe, trace_event, trace_span};
+
+#[cfg(feature = "metrics")]
+impl<T, C> Btree<T, C> {
+    // the counters since the tree was created (or last reset)
+    pub fn metrics(&self) -> Metrics {
+        let get = |counter: Counter| self.counters.counts[counter as usize].load(Relaxed);
+        Metrics {
+            splits: get(Counter::Split),
+            merges: get(Counter::Merge),
+            borrows_left: get(Counter::BorrowLeft),
+            borrows_right: get(Counter::BorrowRight),
+            comparisons: get(Counter::Comparison),
+            nodes_visited: get(Counter::NodeVisit),
+        }
+    }
+
+    pub fn reset_metrics(&self) {
+        for counter in &self.counters.counts {
+            counter.store(0, Relaxed);
+        }
+    }
+}
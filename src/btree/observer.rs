@@ -0,0 +1,59 @@
+#[cfg(feature = "observer")]
+use std::sync::Mutex;
+
+#[cfg(feature = "observer")]
+use super::Btree;
+
+/*
+** a structural change, handed to the observer set with set_observer right
+** where it happens (only with the "observer" feature). the depth is that of
+** the node split, or of the child merged or refilled (the root is at 0)
+**
+** a root split is RootGrew followed by the Split at depth 0, a delete that
+** empties an internal root is the Merge at depth 1 followed by RootShrank.
+** the first key's leaf and the last key's removal aren't reported, nor is
+** anything a bulk rebuild does (rebuild, delete_many, extend_from_tree, ..)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeEvent {
+    // a full node was split in two, its middle key went up into a parent that
+    // held parent_key_count keys before
+    Split {
+        depth: usize,
+        parent_key_count: usize,
+    },
+    // two siblings and the key between them became one node
+    Merge {
+        depth: usize,
+    },
+    // a node took a key from its left sibling (through the parent)
+    BorrowLeft {
+        depth: usize,
+    },
+    // a node took a key from its right sibling (through the parent)
+    BorrowRight {
+        depth: usize,
+    },
+    // a new root went on top of the old one, the tree is one level taller
+    RootGrew,
+    // the root's only child became the root, the tree is one level shorter
+    RootShrank,
+}
+
+// what set_observer keeps, locked so lookups could fire events through a
+// shared reference as well
+#[cfg(feature = "observer")]
+pub(crate) type Observer = Mutex<Box<dyn FnMut(TreeEvent) + Send>>;
+
+#[cfg(feature = "observer")]
+impl<T, C> Btree<T, C> {
+    // call observer with every structural change from now on, in the order
+    // they happen, replacing any observer set before
+    pub fn set_observer<F: FnMut(TreeEvent) + Send + 'static>(&mut self, observer: F) {
+        self.counters.observer = Some(Mutex::new(Box::new(observer)));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.counters.observer = None;
+    }
+}
@@ -0,0 +1,104 @@
+use std::cmp::Ordering;
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+use super::Btree;
+use crate::compare::Compare;
+
+/*
+** the set operators of std's BTreeSet: &a | &b, &a & &b, &a - &b and &a ^ &b
+** build a new tree out of two others. both trees are walked side by side in
+** key order, which hands out the keys of the result already sorted, so the
+** result is bulk loaded in O(n + m) instead of built key by key
+**
+** the keys of the result are clones of the keys of the two trees (the copy
+** in the left one for keys in both). the result has the left tree's degree
+** and comparator, and both trees have to be ordered by the same comparator
+*/
+
+// which keys of the two trees make it into the result, by where they're stored
+#[derive(Clone, Copy)]
+enum SetOp {
+    Union,
+    Intersection,
+    Difference,
+    SymmetricDifference,
+}
+
+impl SetOp {
+    fn keeps(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            SetOp::Union => true,
+            SetOp::Intersection => in_left && in_right,
+            SetOp::Difference => !in_right,
+            SetOp::SymmetricDifference => in_left != in_right,
+        }
+    }
+}
+
+fn combine<T: Clone, C: Compare<T> + Clone>(
+    left: &Btree<T, C>,
+    right: &Btree<T, C>,
+    op: SetOp,
+) -> Btree<T, C> {
+    let mut ours = left.iter().peekable();
+    let mut theirs = right.iter().peekable();
+    let mut keys = Vec::new();
+    loop {
+        let order = match (ours.peek(), theirs.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(ours), Some(theirs)) => left.cmp.compare(ours, theirs),
+        };
+        let (key, in_left, in_right) = match order {
+            Ordering::Less => (ours.next(), true, false),
+            Ordering::Greater => (theirs.next(), false, true),
+            Ordering::Equal => {
+                theirs.next();
+                (ours.next(), true, true)
+            }
+        };
+        if op.keeps(in_left, in_right) {
+            keys.extend(key.cloned());
+        }
+    }
+    let mut result = Btree::with_comparator(left.degree, left.cmp.clone());
+    result.fill_sorted(keys);
+    result
+}
+
+// the keys in either tree
+impl<T: Clone, C: Compare<T> + Clone> BitOr<&Btree<T, C>> for &Btree<T, C> {
+    type Output = Btree<T, C>;
+
+    fn bitor(self, other: &Btree<T, C>) -> Btree<T, C> {
+        combine(self, other, SetOp::Union)
+    }
+}
+
+// the keys in both trees
+impl<T: Clone, C: Compare<T> + Clone> BitAnd<&Btree<T, C>> for &Btree<T, C> {
+    type Output = Btree<T, C>;
+
+    fn bitand(self, other: &Btree<T, C>) -> Btree<T, C> {
+        combine(self, other, SetOp::Intersection)
+    }
+}
+
+// the keys in the left tree but not the right one
+impl<T: Clone, C: Compare<T> + Clone> Sub<&Btree<T, C>> for &Btree<T, C> {
+    type Output = Btree<T, C>;
+
+    fn sub(self, other: &Btree<T, C>) -> Btree<T, C> {
+        combine(self, other, SetOp::Difference)
+    }
+}
+
+// the keys in exactly one of the two trees
+impl<T: Clone, C: Compare<T> + Clone> BitXor<&Btree<T, C>> for &Btree<T, C> {
+    type Output = Btree<T, C>;
+
+    fn bitxor(self, other: &Btree<T, C>) -> Btree<T, C> {
+        combine(self, other, SetOp::SymmetricDifference)
+    }
+}
@@ -0,0 +1,303 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use super::encoded::{Encoding, Iter, Tree};
+
+/*
+** a b-tree of unsigned integer keys where every node stores its smallest key
+** once (the base) and every key as its distance from the base, all packed at
+** the fewest bits the largest distance needs. dense keys like row ids or
+** sequence numbers are close together within a node, so a key takes a few
+** bits instead of 64
+**
+** a probe is turned into a distance as well and searched for among the
+** packed ones, keys are only decoded when they're handed out. an insert that
+** fits the node's width shifts the packed distances in place, anything that
+** moves the base or needs more bits re-encodes the node, as do splits and
+** merges. a removal at either end re-encodes too, so a node's width never
+** stays wider than its keys need
+*/
+pub trait PackKey: Ord + Copy {
+    fn to_u64(self) -> u64;
+    // the value always comes from a key of this type
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! pack_key {
+    ($($t:ty),*) => {
+        $(
+            impl PackKey for $t {
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+pack_key!(u8, u16, u32, u64, usize);
+
+// bits needed to write value down, none for 0
+fn bits(value: u64) -> u32 {
+    u64::BITS - value.leading_zeros()
+}
+
+fn mask(width: u32) -> u64 {
+    match width {
+        64 => u64::MAX,
+        _ => (1 << width) - 1,
+    }
+}
+
+// words needed for len distances of width bits
+fn words_for(len: usize, width: u32) -> usize {
+    (len * width as usize).div_ceil(64)
+}
+
+// a node's keys as a base and the packed distances from it, distance i at
+// bits i * width.. of the words, a distance may straddle two words
+pub(crate) struct Packed<K> {
+    base: u64,
+    width: u32,
+    len: usize,
+    words: Vec<u64>,
+    marker: PhantomData<K>,
+}
+
+impl<K> Default for Packed<K> {
+    fn default() -> Self {
+        Packed {
+            base: 0,
+            width: 0,
+            len: 0,
+            words: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K> Packed<K> {
+    fn delta(&self, i: usize) -> u64 {
+        if self.width == 0 {
+            return 0;
+        }
+        let bit = i * self.width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.words[word] >> offset;
+        if offset + self.width as usize > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        value & mask(self.width)
+    }
+
+    fn set_delta(&mut self, i: usize, value: u64) {
+        if self.width == 0 {
+            return;
+        }
+        let mask = mask(self.width);
+        let bit = i * self.width as usize;
+        let (word, offset) = (bit / 64, bit % 64);
+        self.words[word] = (self.words[word] & !(mask << offset)) | (value << offset);
+        if offset + self.width as usize > 64 {
+            let spilled = 64 - offset;
+            self.words[word + 1] = (self.words[word + 1] & !(mask >> spilled)) | (value >> spilled);
+        }
+    }
+
+    fn decode(&self) -> Vec<u64> {
+        (0..self.len).map(|i| self.base + self.delta(i)).collect()
+    }
+
+    // keys in order, the first one is the base and the last one the widest
+    fn encode(&mut self, keys: &[u64]) {
+        self.base = keys.first().copied().unwrap_or(0);
+        self.width = keys.last().map_or(0, |last| bits(last - self.base));
+        self.len = keys.len();
+        self.words = vec![0; words_for(self.len, self.width)];
+        for (i, key) in keys.iter().enumerate() {
+            self.set_delta(i, key - self.base);
+        }
+    }
+}
+
+impl<K: PackKey> Encoding for Packed<K> {
+    type Key = K;
+    type Probe = K;
+
+    fn probe(key: &K) -> &K {
+        key
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn key(&self, i: usize) -> K {
+        K::from_u64(self.base + self.delta(i))
+    }
+
+    // a probe below the base or too far from it for the width is outside the
+    // node, anything else is binary searched for by its distance
+    fn find(&self, probe: &K) -> Result<usize, usize> {
+        let probe = probe.to_u64();
+        if self.len == 0 || probe < self.base {
+            return Err(0);
+        }
+        let distance = probe - self.base;
+        if bits(distance) > self.width {
+            return Err(self.len);
+        }
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.delta(middle).cmp(&distance) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Ok(middle),
+            }
+        }
+        Err(low)
+    }
+
+    fn insert(&mut self, i: usize, key: K) {
+        let key = key.to_u64();
+        let fits = self.len > 0 && key > self.base && bits(key - self.base) <= self.width;
+        if !fits {
+            let mut keys = self.decode();
+            keys.insert(i, key);
+            self.encode(&keys);
+            return;
+        }
+        self.words.resize(words_for(self.len + 1, self.width), 0);
+        for j in (i..self.len).rev() {
+            self.set_delta(j + 1, self.delta(j));
+        }
+        self.set_delta(i, key - self.base);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, i: usize) -> K {
+        let key = self.base + self.delta(i);
+        if i == 0 || i == self.len - 1 {
+            let mut keys = self.decode();
+            keys.remove(i);
+            self.encode(&keys);
+        } else {
+            for j in i..self.len - 1 {
+                self.set_delta(j, self.delta(j + 1));
+            }
+            self.len -= 1;
+            self.words.truncate(words_for(self.len, self.width));
+        }
+        K::from_u64(key)
+    }
+
+    fn set(&mut self, keys: Vec<K>) {
+        let keys: Vec<u64> = keys.into_iter().map(PackKey::to_u64).collect();
+        self.encode(&keys);
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.words.capacity() * size_of::<u64>()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        if self.delta(0) != 0 {
+            return Err("base is not the smallest key".to_string());
+        }
+        if self.width != bits(self.delta(self.len - 1)) {
+            return Err(format!("width {} is not the narrowest one", self.width));
+        }
+        match self.words.len() == words_for(self.len, self.width) {
+            true => Ok(()),
+            false => Err(format!("{} words for {} keys", self.words.len(), self.len)),
+        }
+    }
+}
+
+pub struct PackedBtree<K: PackKey> {
+    tree: Tree<Packed<K>>,
+}
+
+impl<K: PackKey> PackedBtree<K> {
+    pub fn new(degree: usize) -> Self {
+        PackedBtree {
+            tree: Tree::new(degree),
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.tree.degree()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.tree.contains(key)
+    }
+
+    // false if the key was already stored
+    pub fn insert(&mut self, key: K) -> bool {
+        self.tree.insert(key)
+    }
+
+    // false if the key wasn't stored
+    pub fn delete(&mut self, key: &K) -> bool {
+        self.tree.delete(key)
+    }
+
+    pub fn first(&self) -> Option<K> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<K> {
+        self.tree.last()
+    }
+
+    // every key in order, each one decoded as it's handed out
+    pub fn iter(&self) -> PackedIter<'_, K> {
+        self.range(..)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> PackedIter<'_, K> {
+        PackedIter(
+            self.tree
+                .range(range.start_bound(), range.end_bound().cloned()),
+        )
+    }
+
+    // heap bytes the tree owns: the nodes, their children vectors and the
+    // packed keys. comparable to Btree::memory_usage, whose keys own nothing
+    pub fn memory_usage(&self) -> usize {
+        self.tree.memory_usage()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tree.validate()
+    }
+}
+
+// the keys of a PackedBtree in order, see PackedBtree::range
+pub struct PackedIter<'a, K: PackKey>(Iter<'a, Packed<K>>);
+
+impl<K: PackKey> Iterator for PackedIter<'_, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.0.next()
+    }
+}
@@ -0,0 +1,50 @@
+use super::Btree;
+use crate::compare::Compare;
+use crate::error::Stale;
+
+/*
+** pagination by rank: a page ends with a token holding the rank of the next
+** key, and the next page starts from that rank in a single descent. nothing
+** has to be copied out of the tree to continue, unlike with scan, which hands
+** back a key to continue from
+**
+** a rank only points at the same key as long as the tree doesn't change, so
+** the token is stamped with the tree's generation and turned down (Stale) by
+** a tree in any other generation. a caller getting Stale starts over, or
+** switches to scan from the last key it got
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageToken {
+    // rank of the first key of the next page
+    rank: usize,
+    generation: u64,
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // the page of at most limit keys a token leads to (the first page for
+    // None), plus the token for the page after it, None once nothing comes
+    // after the page. a zero limit gives an empty page and no token
+    pub fn page(
+        &self,
+        token: Option<PageToken>,
+        limit: usize,
+    ) -> Result<(Vec<&T>, Option<PageToken>), Stale> {
+        let start = match token {
+            Some(token) if token.generation != self.generation => {
+                return Err(Stale {
+                    expected: token.generation,
+                    found: self.generation,
+                });
+            }
+            Some(token) => token.rank,
+            None => 0,
+        };
+        let end = start.saturating_add(limit);
+        let page: Vec<&T> = self.range_by_rank(start..end).collect();
+        let next = (limit > 0 && end < self.len).then_some(PageToken {
+            rank: end,
+            generation: self.generation,
+        });
+        Ok((page, next))
+    }
+}
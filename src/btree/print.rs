@@ -0,0 +1,171 @@
+use std::fmt::{Debug, Write};
+
+use super::{Btree, NodeInfo, Order, Visit};
+
+/*
+** how render_with lays a tree out, one line per node in pre-order:
+**
+**   B-tree (degree 2):
+**   Keys: [4] (leaf: false)
+**    Keys: [1, 2, 3] (leaf: true)
+**    Keys: [5, 6] (leaf: true)
+**
+** the default is exactly what print_tree writes. the output only depends on
+** the tree's shape and keys, so it can be compared against a stored snapshot
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    leaf_flags: bool,
+    key_counts: bool,
+    max_keys: Option<usize>,
+    max_depth: Option<usize>,
+    indent: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            leaf_flags: true,
+            key_counts: false,
+            max_keys: None,
+            max_depth: None,
+            indent: 1,
+        }
+    }
+}
+
+impl PrintOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the (leaf: ..) note after every node, on by default
+    pub fn leaf_flags(mut self, show: bool) -> Self {
+        self.leaf_flags = show;
+        self
+    }
+
+    // a (keys: n) note after every node, off by default
+    pub fn key_counts(mut self, show: bool) -> Self {
+        self.key_counts = show;
+        self
+    }
+
+    // a node with more keys shows the first and last of them around a ...
+    // (one more at the front when max is odd)
+    pub fn max_keys(mut self, max: usize) -> Self {
+        self.max_keys = Some(max);
+        self
+    }
+
+    // nodes below this depth aren't printed, a node at it that has children
+    // gets a "... n children" line instead of them. the root is at depth 0
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    // spaces per level, 1 by default
+    pub fn indent(mut self, width: usize) -> Self {
+        self.indent = width;
+        self
+    }
+}
+
+// in pre-order a node's keys come right after it's entered, the line is
+// written as soon as the next node is entered or the node is left
+struct Printer<'a> {
+    options: &'a PrintOptions,
+    out: String,
+    line: Option<(NodeInfo, String)>,
+    // position of the next key in the node being visited
+    index: usize,
+}
+
+impl Printer<'_> {
+    fn flush(&mut self) {
+        let Some((info, keys)) = self.line.take() else {
+            return;
+        };
+        let indent = " ".repeat(info.depth() * self.options.indent);
+        let _ = write!(self.out, "{}Keys: [{}]", indent, keys);
+        let mut notes = Vec::new();
+        if self.options.key_counts {
+            notes.push(format!("keys: {}", info.key_count()));
+        }
+        if self.options.leaf_flags {
+            notes.push(format!("leaf: {}", info.is_leaf()));
+        }
+        if !notes.is_empty() {
+            let _ = write!(self.out, " ({})", notes.join(", "));
+        }
+        self.out.push('\n');
+
+        if !info.is_leaf() && self.options.max_depth == Some(info.depth()) {
+            let indent = " ".repeat((info.depth() + 1) * self.options.indent);
+            let _ = writeln!(self.out, "{}... {} children", indent, info.key_count() + 1);
+        }
+    }
+}
+
+impl<T: Debug> Visit<T> for Printer<'_> {
+    fn enter_node(&mut self, info: &NodeInfo) {
+        self.flush();
+        self.index = 0;
+        if self.options.max_depth.is_none_or(|max| info.depth() <= max) {
+            self.line = Some((*info, String::new()));
+        }
+    }
+
+    fn key(&mut self, key: &T) {
+        let index = self.index;
+        self.index += 1;
+        let Some((info, keys)) = &mut self.line else {
+            return;
+        };
+        let (front, back) = match self.options.max_keys {
+            Some(max) if info.key_count() > max => (max.div_ceil(2), max / 2),
+            _ => (info.key_count(), 0),
+        };
+        if index >= front && index < info.key_count() - back {
+            // everything in the middle is a single ...
+            if index == front {
+                if !keys.is_empty() {
+                    keys.push_str(", ");
+                }
+                keys.push_str("...");
+            }
+            return;
+        }
+        if !keys.is_empty() {
+            keys.push_str(", ");
+        }
+        let _ = write!(keys, "{:?}", key);
+    }
+
+    fn leave_node(&mut self, _info: &NodeInfo) {
+        self.flush();
+    }
+}
+
+impl<T: Debug, C> Btree<T, C> {
+    // print the entire tree structure
+    pub fn print_tree(&self) {
+        print!("{}", self.render_with(&PrintOptions::default()));
+    }
+
+    // the tree structure laid out as the options say, see PrintOptions
+    pub fn render_with(&self, options: &PrintOptions) -> String {
+        if self.root.is_none() {
+            return "Empty tree\n".to_string();
+        }
+        let mut printer = Printer {
+            options,
+            out: format!("B-tree (degree {}):\n", self.degree),
+            line: None,
+            index: 0,
+        };
+        self.walk(&mut printer, Order::PreOrder);
+        printer.out
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashSet;
+
+use rand::Rng;
+
+use super::Btree;
+
+impl<T, C> Btree<T, C> {
+    // a stored key picked uniformly at random, None for an empty tree
+    // one random rank and a single descent to it, O(log n)
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        let root = self.root.as_deref()?;
+        Some(root.select(rng.random_range(0..self.len)))
+    }
+
+    // n distinct stored keys picked uniformly at random (every key of the tree
+    // if it holds no more than n), returned in the tree's order
+    // the ranks are drawn with floyd's algorithm, n draws however large the tree
+    pub fn sample_n<R: Rng + ?Sized>(&self, rng: &mut R, n: usize) -> Vec<&T> {
+        let Some(root) = self.root.as_deref() else {
+            return Vec::new();
+        };
+        let n = n.min(self.len);
+        let mut ranks = HashSet::with_capacity(n);
+        for upper in self.len - n..self.len {
+            let rank = rng.random_range(0..=upper);
+            // upper is new to the set every round, so a repeat is replaced by it
+            if !ranks.insert(rank) {
+                ranks.insert(upper);
+            }
+        }
+        let mut ranks: Vec<usize> = ranks.into_iter().collect();
+        ranks.sort_unstable();
+        ranks.into_iter().map(|rank| root.select(rank)).collect()
+    }
+}
@@ -0,0 +1,122 @@
+use std::borrow::Borrow;
+use std::ops::RangeBounds;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::Btree;
+use crate::compare::{Compare, Natural};
+use crate::error::Poisoned;
+
+/*
+** a tree behind an Arc<RwLock<..>>, for handing to several threads without
+** writing out the locking every time. cloning it clones the handle, every
+** clone works on the same tree. it's one lock around a plain tree, writers
+** have it all to themselves
+**
+** a lock poisoned by a thread panicking while it held it for writing turns
+** every call into Err(Poisoned) rather than a panic of its own. the tree
+** itself is never left broken by a panic inside its own operations, but a
+** panic in the middle of a with_write closure may have left the closure's
+** work half done. clear_poison is for a caller who has checked that it's fine
+*/
+pub struct SharedBtree<T, C = Natural> {
+    tree: Arc<RwLock<Btree<T, C>>>,
+}
+
+impl<T, C> Clone for SharedBtree<T, C> {
+    fn clone(&self) -> Self {
+        SharedBtree {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+}
+
+impl<T: Ord> SharedBtree<T> {
+    pub fn new(degree: usize) -> Self {
+        SharedBtree::from(Btree::new(degree))
+    }
+}
+
+impl<T, C> From<Btree<T, C>> for SharedBtree<T, C> {
+    fn from(tree: Btree<T, C>) -> Self {
+        SharedBtree {
+            tree: Arc::new(RwLock::new(tree)),
+        }
+    }
+}
+
+impl<T, C> SharedBtree<T, C> {
+    fn read(&self) -> Result<RwLockReadGuard<'_, Btree<T, C>>, Poisoned> {
+        self.tree.read().map_err(|_| Poisoned)
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<'_, Btree<T, C>>, Poisoned> {
+        self.tree.write().map_err(|_| Poisoned)
+    }
+
+    // run f on the tree, with other readers but no writer in the meantime
+    pub fn with_read<R, F: FnOnce(&Btree<T, C>) -> R>(&self, f: F) -> Result<R, Poisoned> {
+        Ok(f(&*self.read()?))
+    }
+
+    // run f on the tree, with nobody else in the meantime
+    // a panic in f poisons the lock for every handle
+    pub fn with_write<R, F: FnOnce(&mut Btree<T, C>) -> R>(&self, f: F) -> Result<R, Poisoned> {
+        Ok(f(&mut *self.write()?))
+    }
+
+    // make a poisoned lock usable again
+    pub fn clear_poison(&self) {
+        self.tree.clear_poison();
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.tree.is_poisoned()
+    }
+}
+
+impl<T, C: Compare<T>> SharedBtree<T, C> {
+    pub fn len(&self) -> Result<usize, Poisoned> {
+        self.with_read(Btree::len)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Poisoned> {
+        self.with_read(Btree::is_empty)
+    }
+
+    pub fn insert(&self, key: T) -> Result<bool, Poisoned> {
+        self.with_write(|tree| tree.insert(key))
+    }
+
+    pub fn delete<Q: ?Sized>(&self, key: &Q) -> Result<bool, Poisoned>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.with_write(|tree| tree.delete(key))
+    }
+
+    pub fn contains<Q: ?Sized>(&self, key: &Q) -> Result<bool, Poisoned>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.with_read(|tree| tree.search(key))
+    }
+
+    // a copy of every key, in order, as they were at one moment
+    pub fn snapshot_vec(&self) -> Result<Vec<T>, Poisoned>
+    where
+        T: Clone,
+    {
+        self.with_read(|tree| tree.iter().cloned().collect())
+    }
+
+    // a copy of the keys inside the range, in order
+    pub fn range_vec<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Result<Vec<T>, Poisoned>
+    where
+        T: Borrow<Q> + Clone,
+        C: Compare<Q>,
+    {
+        self.with_read(|tree| tree.range(range).cloned().collect())
+    }
+}
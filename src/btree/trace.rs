@@ -0,0 +1,334 @@
+use std::fmt::{self, Display};
+
+use super::Btree;
+use super::key_check::admit;
+use crate::compare::Compare;
+
+/*
+** a narrated insert or delete: every decision the top-down pass makes, in the
+** order it makes it, with the keys of the nodes involved as they were at that
+** moment. enough to print a log of the operation or to drive an animation of
+** it, see insert_traced and delete_traced
+**
+** the descent and rebalancing functions take a Tracer, which the plain
+** operations pass as NoTrace: its record does nothing and never builds the
+** step it's handed, so an untraced operation compiles to what it was without
+** one. steps are built out of borrowed keys and only cloned by a Recorder
+**
+** depths count from the root (0). both passes only ever go down, so the depth
+** a step happens at is the number of Descend steps before it
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceStep<T> {
+    // the root was full: a new empty root went on top of it, and the old root
+    // (now its only child, at depth 1) is split next
+    RootGrew,
+    // a full node (its keys before) was split in two around its median, which
+    // went up into the parent
+    Split {
+        depth: usize,
+        node: Vec<T>,
+        median: T,
+    },
+    // went down from a node (its keys) into one of its children
+    Descend {
+        depth: usize,
+        node: Vec<T>,
+        child: usize,
+    },
+    // the key is in a node at index, an insert ends here
+    Found {
+        depth: usize,
+        node: Vec<T>,
+        index: usize,
+    },
+    // a leaf was reached and the key isn't there, a delete ends here
+    NotFound {
+        depth: usize,
+        node: Vec<T>,
+    },
+    // the key went into a leaf at index (the leaf's keys after)
+    Inserted {
+        depth: usize,
+        node: Vec<T>,
+        index: usize,
+    },
+    // the child about to be entered had t - 1 keys and took one from its left
+    // sibling: the separator between them came down, the sibling's last key
+    // went up in its place. depth is the child's
+    BorrowLeft {
+        depth: usize,
+        child: usize,
+        separator: T,
+    },
+    // the same with the right sibling's first key
+    BorrowRight {
+        depth: usize,
+        child: usize,
+        separator: T,
+    },
+    // children left and left + 1 and the separator between them became one
+    // node, the siblings had t - 1 keys each. depth is the children's
+    Merge {
+        depth: usize,
+        left: usize,
+        separator: T,
+    },
+    // the key to delete sits in an internal node at index, its predecessor
+    // (the last key of the left subtree) takes its place and is removed from
+    // its leaf instead
+    ReplaceWithPredecessor {
+        depth: usize,
+        node: Vec<T>,
+        index: usize,
+    },
+    // the same with the successor, the right subtree's first key
+    ReplaceWithSuccessor {
+        depth: usize,
+        node: Vec<T>,
+        index: usize,
+    },
+    // the key at index was removed from a leaf (its keys before)
+    Removed {
+        depth: usize,
+        node: Vec<T>,
+        index: usize,
+    },
+    // the root lost its last key and its only child became the root
+    RootShrank,
+}
+
+impl<T: Clone> TraceStep<&T> {
+    fn cloned(self) -> TraceStep<T> {
+        let keys = |node: Vec<&T>| node.into_iter().cloned().collect();
+        match self {
+            TraceStep::RootGrew => TraceStep::RootGrew,
+            TraceStep::Split {
+                depth,
+                node,
+                median,
+            } => TraceStep::Split {
+                depth,
+                node: keys(node),
+                median: median.clone(),
+            },
+            TraceStep::Descend { depth, node, child } => TraceStep::Descend {
+                depth,
+                node: keys(node),
+                child,
+            },
+            TraceStep::Found { depth, node, index } => TraceStep::Found {
+                depth,
+                node: keys(node),
+                index,
+            },
+            TraceStep::NotFound { depth, node } => TraceStep::NotFound {
+                depth,
+                node: keys(node),
+            },
+            TraceStep::Inserted { depth, node, index } => TraceStep::Inserted {
+                depth,
+                node: keys(node),
+                index,
+            },
+            TraceStep::BorrowLeft {
+                depth,
+                child,
+                separator,
+            } => TraceStep::BorrowLeft {
+                depth,
+                child,
+                separator: separator.clone(),
+            },
+            TraceStep::BorrowRight {
+                depth,
+                child,
+                separator,
+            } => TraceStep::BorrowRight {
+                depth,
+                child,
+                separator: separator.clone(),
+            },
+            TraceStep::Merge {
+                depth,
+                left,
+                separator,
+            } => TraceStep::Merge {
+                depth,
+                left,
+                separator: separator.clone(),
+            },
+            TraceStep::ReplaceWithPredecessor { depth, node, index } => {
+                TraceStep::ReplaceWithPredecessor {
+                    depth,
+                    node: keys(node),
+                    index,
+                }
+            }
+            TraceStep::ReplaceWithSuccessor { depth, node, index } => {
+                TraceStep::ReplaceWithSuccessor {
+                    depth,
+                    node: keys(node),
+                    index,
+                }
+            }
+            TraceStep::Removed { depth, node, index } => TraceStep::Removed {
+                depth,
+                node: keys(node),
+                index,
+            },
+            TraceStep::RootShrank => TraceStep::RootShrank,
+        }
+    }
+}
+
+// [10 20], the way print_tree writes a node
+struct Node<'a, T>(&'a [T]);
+
+impl<T: Display> Display for Node<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        write!(f, "]")
+    }
+}
+
+// one line of a narrated log, "descend into child 2 of node [10 20]"
+impl<T: Display> Display for TraceStep<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceStep::RootGrew => write!(f, "root full, growing a new root on top of it"),
+            TraceStep::Split {
+                depth,
+                node,
+                median,
+            } => write!(
+                f,
+                "node {} at depth {} full, splitting: median {} promoted",
+                Node(node),
+                depth,
+                median
+            ),
+            TraceStep::Descend { node, child, .. } => {
+                write!(f, "descend into child {} of node {}", child, Node(node))
+            }
+            TraceStep::Found { node, index, .. } => {
+                write!(f, "found {} in node {}", node[*index], Node(node))
+            }
+            TraceStep::NotFound { node, .. } => write!(f, "not in leaf {}", Node(node)),
+            TraceStep::Inserted { node, index, .. } => {
+                write!(f, "inserted {} into leaf, now {}", node[*index], Node(node))
+            }
+            TraceStep::BorrowLeft {
+                child, separator, ..
+            } => write!(
+                f,
+                "child {} has too few keys, borrowing from left sibling: {} comes down",
+                child, separator
+            ),
+            TraceStep::BorrowRight {
+                child, separator, ..
+            } => write!(
+                f,
+                "child {} has too few keys, borrowing from right sibling: {} comes down",
+                child, separator
+            ),
+            TraceStep::Merge {
+                left, separator, ..
+            } => write!(
+                f,
+                "merging children {} and {} around separator {}",
+                left,
+                left + 1,
+                separator
+            ),
+            TraceStep::ReplaceWithPredecessor { node, index, .. } => write!(
+                f,
+                "{} is in internal node {}, replacing it with its predecessor",
+                node[*index],
+                Node(node)
+            ),
+            TraceStep::ReplaceWithSuccessor { node, index, .. } => write!(
+                f,
+                "{} is in internal node {}, replacing it with its successor",
+                node[*index],
+                Node(node)
+            ),
+            TraceStep::Removed { node, index, .. } => {
+                write!(f, "removed {} from leaf {}", node[*index], Node(node))
+            }
+            TraceStep::RootShrank => write!(f, "root empty, its only child becomes the root"),
+        }
+    }
+}
+
+pub(crate) trait Tracer<T> {
+    // step builds the step from the depth the operation is at
+    fn record<'a>(&mut self, step: impl FnOnce(usize) -> TraceStep<&'a T>)
+    where
+        T: 'a;
+}
+
+// what untraced operations pass
+pub(crate) struct NoTrace;
+
+impl<T> Tracer<T> for NoTrace {
+    #[inline(always)]
+    fn record<'a>(&mut self, _step: impl FnOnce(usize) -> TraceStep<&'a T>)
+    where
+        T: 'a,
+    {
+    }
+}
+
+// the steps of one traced operation
+struct Recorder<T> {
+    steps: Vec<TraceStep<T>>,
+    depth: usize,
+}
+
+impl<T: Clone> Tracer<T> for Recorder<T> {
+    fn record<'a>(&mut self, step: impl FnOnce(usize) -> TraceStep<&'a T>)
+    where
+        T: 'a,
+    {
+        let step = step(self.depth);
+        if let TraceStep::Descend { .. } = step {
+            self.depth += 1;
+        }
+        self.steps.push(step.cloned());
+    }
+}
+
+impl<T: Clone, C: Compare<T>> Btree<T, C> {
+    // insert, returning every step it took
+    pub fn insert_traced(&mut self, key: T) -> Vec<TraceStep<T>> {
+        let mut recorder = Recorder {
+            steps: Vec::new(),
+            depth: 0,
+        };
+        admit(self.key_check.as_ref(), &key);
+        self.insert_tracked(key, None, &mut recorder);
+        recorder.steps
+    }
+
+    // delete, returning every step it took
+    pub fn delete_traced(&mut self, key: &T) -> Vec<TraceStep<T>> {
+        let mut recorder = Recorder {
+            steps: Vec::new(),
+            depth: 0,
+        };
+        let height = self.height();
+        self.remove_traced_with(|cmp, stored| cmp.compare(stored, key), &mut recorder);
+        // a leaf root running out of keys leaves no root at all, that's no shrink
+        if self.height() < height && self.root.is_some() {
+            recorder.steps.push(TraceStep::RootShrank);
+        }
+        recorder.steps
+    }
+}
@@ -0,0 +1,170 @@
+use std::borrow::Borrow;
+use std::time::{Duration, Instant};
+
+use super::BtreeMap;
+use crate::compare::{Compare, Natural};
+
+/*
+** a map whose entries can be given a time to live, for use as a cache. an
+** entry with a ttl expires at the instant it was inserted plus the ttl, an
+** entry inserted without one never does
+**
+** expired entries stay in the tree until something removes them: lookups and
+** iteration treat them as absent, get_or_evict drops the one it runs into,
+** and purge_expired sweeps all of them out in a single rebuild of the tree.
+** len counts them until then
+**
+** the time comes from a clock, Instant::now unless the map was made with
+** with_clock, so tests can move time along by hand
+*/
+
+// a value and the instant it stops being there, if it ever does
+struct Expiring<V> {
+    value: V,
+    expires: Option<Instant>,
+}
+
+impl<V> Expiring<V> {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|at| at <= now)
+    }
+}
+
+pub struct TtlMap<K, V, C = Natural, F = fn() -> Instant> {
+    map: BtreeMap<K, Expiring<V>, C>,
+    clock: F,
+}
+
+impl<K: Ord, V> TtlMap<K, V> {
+    pub fn new(degree: usize) -> Self {
+        TtlMap::with_clock(degree, Instant::now)
+    }
+}
+
+impl<K: Ord, V, F: Fn() -> Instant> TtlMap<K, V, Natural, F> {
+    // a map telling the time by calling clock
+    pub fn with_clock(degree: usize, clock: F) -> Self {
+        TtlMap::with_comparator(degree, Natural, clock)
+    }
+}
+
+impl<K, V, C: Compare<K>, F: Fn() -> Instant> TtlMap<K, V, C, F> {
+    // a map ordering its keys by cmp and telling the time by calling clock
+    pub fn with_comparator(degree: usize, cmp: C, clock: F) -> Self {
+        TtlMap {
+            map: BtreeMap::with_comparator(degree, cmp),
+            clock,
+        }
+    }
+
+    // stored entries, the expired ones not purged yet included
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // store value under key for good, handing back the live value it replaces
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.store(key, value, None)
+    }
+
+    // store value under key until ttl from now, handing back the live value it
+    // replaces. inserting a key again starts its ttl over
+    // a ttl too long for an Instant to hold never runs out
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let expires = (self.clock)().checked_add(ttl);
+        self.store(key, value, expires)
+    }
+
+    fn store(&mut self, key: K, value: V, expires: Option<Instant>) -> Option<V> {
+        let old = self.map.insert(key, Expiring { value, expires })?;
+        let now = (self.clock)();
+        (!old.expired(now)).then_some(old.value)
+    }
+
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // the value under key, None once it has expired
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let now = (self.clock)();
+        self.map
+            .get(key)
+            .filter(|entry| !entry.expired(now))
+            .map(|entry| &entry.value)
+    }
+
+    // get, removing the entry if it turns out to have expired
+    pub fn get_or_evict<Q: ?Sized>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let now = (self.clock)();
+        if self.map.get(key)?.expired(now) {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.get(key).map(|entry| &entry.value)
+    }
+
+    // when the entry under key expires, None for one that never does
+    // an expired entry is absent like everywhere else
+    pub fn expires_at<Q: ?Sized>(&self, key: &Q) -> Option<Option<Instant>>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let now = (self.clock)();
+        self.map
+            .get(key)
+            .filter(|entry| !entry.expired(now))
+            .map(|entry| entry.expires)
+    }
+
+    // remove key, handing back its value if it was still live
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let now = (self.clock)();
+        self.map
+            .remove(key)
+            .filter(|entry| !entry.expired(now))
+            .map(|entry| entry.value)
+    }
+
+    // remove every entry expired at now, returns how many went
+    // the tree is rebuilt once for all of them, see BtreeMap::retain
+    pub fn purge_expired(&mut self, now: Instant) -> usize {
+        let before = self.map.len();
+        self.map.retain(|_, entry| !entry.expired(now));
+        before - self.map.len()
+    }
+
+    // the live keys and values, in key order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> {
+        let now = (self.clock)();
+        self.map
+            .iter()
+            .filter(move |(_, entry)| !entry.expired(now))
+            .map(|(key, entry)| (key, &entry.value))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.map.validate()
+    }
+}
@@ -0,0 +1,139 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+use super::Btree;
+use super::iter::Range;
+use crate::compare::{Compare, Natural};
+
+/*
+** part of a tree handed out without copying anything: the keys of a range,
+** with the tree's read api (contains, get, iter, len, first, last) limited to
+** them. the tree is borrowed, so its keys can't change while a view is
+** around, and the view is kept as the first and last stored key inside the
+** range: bounds falling between stored keys, and views of views, all come
+** down to those two keys (or none for a range without keys)
+*/
+pub struct BtreeView<'a, T, C = Natural> {
+    tree: &'a Btree<T, C>,
+    // the first and last key of the view, None if it holds none
+    ends: Option<(&'a T, &'a T)>,
+}
+
+impl<T, C: Compare<T>> Btree<T, C> {
+    // the keys inside range, as a view borrowing the tree
+    pub fn view<R: RangeBounds<T>>(&self, range: R) -> BtreeView<'_, T, C> {
+        BtreeView::new(self, range.start_bound(), range.end_bound())
+    }
+}
+
+impl<'a, T, C: Compare<T>> BtreeView<'a, T, C> {
+    fn new(tree: &'a Btree<T, C>, start: Bound<&T>, end: Bound<&T>) -> Self {
+        let mut keys = tree.range((start, end));
+        let first = keys.next();
+        let last = keys.next_back().or(first);
+        BtreeView {
+            tree,
+            ends: first.zip(last),
+        }
+    }
+
+    // the keys of range that are in this view as well. a bound outside the
+    // view is moved to the view's end, one inside it stays as it is
+    pub fn view<R: RangeBounds<T>>(&self, range: R) -> BtreeView<'a, T, C> {
+        let Some((first, last)) = self.ends else {
+            return BtreeView {
+                tree: self.tree,
+                ends: None,
+            };
+        };
+        let cmp = &self.tree.cmp;
+        let start = match range.start_bound() {
+            Bound::Included(start) | Bound::Excluded(start)
+                if cmp.compare(start, first) != Ordering::Less =>
+            {
+                range.start_bound()
+            }
+            _ => Bound::Included(first),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(end) | Bound::Excluded(end)
+                if cmp.compare(end, last) != Ordering::Greater =>
+            {
+                range.end_bound()
+            }
+            _ => Bound::Included(last),
+        };
+        BtreeView::new(self.tree, start, end)
+    }
+
+    // number of keys in the view, two descents to find the ranks of its ends
+    pub fn len(&self) -> usize {
+        self.ends.map_or(0, |(first, last)| {
+            let rank = |key: &T| self.tree.locate(key).unwrap_or_else(|rank| rank);
+            rank(last) - rank(first) + 1
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_none()
+    }
+
+    // true if key is stored and inside the view
+    pub fn contains<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // the stored key equal to key, if the view holds it
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&'a T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        let (first, last) = self.ends?;
+        let cmp = &self.tree.cmp;
+        if cmp.compare(first.borrow(), key) == Ordering::Greater
+            || cmp.compare(last.borrow(), key) == Ordering::Less
+        {
+            return None;
+        }
+        self.tree.get(key)
+    }
+
+    pub fn first(&self) -> Option<&'a T> {
+        self.ends.map(|(first, _)| first)
+    }
+
+    pub fn last(&self) -> Option<&'a T> {
+        self.ends.map(|(_, last)| last)
+    }
+
+    // the keys of the view in order
+    pub fn iter(&self) -> Range<'a, T, C> {
+        match self.ends {
+            Some((first, last)) => self.tree.range(first..=last),
+            None => Range::new(None, &self.tree.cmp, |_| true, |_| true),
+        }
+    }
+}
+
+impl<T, C> Clone for BtreeView<'_, T, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, C> Copy for BtreeView<'_, T, C> {}
+
+impl<'a, T, C: Compare<T>> IntoIterator for &BtreeView<'a, T, C> {
+    type Item = &'a T;
+    type IntoIter = Range<'a, T, C>;
+
+    fn into_iter(self) -> Range<'a, T, C> {
+        self.iter()
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+use super::{Btree, BtreeNode};
+
+/*
+** structured traversal: a visitor is told when a node is entered, about every
+** key of it, and when the node is left. the order decides where the keys of a
+** node fall relative to its children
+**
+** pre-order:   enter, keys, children, leave
+** in-order:    enter, child 0, key 0, child 1, ..., key n-1, child n, leave
+** post-order:  enter, children, keys, leave
+** level-order: one level after the other, each node as enter, keys, leave
+**
+** every method has an empty default so a visitor only writes what it needs
+*/
+pub trait Visit<T> {
+    fn enter_node(&mut self, _info: &NodeInfo) {}
+    fn key(&mut self, _key: &T) {}
+    fn leave_node(&mut self, _info: &NodeInfo) {}
+}
+
+// what a visitor gets to know about the node it's in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeInfo {
+    depth: usize,
+    key_count: usize,
+    leaf: bool,
+}
+
+impl NodeInfo {
+    fn of<T>(node: &BtreeNode<T>, depth: usize) -> Self {
+        NodeInfo {
+            depth,
+            key_count: node.keys().len(),
+            leaf: node.is_leaf(),
+        }
+    }
+
+    // the root is at depth 0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.leaf
+    }
+}
+
+/*
+** one node on the way down to a key, see Btree::path_to
+** when the key matched here, index is its position in the node, otherwise it's
+** the child the descent went on with (or the spot the key would go in a leaf)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathStep {
+    index: usize,
+    key_count: usize,
+    matched: bool,
+}
+
+impl PathStep {
+    pub(crate) fn new(index: usize, key_count: usize, matched: bool) -> Self {
+        PathStep {
+            index,
+            key_count,
+            matched,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
+
+    pub fn matched(&self) -> bool {
+        self.matched
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    InOrder,
+    PreOrder,
+    PostOrder,
+    LevelOrder,
+}
+
+impl<T> BtreeNode<T> {
+    // depth first walk of this subtree, level-order never comes through here
+    fn walk<V: Visit<T> + ?Sized>(&self, depth: usize, visitor: &mut V, order: Order) {
+        let info = NodeInfo::of(self, depth);
+        visitor.enter_node(&info);
+        let keys = self.keys();
+        let children = self.children().map_or(&[][..], |children| &children[..]);
+        match order {
+            // a leaf has no children, so it sees its keys without a gap
+            Order::InOrder if !children.is_empty() => {
+                for (child, key) in children.iter().zip(keys) {
+                    child.walk(depth + 1, visitor, order);
+                    visitor.key(key);
+                }
+                children[keys.len()].walk(depth + 1, visitor, order);
+            }
+            Order::PostOrder => {
+                for child in children {
+                    child.walk(depth + 1, visitor, order);
+                }
+                keys.iter().for_each(|key| visitor.key(key));
+            }
+            _ => {
+                keys.iter().for_each(|key| visitor.key(key));
+                for child in children {
+                    child.walk(depth + 1, visitor, order);
+                }
+            }
+        }
+        visitor.leave_node(&info);
+    }
+}
+
+impl<T, C> Btree<T, C> {
+    // walk the whole tree in the given order, an empty tree has no events
+    pub fn walk<V: Visit<T> + ?Sized>(&self, visitor: &mut V, order: Order) {
+        let Some(root) = &self.root else {
+            return;
+        };
+        if order != Order::LevelOrder {
+            root.walk(0, visitor, order);
+            return;
+        }
+
+        // no recursion here, the nodes of the next level wait in a queue
+        let mut queue = VecDeque::from([(&**root, 0)]);
+        while let Some((node, depth)) = queue.pop_front() {
+            let info = NodeInfo::of(node, depth);
+            visitor.enter_node(&info);
+            node.keys().iter().for_each(|key| visitor.key(key));
+            visitor.leave_node(&info);
+            for child in node.children().into_iter().flatten() {
+                queue.push_back((child, depth + 1));
+            }
+        }
+    }
+}
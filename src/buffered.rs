@@ -0,0 +1,822 @@
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::Range;
+
+use crate::compare::{Compare, Natural};
+
+/*
+** write optimized b+ tree: internal nodes carry a buffer of pending inserts
+** and deletes (messages) on their way down to the leaves. a write lands in
+** the root's buffer, and only once a buffer overflows are the messages for
+** its busiest child moved down, all in one go, so many scattered writes turn
+** into a few batched updates of the same nodes
+**
+** keys live in the leaves, the pivots of internal nodes are copies used for
+** routing: child i holds the keys from pivot i - 1 up to (not including)
+** pivot i. a message for a key sits in the buffer of some node on the way to
+** the key's leaf, and a message higher up is newer than any below it, so a
+** lookup goes with the first message it meets and only asks the leaf when
+** there is none
+**
+** writes are blind: insert leaves an upsert and delete a tombstone in the
+** root's buffer without looking for the key, so neither can tell whether it
+** changed anything. what a message does is only settled once a flush gets it
+** to its leaf, which is also when len catches up with it
+** a node a flush makes too large is split into as many pieces as it takes,
+** one it makes too small is merged with a neighbour (and split again if that
+** turns out too large), so the size rules are the b+ tree's:
+** a leaf other than the root holds t - 1 to 2t - 1 keys, an internal node
+** other than the root t to 2t children, and a buffer at most its capacity
+*/
+
+const DEFAULT_BUFFER_PER_DEGREE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Message {
+    Insert,
+    Delete,
+}
+
+// sorted by key, at most one message per key
+type Buffer<T> = Vec<(T, Message)>;
+
+enum Node<T> {
+    Leaf {
+        keys: Vec<T>,
+    },
+    Internal {
+        pivots: Vec<T>,
+        children: Vec<Node<T>>,
+        buffer: Buffer<T>,
+    },
+}
+
+// what the node operations need to know about their tree
+struct Config<'a, C> {
+    degree: usize,
+    capacity: usize,
+    cmp: &'a C,
+    // keys in the leaves, counted as messages reach them
+    len: Cell<usize>,
+}
+
+// the child of an internal node a key belongs to
+// keys equal to a pivot live to its right
+fn route<T, F: Fn(&T) -> Ordering>(pivots: &[T], probe: F) -> usize {
+    pivots.partition_point(|pivot| probe(pivot) != Ordering::Greater)
+}
+
+// two sorted message lists as one, newer wins for a key in both
+fn merge_messages<K, F: Fn(&K, &K) -> Ordering>(
+    older: Vec<(K, Message)>,
+    newer: Vec<(K, Message)>,
+    compare: F,
+) -> Vec<(K, Message)> {
+    let mut merged = Vec::with_capacity(older.len() + newer.len());
+    let mut older = older.into_iter().peekable();
+    for (key, message) in newer {
+        while let Some(entry) = older.next_if(|(old, _)| compare(old, &key) == Ordering::Less) {
+            merged.push(entry);
+        }
+        let _ = older.next_if(|(old, _)| compare(old, &key) == Ordering::Equal);
+        merged.push((key, message));
+    }
+    merged.extend(older);
+    merged
+}
+
+impl<T> Node<T> {
+    fn buffer_len(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Internal { buffer, .. } => buffer.len(),
+        }
+    }
+
+    // messages in this whole subtree
+    fn pending(&self) -> usize {
+        match self {
+            Node::Leaf { .. } => 0,
+            Node::Internal {
+                children, buffer, ..
+            } => buffer.len() + children.iter().map(Node::pending).sum::<usize>(),
+        }
+    }
+
+    fn too_small(&self, degree: usize) -> bool {
+        match self {
+            Node::Leaf { keys } => keys.len() < degree - 1,
+            Node::Internal { children, .. } => children.len() < degree,
+        }
+    }
+
+    fn too_big(&self, degree: usize) -> bool {
+        match self {
+            Node::Leaf { keys } => keys.len() > 2 * degree - 1,
+            Node::Internal { children, .. } => children.len() > 2 * degree,
+        }
+    }
+
+    fn internal_parts(&mut self) -> (&mut Vec<T>, &mut Vec<Node<T>>, &mut Buffer<T>) {
+        match self {
+            Node::Internal {
+                pivots,
+                children,
+                buffer,
+            } => (pivots, children, buffer),
+            Node::Leaf { .. } => unreachable!("leaves have no children to flush into"),
+        }
+    }
+
+    // the key equal to key, looked for in the buffers first
+    fn get<Q: ?Sized, C: Compare<Q>>(&self, key: &Q, cmp: &C) -> Option<&T>
+    where
+        T: Borrow<Q>,
+    {
+        let probe = |stored: &T| cmp.compare(stored.borrow(), key);
+        let mut node = self;
+        loop {
+            match node {
+                Node::Leaf { keys } => {
+                    return keys.binary_search_by(probe).ok().map(|i| &keys[i]);
+                }
+                Node::Internal {
+                    pivots,
+                    children,
+                    buffer,
+                } => {
+                    if let Ok(i) = buffer.binary_search_by(|(stored, _)| probe(stored)) {
+                        return match buffer[i].1 {
+                            Message::Insert => Some(&buffer[i].0),
+                            Message::Delete => None,
+                        };
+                    }
+                    node = &children[route(pivots, probe)];
+                }
+            }
+        }
+    }
+
+    // keys that made it into the leaves of this subtree
+    fn leaf_keys(&self) -> usize {
+        match self {
+            Node::Leaf { keys } => keys.len(),
+            Node::Internal { children, .. } => children.iter().map(Node::leaf_keys).sum(),
+        }
+    }
+
+    /*
+     ** check the size rules, the order of keys, pivots and messages and that
+     ** everything lies within the bounds the parent's pivots set
+     ** (lower included, upper excluded). all leaves have to be at the same depth
+     */
+    fn validate<C: Compare<T>>(
+        &self,
+        config: &Config<'_, C>,
+        depth: usize,
+        bounds: (Option<&T>, Option<&T>),
+        leaf_depth: &mut Option<usize>,
+    ) -> Result<(), String> {
+        let (lower, upper) = bounds;
+        let sorted = |keys: &mut dyn Iterator<Item = &T>, what: &str| -> Result<(), String> {
+            let mut previous: Option<&T> = None;
+            for key in keys {
+                let below =
+                    lower.is_some_and(|lower| config.cmp.compare(key, lower) == Ordering::Less);
+                let above =
+                    upper.is_some_and(|upper| config.cmp.compare(key, upper) != Ordering::Less);
+                if below || above {
+                    return Err(format!(
+                        "{} at depth {} outside the parent's pivots",
+                        what, depth
+                    ));
+                }
+                if previous
+                    .is_some_and(|previous| config.cmp.compare(previous, key) != Ordering::Less)
+                {
+                    return Err(format!("{} at depth {} out of order", what, depth));
+                }
+                previous = Some(key);
+            }
+            Ok(())
+        };
+        if depth > 0 && self.too_small(config.degree) {
+            return Err(format!("node at depth {} is too small", depth));
+        }
+        if self.too_big(config.degree) {
+            return Err(format!("node at depth {} is too big", depth));
+        }
+        match self {
+            Node::Leaf { keys } => {
+                sorted(&mut keys.iter(), "keys")?;
+                match *leaf_depth.get_or_insert(depth) == depth {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "leaf at depth {} while others are deeper or shallower",
+                        depth
+                    )),
+                }
+            }
+            Node::Internal {
+                pivots,
+                children,
+                buffer,
+            } => {
+                if children.len() != pivots.len() + 1 || children.len() < 2 {
+                    return Err(format!(
+                        "node at depth {} has {} pivots and {} children",
+                        depth,
+                        pivots.len(),
+                        children.len()
+                    ));
+                }
+                if buffer.len() > config.capacity {
+                    return Err(format!(
+                        "buffer at depth {} holds {} messages",
+                        depth,
+                        buffer.len()
+                    ));
+                }
+                sorted(&mut pivots.iter(), "pivots")?;
+                sorted(&mut buffer.iter().map(|(key, _)| key), "messages")?;
+                children.iter().enumerate().try_for_each(|(i, child)| {
+                    let lower = if i == 0 { lower } else { Some(&pivots[i - 1]) };
+                    let upper = pivots.get(i).or(upper);
+                    child.validate(config, depth + 1, (lower, upper), leaf_depth)
+                })
+            }
+        }
+    }
+}
+
+impl<T: Clone> Node<T> {
+    // hand messages to this node: a leaf applies them, an internal node adds
+    // them to its buffer (where they're newer than what's already there)
+    fn deliver<C: Compare<T>>(&mut self, messages: Vec<(T, Message)>, config: &Config<'_, C>) {
+        match self {
+            Node::Leaf { keys } => {
+                for (key, message) in messages {
+                    match (
+                        keys.binary_search_by(|stored| config.cmp.compare(stored, &key)),
+                        message,
+                    ) {
+                        (Ok(i), Message::Insert) => keys[i] = key,
+                        (Err(i), Message::Insert) => {
+                            keys.insert(i, key);
+                            config.len.set(config.len.get() + 1);
+                        }
+                        (Ok(i), Message::Delete) => {
+                            keys.remove(i);
+                            config.len.set(config.len.get() - 1);
+                        }
+                        (Err(_), Message::Delete) => {}
+                    }
+                }
+            }
+            Node::Internal { buffer, .. } => {
+                let older = mem::take(buffer);
+                *buffer = merge_messages(older, messages, |a, b| config.cmp.compare(a, b));
+            }
+        }
+    }
+
+    // move messages down until at most keep are left here, always those of
+    // the child that has the most of them waiting
+    fn flush<C: Compare<T>>(&mut self, config: &Config<'_, C>, keep: usize) {
+        while self.buffer_len() > keep {
+            let (pivots, children, buffer) = self.internal_parts();
+            let bounds: Vec<usize> = (0..=children.len())
+                .map(|i| match i {
+                    0 => 0,
+                    i if i == children.len() => buffer.len(),
+                    i => buffer.partition_point(|(key, _)| {
+                        config.cmp.compare(key, &pivots[i - 1]) == Ordering::Less
+                    }),
+                })
+                .collect();
+            let busiest = (0..children.len())
+                .max_by_key(|&i| bounds[i + 1] - bounds[i])
+                .expect("an internal node has children");
+            let messages = buffer.drain(bounds[busiest]..bounds[busiest + 1]).collect();
+            children[busiest].deliver(messages, config);
+            self.fix_child(busiest, config);
+        }
+    }
+
+    // bring the child at i back within the size rules after it changed
+    // returns the index it ends up at (merging with the left neighbour moves it)
+    fn fix_child<C: Compare<T>>(&mut self, mut i: usize, config: &Config<'_, C>) -> usize {
+        loop {
+            let children = self.internal_parts().1;
+            if children[i].buffer_len() > config.capacity {
+                children[i].flush(config, config.capacity);
+            } else if children.len() > 1 && children[i].too_small(config.degree) {
+                i = i.saturating_sub(1);
+                self.merge_children(i);
+            } else {
+                if children[i].too_big(config.degree) {
+                    self.split_child(i, config);
+                }
+                return i;
+            }
+        }
+    }
+
+    // the children at i and i + 1 become one at i
+    // leaves drop the pivot between them, internal nodes pull it down
+    fn merge_children(&mut self, i: usize) {
+        let (pivots, children, _) = self.internal_parts();
+        let pivot = pivots.remove(i);
+        let right = children.remove(i + 1);
+        match (&mut children[i], right) {
+            (Node::Leaf { keys }, Node::Leaf { keys: right_keys }) => keys.extend(right_keys),
+            (
+                Node::Internal {
+                    pivots,
+                    children,
+                    buffer,
+                },
+                Node::Internal {
+                    pivots: right_pivots,
+                    children: right_children,
+                    buffer: right_buffer,
+                },
+            ) => {
+                pivots.push(pivot);
+                pivots.extend(right_pivots);
+                children.extend(right_children);
+                // everything in the right buffer is past everything in the left one
+                buffer.extend(right_buffer);
+            }
+            _ => unreachable!("siblings are always at the same level"),
+        }
+    }
+
+    // split the too big child at i into as few pieces as fit the size rules
+    fn split_child<C: Compare<T>>(&mut self, i: usize, config: &Config<'_, C>) {
+        let (pivots, children, _) = self.internal_parts();
+        let child = children.remove(i);
+        let (pieces, ups) = child.split(config);
+        pivots.splice(i..i, ups);
+        children.splice(i..i, pieces);
+    }
+
+    // the pieces this node splits into and the pivots that go between them
+    fn split<C: Compare<T>>(self, config: &Config<'_, C>) -> (Vec<Node<T>>, Vec<T>) {
+        // n things in pieces of at most max, as even as they come
+        let sizes = |n: usize, max: usize| {
+            let count = n.div_ceil(max);
+            (0..count).map(move |piece| n / count + usize::from(piece < n % count))
+        };
+        let mut pieces = Vec::new();
+        let mut ups = Vec::new();
+        match self {
+            // a copy of the first key of every piece but the first routes to it
+            Node::Leaf { keys } => {
+                let mut keys = keys.into_iter();
+                for size in sizes(keys.len(), 2 * config.degree - 1) {
+                    let piece: Vec<T> = keys.by_ref().take(size).collect();
+                    if !pieces.is_empty() {
+                        ups.push(piece[0].clone());
+                    }
+                    pieces.push(Node::Leaf { keys: piece });
+                }
+            }
+            // the pivot between two pieces moves up, the messages follow it
+            Node::Internal {
+                pivots,
+                children,
+                mut buffer,
+            } => {
+                let mut pivots = pivots.into_iter();
+                let mut children = children.into_iter();
+                for size in sizes(children.len(), 2 * config.degree) {
+                    let own_children: Vec<Node<T>> = children.by_ref().take(size).collect();
+                    let own_pivots: Vec<T> = pivots.by_ref().take(size - 1).collect();
+                    let own_buffer = match pivots.next() {
+                        Some(up) => {
+                            let at = buffer.partition_point(|(key, _)| {
+                                config.cmp.compare(key, &up) == Ordering::Less
+                            });
+                            let rest = buffer.split_off(at);
+                            ups.push(up);
+                            mem::replace(&mut buffer, rest)
+                        }
+                        None => mem::take(&mut buffer),
+                    };
+                    pieces.push(Node::Internal {
+                        pivots: own_pivots,
+                        children: own_children,
+                        buffer: own_buffer,
+                    });
+                }
+            }
+        }
+        (pieces, ups)
+    }
+
+    // push every message of this subtree as far down as this pass gets it
+    fn flush_all<C: Compare<T>>(&mut self, config: &Config<'_, C>) {
+        if let Node::Leaf { .. } = self {
+            return;
+        }
+        self.flush(config, 0);
+        let mut i = 0;
+        while i < self.internal_parts().1.len() {
+            self.internal_parts().1[i].flush_all(config);
+            i = self.fix_child(i, config) + 1;
+        }
+    }
+
+    // the root follows the size rules of any node from above, and on top of
+    // that an internal root needs two children. the tree grows by splitting
+    // the root under a new one and shrinks by handing a lone child the root's
+    // messages and making it the root
+    fn fix_root<C: Compare<T>>(&mut self, config: &Config<'_, C>) {
+        loop {
+            if self.buffer_len() > config.capacity {
+                self.flush(config, config.capacity);
+            } else if self.too_big(config.degree) {
+                let old_root = mem::replace(self, Node::Leaf { keys: Vec::new() });
+                *self = Node::Internal {
+                    pivots: Vec::new(),
+                    children: vec![old_root],
+                    buffer: Vec::new(),
+                };
+                self.split_child(0, config);
+            } else if let Node::Internal {
+                children, buffer, ..
+            } = self
+                && children.len() == 1
+            {
+                let mut child = children.pop().expect("a single child");
+                child.deliver(mem::take(buffer), config);
+                *self = child;
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+pub struct BufferedBtree<T, C = Natural> {
+    // an empty leaf when the tree is empty
+    root: Node<T>,
+    // keys in the leaves, pending messages not counted
+    len: usize,
+    degree: usize,
+    buffer_capacity: usize,
+    cmp: C,
+}
+
+impl<T: Ord> BufferedBtree<T> {
+    // buffers of 4t messages
+    pub fn new(degree: usize) -> Self {
+        BufferedBtree::with_comparator(degree, DEFAULT_BUFFER_PER_DEGREE * degree, Natural)
+    }
+
+    pub fn with_buffer_capacity(degree: usize, buffer_capacity: usize) -> Self {
+        BufferedBtree::with_comparator(degree, buffer_capacity, Natural)
+    }
+}
+
+impl<T, C: Compare<T>> BufferedBtree<T, C> {
+    // a larger buffer capacity batches more writes per flush and leaves more
+    // for every lookup to go through on the way down
+    pub fn with_comparator(degree: usize, buffer_capacity: usize, cmp: C) -> Self {
+        assert!(degree >= 2, "degree must be atleast 2!");
+        assert!(buffer_capacity >= 1, "buffer capacity must be atleast 1!");
+        BufferedBtree {
+            root: Node::Leaf { keys: Vec::new() },
+            len: 0,
+            degree,
+            buffer_capacity,
+            cmp,
+        }
+    }
+
+    // keys that have reached the leaves. a pending message isn't counted
+    // until a flush settles what it does, so this is exact once pending() is 0
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // exact even with messages pending, it looks for a first key
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn buffer_capacity(&self) -> usize {
+        self.buffer_capacity
+    }
+
+    // number of levels, counting the leaves
+    pub fn height(&self) -> usize {
+        let mut height = 1;
+        let mut node = &self.root;
+        while let Node::Internal { children, .. } = node {
+            height += 1;
+            node = &children[0];
+        }
+        height
+    }
+
+    // messages waiting in buffers, none right after flush_all
+    pub fn pending(&self) -> usize {
+        self.root.pending()
+    }
+
+    pub fn search<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // the key equal to key, from a pending insert if that's where it is
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Compare<Q>,
+    {
+        self.root.get(key, &self.cmp)
+    }
+
+    // every key in ascending order, pending messages applied
+    // a leaf is only reached once the iteration gets to it, with the messages
+    // from the buffers on the way down merged into it then
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        Iter {
+            front: Cursor::new(&self.root, false),
+            back: Cursor::new(&self.root, true),
+            front_last: None,
+            back_last: None,
+            cmp: &self.cmp,
+        }
+    }
+
+    // check that the tree satisfies every invariant
+    // returns a description of the first violation found
+    pub fn validate(&self) -> Result<(), String> {
+        let config = Config {
+            degree: self.degree,
+            capacity: self.buffer_capacity,
+            cmp: &self.cmp,
+            len: Cell::new(self.len),
+        };
+        self.root.validate(&config, 0, (None, None), &mut None)?;
+        let count = self.root.leaf_keys();
+        match count == self.len {
+            true => Ok(()),
+            false => Err(format!(
+                "leaves hold {} keys but len is {}",
+                count, self.len
+            )),
+        }
+    }
+}
+
+// splitting a leaf copies its first key up as a pivot, so only the writes
+// need T: Clone
+impl<T: Clone, C: Compare<T>> BufferedBtree<T, C> {
+    // an upsert: an equal key already there is replaced once it reaches the leaf
+    pub fn insert(&mut self, key: T) {
+        self.write(key, Message::Insert);
+    }
+
+    // a tombstone for key, a no-op if the leaf turns out not to have it
+    pub fn delete(&mut self, key: T) {
+        self.write(key, Message::Delete);
+    }
+
+    // push every pending message down into the leaves
+    pub fn flush_all(&mut self) {
+        let config = Config {
+            degree: self.degree,
+            capacity: self.buffer_capacity,
+            cmp: &self.cmp,
+            len: Cell::new(self.len),
+        };
+        while self.root.pending() > 0 {
+            self.root.flush_all(&config);
+            self.root.fix_root(&config);
+        }
+        self.len = config.len.get();
+    }
+
+    // a write goes into the root's buffer, or straight into the root if it's
+    // a leaf. either is O(1) amortized, a full buffer is flushed every
+    // capacity writes
+    fn write(&mut self, key: T, message: Message) {
+        let config = Config {
+            degree: self.degree,
+            capacity: self.buffer_capacity,
+            cmp: &self.cmp,
+            len: Cell::new(self.len),
+        };
+        self.root.deliver(vec![(key, message)], &config);
+        self.root.fix_root(&config);
+        self.len = config.len.get();
+    }
+}
+
+// one end of an iteration: the internal nodes on the way down to the leaf
+// it's in, each with the children it hasn't been into yet and the messages
+// still waiting for them, and what is left of the leaf and its messages
+struct Cursor<'a, T> {
+    // the root, until the first step goes down from it
+    root: Option<&'a Node<T>>,
+    stack: Vec<Frame<'a, T>>,
+    keys: &'a [T],
+    messages: VecDeque<(&'a T, Message)>,
+    rev: bool,
+}
+
+struct Frame<'a, T> {
+    pivots: &'a [T],
+    children: &'a [Node<T>],
+    left: Range<usize>,
+    // sorted, the node's own buffer with what came from above merged in
+    messages: VecDeque<(&'a T, Message)>,
+}
+
+impl<'a, T> Cursor<'a, T> {
+    fn new(root: &'a Node<T>, rev: bool) -> Self {
+        Cursor {
+            root: Some(root),
+            stack: Vec::new(),
+            keys: &[],
+            messages: VecDeque::new(),
+            rev,
+        }
+    }
+
+    // the next key from this end, a leaf key or a pending insert, whichever
+    // comes first. a message for a leaf key decides on its own
+    fn next<C: Compare<T>>(&mut self, cmp: &C) -> Option<&'a T> {
+        loop {
+            let (key, message) = match self.rev {
+                false => (self.keys.first(), self.messages.front()),
+                true => (self.keys.last(), self.messages.back()),
+            };
+            let order = match (key, message) {
+                (None, None) => match self.next_leaf(cmp) {
+                    true => continue,
+                    false => return None,
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(key), Some((message_key, _))) => match self.rev {
+                    false => cmp.compare(key, message_key),
+                    true => cmp.compare(message_key, key),
+                },
+            };
+            if order != Ordering::Greater {
+                self.keys = match self.rev {
+                    false => &self.keys[1..],
+                    true => &self.keys[..self.keys.len() - 1],
+                };
+            }
+            if order == Ordering::Less {
+                return key;
+            }
+            let (message_key, message) = match self.rev {
+                false => self.messages.pop_front(),
+                true => self.messages.pop_back(),
+            }
+            .expect("a message is waiting");
+            if message == Message::Insert {
+                return Some(message_key);
+            }
+        }
+    }
+
+    // move on to the next leaf, false if there is none
+    fn next_leaf<C: Compare<T>>(&mut self, cmp: &C) -> bool {
+        if let Some(root) = self.root.take() {
+            self.enter(root, VecDeque::new(), cmp);
+            return true;
+        }
+        while self.stack.last().is_some_and(|frame| frame.left.is_empty()) {
+            self.stack.pop();
+        }
+        if self.stack.is_empty() {
+            return false;
+        }
+        let (child, messages) = self.next_child(cmp);
+        self.enter(child, messages, cmp);
+        true
+    }
+
+    // go down from node to the leaf at this end of it
+    fn enter<C: Compare<T>>(
+        &mut self,
+        mut node: &'a Node<T>,
+        mut pending: VecDeque<(&'a T, Message)>,
+        cmp: &C,
+    ) {
+        loop {
+            match node {
+                Node::Leaf { keys } => {
+                    self.keys = keys;
+                    self.messages = pending;
+                    return;
+                }
+                Node::Internal {
+                    pivots,
+                    children,
+                    buffer,
+                } => {
+                    let own = buffer
+                        .iter()
+                        .map(|(key, message)| (key, *message))
+                        .collect();
+                    // what came from above is newer than anything here
+                    let messages =
+                        merge_messages(own, pending.into(), |a: &&T, b: &&T| cmp.compare(a, b));
+                    self.stack.push(Frame {
+                        pivots,
+                        children,
+                        left: 0..children.len(),
+                        messages: messages.into(),
+                    });
+                    (node, pending) = self.next_child(cmp);
+                }
+            }
+        }
+    }
+
+    // the next child of the deepest node on the stack, with its messages
+    fn next_child<C: Compare<T>>(&mut self, cmp: &C) -> (&'a Node<T>, VecDeque<(&'a T, Message)>) {
+        let frame = self.stack.last_mut().expect("a node to go down from");
+        let below = |pivot: &T| {
+            frame
+                .messages
+                .partition_point(|(key, _)| cmp.compare(key, pivot) == Ordering::Less)
+        };
+        let (i, messages) = match self.rev {
+            false => {
+                let i = frame.left.next().expect("a child left to go into");
+                let end = frame.pivots.get(i).map_or(frame.messages.len(), below);
+                (i, frame.messages.drain(..end).collect())
+            }
+            true => {
+                let i = frame.left.next_back().expect("a child left to go into");
+                let start = match i {
+                    0 => 0,
+                    i => below(&frame.pivots[i - 1]),
+                };
+                (i, frame.messages.drain(start..).collect())
+            }
+        };
+        (&frame.children[i], messages)
+    }
+}
+
+// the keys of a BufferedBtree in order, from either end
+// the two ends stop where they meet, told by the last key each handed out
+pub struct Iter<'a, T, C> {
+    front: Cursor<'a, T>,
+    back: Cursor<'a, T>,
+    front_last: Option<&'a T>,
+    back_last: Option<&'a T>,
+    cmp: &'a C,
+}
+
+impl<'a, T, C: Compare<T>> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let key = self.front.next(self.cmp)?;
+        if self
+            .back_last
+            .is_some_and(|last| self.cmp.compare(key, last) != Ordering::Less)
+        {
+            return None;
+        }
+        self.front_last = Some(key);
+        Some(key)
+    }
+}
+
+impl<T, C: Compare<T>> DoubleEndedIterator for Iter<'_, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let key = self.back.next(self.cmp)?;
+        if self
+            .front_last
+            .is_some_and(|last| self.cmp.compare(key, last) != Ordering::Greater)
+        {
+            return None;
+        }
+        self.back_last = Some(key);
+        Some(key)
+    }
+}
@@ -0,0 +1,93 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+/*
+** every comparison the tree makes goes through a comparator
+** the comparator is captured when the tree is built and has to stay consistent
+** for the whole lifetime of the tree (a total order that never changes), otherwise
+** keys can end up in the wrong subtree and go missing from searches
+**
+** a comparator may also know how to compare other types than the stored key,
+** that's what lets a Btree<String> be searched with a plain &str
+*/
+pub trait Compare<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+// the natural ordering of the keys (their Ord implementation)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Compare<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+// any closure comparing two keys works as a comparator
+// e.g. |a: &String, b: &String| a.to_lowercase().cmp(&b.to_lowercase())
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Compare<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+// the natural ordering turned around, largest key first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Desc;
+
+impl<T: Ord + ?Sized> Compare<T> for Desc {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+// floats ordered by total_cmp, which (unlike PartialOrd) is a total order:
+// -NaN < -inf < .. < -0.0 < 0.0 < .. < inf < NaN. so -0.0 and 0.0 are two
+// different keys, and NaN is a key like any other, after (or with its sign
+// bit set before) every number. NaNs with different payloads are different keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TotalOrder;
+
+impl Compare<f64> for TotalOrder {
+    fn compare(&self, a: &f64, b: &f64) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+impl Compare<f32> for TotalOrder {
+    fn compare(&self, a: &f32, b: &f32) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+// order keys by a value derived from them (e.g. structs by their timestamp)
+// the projection is called on both sides of every comparison, so it should be cheap
+pub struct ByKey<F, K> {
+    key: F,
+    _key_type: PhantomData<fn() -> K>,
+}
+
+impl<F, K> ByKey<F, K> {
+    pub fn new(key: F) -> Self {
+        ByKey {
+            key,
+            _key_type: PhantomData,
+        }
+    }
+
+    // compare a stored key with an already projected key
+    pub fn compare_key<T: ?Sized>(&self, stored: &T, key: &K) -> Ordering
+    where
+        F: Fn(&T) -> K,
+        K: Ord,
+    {
+        (self.key)(stored).cmp(key)
+    }
+}
+
+impl<T: ?Sized, K: Ord, F: Fn(&T) -> K> Compare<T> for ByKey<F, K> {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.key)(a).cmp(&(self.key)(b))
+    }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// a degree below 2 was asked for, a node needs room for at least 3 keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDegree(pub usize);
+
+impl fmt::Display for InvalidDegree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "degree must be atleast 2, got {}", self.0)
+    }
+}
+
+impl Error for InvalidDegree {}
+
+// a quantile outside [0, 1] (or NaN) was asked for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidQuantile(pub f64);
+
+impl fmt::Display for InvalidQuantile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "quantile must be between 0 and 1, got {}", self.0)
+    }
+}
+
+impl Error for InvalidQuantile {}
+
+// import_lines couldn't read its input, or a line didn't parse (line numbers
+// start at 1)
+#[derive(Debug)]
+pub enum ImportError<E> {
+    Io(io::Error),
+    Parse { line: usize, error: E },
+}
+
+impl<E: fmt::Display> fmt::Display for ImportError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "can't read input: {}", error),
+            ImportError::Parse { line, error } => write!(f, "line {}: {}", line, error),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ImportError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(error) => Some(error),
+            ImportError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+impl<E> From<io::Error> for ImportError<E> {
+    fn from(error: io::Error) -> Self {
+        ImportError::Io(error)
+    }
+}
+
+// a page token was used on a tree whose generation isn't the one it was
+// handed out in: the tree changed since (or it's another tree)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stale {
+    pub expected: u64,
+    pub found: u64,
+}
+
+impl fmt::Display for Stale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "stale token from generation {}, the tree is at {}",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for Stale {}
+
+// keys that had to be sorted weren't: the one at this index comes before the
+// one ahead of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotSorted(pub usize);
+
+impl fmt::Display for NotSorted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "keys are not sorted, key {} comes before key {}",
+            self.0,
+            self.0 - 1
+        )
+    }
+}
+
+impl Error for NotSorted {}
+
+// a thread panicked while it had a SharedBtree locked for writing, whatever it
+// was doing to the tree may be half done (see SharedBtree::clear_poison)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poisoned;
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a thread panicked while holding the tree's lock")
+    }
+}
+
+impl Error for Poisoned {}
+
+// a key the tree's key check (see Btree::validate_keys) turned down, for the
+// reason it gave. index is where the key sat among the keys of the call, 0
+// for a call taking a single key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidKey {
+    pub index: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key {} is invalid: {}", self.index, self.reason)
+    }
+}
+
+impl Error for InvalidKey {}
+
+// why apply_batch turned a script down, before applying any of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    NotSorted(NotSorted),
+    InvalidKey(InvalidKey),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::NotSorted(error) => error.fmt(f),
+            BatchError::InvalidKey(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for BatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BatchError::NotSorted(error) => Some(error),
+            BatchError::InvalidKey(error) => Some(error),
+        }
+    }
+}
+
+impl From<NotSorted> for BatchError {
+    fn from(error: NotSorted) -> Self {
+        BatchError::NotSorted(error)
+    }
+}
+
+impl From<InvalidKey> for BatchError {
+    fn from(error: InvalidKey) -> Self {
+        BatchError::InvalidKey(error)
+    }
+}
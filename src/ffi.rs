@@ -0,0 +1,109 @@
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::btree::Btree;
+
+/*
+** a C api over Btree<i64>, declared in include/btree.h. build it as a shared
+** library with
+**     cargo rustc --lib --release --features ffi --crate-type cdylib
+**
+** a tree is handed out as an opaque BtreeHandle pointer from btree_new and
+** has to go back to btree_free exactly once. every other call takes the
+** handle it was given (or null) and must not run on the same handle from two
+** threads at once
+**
+** nothing unwinds into C: a panic is caught at the boundary and the call
+** returns what it returns for a null handle. a null handle is never an error
+** that crashes, each function says what it returns for one
+*/
+pub struct BtreeHandle(Btree<i64>);
+
+// run f, handing out fallback instead if it panics
+fn guard<R>(fallback: R, f: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+// a new empty tree, null if degree is below 2
+#[unsafe(no_mangle)]
+pub extern "C" fn btree_new(degree: usize) -> *mut BtreeHandle {
+    if degree < 2 {
+        return ptr::null_mut();
+    }
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(BtreeHandle(Btree::new(degree))))
+    })
+}
+
+// true if key was added, false if it was already stored or handle is null
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_insert(handle: *mut BtreeHandle, key: i64) -> bool {
+    match unsafe { handle.as_mut() } {
+        Some(handle) => guard(false, || handle.0.insert(key)),
+        None => false,
+    }
+}
+
+// true if key was removed, false if it wasn't stored or handle is null
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_delete(handle: *mut BtreeHandle, key: i64) -> bool {
+    match unsafe { handle.as_mut() } {
+        Some(handle) => guard(false, || handle.0.delete(&key)),
+        None => false,
+    }
+}
+
+// false for a null handle
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_contains(handle: *const BtreeHandle, key: i64) -> bool {
+    match unsafe { handle.as_ref() } {
+        Some(handle) => guard(false, || handle.0.search(&key)),
+        None => false,
+    }
+}
+
+// 0 for a null handle
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_len(handle: *const BtreeHandle) -> usize {
+    match unsafe { handle.as_ref() } {
+        Some(handle) => handle.0.len(),
+        None => 0,
+    }
+}
+
+// write the smallest cap keys in ascending order to out, which has room for
+// cap of them, and return how many were written (fewer than btree_len tells
+// when out was too short). 0 for a null handle, or a null out with cap > 0
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_iter_collect(
+    handle: *const BtreeHandle,
+    out: *mut i64,
+    cap: usize,
+) -> usize {
+    let Some(handle) = (unsafe { handle.as_ref() }) else {
+        return 0;
+    };
+    if cap == 0 || out.is_null() {
+        return 0;
+    }
+    let out = unsafe { slice::from_raw_parts_mut(out, cap) };
+    guard(0, || {
+        let mut written = 0;
+        for (slot, key) in out.iter_mut().zip(handle.0.iter()) {
+            *slot = *key;
+            written += 1;
+        }
+        written
+    })
+}
+
+// release the tree, a null handle is ignored
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn btree_free(handle: *mut BtreeHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
@@ -1 +1,9 @@
+pub mod bplus;
 pub mod btree;
+pub mod buffered;
+pub mod compare;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod ordered_set;
+pub mod repl;
@@ -1,5 +1,4 @@
-mod btree;
-use crate::btree::Btree;
+use btree::btree::Btree;
 
 // ***Example usage and testing***
 fn main() {
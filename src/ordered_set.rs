@@ -0,0 +1,156 @@
+use std::collections::{BTreeSet, btree_set};
+use std::ops::RangeBounds;
+
+use crate::bplus::{self, BPlusTree};
+use crate::btree::{Btree, Iter, Range};
+use crate::compare::Compare;
+
+/*
+** what code generic over "some ordered set" gets to use, implemented by this
+** crate's trees and by std's BTreeSet, so one can be swapped for another
+** (to benchmark them against each other, say)
+**
+** the iterators borrow the set, so they're generic associated types taking
+** the lifetime of that borrow. both only go forward, the b+ tree's iterators
+** can't go back
+**
+** a range whose start comes after its end is empty for this crate's trees
+** but makes std panic, generic code can't count on either
+*/
+pub trait OrderedSet<T> {
+    type Iter<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+    type Range<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    // true if the key wasn't stored yet
+    fn insert(&mut self, key: T) -> bool;
+    // true if the key was stored
+    fn remove(&mut self, key: &T) -> bool;
+    fn contains(&self, key: &T) -> bool;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // every key in ascending order
+    fn iter(&self) -> Self::Iter<'_>;
+    // the keys inside the range in ascending order
+    fn range<R: RangeBounds<T>>(&self, range: R) -> Self::Range<'_>;
+}
+
+impl<T, C: Compare<T>> OrderedSet<T> for Btree<T, C> {
+    type Iter<'a>
+        = Iter<'a, T, C>
+    where
+        Self: 'a,
+        T: 'a;
+    type Range<'a>
+        = Range<'a, T, C>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn insert(&mut self, key: T) -> bool {
+        Btree::insert(self, key)
+    }
+
+    fn remove(&mut self, key: &T) -> bool {
+        self.delete(key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        self.search(key)
+    }
+
+    fn len(&self) -> usize {
+        Btree::len(self)
+    }
+
+    fn iter(&self) -> Iter<'_, T, C> {
+        Btree::iter(self)
+    }
+
+    fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T, C> {
+        Btree::range(self, range)
+    }
+}
+
+impl<T: Clone, C: Compare<T>> OrderedSet<T> for BPlusTree<T, C> {
+    type Iter<'a>
+        = bplus::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+    type Range<'a>
+        = bplus::Range<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn insert(&mut self, key: T) -> bool {
+        BPlusTree::insert(self, key)
+    }
+
+    fn remove(&mut self, key: &T) -> bool {
+        self.delete(key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        self.search(key)
+    }
+
+    fn len(&self) -> usize {
+        BPlusTree::len(self)
+    }
+
+    fn iter(&self) -> bplus::Iter<'_, T> {
+        BPlusTree::iter(self)
+    }
+
+    fn range<R: RangeBounds<T>>(&self, range: R) -> bplus::Range<'_, T> {
+        BPlusTree::range(self, range)
+    }
+}
+
+impl<T: Ord> OrderedSet<T> for BTreeSet<T> {
+    type Iter<'a>
+        = btree_set::Iter<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+    type Range<'a>
+        = btree_set::Range<'a, T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn insert(&mut self, key: T) -> bool {
+        BTreeSet::insert(self, key)
+    }
+
+    fn remove(&mut self, key: &T) -> bool {
+        BTreeSet::remove(self, key)
+    }
+
+    fn contains(&self, key: &T) -> bool {
+        BTreeSet::contains(self, key)
+    }
+
+    fn len(&self) -> usize {
+        BTreeSet::len(self)
+    }
+
+    fn iter(&self) -> btree_set::Iter<'_, T> {
+        BTreeSet::iter(self)
+    }
+
+    fn range<R: RangeBounds<T>>(&self, range: R) -> btree_set::Range<'_, T> {
+        BTreeSet::range(self, range)
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use crate::btree::{Btree, NodeInfo, Order, PrintOptions, Visit};
+use crate::error::InvalidDegree;
+
+/*
+** an interactive shell over a Btree<i64>, for watching splits and merges
+** happen one command at a time. every line is one command, blank lines and
+** lines starting with # are skipped, and a line that doesn't parse is
+** reported without ending the session
+*/
+
+const HELP: &str = "\
+commands:
+  insert <keys...>   insert one or more keys
+  delete <keys...>   delete one or more keys
+  search <key>       look a key up
+  range <from> <to>  the keys from..=to
+  print              the tree, one node per line
+  pretty             the tree, one level per line
+  stats              size, height and node count
+  validate           check every b-tree property
+  degree <t>         start over with an empty tree of minimum degree t
+  load <file>        insert every whitespace separated key in a file
+  help               this text
+  quit               leave";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Insert(Vec<i64>),
+    Delete(Vec<i64>),
+    Search(i64),
+    Range(i64, i64),
+    Print,
+    Pretty,
+    Stats,
+    Validate,
+    Degree(usize),
+    Load(String),
+    Help,
+    Quit,
+}
+
+fn parse_key(word: &str) -> Result<i64, String> {
+    word.parse().map_err(|_| format!("not a key: {}", word))
+}
+
+// the keys after a command, at least one
+fn parse_keys(name: &str, words: &[&str]) -> Result<Vec<i64>, String> {
+    if words.is_empty() {
+        return Err(format!("{} needs at least one key", name));
+    }
+    words.iter().map(|word| parse_key(word)).collect()
+}
+
+// exactly count arguments after a command
+fn arguments<'a, const N: usize>(name: &str, words: &[&'a str]) -> Result<[&'a str; N], String> {
+    words.try_into().map_err(|_| match N {
+        0 => format!("{} takes no arguments", name),
+        1 => format!("{} takes one argument", name),
+        n => format!("{} takes {} arguments", name, n),
+    })
+}
+
+// one line of input, None for a blank line or a comment
+pub fn parse(line: &str) -> Result<Option<Command>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((name, rest)) = words.split_first() else {
+        return Ok(None);
+    };
+    if name.starts_with('#') {
+        return Ok(None);
+    }
+    let command = match *name {
+        "insert" => Command::Insert(parse_keys(name, rest)?),
+        "delete" => Command::Delete(parse_keys(name, rest)?),
+        "search" => Command::Search(parse_key(arguments::<1>(name, rest)?[0])?),
+        "range" => {
+            let [from, to] = arguments(name, rest)?;
+            Command::Range(parse_key(from)?, parse_key(to)?)
+        }
+        "degree" => {
+            let [degree] = arguments(name, rest)?;
+            let degree = degree
+                .parse()
+                .map_err(|_| format!("not a degree: {}", degree))?;
+            Command::Degree(degree)
+        }
+        "load" => Command::Load(arguments::<1>(name, rest)?[0].to_string()),
+        "print" | "pretty" | "stats" | "validate" | "help" | "quit" | "exit" => {
+            arguments::<0>(name, rest)?;
+            match *name {
+                "print" => Command::Print,
+                "pretty" => Command::Pretty,
+                "stats" => Command::Stats,
+                "validate" => Command::Validate,
+                "help" => Command::Help,
+                _ => Command::Quit,
+            }
+        }
+        _ => return Err(format!("unknown command: {} (try help)", name)),
+    };
+    Ok(Some(command))
+}
+
+// level-order walk writing every level on a line of its own, [1 2] [4 5]
+#[derive(Default)]
+struct Levels {
+    out: String,
+    depth: Option<usize>,
+}
+
+impl Visit<i64> for Levels {
+    fn enter_node(&mut self, info: &NodeInfo) {
+        match self.depth {
+            Some(depth) if depth == info.depth() => self.out.push(' '),
+            Some(_) => self.out.push('\n'),
+            None => {}
+        }
+        self.depth = Some(info.depth());
+        self.out.push('[');
+    }
+
+    fn key(&mut self, key: &i64) {
+        if !self.out.ends_with('[') {
+            self.out.push(' ');
+        }
+        self.out.push_str(&key.to_string());
+    }
+
+    fn leave_node(&mut self, _info: &NodeInfo) {
+        self.out.push(']');
+    }
+}
+
+pub struct Session {
+    tree: Btree<i64>,
+}
+
+impl Session {
+    pub fn new(degree: usize) -> Result<Self, InvalidDegree> {
+        if degree < 2 {
+            return Err(InvalidDegree(degree));
+        }
+        Ok(Session {
+            tree: Btree::new(degree),
+        })
+    }
+
+    // run one command, writing what it has to say to out
+    // returns false once the session should end
+    pub fn execute<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<bool> {
+        match command {
+            Command::Insert(keys) => {
+                for key in keys {
+                    match self.tree.insert(key) {
+                        true => writeln!(out, "inserted {}", key)?,
+                        false => writeln!(out, "{} is already in the tree", key)?,
+                    }
+                }
+            }
+            Command::Delete(keys) => {
+                for key in keys {
+                    match self.tree.delete(&key) {
+                        true => writeln!(out, "deleted {}", key)?,
+                        false => writeln!(out, "{} is not in the tree", key)?,
+                    }
+                }
+            }
+            Command::Search(key) => match self.tree.search(&key) {
+                true => writeln!(out, "found {}", key)?,
+                false => writeln!(out, "{} is not in the tree", key)?,
+            },
+            Command::Range(from, to) => {
+                let keys: Vec<&i64> = self.tree.range(from..=to).collect();
+                writeln!(out, "{:?}", keys)?;
+            }
+            Command::Print => write!(out, "{}", self.tree.render_with(&PrintOptions::default()))?,
+            Command::Pretty => {
+                let mut levels = Levels::default();
+                self.tree.walk(&mut levels, Order::LevelOrder);
+                match levels.depth {
+                    None => writeln!(out, "Empty tree")?,
+                    Some(_) => writeln!(out, "{}", levels.out)?,
+                }
+            }
+            Command::Stats => writeln!(
+                out,
+                "keys: {}, height: {}, nodes: {}, degree: {}, memory: {} bytes",
+                self.tree.len(),
+                self.tree.height(),
+                self.tree.node_count(),
+                self.tree.degree(),
+                self.tree.memory_usage()
+            )?,
+            Command::Validate => match self.tree.validate() {
+                Ok(()) => writeln!(out, "ok")?,
+                Err(problem) => writeln!(out, "invalid: {}", problem)?,
+            },
+            Command::Degree(degree) => match Session::new(degree) {
+                Ok(session) => {
+                    *self = session;
+                    writeln!(out, "new empty tree with degree {}", degree)?;
+                }
+                Err(error) => writeln!(out, "error: {}", error)?,
+            },
+            Command::Load(path) => self.load(&path, out)?,
+            Command::Help => writeln!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // a file that can't be read or holds something other than keys is an
+    // error for the command, nothing is inserted then
+    fn load<W: Write>(&mut self, path: &str, out: &mut W) -> io::Result<()> {
+        let keys: Result<Vec<i64>, String> = fs::read_to_string(path)
+            .map_err(|error| format!("can't read {}: {}", path, error))
+            .and_then(|text| text.split_whitespace().map(parse_key).collect());
+        match keys {
+            Ok(keys) => {
+                let total = keys.len();
+                let new = keys
+                    .into_iter()
+                    .filter(|key| self.tree.insert(*key))
+                    .count();
+                writeln!(out, "loaded {} keys ({} new)", total, new)
+            }
+            Err(error) => writeln!(out, "error: {}", error),
+        }
+    }
+
+    // read commands line by line until the input ends or quit
+    // with a prompt, "> " is written before every line is read
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        mut out: W,
+        prompt: bool,
+    ) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            if prompt {
+                write!(out, "> ")?;
+                out.flush()?;
+            }
+            let Some(line) = lines.next() else {
+                return Ok(());
+            };
+            match parse(&line?) {
+                Ok(None) => {}
+                Ok(Some(command)) => {
+                    if !self.execute(command, &mut out)? {
+                        return Ok(());
+                    }
+                }
+                Err(error) => writeln!(out, "error: {}", error)?,
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use btree::btree::Btree;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// counts allocations and reallocations made by the current thread
+// (the test harness runs tests on several threads, so the counters are per thread)
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static REALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+fn reallocations() -> usize {
+    REALLOCATIONS.with(|count| count.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // keys in a scrambled but deterministic order
+    fn scrambled(n: u64) -> Vec<u64> {
+        (0..n).map(|i| (i * 7919) % n).collect()
+    }
+
+    #[test]
+    fn test_inserts_never_reallocate() {
+        for degree in [2, 3, 16] {
+            let keys = scrambled(20_000);
+            let mut btree = Btree::new(degree);
+
+            let before = reallocations();
+            for &key in &keys {
+                btree.insert(key);
+            }
+            assert_eq!(reallocations() - before, 0, "degree {}", degree);
+            assert_eq!(btree.validate(), Ok(()));
+            assert!(btree.iter().copied().eq(0..20_000));
+        }
+    }
+
+    #[test]
+    fn test_deletes_never_reallocate() {
+        for degree in [2, 3, 16] {
+            let keys = scrambled(20_000);
+            let mut btree = Btree::new(degree);
+            for &key in &keys {
+                btree.insert(key);
+            }
+
+            // merges reuse the left child's buffers, borrows stay within capacity
+            // the only allocation left is the box for a new root when the tree gets shorter
+            let (allocated, reallocated) = (allocations(), reallocations());
+            for key in keys.iter().filter(|key| *key % 3 != 0) {
+                assert!(btree.delete(key));
+            }
+            assert!(allocations() - allocated <= 16, "degree {}", degree);
+            assert_eq!(reallocations() - reallocated, 0, "degree {}", degree);
+            assert_eq!(btree.validate(), Ok(()));
+            assert!(btree.iter().copied().eq((0..20_000).step_by(3)));
+        }
+    }
+
+    #[test]
+    fn test_for_each_does_not_allocate() {
+        let mut btree = Btree::new(3);
+        for key in scrambled(10_000) {
+            btree.insert(key);
+        }
+
+        let before = allocations();
+        let (mut sum, mut count) = (0u64, 0usize);
+        btree.for_each(|key| {
+            sum += key;
+            count += 1;
+        });
+        btree.for_each_rev(|key| sum -= key);
+        assert_eq!(allocations() - before, 0);
+        assert_eq!((sum, count), (0, 10_000));
+    }
+}
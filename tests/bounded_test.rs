@@ -0,0 +1,117 @@
+use btree::btree::{BoundedBtree, EvictPolicy, Insertion};
+use btree::compare::Desc;
+use std::cell::Cell;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the keys 0..n in a scrambled order (n has to be a power of two)
+    fn scrambled(n: u64) -> impl Iterator<Item = u64> {
+        (0..n).map(move |i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15 | 1) % n)
+    }
+
+    #[test]
+    fn test_top_k_stream() {
+        let n = 1 << 20;
+        let mut top = BoundedBtree::with_capacity_limit(16, 1_000, EvictPolicy::DropSmallest);
+        let mut bottom = BoundedBtree::with_capacity_limit(16, 1_000, EvictPolicy::DropLargest);
+        for key in scrambled(n) {
+            top.insert(key);
+            bottom.insert(key);
+        }
+        assert_eq!(top.len(), 1_000);
+        assert!(top.iter().copied().eq(n - 1_000..n));
+        assert!(bottom.iter().copied().eq(0..1_000));
+        assert_eq!(top.validate(), Ok(()));
+        assert_eq!(bottom.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_insertion_outcomes() {
+        let mut tree = BoundedBtree::with_capacity_limit(2, 3, EvictPolicy::DropSmallest);
+        assert_eq!(tree.insert(20), Insertion::Added);
+        assert_eq!(tree.insert(10), Insertion::Added);
+        assert_eq!(tree.insert(30), Insertion::Added);
+        assert_eq!(tree.insert(20), Insertion::Present);
+        assert_eq!(tree.insert(25), Insertion::Evicted(10));
+        // smaller than everything kept, it would go straight back out
+        assert_eq!(tree.insert(5), Insertion::Rejected(5));
+        assert!(tree.iter().copied().eq([20, 25, 30]));
+
+        let mut tree = BoundedBtree::with_capacity_limit(2, 3, EvictPolicy::DropLargest);
+        for key in [20, 10, 30] {
+            tree.insert(key);
+        }
+        assert_eq!(tree.insert(15), Insertion::Evicted(30));
+        assert_eq!(tree.insert(40), Insertion::Rejected(40));
+        assert!(tree.iter().copied().eq([10, 15, 20]));
+
+        let mut tree = BoundedBtree::with_capacity_limit(2, 2, EvictPolicy::Reject);
+        assert_eq!(tree.insert(1), Insertion::Added);
+        assert_eq!(tree.insert(2), Insertion::Added);
+        assert_eq!(tree.insert(3), Insertion::Rejected(3));
+        assert_eq!(tree.insert(0), Insertion::Rejected(0));
+        // room made by hand is taken again
+        assert_eq!(tree.pop_first(), Some(1));
+        assert_eq!(tree.insert(0), Insertion::Added);
+        assert!(tree.delete(&2));
+        assert_eq!(tree.insert(3), Insertion::Added);
+        assert!(tree.into_tree().iter().copied().eq([0, 3]));
+
+        let mut tree = BoundedBtree::with_capacity_limit(2, 0, EvictPolicy::DropSmallest);
+        assert_eq!(tree.insert(1), Insertion::Rejected(1));
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_comparisons_per_insert() {
+        let comparisons = Cell::new(0);
+        let counted = |a: &u64, b: &u64| {
+            comparisons.set(comparisons.get() + 1);
+            a.cmp(b)
+        };
+        let mut tree = BoundedBtree::with_comparator(2, 1_000, EvictPolicy::DropSmallest, counted);
+        for key in 1_000..2_000 {
+            tree.insert(key);
+        }
+        // a key that can't get in is told apart by the smallest key alone
+        comparisons.set(0);
+        assert_eq!(tree.insert(5), Insertion::Rejected(5));
+        assert_eq!(tree.insert(1_000), Insertion::Present);
+        assert_eq!(comparisons.get(), 2);
+        // one that does takes a single descent on top of that
+        comparisons.set(0);
+        assert_eq!(tree.insert(5_000), Insertion::Evicted(1_000));
+        let descent = comparisons.get();
+        comparisons.set(0);
+        assert!(!tree.search(&4_999));
+        assert!(descent <= comparisons.get() + 1, "{} comparisons", descent);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_range() {
+        let mut tree = BoundedBtree::with_capacity_limit(3, 10, EvictPolicy::DropLargest);
+        for key in (0..100).rev() {
+            tree.insert(key);
+        }
+        assert!(tree.range(3..7).copied().eq(3..7));
+        assert!(tree.range(8..).rev().copied().eq([9, 8]));
+        assert_eq!(tree.range(10..).count(), 0);
+    }
+
+    #[test]
+    fn test_policy_follows_comparator() {
+        // smallest by a descending order is the largest number
+        let mut tree = BoundedBtree::with_comparator(3, 5, EvictPolicy::DropSmallest, Desc);
+        for key in 0..100 {
+            tree.insert(key);
+        }
+        assert!(tree.iter().copied().eq([4, 3, 2, 1, 0]));
+        assert_eq!(tree.first(), Some(&4));
+        assert_eq!(tree.insert(50), Insertion::Rejected(50));
+        assert_eq!(tree.capacity(), 5);
+        assert_eq!(tree.policy(), EvictPolicy::DropSmallest);
+    }
+}
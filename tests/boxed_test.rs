@@ -0,0 +1,70 @@
+mod common;
+
+use btree::btree::{BoxedBtree, Btree};
+use common::Rng;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a key well past the size of a pointer, ordered by its first word
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    struct Large([u64; 64]);
+
+    fn large(n: u64) -> Large {
+        let mut words = [n; 64];
+        words[63] = !n;
+        Large(words)
+    }
+
+    #[test]
+    fn test_matches_plain_tree() {
+        let mut rng = Rng(0x9d3a61f0c47be215);
+        for degree in 2..=5 {
+            let mut boxed = BoxedBtree::new(degree);
+            let mut plain = Btree::new(degree);
+            for _ in 0..400 {
+                let key = large(rng.below(300) as u64);
+                match rng.below(4) {
+                    0 => assert_eq!(boxed.take(&key), plain.take(&key)),
+                    1 => assert_eq!(boxed.delete(&key), plain.delete(&key)),
+                    _ => assert_eq!(boxed.insert(key.clone()), plain.insert(key)),
+                }
+            }
+            assert_eq!(boxed.validate(), Ok(()));
+            assert_eq!(boxed.degree(), degree);
+            assert_eq!(boxed.len(), plain.len());
+            assert!(boxed.iter().eq(plain.iter()));
+            assert!(boxed.iter().rev().eq(plain.iter().rev()));
+            assert_eq!(boxed.first(), plain.first());
+            assert_eq!(boxed.last(), plain.last());
+
+            for n in 0..300 {
+                let key = large(n);
+                assert_eq!(boxed.contains(&key), plain.search(&key));
+                assert_eq!(boxed.get(&key), plain.get(&key));
+            }
+            let (low, high) = (large(50), large(150));
+            assert!(boxed.range(&low..&high).eq(plain.range(&low..&high)));
+            assert!(boxed.range(&low..).eq(plain.range(&low..)));
+
+            while let Some(key) = boxed.pop_first() {
+                assert_eq!(Some(key), plain.pop_first());
+                assert_eq!(boxed.pop_last(), plain.pop_last());
+            }
+            assert!(boxed.is_empty() && plain.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_nodes_hold_pointers() {
+        let mut boxed = BoxedBtree::new(3);
+        let mut plain = Btree::new(3);
+        for n in 0..100 {
+            assert!(boxed.insert(large(n)));
+            plain.insert(large(n));
+        }
+        assert!(!boxed.insert(large(7)));
+        assert!(boxed.memory_usage() * 16 < plain.memory_usage());
+    }
+}
@@ -0,0 +1,245 @@
+mod common;
+
+use btree::bplus::BPlusTree;
+use common::Rng;
+use std::ops::Bound;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_tree() {
+        let tree: BPlusTree<i32> = BPlusTree::new(2);
+        assert!(tree.is_empty());
+        assert!(!tree.search(&5));
+        assert_eq!(tree.iter().count(), 0);
+        assert_eq!(tree.range(1..10).count(), 0);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_multiple_insertions() {
+        let mut tree = BPlusTree::new(3);
+        let keys = vec![1, 3, 7, 10, 16, 18, 23, 26, 30];
+
+        for key in keys.clone() {
+            assert!(tree.insert(key));
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), keys.len());
+
+        for key in keys {
+            assert!(tree.search(&key));
+        }
+        assert!(!tree.search(&2));
+        assert!(!tree.search(&25));
+        assert!(!tree.search(&50));
+
+        // equal keys collide
+        assert!(!tree.insert(7));
+        assert_eq!(tree.len(), 9);
+    }
+
+    #[test]
+    fn test_every_key_lives_in_a_leaf() {
+        let mut tree = BPlusTree::new(2);
+        for key in 0..100 {
+            tree.insert(key);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.height() > 2);
+        // the iteration only reads leaves, separators would show up twice
+        assert!(tree.iter().copied().eq(0..100));
+    }
+
+    #[test]
+    fn test_deletion_keeps_invariants() {
+        let mut tree = BPlusTree::new(3);
+        let keys = vec![1, 3, 7, 10, 16, 18, 23, 26, 30, 33, 35, 38, 41, 45];
+
+        for key in keys.clone() {
+            tree.insert(key);
+        }
+        for key in keys {
+            assert!(tree.delete(&key));
+            assert!(!tree.search(&key));
+            assert!(!tree.delete(&key));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_deleted_separator_keys_stay_gone() {
+        let mut tree = BPlusTree::new(2);
+        for key in 0..50 {
+            tree.insert(key);
+        }
+        // with relaxed deletion separators may outlive their keys, lookups
+        // must still only report what the leaves hold
+        for key in (0..50).step_by(2) {
+            assert_eq!(tree.take(&key), Some(key));
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        for key in 0..50 {
+            assert_eq!(tree.search(&key), key % 2 == 1);
+        }
+        assert!(tree.iter().copied().eq((1..50).step_by(2)));
+    }
+
+    #[test]
+    fn test_randomized_against_model() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for degree in 2..=5 {
+            let mut tree = BPlusTree::new(degree);
+            let mut model = std::collections::BTreeSet::new();
+            for _ in 0..4000 {
+                let key = rng.below(500) as u32;
+                if rng.below(3) == 0 {
+                    assert_eq!(tree.delete(&key), model.remove(&key));
+                } else {
+                    assert_eq!(tree.insert(key), model.insert(key));
+                }
+            }
+            assert_eq!(tree.validate(), Ok(()), "degree {}", degree);
+            assert_eq!(tree.len(), model.len());
+            assert!(tree.iter().eq(model.iter()));
+            assert!(tree.iter().rev().eq(model.iter().rev()));
+
+            // drain it completely, the freed nodes get reused along the way
+            let mut keys: Vec<u32> = model.into_iter().collect();
+            rng.shuffle(&mut keys);
+            for key in keys {
+                assert!(tree.delete(&key));
+            }
+            assert_eq!(tree.validate(), Ok(()));
+            assert!(tree.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let mut tree = BPlusTree::new(2);
+        for key in (0..100).step_by(5) {
+            tree.insert(key);
+        }
+
+        let collect = |range: btree::bplus::Range<'_, i32>| range.copied().collect::<Vec<_>>();
+        assert_eq!(collect(tree.range(10..25)), vec![10, 15, 20]);
+        assert_eq!(collect(tree.range(11..=25)), vec![15, 20, 25]);
+        assert_eq!(collect(tree.range(..7)), vec![0, 5]);
+        assert_eq!(collect(tree.range(93..)), vec![95]);
+        assert_eq!(collect(tree.range(96..)), vec![]);
+        assert_eq!(collect(tree.range(11..14)), vec![]);
+        assert_eq!(
+            collect(tree.range((Bound::Included(30), Bound::Excluded(10)))),
+            vec![]
+        );
+        assert_eq!(
+            collect(tree.range((Bound::Excluded(10), Bound::Excluded(25)))),
+            vec![15, 20]
+        );
+        assert_eq!(
+            tree.range(10..=30).rev().copied().collect::<Vec<_>>(),
+            vec![30, 25, 20, 15, 10]
+        );
+    }
+
+    #[test]
+    fn test_range_scans_match_model() {
+        let mut rng = Rng(0x853c49e6748fea9b);
+        for degree in 2..=4 {
+            let mut tree = BPlusTree::new(degree);
+            let mut model = Vec::new();
+            for _ in 0..600 {
+                let key = rng.below(1000) as u32;
+                if tree.insert(key) {
+                    model.push(key);
+                }
+            }
+            // delete some so separators without keys show up in the scans
+            for key in model.iter().filter(|key| *key % 3 == 0) {
+                assert!(tree.delete(key));
+            }
+            model.retain(|key| key % 3 != 0);
+            model.sort();
+
+            for _ in 0..300 {
+                let bound = |rng: &mut Rng| match rng.below(3) {
+                    0 => Bound::Included(rng.below(1100) as u32),
+                    1 => Bound::Excluded(rng.below(1100) as u32),
+                    _ => Bound::Unbounded,
+                };
+                let bounds = (bound(&mut rng), bound(&mut rng));
+                let expected: Vec<u32> = model
+                    .iter()
+                    .copied()
+                    .filter(|key| std::ops::RangeBounds::contains(&bounds, key))
+                    .collect();
+
+                assert_eq!(tree.range(bounds).copied().collect::<Vec<_>>(), expected);
+                let mut backwards: Vec<u32> = tree.range(bounds).rev().copied().collect();
+                backwards.reverse();
+                assert_eq!(backwards, expected);
+
+                // alternate between both ends, they must meet without overlapping
+                let mut range = tree.range(bounds);
+                let (mut front, mut back) = (Vec::new(), Vec::new());
+                loop {
+                    let item = if rng.below(2) == 0 {
+                        range.next().map(|key| front.push(*key))
+                    } else {
+                        range.next_back().map(|key| back.push(*key))
+                    };
+                    if item.is_none() {
+                        break;
+                    }
+                }
+                back.reverse();
+                front.extend(back);
+                assert_eq!(front, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut tree = BPlusTree::new(2);
+        for word in ["pear", "apple", "fig", "kiwi", "plum", "date"] {
+            tree.insert(word.to_string());
+        }
+        assert_eq!(tree.get("fig").map(String::as_str), Some("fig"));
+        assert_eq!(tree.take("kiwi"), Some("kiwi".to_string()));
+        assert!(!tree.search("kiwi"));
+        let scan: Vec<&str> = tree
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("p")))
+            .map(String::as_str)
+            .collect();
+        assert_eq!(scan, vec!["date", "fig"]);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_leaf_chain_survives_splits_and_merges() {
+        let mut rng = Rng(0x6a09e667f3bcc909);
+        for degree in 2..=4 {
+            let mut tree = BPlusTree::new(degree);
+            let mut model = std::collections::BTreeSet::new();
+            for round in 0..3000 {
+                let key = rng.below(300) as u32;
+                // grow for a while, then lean towards deletes so leaves merge
+                let delete = rng.below(10) < if round < 1500 { 3 } else { 7 };
+                if delete {
+                    assert_eq!(tree.delete(&key), model.remove(&key));
+                } else {
+                    assert_eq!(tree.insert(key), model.insert(key));
+                }
+                // validate walks the chain both ways against the in-order leaves
+                assert_eq!(tree.validate(), Ok(()), "degree {} round {}", degree, round);
+            }
+            assert!(tree.iter().eq(model.iter()));
+            assert!(tree.iter().rev().eq(model.iter().rev()));
+        }
+    }
+}
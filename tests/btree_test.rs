@@ -1,4 +1,19 @@
-use btree::btree::Btree;
+mod common;
+
+use btree::btree::{
+    AUTO_NODE_BYTES, BatchSummary, Btree, BtreeBy, BytesBtree, Change, DEFAULT_DEGREE, DiffEntry,
+    Finger, InternedBtree, Interner, JoinItem, NodeInfo, Op, Order, PrintOptions, SearchLevel,
+    TraceStep, Visit, auto_degree, merge_iter,
+};
+use btree::error::{
+    BatchError, ImportError, InvalidDegree, InvalidKey, InvalidQuantile, NotSorted, Stale,
+};
+use common::Rng;
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::ops::{Bound, ControlFlow};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::rc::Rc;
 
 #[cfg(test)]
 mod tests {
@@ -108,4 +123,3931 @@ mod tests {
         // tree should be empty
         assert!(btree.is_empty());
     }
+
+    #[test]
+    fn test_deletion_keeps_invariants() {
+        let mut btree = Btree::new(3);
+        let keys = vec![1, 3, 7, 10, 16, 18, 23, 26, 30, 33, 35, 38, 41, 45];
+
+        for key in keys.clone() {
+            btree.insert(key);
+        }
+        assert!(btree.validate().is_ok());
+
+        for key in keys {
+            assert!(btree.delete(&key));
+            assert!(!btree.search(&key));
+            assert_eq!(btree.validate(), Ok(()));
+        }
+        assert!(btree.is_empty());
+    }
+
+    #[test]
+    fn test_randomized_delete_storms() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+
+        for degree in 2..=5 {
+            for _ in 0..20 {
+                let mut btree = Btree::new(degree);
+                let mut keys: Vec<u32> = (0..300).collect();
+                rng.shuffle(&mut keys);
+                for &key in &keys {
+                    btree.insert(key);
+                }
+                assert_eq!(btree.validate(), Ok(()));
+
+                rng.shuffle(&mut keys);
+                let (deleted, kept) = keys.split_at(rng.below(keys.len() + 1));
+                for key in deleted {
+                    assert!(btree.delete(key));
+                    assert!(!btree.delete(key));
+                    assert_eq!(btree.validate(), Ok(()), "degree {}", degree);
+                }
+
+                for key in deleted {
+                    assert!(!btree.search(key));
+                }
+                for key in kept {
+                    assert!(btree.search(key));
+                }
+                assert_eq!(btree.is_empty(), kept.is_empty());
+            }
+        }
+    }
+
+    thread_local! {
+        static CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    // key wrapper that counts how many times it gets cloned (per test thread)
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct CountedKey(u32);
+
+    impl Clone for CountedKey {
+        fn clone(&self) -> Self {
+            CLONES.with(|clones| clones.set(clones.get() + 1));
+            CountedKey(self.0)
+        }
+    }
+
+    #[test]
+    fn test_splits_do_not_clone_keys() {
+        for degree in 2..=4 {
+            CLONES.with(|clones| clones.set(0));
+            let mut btree = Btree::new(degree);
+            for key in 0..500 {
+                btree.insert(CountedKey(key));
+            }
+            assert_eq!(btree.validate(), Ok(()));
+            assert_eq!(CLONES.with(|clones| clones.get()), 0);
+
+            for key in 0..500 {
+                assert!(btree.search(&CountedKey(key)));
+            }
+        }
+    }
+
+    // deliberately does not implement Clone
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct NonCloneKey(u32);
+
+    #[test]
+    fn test_non_clone_keys() {
+        let mut btree = Btree::new(2);
+        for key in (0..200).rev() {
+            btree.insert(NonCloneKey(key));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(btree.search(&NonCloneKey(150)));
+
+        for key in (0..200).step_by(2) {
+            assert!(btree.delete(&NonCloneKey(key)));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(!btree.search(&NonCloneKey(150)));
+
+        let remaining: Vec<u32> = btree.iter().map(|key| key.0).collect();
+        assert_eq!(remaining, (1..200).step_by(2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_iter_in_order() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        let mut btree = Btree::new(3);
+        assert_eq!(btree.iter().next(), None);
+
+        let mut keys: Vec<u32> = (0..500).collect();
+        rng.shuffle(&mut keys);
+        for &key in &keys {
+            btree.insert(key);
+        }
+
+        let collected: Vec<u32> = (&btree).into_iter().copied().collect();
+        assert_eq!(collected, (0..500).collect::<Vec<_>>());
+    }
+
+    // deliberately implements neither Debug nor Clone
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct OpaqueKey(u32);
+
+    #[test]
+    fn test_non_debug_keys() {
+        let mut btree = Btree::new(3);
+        for key in 0..100 {
+            btree.insert(OpaqueKey(key));
+        }
+        assert!(btree.search(&OpaqueKey(42)));
+        assert!(btree.delete(&OpaqueKey(42)));
+        assert!(!btree.search(&OpaqueKey(42)));
+        assert!(!btree.delete(&OpaqueKey(100)));
+        assert_eq!(btree.validate(), Ok(()));
+        assert_eq!(btree.iter().count(), 99);
+    }
+
+    #[test]
+    fn test_debug_output() {
+        let mut btree = Btree::new(2);
+        btree.insert(1);
+        let output = format!("{:?}", btree);
+        assert!(output.starts_with("Btree {"));
+        assert!(output.contains("keys: [1]"));
+    }
+
+    #[test]
+    fn test_insert_rejects_duplicates() {
+        let mut btree = Btree::new(2);
+        for key in 0..50 {
+            assert!(btree.insert(key));
+        }
+        for key in 0..50 {
+            assert!(!btree.insert(key));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        assert_eq!(btree.iter().count(), 50);
+    }
+
+    #[test]
+    fn test_range() {
+        let mut btree = Btree::new(2);
+        for key in (0..100).step_by(5) {
+            btree.insert(key);
+        }
+
+        let collect = |range: btree::btree::Range<'_, i32>| range.copied().collect::<Vec<_>>();
+        assert_eq!(collect(btree.range(10..25)), vec![10, 15, 20]);
+        assert_eq!(collect(btree.range(11..=25)), vec![15, 20, 25]);
+        assert_eq!(collect(btree.range(..7)), vec![0, 5]);
+        assert_eq!(collect(btree.range(93..)), vec![95]);
+        assert_eq!(collect(btree.range(96..)), vec![]);
+        assert_eq!(collect(btree.range(11..14)), vec![]);
+        assert_eq!(
+            collect(btree.range((Bound::Included(30), Bound::Excluded(10)))),
+            vec![]
+        );
+        assert_eq!(
+            collect(btree.range((Bound::Excluded(10), Bound::Excluded(25)))),
+            vec![15, 20]
+        );
+        assert_eq!(
+            btree.range(10..=30).rev().copied().collect::<Vec<_>>(),
+            vec![30, 25, 20, 15, 10]
+        );
+    }
+
+    #[test]
+    fn test_range_matches_model() {
+        let mut rng = Rng(0x853c49e6748fea9b);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            let mut model = Vec::new();
+            for _ in 0..300 {
+                let key = rng.below(1000) as u32;
+                if btree.insert(key) {
+                    model.push(key);
+                }
+            }
+            model.sort();
+
+            for _ in 0..200 {
+                let bound = |rng: &mut Rng| match rng.below(3) {
+                    0 => Bound::Included(rng.below(1100) as u32),
+                    1 => Bound::Excluded(rng.below(1100) as u32),
+                    _ => Bound::Unbounded,
+                };
+                let bounds = (bound(&mut rng), bound(&mut rng));
+                let expected: Vec<u32> = model
+                    .iter()
+                    .copied()
+                    .filter(|key| std::ops::RangeBounds::contains(&bounds, key))
+                    .collect();
+
+                assert_eq!(btree.range(bounds).copied().collect::<Vec<_>>(), expected);
+                let mut backwards: Vec<u32> = btree.range(bounds).rev().copied().collect();
+                backwards.reverse();
+                assert_eq!(backwards, expected);
+
+                // alternate between both ends, they must meet without overlapping
+                let mut range = btree.range(bounds);
+                let (mut front, mut back) = (Vec::new(), Vec::new());
+                loop {
+                    let item = if rng.below(2) == 0 {
+                        range.next().map(|key| front.push(*key))
+                    } else {
+                        range.next_back().map(|key| back.push(*key))
+                    };
+                    if item.is_none() {
+                        break;
+                    }
+                }
+                front.extend(back.into_iter().rev());
+                assert_eq!(front, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_comparator() {
+        let mut btree: BtreeBy<String, _> = Btree::with_comparator(2, |a: &String, b: &String| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        });
+
+        assert!(btree.insert("Apple".to_string()));
+        // collides with "Apple", the first key stays
+        assert!(!btree.insert("apple".to_string()));
+        assert!(btree.search(&"APPLE".to_string()));
+
+        let mut rng = Rng(0xda942042e4dd58b5);
+        let mut words: Vec<String> = (0..200).map(|i| format!("Word{:03}", i)).collect();
+        rng.shuffle(&mut words);
+        for word in &words {
+            assert!(btree.insert(word.clone()));
+            assert!(!btree.insert(word.to_uppercase()));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+
+        let stored: Vec<&String> = btree.iter().collect();
+        assert_eq!(stored[0], "Apple");
+        assert_eq!(stored.len(), 201);
+
+        // deleting through a differently cased key triggers merges and borrows
+        for word in &words[..150] {
+            assert!(btree.delete(&word.to_lowercase()));
+            assert_eq!(btree.validate(), Ok(()));
+        }
+        for word in &words[150..] {
+            assert!(btree.search(&word.to_uppercase()));
+        }
+
+        let range: Vec<&String> = btree
+            .range("word000".to_string().."WORD050".to_string())
+            .collect();
+        assert!(
+            range
+                .iter()
+                .all(|word| word.as_str() >= "Word000" && word.as_str() < "Word050")
+        );
+    }
+
+    #[derive(Debug)]
+    struct Employee {
+        id: u32,
+        name: String,
+    }
+
+    #[test]
+    fn test_comparator_by_field() {
+        let by_id = |a: &Employee, b: &Employee| a.id.cmp(&b.id);
+        let mut btree = Btree::with_comparator(3, by_id);
+
+        let mut rng = Rng(0x5851f42d4c957f2d);
+        let mut ids: Vec<u32> = (0..300).collect();
+        rng.shuffle(&mut ids);
+        for &id in &ids {
+            let name = format!("employee {}", id);
+            assert!(btree.insert(Employee { id, name }));
+        }
+        // same id, different name: still a collision
+        assert!(!btree.insert(Employee {
+            id: 7,
+            name: "someone else".to_string(),
+        }));
+        assert_eq!(btree.validate(), Ok(()));
+
+        let probe = |id| Employee {
+            id,
+            name: String::new(),
+        };
+        for &id in &ids[..200] {
+            assert!(btree.delete(&probe(id)));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+
+        let mut remaining: Vec<u32> = ids[200..].to_vec();
+        remaining.sort();
+        let stored: Vec<u32> = btree.iter().map(|employee| employee.id).collect();
+        assert_eq!(stored, remaining);
+        assert!(
+            btree
+                .iter()
+                .all(|employee| employee.name == format!("employee {}", employee.id))
+        );
+
+        let in_range = btree.range(probe(100)..probe(200)).count();
+        assert_eq!(
+            in_range,
+            remaining
+                .iter()
+                .filter(|id| (100..200).contains(*id))
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_first_last_and_pops() {
+        let mut btree = Btree::new(2);
+        assert_eq!(btree.first(), None);
+        assert_eq!(btree.pop_last(), None);
+
+        for key in [5, 1, 9, 3, 7, 2, 8, 4, 6] {
+            btree.insert(key);
+        }
+        assert_eq!(btree.first(), Some(&1));
+        assert_eq!(btree.last(), Some(&9));
+
+        assert_eq!(btree.pop_first(), Some(1));
+        assert_eq!(btree.pop_last(), Some(9));
+        assert_eq!(btree.validate(), Ok(()));
+
+        let mut drained = Vec::new();
+        while let Some(key) = btree.pop_first() {
+            drained.push(key);
+            assert_eq!(btree.validate(), Ok(()));
+        }
+        assert_eq!(drained, vec![2, 3, 4, 5, 6, 7, 8]);
+        assert!(btree.is_empty());
+    }
+
+    #[test]
+    fn test_floor_and_ceiling() {
+        let mut btree = Btree::new(2);
+        for key in (10..=100).step_by(10) {
+            btree.insert(key);
+        }
+        assert_eq!(btree.floor(&35), Some(&30));
+        assert_eq!(btree.floor(&30), Some(&30));
+        assert_eq!(btree.floor(&5), None);
+        assert_eq!(btree.ceiling(&35), Some(&40));
+        assert_eq!(btree.ceiling(&40), Some(&40));
+        assert_eq!(btree.ceiling(&105), None);
+    }
+
+    #[test]
+    fn test_reversed_tree() {
+        let mut btree = Btree::new_reversed(2);
+        let mut rng = Rng(0x14057b7ef767814f);
+        let mut keys: Vec<i32> = (0..200).collect();
+        rng.shuffle(&mut keys);
+        for &key in &keys {
+            assert!(btree.insert(key));
+        }
+        assert!(!btree.insert(42));
+        assert_eq!(btree.validate(), Ok(()));
+
+        let all: Vec<i32> = btree.iter().copied().collect();
+        assert_eq!(all, (0..200).rev().collect::<Vec<_>>());
+        assert_eq!(btree.first(), Some(&199));
+        assert_eq!(btree.last(), Some(&0));
+
+        // bounds are given in the tree's order, so they flip
+        let range: Vec<i32> = btree
+            .range((Bound::Included(10), Bound::Excluded(5)))
+            .copied()
+            .collect();
+        assert_eq!(range, vec![10, 9, 8, 7, 6]);
+        assert_eq!(btree.range(5..10).count(), 0);
+        assert_eq!(btree.range(..=197).rev().take(2).count(), 2);
+        assert_eq!(btree.range(..=197).next(), Some(&199));
+
+        // floor and ceiling swap roles
+        btree.delete(&50);
+        assert_eq!(btree.floor(&50), Some(&51));
+        assert_eq!(btree.ceiling(&50), Some(&49));
+
+        assert_eq!(btree.pop_first(), Some(199));
+        assert_eq!(btree.pop_last(), Some(0));
+        for &key in &keys[..100] {
+            btree.delete(&key);
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        let remaining: Vec<i32> = btree.iter().copied().collect();
+        assert!(remaining.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Event {
+        timestamp: u64,
+        message: String,
+    }
+
+    fn event(timestamp: u64) -> Event {
+        Event {
+            timestamp,
+            message: format!("event at {}", timestamp),
+        }
+    }
+
+    #[test]
+    fn test_ordered_by_derived_key() {
+        let mut btree = Btree::new_by_key(3, |event: &Event| event.timestamp);
+        let mut rng = Rng(0x6a09e667f3bcc908);
+        let mut timestamps: Vec<u64> = (0..300).map(|i| i * 10).collect();
+        rng.shuffle(&mut timestamps);
+        for &timestamp in &timestamps {
+            assert!(btree.insert(event(timestamp)));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+
+        // lookups by bare timestamp or by a whole event
+        assert_eq!(
+            btree.get_by(&1230).map(|e| e.message.as_str()),
+            Some("event at 1230")
+        );
+        assert_eq!(btree.get_by(&1235), None);
+        assert!(btree.search(&event(1230)));
+        assert_eq!(btree.get(&event(40)), Some(&event(40)));
+
+        // a different message with the same timestamp collides
+        assert!(!btree.insert(Event {
+            timestamp: 40,
+            message: "duplicate".to_string(),
+        }));
+
+        let window: Vec<u64> = btree.range_by(95..=130).map(|e| e.timestamp).collect();
+        assert_eq!(window, vec![100, 110, 120, 130]);
+
+        // delete by timestamp, merges and borrows keep the values intact
+        for &timestamp in &timestamps[..250] {
+            assert_eq!(btree.remove_by(&timestamp), Some(event(timestamp)));
+            assert_eq!(btree.remove_by(&timestamp), None);
+        }
+        assert_eq!(btree.validate(), Ok(()));
+
+        let mut remaining = timestamps[250..].to_vec();
+        remaining.sort();
+        let stored: Vec<u64> = btree.iter().map(|e| e.timestamp).collect();
+        assert_eq!(stored, remaining);
+    }
+
+    #[test]
+    fn test_get_and_take_borrowed() {
+        let mut btree = Btree::new(2);
+        for word in ["pear", "apple", "fig", "kiwi", "plum", "date"] {
+            btree.insert(word.to_string());
+        }
+        // String keys can be looked up with a plain &str
+        assert_eq!(btree.get("fig").map(String::as_str), Some("fig"));
+        assert!(btree.search("kiwi"));
+        assert_eq!(btree.take("kiwi"), Some("kiwi".to_string()));
+        assert_eq!(btree.take("kiwi"), None);
+        assert!(btree.delete("plum"));
+        assert_eq!(btree.validate(), Ok(()));
+        assert_eq!(btree.iter().count(), 4);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_after_heavy_deletion() {
+        let mut rng = Rng(0x5eed);
+        let mut keys: Vec<u32> = (0..50_000).collect();
+        rng.shuffle(&mut keys);
+
+        let mut btree = Btree::new(16);
+        for &key in &keys {
+            btree.insert(key);
+        }
+
+        // delete 90% of the keys, the remaining nodes keep their full capacity
+        for key in &keys[..45_000] {
+            assert!(btree.delete(key));
+        }
+        let before = btree.memory_usage();
+        let mut remaining = keys[45_000..].to_vec();
+        remaining.sort();
+
+        let reclaimed = btree.shrink_to_fit();
+        let after = btree.memory_usage();
+        assert_eq!(before - after, reclaimed);
+        assert!(after * 10 < before * 7, "{} -> {} bytes", before, after);
+
+        // nothing but capacity changed
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(btree.iter().eq(remaining.iter()));
+        assert_eq!(btree.shrink_to_fit(), 0);
+
+        // the tree keeps working, shrunk nodes just grow again
+        for key in &keys[..1000] {
+            assert!(btree.insert(*key));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_rebuild_after_fragmenting_deletes() {
+        for degree in [2, 3, 8] {
+            let mut btree = Btree::new(degree);
+            for key in 0..20_000 {
+                btree.insert(key);
+            }
+            // keep one key out of every few, skipping around so every node is
+            // left close to its minimum
+            for key in 0..20_000 {
+                if key % 7 != 0 && key % 11 != 3 {
+                    assert!(btree.delete(&key));
+                }
+            }
+            let expected: Vec<i32> = btree.iter().copied().collect();
+            let (height, nodes) = (btree.height(), btree.node_count());
+
+            btree.rebuild();
+            assert_eq!(btree.validate(), Ok(()));
+            assert!(btree.iter().copied().eq(expected.iter().copied()));
+            assert!(btree.height() <= height);
+            assert!(btree.node_count() < nodes, "degree {}", degree);
+
+            // still a regular tree afterwards
+            assert!(btree.insert(1));
+            assert!(btree.delete(&0));
+            assert_eq!(btree.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_rebuild_small_trees() {
+        for degree in [2, 3, 4] {
+            for fill in [0.1, 0.5, 0.75, 0.9, 1.0] {
+                for n in 0..200 {
+                    let mut btree = Btree::new(degree);
+                    for key in (0..n).rev() {
+                        btree.insert(key);
+                    }
+                    btree.rebuild_with_fill(fill);
+                    assert_eq!(
+                        btree.validate(),
+                        Ok(()),
+                        "degree {} fill {} n {}",
+                        degree,
+                        fill,
+                        n
+                    );
+                    assert!(btree.iter().copied().eq(0..n));
+                    assert_eq!(btree.is_empty(), n == 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebuild_leaves_room() {
+        for degree in [2, 3, 8] {
+            let build = || {
+                let mut btree = Btree::new(degree);
+                for key in 0..5_000 {
+                    btree.insert(2 * key);
+                }
+                btree
+            };
+            // odd keys 2t even keys apart, so no two end up in the same leaf
+            let spread = 4 * degree as i32;
+            let between = (1..10_000).step_by(spread as usize);
+
+            // the default leaves room in every node, nothing splits
+            let mut roomy = build();
+            roomy.rebuild();
+            let nodes = roomy.node_count();
+            for key in between.clone() {
+                assert!(roomy.insert(key));
+            }
+            assert_eq!(roomy.node_count(), nodes, "degree {}", degree);
+            assert_eq!(roomy.validate(), Ok(()));
+
+            // packed full, the same inserts split leaves and the nodes above
+            let mut packed = build();
+            packed.rebuild_with_fill(1.0);
+            let nodes = packed.node_count();
+            for key in between {
+                assert!(packed.insert(key));
+            }
+            assert!(packed.node_count() > nodes, "degree {}", degree);
+            assert!(packed.iter().eq(roomy.iter()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "fill must be in (0, 1]!")]
+    fn test_rebuild_with_no_fill() {
+        Btree::<i32>::new(3).rebuild_with_fill(0.0);
+    }
+
+    #[test]
+    fn test_rebuild_moves_keys() {
+        let mut btree = Btree::new(2);
+        for key in 0..100 {
+            btree.insert(NonCloneKey(key));
+        }
+        btree.rebuild_with_fill(1.0);
+        assert_eq!(btree.validate(), Ok(()));
+        assert_eq!(btree.height(), 4);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut keys: Vec<u32> = (0..1000).collect();
+        Rng(0xca7).shuffle(&mut keys);
+        let mut ascending = Btree::new(3);
+        let mut shuffled = Btree::new(3);
+        let mut descending = Btree::new(3);
+        for key in 0..1000 {
+            ascending.insert(key);
+            descending.insert(999 - key);
+        }
+        for &key in &keys {
+            shuffled.insert(key);
+        }
+        // same contents, different shapes
+        assert!(ascending.iter().eq(shuffled.iter()));
+        assert!(ascending.iter().eq(descending.iter()));
+        assert!(!ascending.structurally_eq(&shuffled));
+        assert!(!ascending.structurally_eq(&descending));
+        assert!(ascending.structurally_eq(&ascending));
+
+        for btree in [&mut ascending, &mut shuffled, &mut descending] {
+            btree.canonicalize();
+            assert_eq!(btree.validate(), Ok(()));
+        }
+        assert!(ascending.structurally_eq(&shuffled));
+        assert!(shuffled.structurally_eq(&descending));
+        let set: BTreeSet<u32> = (0..1000).collect();
+        assert!(Btree::from(set).structurally_eq(&ascending.with_degree(DEFAULT_DEGREE).unwrap()));
+
+        // one key apart, or the same keys in fewer nodes
+        shuffled.delete(&500);
+        assert!(!ascending.structurally_eq(&shuffled));
+        assert!(!ascending.structurally_eq(&ascending.with_degree(4).unwrap()));
+        assert!(Btree::<u32>::new(3).structurally_eq(&Btree::new(5)));
+        assert!(!ascending.structurally_eq(&Btree::new(3)));
+    }
+
+    #[test]
+    fn test_auto_degree() {
+        assert_eq!(Btree::<u8>::new_auto().degree(), 128);
+        assert_eq!(Btree::<u64>::new_auto().degree(), 16);
+        assert_eq!(Btree::<[u8; 64]>::new_auto().degree(), 2);
+        assert_eq!(Btree::<[u8; 1000]>::new_auto().degree(), 2);
+        assert_eq!(Btree::<()>::new_auto().degree(), 128);
+        assert_eq!(Btree::<u64>::with_node_bytes(64).degree(), 4);
+        assert_eq!(Btree::<u8>::with_node_bytes(4096).degree(), 256);
+        assert_eq!(auto_degree::<u32>(0), 2);
+        // a full node stays within the budget
+        for bytes in [64, 100, 256, 1000] {
+            let degree = auto_degree::<u64>(bytes);
+            assert!((2 * degree - 1) * 8 <= bytes.max(24), "{} bytes", bytes);
+        }
+
+        let mut keys: Vec<u64> = (0..3000).collect();
+        Rng(0xa07).shuffle(&mut keys);
+        let mut auto = Btree::new_auto();
+        let mut explicit = Btree::new(auto_degree::<u64>(AUTO_NODE_BYTES));
+        for &key in &keys {
+            assert_eq!(auto.insert(key), explicit.insert(key));
+        }
+        for key in keys.iter().step_by(3) {
+            assert_eq!(auto.delete(key), explicit.delete(key));
+        }
+        assert_eq!(auto.validate(), Ok(()));
+        assert!(auto.structurally_eq(&explicit));
+    }
+
+    #[test]
+    fn test_change_degree() {
+        // smallest height that fits n keys with every node full
+        fn packed_height(n: usize, degree: usize) -> usize {
+            let mut height = 0;
+            while (2 * degree).pow(height as u32) - 1 < n {
+                height += 1;
+            }
+            height
+        }
+
+        let n = 5000;
+        let mut rng = Rng(0xdec0de);
+        let mut keys: Vec<usize> = (0..n).collect();
+        rng.shuffle(&mut keys);
+
+        let mut btree = Btree::new(2);
+        for &key in &keys {
+            btree.insert(key);
+        }
+        for (from, to) in [(2, 3), (3, 64), (64, 2), (2, 64), (64, 3), (3, 2)] {
+            assert_eq!(btree.degree(), from);
+            let copy = btree.with_degree(to).unwrap();
+            assert_eq!(copy.degree(), to);
+            assert_eq!(copy.validate(), Ok(()));
+            assert_eq!(copy.height(), packed_height(n, to));
+            assert!(copy.iter().eq(btree.iter()));
+
+            btree = btree.into_degree(to).unwrap();
+            assert_eq!(btree.validate(), Ok(()));
+            assert_eq!(btree.height(), packed_height(n, to));
+            assert!(btree.iter().copied().eq(0..n));
+        }
+
+        assert_eq!(btree.with_degree(1).unwrap_err(), InvalidDegree(1));
+        assert_eq!(btree.into_degree(0).unwrap_err(), InvalidDegree(0));
+
+        // the consuming version never clones
+        let mut btree = Btree::new(3);
+        for key in 0..300 {
+            btree.insert(NonCloneKey(key));
+        }
+        let btree = btree.into_degree(2).unwrap();
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(btree.search(&NonCloneKey(299)));
+    }
+
+    #[test]
+    fn test_cursor_walks_both_ways() {
+        let mut btree = Btree::new(2);
+        for key in (0..200).step_by(2) {
+            btree.insert(key);
+        }
+        let mut cursor = btree.cursor();
+        assert_eq!(cursor.current(), Some(&0));
+
+        // forward across every node boundary, then off the end
+        for expected in (0..200).step_by(2) {
+            assert_eq!(cursor.current(), Some(&expected));
+            cursor.next();
+        }
+        assert_eq!(cursor.current(), None);
+        cursor.next();
+        assert_eq!(cursor.current(), None);
+
+        // coming back from past the end lands on the last key
+        for expected in (0..200).step_by(2).rev() {
+            cursor.prev();
+            assert_eq!(cursor.current(), Some(&expected));
+        }
+        cursor.prev();
+        assert_eq!(cursor.current(), None);
+        cursor.next();
+        assert_eq!(cursor.current(), Some(&0));
+
+        cursor.seek_to_last();
+        assert_eq!(cursor.current(), Some(&198));
+        cursor.seek_to_first();
+        assert_eq!(cursor.current(), Some(&0));
+    }
+
+    #[test]
+    fn test_cursor_seek() {
+        let mut btree = Btree::new(3);
+        for key in (10..1000).step_by(10) {
+            btree.insert(key);
+        }
+        let mut cursor = btree.cursor();
+
+        // exact hits and keys in between stored values
+        cursor.seek(&500);
+        assert_eq!(cursor.current(), Some(&500));
+        cursor.seek(&501);
+        assert_eq!(cursor.current(), Some(&510));
+        cursor.prev();
+        assert_eq!(cursor.current(), Some(&500));
+        cursor.seek(&0);
+        assert_eq!(cursor.current(), Some(&10));
+        cursor.seek(&985);
+        assert_eq!(cursor.current(), Some(&990));
+
+        // past the max, stepping back returns to the last key
+        cursor.seek(&991);
+        assert_eq!(cursor.current(), None);
+        cursor.prev();
+        assert_eq!(cursor.current(), Some(&990));
+
+        // every probe matches a model
+        let keys: Vec<i32> = btree.iter().copied().collect();
+        for probe in 0..1010 {
+            cursor.seek(&probe);
+            let i = keys.partition_point(|key| *key < probe);
+            assert_eq!(cursor.current(), keys.get(i));
+        }
+    }
+
+    #[test]
+    fn test_cursor_on_empty_tree() {
+        let btree: Btree<i32> = Btree::new(2);
+        let mut cursor = btree.cursor();
+        assert_eq!(cursor.current(), None);
+        cursor.next();
+        cursor.prev();
+        cursor.seek(&1);
+        assert_eq!(cursor.current(), None);
+        cursor.seek_to_last();
+        cursor.prev();
+        assert_eq!(cursor.current(), None);
+    }
+
+    #[test]
+    fn test_cursor_removes_every_other_key() {
+        for degree in 2..=5 {
+            let mut btree = Btree::new(degree);
+            for key in 0..5000 {
+                btree.insert(key);
+            }
+
+            // one pass over the tree, removing the key under the cursor moves it on
+            let mut cursor = btree.cursor_mut();
+            let mut removed = Vec::new();
+            while let Some(&key) = cursor.current() {
+                if key % 2 == 0 {
+                    removed.push(cursor.remove_current().unwrap());
+                } else {
+                    cursor.next();
+                }
+            }
+            assert_eq!(cursor.remove_current(), None);
+
+            assert!(removed.into_iter().eq((0..5000).step_by(2)));
+            assert_eq!(btree.validate(), Ok(()), "degree {}", degree);
+            assert!(btree.iter().copied().eq((1..5000).step_by(2)));
+        }
+    }
+
+    #[test]
+    fn test_cursor_removes_at_random() {
+        let mut rng = Rng(0xb5ad4eceda1ce2a9);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            let mut model: Vec<u32> = (0..800).collect();
+            for &key in &model {
+                btree.insert(key);
+            }
+            let mut cursor = btree.cursor_mut();
+            for _ in 0..600 {
+                let i = rng.below(model.len());
+                cursor.seek(&model[i]);
+                assert_eq!(cursor.remove_current(), Some(model.remove(i)));
+                assert_eq!(cursor.current(), model.get(i));
+            }
+            assert_eq!(btree.validate(), Ok(()));
+            assert!(btree.iter().eq(model.iter()));
+        }
+    }
+
+    #[test]
+    fn test_cursor_inserts_around_position() {
+        let mut btree = Btree::new(2);
+        for key in (0..100).step_by(10) {
+            btree.insert(key);
+        }
+        let mut cursor = btree.cursor_mut();
+        cursor.seek(&50);
+        cursor.insert_before(45);
+        cursor.insert_after(55);
+        assert_eq!(cursor.current(), Some(&50));
+        cursor.prev();
+        assert_eq!(cursor.current(), Some(&45));
+        cursor.next();
+        cursor.next();
+        assert_eq!(cursor.current(), Some(&55));
+
+        // past the end the new key goes after the last one
+        cursor.seek_to_last();
+        cursor.next();
+        cursor.insert_before(95);
+        assert_eq!(cursor.current(), None);
+        cursor.prev();
+        assert_eq!(cursor.current(), Some(&95));
+
+        // filling a gap one key at a time keeps the cursor in place
+        cursor.seek(&20);
+        for key in 11..20 {
+            cursor.insert_before(key);
+            assert_eq!(cursor.current(), Some(&20));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        let expected: Vec<i32> = vec![0, 10]
+            .into_iter()
+            .chain(11..=20)
+            .chain([30, 40, 45, 50, 55, 60, 70, 80, 90, 95])
+            .collect();
+        assert!(btree.iter().copied().eq(expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "key does not fit")]
+    fn test_cursor_insert_out_of_order_panics() {
+        let mut btree = Btree::new(2);
+        for key in [10, 20, 30] {
+            btree.insert(key);
+        }
+        let mut cursor = btree.cursor_mut();
+        cursor.seek(&20);
+        cursor.insert_after(35);
+    }
+
+    #[test]
+    fn test_iterator_seek_matches_model() {
+        let mut rng = Rng(0x3c6ef372fe94f82b);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            for _ in 0..400 {
+                btree.insert(rng.below(2000) as u32);
+            }
+            let keys: Vec<u32> = btree.iter().copied().collect();
+
+            for _ in 0..50 {
+                let (start, end) = (rng.below(2100) as u32, rng.below(2100) as u32);
+                let mut range = btree.range(start..end);
+                // what's left of the range is model[lo..hi]
+                let mut lo = keys.partition_point(|key| *key < start);
+                let mut hi = keys.partition_point(|key| *key < end).max(lo);
+
+                for _ in 0..40 {
+                    match rng.below(4) {
+                        0 => {
+                            let got = range.next();
+                            assert_eq!(got, keys[lo..hi].first());
+                            lo += usize::from(got.is_some());
+                        }
+                        1 => {
+                            let got = range.next_back();
+                            assert_eq!(got, keys[lo..hi].last());
+                            hi -= usize::from(got.is_some());
+                        }
+                        _ => {
+                            let target = rng.below(2100) as u32;
+                            range.seek(&target);
+                            let i = keys.partition_point(|key| *key < target);
+                            lo = lo.max(i.min(hi));
+                        }
+                    }
+                }
+                assert!(range.eq(keys[lo..hi].iter()));
+            }
+
+            // iter seeks the same way across the whole tree
+            let mut iter = btree.iter();
+            iter.seek(&1000);
+            let i = keys.partition_point(|key| *key < 1000);
+            assert_eq!(iter.next(), keys.get(i));
+            iter.seek(&0);
+            assert_eq!(iter.next(), keys.get(i + 1));
+        }
+    }
+
+    #[test]
+    fn test_scan_pages_through_the_tree() {
+        let mut btree = Btree::new(3);
+        for key in 0..1000 {
+            btree.insert(key);
+        }
+        for limit in [1, 7, 100, 999, 1000, 5000] {
+            let mut all = Vec::new();
+            let mut start = Bound::Unbounded;
+            loop {
+                let (page, next) = btree.scan(start, limit);
+                assert!(page.len() <= limit);
+                all.extend(page.iter().copied());
+                match next {
+                    Some(key) => {
+                        assert_eq!(Some(key), page.last().copied());
+                        start = Bound::Excluded(key);
+                    }
+                    None => break,
+                }
+            }
+            // no duplicates and no gaps
+            assert!(all.into_iter().copied().eq(0..1000), "limit {}", limit);
+        }
+    }
+
+    #[test]
+    fn test_scan_edge_cases() {
+        let mut btree = Btree::new(2);
+        for key in (0..50).step_by(5) {
+            btree.insert(key);
+        }
+        assert_eq!(btree.scan(Bound::Unbounded, 0), (vec![], None));
+        assert_eq!(btree.scan(Bound::Included(&46), 10), (vec![], None));
+        assert_eq!(btree.scan(Bound::Excluded(&45), 10), (vec![], None));
+
+        // the page ends exactly on the last key: nothing more to fetch
+        assert_eq!(
+            btree.scan(Bound::Included(&35), 3),
+            (vec![&35, &40, &45], None)
+        );
+        assert_eq!(
+            btree.scan(Bound::Included(&30), 3),
+            (vec![&30, &35, &40], Some(&40))
+        );
+        assert_eq!(
+            btree.scan(Bound::Excluded(&12), 2),
+            (vec![&15, &20], Some(&20))
+        );
+
+        let empty: Btree<i32> = Btree::new(2);
+        assert_eq!(empty.scan(Bound::Unbounded, 10), (vec![], None));
+    }
+
+    #[test]
+    fn test_multi_get_matches_single_lookups() {
+        let mut rng = Rng(0xa54ff53a5f1d36f1);
+        for degree in 2..=5 {
+            let mut btree = Btree::new(degree);
+            for _ in 0..1000 {
+                btree.insert(rng.below(5000) as u32 + 100);
+            }
+            for probes in [0, 1, 10, 300, 2000] {
+                // absent keys, duplicates and probes outside the stored range
+                let mut keys: Vec<u32> = (0..probes).map(|_| rng.below(5300) as u32).collect();
+                let found = btree.multi_get(&mut keys);
+                assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+                assert_eq!(found.len(), keys.len());
+                for (key, found) in keys.iter().zip(found) {
+                    assert_eq!(found, btree.get(key));
+                }
+            }
+        }
+
+        let empty: Btree<u32> = Btree::new(2);
+        assert_eq!(empty.multi_get(&mut [3, 1, 2]), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_delete_many_matches_model() {
+        let mut rng = Rng(0x510e527fade682d1);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            let mut model = std::collections::BTreeSet::new();
+            for _ in 0..3000 {
+                let key = rng.below(4000) as u32;
+                btree.insert(key);
+                model.insert(key);
+            }
+            assert_eq!(btree.len(), model.len());
+
+            // small batches go key by key, big ones through a rebuild
+            for size in [0, 1, 5, 40, 200, 1500, 5000] {
+                let mut batch: Vec<u32> = (0..size).map(|_| rng.below(4200) as u32).collect();
+                let expected = batch.iter().filter(|key| model.remove(*key)).count();
+                assert_eq!(btree.delete_many(&mut batch), expected, "size {}", size);
+                assert!(batch.windows(2).all(|pair| pair[0] <= pair[1]));
+                assert_eq!(btree.validate(), Ok(()), "degree {} size {}", degree, size);
+                assert_eq!(btree.len(), model.len());
+                assert!(btree.iter().eq(model.iter()));
+
+                // refill a bit so the next batch has something to hit
+                for _ in 0..500 {
+                    let key = rng.below(4000) as u32;
+                    btree.insert(key);
+                    model.insert(key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_delete_sorted_matches_single_deletes() {
+        let mut rng = Rng(0x9b05688c2b3e6c1f);
+        for degree in [2, 3, 8] {
+            let mut btree = Btree::new(degree);
+            let mut single = Btree::new(degree);
+            for _ in 0..3000 {
+                let key = rng.below(5000) as u32;
+                btree.insert(key);
+                single.insert(key);
+            }
+            for size in [0, 1, 10, 100, 600, 2000, 6000] {
+                let mut batch: Vec<u32> = (0..size).map(|_| rng.below(5200) as u32).collect();
+                batch.sort();
+                let expected = batch.iter().filter(|key| single.delete(*key)).count();
+                assert_eq!(btree.delete_sorted(&batch), Ok(expected), "size {}", size);
+                assert_eq!(btree.validate(), Ok(()), "degree {} size {}", degree, size);
+                assert!(btree.iter().eq(single.iter()));
+
+                for _ in 0..400 {
+                    let key = rng.below(5000) as u32;
+                    btree.insert(key);
+                    single.insert(key);
+                }
+            }
+
+            // everything below a watermark plus a sprinkling above it
+            let mut batch: Vec<u32> = (0..2500).collect();
+            batch.extend((2500..5000).step_by(97));
+            let expected = batch.iter().filter(|key| single.delete(*key)).count();
+            assert_eq!(btree.delete_sorted(&batch), Ok(expected));
+            assert_eq!(btree.validate(), Ok(()));
+            assert!(btree.iter().eq(single.iter()));
+        }
+    }
+
+    #[test]
+    fn test_delete_sorted_rejects_unsorted() {
+        let mut btree = Btree::new(2);
+        for key in 0..100 {
+            btree.insert(key);
+        }
+        assert_eq!(btree.delete_sorted(&[1, 5, 3, 7]), Err(NotSorted(2)));
+        assert_eq!(btree.delete_sorted(&[9, 8]), Err(NotSorted(1)));
+        assert_eq!(btree.len(), 100);
+        assert_eq!(
+            NotSorted(2).to_string(),
+            "keys are not sorted, key 2 comes before key 1"
+        );
+
+        // order is the tree's, repeats are fine
+        let mut reversed = Btree::new_reversed(2);
+        for key in 0..100 {
+            reversed.insert(key);
+        }
+        assert_eq!(reversed.delete_sorted(&[1, 5]), Err(NotSorted(1)));
+        assert_eq!(reversed.delete_sorted(&[90, 50, 50, 5, -1]), Ok(3));
+        assert_eq!(reversed.len(), 97);
+    }
+
+    #[test]
+    fn test_apply_batch_matches_single_ops() {
+        let mut rng = Rng(0x3c6ef372fe94f82b);
+        for degree in [2, 3, 8] {
+            let mut btree = Btree::new(degree);
+            let mut single = Btree::new(degree);
+            for _ in 0..2000 {
+                let key = rng.below(4000) as u32;
+                btree.insert(key);
+                single.insert(key);
+            }
+            // small scripts go op by op, big ones through a merge
+            for size in [0, 1, 20, 100, 1000, 5000] {
+                let mut ops: Vec<Op<u32>> = (0..size)
+                    .map(|_| {
+                        let key = rng.below(4200) as u32;
+                        match rng.below(2) {
+                            0 => Op::Insert(key),
+                            _ => Op::Delete(key),
+                        }
+                    })
+                    .collect();
+                // stable, so ops on one key keep their order
+                ops.sort_by_key(|op| *op.key());
+
+                let mut expected = BatchSummary::default();
+                for op in ops.clone() {
+                    match op {
+                        Op::Insert(key) if single.insert(key) => expected.inserted += 1,
+                        Op::Delete(key) if single.delete(&key) => expected.deleted += 1,
+                        _ => expected.ignored += 1,
+                    }
+                }
+                assert_eq!(btree.apply_batch(ops), Ok(expected), "size {}", size);
+                assert_eq!(btree.validate(), Ok(()), "degree {} size {}", degree, size);
+                assert!(btree.iter().eq(single.iter()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_batch_same_key() {
+        let mut btree = Btree::new(2);
+        for key in 0..10 {
+            btree.insert(key);
+        }
+        // the script is big next to the tree, so it's merged
+        let ops = [
+            Op::Delete(3),
+            Op::Insert(3),
+            Op::Insert(3),
+            Op::Delete(4),
+            Op::Delete(4),
+            Op::Insert(20),
+            Op::Delete(20),
+            Op::Insert(20),
+            Op::Delete(30),
+        ];
+        let summary = btree.apply_batch(ops).unwrap();
+        assert_eq!(
+            summary,
+            BatchSummary {
+                inserted: 3,
+                deleted: 3,
+                ignored: 3
+            }
+        );
+        assert!(btree.iter().copied().eq([0, 1, 2, 3, 5, 6, 7, 8, 9, 20]));
+        assert_eq!(btree.validate(), Ok(()));
+
+        // out of order, nothing happens
+        let ops = [Op::Insert(40), Op::Delete(1), Op::Insert(50)];
+        assert_eq!(
+            btree.apply_batch(ops),
+            Err(BatchError::NotSorted(NotSorted(1)))
+        );
+        assert_eq!(btree.len(), 10);
+    }
+
+    #[test]
+    fn test_contains_all_and_any() {
+        let mut btree = Btree::new(2);
+        for key in (0..1000).step_by(3) {
+            btree.insert(key);
+        }
+
+        // empty probe sets
+        assert!(btree.contains_all(&[]));
+        assert!(!btree.contains_any(&[]));
+
+        // sorted, unsorted and duplicated probes
+        assert!(btree.contains_all(&[0, 3, 3, 300, 999]));
+        assert!(btree.contains_all(&[999, 0, 501, 3, 501]));
+        assert!(!btree.contains_all(&[0, 3, 4]));
+        assert!(!btree.contains_all(&[1000]));
+        assert!(btree.contains_any(&[1, 2, 2, 4, 6]));
+        assert!(btree.contains_any(&[1000, 5, 998, 999]));
+        assert!(!btree.contains_any(&[1, 2, 4, 1000, 5000]));
+
+        // every sorted probe run agrees with single lookups
+        let mut rng = Rng(0x1f83d9abfb41bd6b);
+        for _ in 0..200 {
+            let mut probes: Vec<i32> = (0..rng.below(20)).map(|_| rng.below(1100) as i32).collect();
+            probes.sort();
+            assert_eq!(
+                btree.contains_all(&probes),
+                probes.iter().all(|key| btree.search(key))
+            );
+            assert_eq!(
+                btree.contains_any(&probes),
+                probes.iter().any(|key| btree.search(key))
+            );
+        }
+    }
+
+    #[test]
+    fn test_contains_all_and_any_short_circuit() {
+        let mut btree = Btree::new(3);
+        for key in 0..100 {
+            btree.insert(key);
+        }
+        let probes = [5, 10, 200, 20, 30, 40];
+
+        // contains_all stops at the first miss, contains_any at the first hit
+        let mut pulled = 0;
+        assert!(!btree.contains_all(probes.iter().inspect(|_| pulled += 1)));
+        assert_eq!(pulled, 3);
+
+        let mut pulled = 0;
+        assert!(btree.contains_any([150, 120, 30, 40].iter().inspect(|_| pulled += 1)));
+        assert_eq!(pulled, 3);
+    }
+
+    #[test]
+    fn test_for_each_matches_iter() {
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            let mut rng = Rng(0x9b05688c2b3e6c1f);
+            for _ in 0..700 {
+                btree.insert(rng.below(3000) as u32);
+            }
+            let mut forward = Vec::new();
+            btree.for_each(|key| forward.push(*key));
+            assert!(forward.iter().eq(btree.iter()));
+
+            let mut backward = Vec::new();
+            btree.for_each_rev(|key| backward.push(*key));
+            assert!(backward.iter().eq(btree.iter().rev()));
+        }
+
+        let empty: Btree<u32> = Btree::new(2);
+        empty.for_each(|_| panic!("nothing to visit"));
+        empty.for_each_rev(|_| panic!("nothing to visit"));
+    }
+
+    #[test]
+    fn test_try_for_each_stops_early() {
+        let mut btree = Btree::new(2);
+        for key in 0..1000 {
+            btree.insert(key);
+        }
+
+        // break at every position, deep inside leaves as well as on internal keys
+        for stop in [0, 1, 2, 3, 37, 500, 998, 999] {
+            let mut visited = 0;
+            let found = btree.try_for_each(|key| {
+                visited += 1;
+                if *key == stop {
+                    ControlFlow::Break(key * 10)
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+            assert_eq!(found, Some(stop * 10));
+            assert_eq!(visited, stop + 1);
+        }
+
+        // never breaking visits everything and returns None
+        let mut visited = 0;
+        let found: Option<()> = btree.try_for_each(|_| {
+            visited += 1;
+            ControlFlow::Continue(())
+        });
+        assert_eq!((found, visited), (None, 1000));
+
+        let empty: Btree<i32> = Btree::new(2);
+        assert_eq!(empty.try_for_each(|_| ControlFlow::Break(1)), None);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum WalkEvent {
+        Enter(usize, usize, bool),
+        Key(i32),
+        Leave(usize),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<WalkEvent>);
+
+    impl Visit<i32> for Recorder {
+        fn enter_node(&mut self, info: &NodeInfo) {
+            self.0.push(WalkEvent::Enter(
+                info.depth(),
+                info.key_count(),
+                info.is_leaf(),
+            ));
+        }
+
+        fn key(&mut self, key: &i32) {
+            self.0.push(WalkEvent::Key(*key));
+        }
+
+        fn leave_node(&mut self, info: &NodeInfo) {
+            self.0.push(WalkEvent::Leave(info.depth()));
+        }
+    }
+
+    #[test]
+    fn test_walk_orders() {
+        use WalkEvent::*;
+
+        // root [2, 4] over the leaves [1], [3] and [5, 6, 7]
+        let mut btree = Btree::new(2);
+        for key in 1..=7 {
+            btree.insert(key);
+        }
+        let events = |order| {
+            let mut recorder = Recorder::default();
+            btree.walk(&mut recorder, order);
+            recorder.0
+        };
+
+        assert_eq!(
+            events(Order::InOrder),
+            vec![
+                Enter(0, 2, false),
+                Enter(1, 1, true),
+                Key(1),
+                Leave(1),
+                Key(2),
+                Enter(1, 1, true),
+                Key(3),
+                Leave(1),
+                Key(4),
+                Enter(1, 3, true),
+                Key(5),
+                Key(6),
+                Key(7),
+                Leave(1),
+                Leave(0),
+            ]
+        );
+        assert_eq!(
+            events(Order::PreOrder),
+            vec![
+                Enter(0, 2, false),
+                Key(2),
+                Key(4),
+                Enter(1, 1, true),
+                Key(1),
+                Leave(1),
+                Enter(1, 1, true),
+                Key(3),
+                Leave(1),
+                Enter(1, 3, true),
+                Key(5),
+                Key(6),
+                Key(7),
+                Leave(1),
+                Leave(0),
+            ]
+        );
+        assert_eq!(
+            events(Order::PostOrder),
+            vec![
+                Enter(0, 2, false),
+                Enter(1, 1, true),
+                Key(1),
+                Leave(1),
+                Enter(1, 1, true),
+                Key(3),
+                Leave(1),
+                Enter(1, 3, true),
+                Key(5),
+                Key(6),
+                Key(7),
+                Leave(1),
+                Key(2),
+                Key(4),
+                Leave(0),
+            ]
+        );
+        assert_eq!(
+            events(Order::LevelOrder),
+            vec![
+                Enter(0, 2, false),
+                Key(2),
+                Key(4),
+                Leave(0),
+                Enter(1, 1, true),
+                Key(1),
+                Leave(1),
+                Enter(1, 1, true),
+                Key(3),
+                Leave(1),
+                Enter(1, 3, true),
+                Key(5),
+                Key(6),
+                Key(7),
+                Leave(1),
+            ]
+        );
+
+        // node_count is a walk as well
+        assert_eq!(btree.node_count(), 4);
+        let empty: Btree<i32> = Btree::new(2);
+        let mut recorder = Recorder::default();
+        empty.walk(&mut recorder, Order::LevelOrder);
+        assert!(recorder.0.is_empty());
+    }
+
+    #[test]
+    fn test_depth_of() {
+        // root [2, 4] over the leaves [1], [3] and [5, 6, 7]
+        let mut btree = Btree::new(2);
+        for key in 1..=7 {
+            btree.insert(key);
+        }
+        assert_eq!(btree.depth_of(&2), Some(0));
+        assert_eq!(btree.depth_of(&4), Some(0));
+        for key in [1, 3, 5, 6, 7] {
+            assert_eq!(btree.depth_of(&key), Some(1));
+        }
+        assert_eq!(btree.depth_of(&0), None);
+        assert_eq!(btree.depth_of(&8), None);
+        assert_eq!(Btree::<i32>::new(2).depth_of(&1), None);
+
+        // every stored key sits above the bottom, at least one sits on it
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            let mut keys = Vec::new();
+            for _ in 0..2000 {
+                let key = rng.below(5000);
+                if btree.insert(key) {
+                    keys.push(key);
+                }
+            }
+            let height = btree.height();
+            let depths: Vec<usize> = keys
+                .iter()
+                .map(|key| btree.depth_of(key).unwrap())
+                .collect();
+            assert!(depths.iter().all(|depth| *depth < height));
+            assert!(depths.contains(&(height - 1)));
+            assert!(depths.contains(&0));
+        }
+    }
+
+    #[test]
+    fn test_path_to() {
+        // root [2, 4] over the leaves [1], [3] and [5, 6, 7]
+        let mut btree = Btree::new(2);
+        for key in 1..=7 {
+            btree.insert(key);
+        }
+        let steps = |key: i32| {
+            btree
+                .path_to(&key)
+                .iter()
+                .map(|step| (step.index(), step.key_count(), step.matched()))
+                .collect::<Vec<_>>()
+        };
+
+        // stored in the root, the search stops right there
+        assert_eq!(steps(4), vec![(1, 2, true)]);
+        assert_eq!(steps(3), vec![(1, 2, false), (0, 1, true)]);
+        assert_eq!(steps(6), vec![(2, 2, false), (1, 3, true)]);
+        // missing keys end in the leaf they would go into
+        assert_eq!(steps(0), vec![(0, 2, false), (0, 1, false)]);
+        assert_eq!(steps(9), vec![(2, 2, false), (3, 3, false)]);
+        assert!(Btree::<i32>::new(2).path_to(&1).is_empty());
+
+        // the path is as long as the key is deep
+        for key in 1..=7 {
+            assert_eq!(btree.path_to(&key).len(), btree.depth_of(&key).unwrap() + 1);
+        }
+    }
+
+    #[test]
+    fn test_find_first_true() {
+        let mut rng = Rng(0xbf58476d1ce4e5b9);
+        let mut btree = Btree::new(3);
+        for _ in 0..1500 {
+            btree.insert(rng.below(10_000) as u32);
+        }
+        let keys: Vec<u32> = btree.iter().copied().collect();
+
+        // thresholds on the key and on things computed from it
+        for threshold in [0, 1, 17, 500, 4999, 5000, 9998, 9999, 10_000, 20_000] {
+            let linear = |pred: &dyn Fn(&u32) -> bool| keys.iter().find(|key| pred(key));
+            let above = |key: &u32| *key >= threshold;
+            assert_eq!(btree.find_first_true(above), linear(&above));
+            let square = |key: &u32| (*key as u64).pow(2) > threshold as u64 * 1000;
+            assert_eq!(btree.find_first_true(square), linear(&square));
+            let hour = |key: &u32| key / 417 >= threshold % 24;
+            assert_eq!(btree.find_first_true(hour), linear(&hour));
+        }
+        assert_eq!(btree.find_first_true(|_| true), btree.first());
+        assert_eq!(btree.find_first_true(|_| false), None);
+
+        // it follows the tree's order, not the natural one
+        let mut reversed = Btree::new_reversed(2);
+        for key in 0..100 {
+            reversed.insert(key);
+        }
+        assert_eq!(reversed.find_first_true(|key| *key < 42), Some(&41));
+    }
+
+    #[test]
+    fn test_partition_point() {
+        let mut rng = Rng(0x94d049bb133111eb);
+        for degree in 2..=5 {
+            let mut btree = Btree::new(degree);
+            for _ in 0..3000 {
+                let key = rng.below(2000) as u32;
+                if rng.below(4) == 0 {
+                    btree.delete(&key);
+                } else {
+                    btree.insert(key);
+                }
+            }
+            assert_eq!(btree.validate(), Ok(()));
+            let sorted: Vec<u32> = btree.iter().copied().collect();
+
+            for _ in 0..200 {
+                let threshold = rng.below(2200) as u32;
+                let divisor = rng.below(50) as u32 + 1;
+                let below = |key: &u32| *key < threshold;
+                assert_eq!(btree.partition_point(below), sorted.partition_point(below));
+                let coarse = |key: &u32| key / divisor <= threshold / divisor;
+                assert_eq!(
+                    btree.partition_point(coarse),
+                    sorted.partition_point(coarse)
+                );
+            }
+            assert_eq!(btree.partition_point(|_| true), btree.len());
+            assert_eq!(btree.partition_point(|_| false), 0);
+        }
+        assert_eq!(Btree::<u32>::new(2).partition_point(|_| true), 0);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_sample_is_uniform() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut btree = Btree::new(2);
+        // deletes leave nodes of every size behind, the sampling must not care
+        for key in 0..80 {
+            btree.insert(key);
+        }
+        for key in (0..80).filter(|key| key % 3 == 0) {
+            btree.delete(&key);
+        }
+        let keys: Vec<i32> = btree.iter().copied().collect();
+
+        let mut counts = vec![0; 80];
+        for _ in 0..keys.len() * 1000 {
+            counts[*btree.sample(&mut rng).unwrap() as usize] += 1;
+        }
+        for (key, count) in counts.into_iter().enumerate() {
+            if key % 3 == 0 {
+                assert_eq!(count, 0);
+            } else {
+                // a thousand expected hits, the standard deviation is about 32
+                assert!((850..1150).contains(&count), "{}: {}", key, count);
+            }
+        }
+
+        for n in [0, 1, 10, 53, 100] {
+            let picked = btree.sample_n(&mut rng, n);
+            assert_eq!(picked.len(), n.min(keys.len()));
+            // in order, so strictly increasing means distinct
+            assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        assert!(btree.sample_n(&mut rng, 100).into_iter().eq(keys.iter()));
+        assert_eq!(Btree::<i32>::new(2).sample(&mut rng), None);
+    }
+
+    #[test]
+    fn test_quantiles_are_exact() {
+        let mut rng = Rng(0xd6e8feb86659fd93);
+        for degree in 2..=4 {
+            let mut btree = Btree::new(degree);
+            for _ in 0..2500 {
+                let key = rng.below(3000) as u32;
+                if rng.below(3) == 0 {
+                    btree.delete(&key);
+                } else {
+                    btree.insert(key);
+                }
+            }
+            let sorted: Vec<u32> = btree.iter().copied().collect();
+            let exact = |q: f64| &sorted[(q * (sorted.len() - 1) as f64).floor() as usize];
+
+            let qs = [0.0, 0.5, 0.25, 0.999, 1.0, 0.1, 0.5, 0.0001, 0.75];
+            for q in qs {
+                assert_eq!(btree.quantile(q), Ok(Some(exact(q))));
+            }
+            let batch = btree.quantiles(&qs).unwrap();
+            assert!(batch.into_iter().eq(qs.iter().map(|q| exact(*q))));
+            assert_eq!(btree.quantile(0.0).unwrap(), btree.first());
+            assert_eq!(btree.quantile(1.0).unwrap(), btree.last());
+        }
+
+        let mut btree = Btree::new(2);
+        btree.insert(5);
+        assert_eq!(btree.quantiles(&[0.0, 1.0]), Ok(vec![&5, &5]));
+        assert_eq!(btree.quantile(1.5), Err(InvalidQuantile(1.5)));
+        assert_eq!(btree.quantile(-0.1), Err(InvalidQuantile(-0.1)));
+        assert!(btree.quantile(f64::NAN).is_err());
+        assert_eq!(btree.quantiles(&[0.5, 2.0]), Err(InvalidQuantile(2.0)));
+        let empty: Btree<i32> = Btree::new(2);
+        assert_eq!(empty.quantile(0.5), Ok(None));
+        assert_eq!(empty.quantiles(&[0.5]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_smallest_and_largest() {
+        let mut btree = Btree::new(2);
+        for key in (0..200).rev() {
+            btree.insert(key);
+        }
+        assert!(btree.height() > 3);
+
+        assert_eq!(btree.smallest(0).count(), 0);
+        assert_eq!(btree.largest(0).count(), 0);
+        assert_eq!(btree.smallest(1).collect::<Vec<_>>(), vec![&0]);
+        assert_eq!(btree.largest(1).collect::<Vec<_>>(), vec![&199]);
+        // enough to cross several leaves and the keys between them
+        assert!(btree.smallest(25).copied().eq(0..25));
+        assert!(btree.largest(25).copied().eq((175..200).rev()));
+        assert!(btree.smallest(500).copied().eq(0..200));
+        assert!(btree.largest(500).copied().eq((0..200).rev()));
+
+        let empty: Btree<i32> = Btree::new(2);
+        assert_eq!(empty.smallest(3).count(), 0);
+        assert_eq!(empty.largest(3).count(), 0);
+    }
+
+    #[test]
+    fn test_partition_points() {
+        let mut rng = Rng(0x2127599bf4325c37);
+        for (degree, size) in [(2, 1), (2, 7), (3, 100), (2, 1000), (5, 777)] {
+            let mut btree = Btree::new(degree);
+            while btree.len() < size {
+                btree.insert(rng.below(100_000));
+            }
+            for parts in [1, 2, 3, 7, 10, 64, size, size + 5] {
+                let splitters = btree.partition_points(parts);
+                assert_eq!(splitters.len(), parts.min(size) - 1);
+
+                // bucket sizes are the gaps between the splitters' ranks
+                let mut bounds = vec![0];
+                bounds.extend(
+                    splitters
+                        .iter()
+                        .map(|splitter| btree.partition_point(|key| key < *splitter)),
+                );
+                bounds.push(size);
+                let buckets: Vec<usize> = bounds.windows(2).map(|pair| pair[1] - pair[0]).collect();
+                let (min, max) = (buckets.iter().min(), buckets.iter().max());
+                assert!(max.unwrap() - min.unwrap() <= 1, "{:?}", buckets);
+                assert!(buckets.iter().all(|bucket| *bucket > 0));
+            }
+        }
+        assert!(Btree::<i32>::new(2).partition_points(4).is_empty());
+    }
+
+    #[test]
+    fn test_split_evenly() {
+        for (size, n) in [(0u32, 3), (5, 1), (5, 8), (100, 3), (1001, 10)] {
+            let mut btree = Btree::new(3);
+            for key in 0..size {
+                btree.insert(NonCloneKey(key * 2));
+            }
+            let pieces = btree.split_evenly(n);
+            assert_eq!(pieces.len(), n);
+
+            let sizes: Vec<usize> = pieces.iter().map(|piece| piece.len()).collect();
+            assert_eq!(sizes.iter().sum::<usize>(), size as usize);
+            assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+            for piece in &pieces {
+                assert_eq!(piece.validate(), Ok(()));
+            }
+            for pair in pieces.windows(2) {
+                if let (Some(last), Some(first)) = (pair[0].last(), pair[1].first()) {
+                    assert!(last < first);
+                }
+            }
+            let joined: Vec<u32> = pieces.iter().flatten().map(|key| key.0).collect();
+            assert!(joined.into_iter().eq((0..size).map(|key| key * 2)));
+        }
+    }
+
+    #[test]
+    fn test_merge_iter() {
+        let mut rng = Rng(0x4cf5ad432745937f);
+        let shard = |rng: &mut Rng, size: usize, spread: usize| {
+            let mut btree = Btree::new(2);
+            for _ in 0..size {
+                btree.insert(rng.below(spread) as u32);
+            }
+            btree
+        };
+
+        // overlapping shards, with empty ones mixed in
+        let shards = vec![
+            shard(&mut rng, 300, 500),
+            Btree::new(3),
+            shard(&mut rng, 50, 500),
+            shard(&mut rng, 400, 500),
+            Btree::new(2),
+        ];
+        let mut all: Vec<u32> = shards.iter().flatten().copied().collect();
+        all.sort();
+        assert!(merge_iter(&shards).copied().eq(all.iter().copied()));
+        all.dedup();
+        assert!(merge_iter(&shards).dedup().copied().eq(all));
+
+        // disjoint shards come out one after the other
+        let mut shards = Vec::new();
+        for start in [200, 0, 100] {
+            let mut btree = Btree::new(2);
+            for key in start..start + 100 {
+                btree.insert(key);
+            }
+            shards.push(btree);
+        }
+        assert!(merge_iter(&shards).copied().eq(0..300));
+        assert!(merge_iter(&shards).dedup().copied().eq(0..300));
+
+        let none: Vec<Btree<u32>> = Vec::new();
+        assert_eq!(merge_iter(&none).count(), 0);
+
+        // the trees' own order is followed
+        let mut shards = vec![Btree::new_reversed(2), Btree::new_reversed(2)];
+        for key in 0..20 {
+            shards[key % 2].insert(key);
+        }
+        assert!(merge_iter(&shards).copied().eq((0..20).rev()));
+    }
+
+    #[test]
+    fn test_diff() {
+        let build = |keys: &mut dyn Iterator<Item = u32>| {
+            let mut btree = Btree::new(2);
+            keys.for_each(|key| {
+                btree.insert(key);
+            });
+            btree
+        };
+
+        let a = build(&mut (0..100));
+        assert_eq!(a.diff(&build(&mut (0..100))).count(), 0);
+        assert_eq!(a.diff(&a).count(), 0);
+
+        // disjoint: everything shows up, tagged by where it lives, in key order
+        let odd = build(&mut (0..50).map(|key| key * 2 + 1));
+        let even = build(&mut (0..50).map(|key| key * 2));
+        let entries: Vec<DiffEntry<u32>> = even.diff(&odd).collect();
+        assert_eq!(entries.len(), 100);
+        for (key, entry) in (0..100).zip(&entries) {
+            match entry {
+                DiffEntry::OnlyInSelf(found) => assert!(**found == key && key % 2 == 0),
+                DiffEntry::OnlyInOther(found) => assert!(**found == key && key % 2 == 1),
+            }
+        }
+        let empty = Btree::new(3);
+        assert!(empty.diff(&odd).eq(odd.iter().map(DiffEntry::OnlyInOther)));
+        assert!(odd.diff(&empty).eq(odd.iter().map(DiffEntry::OnlyInSelf)));
+
+        let mut rng = Rng(0x5851f42d4c957f2d);
+        for _ in 0..20 {
+            let ours: std::collections::BTreeSet<u32> =
+                (0..300).map(|_| rng.below(400) as u32).collect();
+            let theirs: std::collections::BTreeSet<u32> =
+                (0..300).map(|_| rng.below(400) as u32).collect();
+            let (a, b) = (
+                build(&mut ours.iter().copied()),
+                build(&mut theirs.iter().copied()),
+            );
+            let mut expected: Vec<DiffEntry<u32>> = ours
+                .difference(&theirs)
+                .map(DiffEntry::OnlyInSelf)
+                .chain(theirs.difference(&ours).map(DiffEntry::OnlyInOther))
+                .collect();
+            expected.sort_by_key(|entry| match entry {
+                DiffEntry::OnlyInSelf(key) | DiffEntry::OnlyInOther(key) => **key,
+            });
+            assert_eq!(a.diff(&b).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_render_with() {
+        let mut btree = Btree::new(2);
+        assert_eq!(btree.render_with(&PrintOptions::default()), "Empty tree\n");
+        for key in 1..=10 {
+            btree.insert(key);
+        }
+        let expected = "\
+B-tree (degree 2):
+Keys: [4] (leaf: false)
+ Keys: [2] (leaf: false)
+  Keys: [1] (leaf: true)
+  Keys: [3] (leaf: true)
+ Keys: [6, 8] (leaf: false)
+  Keys: [5] (leaf: true)
+  Keys: [7] (leaf: true)
+  Keys: [9, 10] (leaf: true)
+";
+        assert_eq!(btree.render_with(&PrintOptions::default()), expected);
+
+        // counts without leaf flags, two spaces a level, only two levels
+        let options = PrintOptions::new()
+            .leaf_flags(false)
+            .key_counts(true)
+            .indent(2)
+            .max_depth(1);
+        let expected = "\
+B-tree (degree 2):
+Keys: [4] (keys: 1)
+  Keys: [2] (keys: 1)
+    ... 2 children
+  Keys: [6, 8] (keys: 2)
+    ... 3 children
+";
+        assert_eq!(btree.render_with(&options), expected);
+
+        // the root alone, with nothing after its keys
+        let options = PrintOptions::new().leaf_flags(false).max_depth(0);
+        let expected = "B-tree (degree 2):\nKeys: [4]\n ... 2 children\n";
+        assert_eq!(btree.render_with(&options), expected);
+    }
+
+    #[test]
+    fn test_render_with_elision() {
+        let mut btree = Btree::new(4);
+        for key in 1..=30 {
+            btree.insert(key);
+        }
+        // root [4, 8, .., 24] over seven leaves, the last one holding 25..=30
+        let rendered = btree.render_with(&PrintOptions::new().max_keys(3));
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[1], "Keys: [4, 8, ..., 24] (leaf: false)");
+        // three keys fit as they are
+        assert_eq!(lines[2], " Keys: [1, 2, 3] (leaf: true)");
+        assert_eq!(lines[8], " Keys: [25, 26, ..., 30] (leaf: true)");
+
+        let rendered = btree.render_with(&PrintOptions::new().max_keys(2));
+        assert!(rendered.contains(" Keys: [25, ..., 30] (leaf: true)\n"));
+        let rendered = btree.render_with(&PrintOptions::new().max_keys(0));
+        assert!(rendered.contains("Keys: [...] (leaf: false)\n"));
+
+        // the depth limit cuts the leaves off, elision still applies above it
+        let options = PrintOptions::new()
+            .max_keys(4)
+            .max_depth(0)
+            .key_counts(true);
+        let expected = "\
+B-tree (degree 4):
+Keys: [4, 8, ..., 20, 24] (keys: 6, leaf: false)
+ ... 7 children
+";
+        assert_eq!(btree.render_with(&options), expected);
+
+        // a limit deeper than the tree changes nothing
+        assert_eq!(
+            btree.render_with(&PrintOptions::new().max_depth(5)),
+            btree.render_with(&PrintOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_prefix_range() {
+        let mut btree = Btree::new(2);
+        for word in [
+            "app", "apple", "applet", "apply", "apt", "ban", "b", "", "ap", "éa", "éb",
+        ] {
+            btree.insert(word.to_string());
+        }
+        let starting = |prefix: &str| btree.prefix_range(prefix).cloned().collect::<Vec<_>>();
+        // a prefix that is itself a key comes first
+        assert_eq!(starting("app"), ["app", "apple", "applet", "apply"]);
+        assert_eq!(starting("appl"), ["apple", "applet", "apply"]);
+        assert_eq!(starting("apple"), ["apple", "applet"]);
+        assert_eq!(starting("b"), ["b", "ban"]);
+        assert_eq!(starting("c"), Vec::<String>::new());
+        assert_eq!(starting("é"), ["éa", "éb"]);
+        // the empty prefix matches every key, the empty key included
+        assert_eq!(starting(""), btree.iter().cloned().collect::<Vec<_>>());
+        assert_eq!(
+            btree.prefix_range("ap").next_back().map(String::as_str),
+            Some("apt")
+        );
+
+        let mut bytes = Btree::new(3);
+        for key in [
+            vec![0x01],
+            vec![0x01, 0xff],
+            vec![0x01, 0xff, 0x00],
+            vec![0x01, 0xff, 0xff],
+            vec![0x02],
+            vec![0xff],
+            vec![0xff, 0x00],
+            vec![0xff, 0xff, 0xff],
+        ] {
+            bytes.insert(key);
+        }
+        let starting = |prefix: &[u8]| bytes.prefix_range(prefix).cloned().collect::<Vec<_>>();
+        // the bound after 01 ff is 02, the one after 01 ff ff is still 02
+        assert_eq!(
+            starting(&[0x01, 0xff]),
+            [
+                vec![0x01, 0xff],
+                vec![0x01, 0xff, 0x00],
+                vec![0x01, 0xff, 0xff]
+            ]
+        );
+        assert_eq!(starting(&[0x01, 0xff, 0xff]), [vec![0x01, 0xff, 0xff]]);
+        assert_eq!(starting(&[0x01]).len(), 4);
+        // all 0xff has no bound at all
+        assert_eq!(
+            starting(&[0xff]),
+            [vec![0xff], vec![0xff, 0x00], vec![0xff, 0xff, 0xff]]
+        );
+        assert_eq!(starting(&[0xff, 0xff]), [vec![0xff, 0xff, 0xff]]);
+        assert_eq!(starting(&[]).len(), bytes.len());
+    }
+
+    #[test]
+    fn test_bytes_btree() {
+        // lengths from 0 to 300 bytes, zero bytes inside, and chains of keys
+        // where each one is a prefix of the next
+        let mut keys: Vec<Vec<u8>> =
+            vec![vec![], vec![0], vec![0, 0], vec![0, 0, 1], vec![1, 0, 0]];
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..300 {
+            let len = (rng.next() % 300) as usize;
+            keys.push((0..len).map(|_| (rng.next() % 3) as u8).collect());
+        }
+        for len in 0..40 {
+            keys.push(vec![7; len]);
+        }
+
+        let mut btree = BytesBtree::new(3);
+        let mut model = std::collections::BTreeSet::new();
+        for key in &keys {
+            assert_eq!(btree.insert_bytes(key), model.insert(key.clone()));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(
+            btree
+                .iter()
+                .map(|key| &key[..])
+                .eq(model.iter().map(|key| &key[..]))
+        );
+
+        for key in &keys {
+            let key: &[u8] = key;
+            assert_eq!(btree.get(key).map(|stored| &stored[..]), Some(key));
+            assert!(btree.search(key));
+        }
+        assert!(!btree.search(&[9u8][..]));
+        assert!(!btree.search(&[7u8; 40][..]));
+
+        // [7; 10] up to but not including [7; 20], and everything from [7; 30] on
+        let sevens = [7u8; 20];
+        let in_range: Vec<&[u8]> = btree
+            .range::<[u8], _>((Bound::Included(&sevens[..10]), Bound::Excluded(&sevens[..])))
+            .map(|key| &key[..])
+            .collect();
+        assert_eq!(
+            in_range,
+            (10..20).map(|len| &[7u8; 20][..len]).collect::<Vec<_>>()
+        );
+        assert_eq!(btree.prefix_range(&[7u8; 30][..]).count(), 10);
+        assert_eq!(
+            btree.prefix_range(&[0u8, 0][..]).count(),
+            model.iter().filter(|key| key.starts_with(&[0, 0])).count()
+        );
+
+        for key in keys.iter().step_by(2) {
+            assert_eq!(btree.delete(&key[..]), model.remove(key));
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        assert!(
+            btree
+                .iter()
+                .map(|key| &key[..])
+                .eq(model.iter().map(|key| &key[..]))
+        );
+
+        // one descent both finds the spot and stores the copy
+        #[cfg(feature = "metrics")]
+        {
+            // a root split on the way down adds a level
+            let height = btree.height() as u64;
+            assert!(height >= 2);
+            btree.reset_metrics();
+            assert!(btree.insert_bytes(&[5u8; 3]));
+            assert!(btree.metrics().nodes_visited <= height + 1);
+            btree.reset_metrics();
+            assert!(!btree.insert_bytes(&[5u8; 3]));
+            assert!(btree.metrics().nodes_visited <= height + 1);
+        }
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let mut tree: Btree<String> = Btree::new(2);
+        let mut made = 0;
+        let words = [
+            "b", "a", "c", "a", "d", "e", "b", "f", "g", "h", "a", "i", "j",
+        ];
+        for word in words {
+            let stored = tree.get_or_insert_with(word, || {
+                made += 1;
+                word.to_string()
+            });
+            assert_eq!(stored, word);
+        }
+        // the closure only ran for the first time each word came up
+        assert_eq!(made, 10);
+        assert_eq!(tree.len(), 10);
+        assert_eq!(tree.validate(), Ok(()));
+
+        // the reference points at the stored key, even right after splits
+        let mut tree: Btree<String> = Btree::new(2);
+        for i in 0..500 {
+            let word = format!("{:03}", (i * 37) % 500);
+            let pointer: *const String = tree.get_or_insert_with(&word[..], || word.clone());
+            assert!(std::ptr::eq(pointer, tree.get(&word[..]).unwrap()));
+            // splits on the way down may move it, the reference follows
+            let again: *const String = tree.get_or_insert_with(&word[..], || unreachable!());
+            assert!(std::ptr::eq(again, tree.get(&word[..]).unwrap()));
+        }
+        assert_eq!(tree.len(), 500);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_std_set_conversions() {
+        let set: BTreeSet<u32> = (0..100_000).map(|key| key * 3).collect();
+        let tree = Btree::from(set.clone());
+        assert_eq!(tree.degree(), DEFAULT_DEGREE);
+        assert_eq!(tree.len(), set.len());
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.iter().eq(set.iter()));
+        // bulk loaded, so no taller than n keys need: h levels of nodes with
+        // 2t children hold (2t)^h - 1 keys
+        let fanout = 2 * DEFAULT_DEGREE;
+        let optimal = (1..).find(|&h| fanout.pow(h) > set.len()).unwrap();
+        assert_eq!(tree.height(), optimal as usize);
+        assert_eq!(BTreeSet::from(tree), set);
+
+        let empty = Btree::from(BTreeSet::<u32>::new());
+        assert!(empty.is_empty());
+        assert_eq!(BTreeSet::from(empty), BTreeSet::new());
+    }
+
+    #[test]
+    fn test_extend_from_tree() {
+        let cases: [(Vec<u32>, Vec<u32>); 5] = [
+            ((0..500).collect(), (250..750).collect()),
+            (
+                (0..500).map(|key| key * 2).collect(),
+                (0..500).map(|key| key * 2 + 1).collect(),
+            ),
+            ((0..300).collect(), (1_000..1_300).collect()),
+            ((1_000..1_300).collect(), (0..300).collect()),
+            (Vec::new(), (0..10).collect()),
+        ];
+        for (ours, theirs) in cases {
+            let mut tree = Btree::new(3);
+            let mut other = Btree::new(2);
+            let mut model = BTreeSet::new();
+            for key in &ours {
+                tree.insert(*key);
+                model.insert(*key);
+            }
+            for key in &theirs {
+                other.insert(*key);
+                model.insert(*key);
+            }
+            tree.extend_from_tree(other);
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(tree.len(), model.len());
+            assert!(tree.iter().eq(model.iter()));
+            tree.extend_from_tree(Btree::new(2));
+            assert!(tree.iter().eq(model.iter()));
+        }
+
+        // a key in both trees keeps this tree's copy
+        let first = |pair: &(u32, &str)| pair.0;
+        let mut tree = Btree::new_by_key(2, first);
+        let mut other = Btree::new_by_key(2, first);
+        tree.insert((1, "ours"));
+        other.insert((1, "theirs"));
+        other.insert((2, "theirs"));
+        tree.extend_from_tree(other);
+        assert!(tree.iter().eq(&[(1, "ours"), (2, "theirs")]));
+    }
+
+    #[test]
+    fn test_drain_range() {
+        for (from, to) in [(400, 600), (0, 1_000), (10, 20), (990, 2_000), (500, 500)] {
+            let mut tree = Btree::new(2);
+            for key in (0..1_000).map(|i| (i * 7) % 1_000) {
+                tree.insert(key);
+            }
+            let mut shard = Btree::new(3);
+            for key in tree.drain_range(from..to) {
+                assert!(shard.insert(key));
+            }
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(shard.validate(), Ok(()));
+            assert!(shard.iter().copied().eq(from.min(1_000)..to.min(1_000)));
+            // the two trees split the original keys between them
+            assert_eq!(tree.len() + shard.len(), 1_000);
+            assert!(tree.iter().all(|key| !(from..to).contains(key)));
+        }
+
+        // dropping the iterator right away still removes the whole range
+        let mut tree = Btree::new(2);
+        for key in 0..100 {
+            tree.insert(key);
+        }
+        drop(tree.drain_range((Bound::Excluded(10), Bound::Included(20))));
+        assert!(tree.iter().copied().eq((0..=10).chain(21..100)));
+        assert_eq!(
+            tree.drain_range((Bound::Included(50), Bound::Excluded(40)))
+                .count(),
+            0
+        );
+        assert_eq!(tree.drain_range::<i32, _>(..).count(), 90);
+        assert!(tree.is_empty());
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_set_operators() {
+        let cases: [(Vec<u32>, Vec<u32>); 4] = [
+            ((0..300).collect(), (200..500).collect()),
+            (
+                (0..300).map(|key| key * 2).collect(),
+                (0..300).map(|key| key * 3).collect(),
+            ),
+            ((0..100).collect(), (100..200).collect()),
+            (Vec::new(), (0..50).collect()),
+        ];
+        for (left, right) in cases {
+            let (mut a, mut b) = (Btree::new(3), Btree::new(2));
+            for key in &left {
+                a.insert(*key);
+            }
+            for key in &right {
+                b.insert(*key);
+            }
+            let model_a: BTreeSet<u32> = left.into_iter().collect();
+            let model_b: BTreeSet<u32> = right.into_iter().collect();
+            let results = [
+                (&a | &b, &model_a | &model_b),
+                (&a & &b, &model_a & &model_b),
+                (&a - &b, &model_a - &model_b),
+                (&b - &a, &model_b - &model_a),
+                (&a ^ &b, &model_a ^ &model_b),
+            ];
+            for (tree, model) in results {
+                assert_eq!(tree.validate(), Ok(()));
+                assert!(tree.iter().eq(model.iter()));
+            }
+            // the result takes the left tree's degree
+            assert_eq!((&a | &b).degree(), 3);
+            assert_eq!((&b | &a).degree(), 2);
+        }
+    }
+
+    #[test]
+    fn test_range_by_rank() {
+        for degree in [2, 3, 7] {
+            let mut tree = Btree::new(degree);
+            for key in (0..2_000).map(|i| (i * 13) % 2_000) {
+                tree.insert(key * 2);
+            }
+            // every window of a few sizes, so they start and end all over the nodes
+            for size in [0, 1, 2, 5, 17] {
+                for start in (0..2_010).step_by(3) {
+                    let expected: Vec<&u32> = tree.iter().skip(start).take(size).collect();
+                    let got: Vec<&u32> = tree.range_by_rank(start..start + size).collect();
+                    assert_eq!(got, expected, "ranks {}..{}", start, start + size);
+                }
+            }
+            assert!(tree.range_by_rank(0..usize::MAX).eq(tree.iter()));
+            assert_eq!(tree.range_by_rank(2_500..3_000).count(), 0);
+            #[allow(clippy::reversed_empty_ranges)]
+            let backwards = 10..5;
+            assert_eq!(tree.range_by_rank(backwards).count(), 0);
+        }
+        assert_eq!(Btree::<u32>::new(2).range_by_rank(0..10).count(), 0);
+    }
+
+    #[test]
+    fn test_locate() {
+        for degree in [2, 3, 6] {
+            let mut tree = Btree::new(degree);
+            for key in (0..1_500).map(|i| (i * 11) % 1_500) {
+                tree.insert(key * 3 + 10);
+            }
+            let sorted: Vec<u32> = tree.iter().copied().collect();
+            // present keys, the gaps between them, and below min / above max
+            for probe in 0..4_600 {
+                assert_eq!(
+                    tree.locate(&probe),
+                    sorted.binary_search(&probe),
+                    "probe {}",
+                    probe
+                );
+            }
+            assert_eq!(tree.locate(&u32::MAX), Err(1_500));
+        }
+        assert_eq!(Btree::<u32>::new(2).locate(&5), Err(0));
+    }
+
+    // a walk over the key space in small random steps, the keys at odd
+    // positions are missing so about half the lookups miss
+    fn random_walk(steps: usize) -> Vec<u32> {
+        let mut state = 12345u64;
+        let mut position: i64 = 5_000;
+        (0..steps)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                position = (position + (state % 41) as i64 - 20).clamp(0, 10_000);
+                position as u32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_finger_search() {
+        let mut tree = Btree::new(3);
+        for key in (0..10_000).step_by(2) {
+            tree.insert(key);
+        }
+        let mut finger = Finger::new();
+        for (step, key) in random_walk(5_000).into_iter().enumerate() {
+            assert_eq!(tree.get_with_finger(&mut finger, &key), tree.get(&key));
+            // mutations in between leave the finger stale, it starts over
+            if step % 100 == 0 {
+                tree.insert(key | 1);
+            }
+            if step % 100 == 50 {
+                tree.delete(&(key & !1));
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        // a finger left in one tree is no good for another one
+        let mut other = Btree::new(2);
+        for key in 0..50 {
+            other.insert(key * 3);
+        }
+        for key in 0..150 {
+            assert_eq!(other.search_with_finger(&mut finger, &key), key % 3 == 0);
+        }
+        let empty = Btree::<u32>::new(2);
+        assert!(!empty.search_with_finger(&mut finger, &3));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_finger_search_visits_fewer_nodes() {
+        let mut tree = Btree::new(2);
+        for key in (0..10_000).step_by(2) {
+            tree.insert(key);
+        }
+        let walk = random_walk(10_000);
+
+        tree.reset_metrics();
+        let plain: Vec<bool> = walk.iter().map(|key| tree.search(key)).collect();
+        let from_root = tree.metrics().nodes_visited;
+
+        tree.reset_metrics();
+        let mut finger = Finger::new();
+        let fingered: Vec<bool> = walk
+            .iter()
+            .map(|key| tree.search_with_finger(&mut finger, key))
+            .collect();
+        let from_finger = tree.metrics().nodes_visited;
+
+        assert_eq!(plain, fingered);
+        assert!(
+            from_finger * 2 < from_root,
+            "{} nodes with a finger, {} from the root",
+            from_finger,
+            from_root
+        );
+    }
+
+    #[test]
+    fn test_f64_keys() {
+        let mut tree = Btree::new_f64(2);
+        let keys = [
+            1.5,
+            -0.0,
+            0.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            -f64::NAN,
+            -2.25,
+            f64::MIN_POSITIVE,
+            1e300,
+        ];
+        for key in keys {
+            assert!(tree.insert(key));
+        }
+        // the same bits again are the same key, NaN included
+        assert!(!tree.insert(f64::NAN));
+        assert!(!tree.insert(-0.0));
+        assert_eq!(tree.validate(), Ok(()));
+
+        // -0.0 and 0.0 are two keys, NaN sorts by its sign bit past the infinities
+        let bits: Vec<u64> = tree.iter().map(|key| key.to_bits()).collect();
+        let expected: Vec<u64> = [
+            -f64::NAN,
+            f64::NEG_INFINITY,
+            -2.25,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            1.5,
+            1e300,
+            f64::INFINITY,
+            f64::NAN,
+        ]
+        .iter()
+        .map(|key| key.to_bits())
+        .collect();
+        assert_eq!(bits, expected);
+        assert!(tree.search(&f64::NAN));
+        assert_eq!(
+            tree.get(&0.0).map(|key| key.is_sign_negative()),
+            Some(false)
+        );
+
+        // float bounds, a range over every number leaves both NaNs out
+        assert!(
+            tree.range(-1.0..2.0)
+                .eq(&[-0.0, 0.0, f64::MIN_POSITIVE, 1.5])
+        );
+        assert!(tree.range(0.0..=1.5).eq(&[0.0, f64::MIN_POSITIVE, 1.5]));
+        assert_eq!(tree.range(f64::NEG_INFINITY..=f64::INFINITY).count(), 8);
+        assert_eq!(tree.range(f64::INFINITY..).count(), 2);
+        assert!(tree.delete(&-0.0));
+        assert!(
+            tree.range(-1.0..f64::MIN_POSITIVE)
+                .copied()
+                .map(f64::to_bits)
+                .eq([0.0f64.to_bits()])
+        );
+    }
+
+    #[test]
+    fn test_interned_keys() {
+        use std::sync::Arc;
+
+        let interner = Interner::new();
+        let mut users = InternedBtree::new(2);
+        let mut admins = InternedBtree::new(3);
+        for name in ["carol", "alice", "bob", "dave", "alice"] {
+            users.insert_interned(&interner, name);
+        }
+        for name in ["bob", "erin", "alice"] {
+            admins.insert_interned(&interner, name);
+        }
+        assert_eq!(users.len(), 4);
+        assert_eq!(interner.len(), 5);
+
+        // looked up by &str, and both trees point at the same allocation
+        for name in ["alice", "bob"] {
+            let (ours, theirs) = (users.get(name).unwrap(), admins.get(name).unwrap());
+            assert!(Arc::ptr_eq(ours, theirs));
+            assert!(Arc::ptr_eq(ours, &interner.intern(name)));
+        }
+        assert!(!users.search("erin"));
+        assert!(
+            users
+                .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+                .map(|key| &key[..])
+                .eq(["bob", "carol"])
+        );
+        assert!(users.delete("carol"));
+        assert!(!users.search("carol"));
+        assert_eq!(users.validate(), Ok(()));
+
+        // the bytes behind the keys are counted on top of the nodes
+        let key_bytes: usize = ["alice", "bob", "dave"]
+            .iter()
+            .map(|name| name.len() + 2 * size_of::<usize>())
+            .sum();
+        assert_eq!(
+            users.memory_usage_with_keys(),
+            users.memory_usage() + key_bytes
+        );
+    }
+
+    #[cfg(feature = "observer")]
+    #[test]
+    fn test_observer() {
+        use btree::btree::TreeEvent::*;
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut btree = Btree::new(2);
+        let recorded = Arc::clone(&events);
+        btree.set_observer(move |event| recorded.lock().unwrap().push(event));
+        let take = || std::mem::take(&mut *events.lock().unwrap());
+
+        // [1 2 3] is a full root: it goes under a new one and splits
+        for key in 1..=4 {
+            btree.insert(key);
+        }
+        assert_eq!(
+            take(),
+            [
+                RootGrew,
+                Split {
+                    depth: 0,
+                    parent_key_count: 0
+                }
+            ]
+        );
+        // root [2] over [1] and [3 4 5], the leaf splits on the way to 6
+        btree.insert(5);
+        btree.insert(6);
+        assert_eq!(
+            take(),
+            [Split {
+                depth: 1,
+                parent_key_count: 1
+            }]
+        );
+        // root [2 4] over [1], [3] and [5 6]: neither [1] nor [3] has a
+        // key to spare, they merge around 2
+        btree.delete(&1);
+        assert_eq!(take(), [Merge { depth: 1 }]);
+        // root [4] over [2 3] and [5 6], nothing to fix on the way to 6,
+        // then [5] takes 3 through the root on the way to 5
+        btree.delete(&6);
+        assert_eq!(take(), []);
+        btree.delete(&5);
+        assert_eq!(take(), [BorrowLeft { depth: 1 }]);
+        // root [3] over [2] and [4]: they merge and the root goes away
+        btree.delete(&4);
+        assert_eq!(take(), [Merge { depth: 1 }, RootShrank]);
+        assert!(btree.iter().eq(&[2, 3]));
+
+        // the mirror image, a key borrowed from the right
+        let mut btree = Btree::new(2);
+        for key in [2, 4, 1, 3, 5] {
+            btree.insert(key);
+        }
+        let recorded = Arc::clone(&events);
+        btree.set_observer(move |event| recorded.lock().unwrap().push(event));
+        // root [2] over [1] and [3 4 5]
+        btree.delete(&1);
+        assert_eq!(take(), [BorrowRight { depth: 1 }]);
+        btree.clear_observer();
+        btree.delete(&3);
+        assert_eq!(take(), []);
+        assert_eq!(btree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_lines_round_trip() {
+        use std::io::Cursor;
+
+        let mut numbers = Btree::new(3);
+        for key in [40, -7, 12, 0, 1000, 3] {
+            numbers.insert(key);
+        }
+        let mut out = Cursor::new(Vec::new());
+        numbers
+            .export_lines(&mut out, |key| key.to_string())
+            .unwrap();
+        assert_eq!(out.get_ref(), b"-7\n0\n3\n12\n40\n1000\n");
+        out.set_position(0);
+        let back = Btree::import_lines(3, out, |line| line.parse::<i32>()).unwrap();
+        assert!(back.structurally_eq(&numbers.with_degree(3).unwrap()));
+        assert_eq!(back.validate(), Ok(()));
+
+        let mut words = Btree::new(2);
+        for word in ["pear", "apple", "fig", "kiwi", "banana"] {
+            words.insert(word.to_string());
+        }
+        let mut out = Cursor::new(Vec::new());
+        words.export_lines(&mut out, |word| word.clone()).unwrap();
+        out.set_position(0);
+        let back = Btree::import_lines(2, out, |line| Ok::<_, ()>(line.to_string())).unwrap();
+        assert!(back.iter().eq(words.iter()));
+
+        let back = Btree::import_lines(4, Cursor::new(""), |line| line.parse::<u8>()).unwrap();
+        assert!(back.is_empty());
+    }
+
+    #[test]
+    fn test_import_unsorted_lines() {
+        use std::io::Cursor;
+
+        let input = "5\n3\n9\n3\n1\n9\n7\n";
+        let tree = Btree::import_lines(2, Cursor::new(input), |line| line.parse::<u32>()).unwrap();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), 5);
+        assert!(tree.iter().copied().eq([1, 3, 5, 7, 9]));
+
+        // sorted with repeats isn't strictly ascending, repeats still go
+        let tree =
+            Btree::import_lines(2, Cursor::new("1\n1\n2\n"), |line| line.parse::<u32>()).unwrap();
+        assert!(tree.iter().copied().eq([1, 2]));
+    }
+
+    #[test]
+    fn test_import_bad_line() {
+        use std::io::Cursor;
+
+        let input = "1\n2\nthree\n4\n";
+        match Btree::import_lines(2, Cursor::new(input), |line| line.parse::<u32>()) {
+            Err(ImportError::Parse { line, error }) => {
+                assert_eq!(line, 3);
+                assert_eq!(error, "three".parse::<u32>().unwrap_err());
+            }
+            other => panic!(
+                "expected a parse error, got {:?}",
+                other.map(|tree| tree.len())
+            ),
+        }
+        let error =
+            Btree::import_lines(2, Cursor::new("\n"), |line| line.parse::<u32>()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 1: cannot parse integer from empty string"
+        );
+
+        // not utf-8
+        let error = Btree::import_lines(2, Cursor::new(vec![0xff, b'\n']), |line| {
+            line.parse::<u32>()
+        })
+        .unwrap_err();
+        assert!(matches!(error, ImportError::Io(_)));
+    }
+
+    #[test]
+    fn test_view() {
+        let mut btree = Btree::new(2);
+        for key in (0..200).step_by(2) {
+            btree.insert(key);
+        }
+        let view = btree.view(50..=150);
+        assert_eq!(view.len(), 51);
+        assert_eq!((view.first(), view.last()), (Some(&50), Some(&150)));
+        assert!(view.iter().copied().eq((50..=150).step_by(2)));
+        assert!(view.iter().rev().copied().eq((50..=150).rev().step_by(2)));
+        assert!(view.contains(&50) && view.contains(&150) && view.contains(&100));
+        assert!(!view.contains(&48) && !view.contains(&152) && !view.contains(&51));
+        assert_eq!(view.get(&152), None);
+        assert!(btree.contains_all([&48, &152]));
+
+        // nested views only narrow, bounds past the outer view are clamped
+        let inner = view.view(100..);
+        assert!(inner.iter().copied().eq((100..=150).step_by(2)));
+        let inner = view.view(..=60);
+        assert!(inner.iter().copied().eq((50..=60).step_by(2)));
+        assert_eq!(inner.len(), 6);
+        let inner = view.view((Bound::Excluded(0), Bound::Excluded(1000)));
+        assert!(inner.iter().eq(view.iter()));
+        let innermost = view.view(70..90).view(80..);
+        assert!(innermost.iter().copied().eq([80, 82, 84, 86, 88]));
+        assert!(!innermost.contains(&90) && !innermost.contains(&78));
+        assert!(view.view(160..170).is_empty());
+    }
+
+    #[test]
+    fn test_view_bounds_between_keys() {
+        let btree: Btree<i32> = (0..100).map(|key| key * 10).collect::<BTreeSet<_>>().into();
+        let view = btree.view(15..=45);
+        assert!(view.iter().copied().eq([20, 30, 40]));
+        assert_eq!(view.len(), 3);
+        assert_eq!((view.first(), view.last()), (Some(&20), Some(&40)));
+        let view = btree.view((Bound::Excluded(20), Bound::Excluded(50)));
+        assert!(view.iter().copied().eq([30, 40]));
+        assert!(!view.contains(&20) && !view.contains(&50));
+        assert!(view.view(31..39).is_empty());
+        assert_eq!(view.view(25..).iter().count(), 2);
+        assert!(btree.view(..).iter().eq(btree.iter()));
+        assert_eq!(btree.view(..).len(), 100);
+    }
+
+    #[test]
+    fn test_empty_view() {
+        let btree: Btree<i32> = (0..10).map(|key| key * 10).collect::<BTreeSet<_>>().into();
+        for view in [
+            btree.view(41..49),
+            btree.view(200..),
+            btree.view(..-5),
+            btree.view(40..40),
+        ] {
+            assert!(view.is_empty());
+            assert_eq!(view.len(), 0);
+            assert_eq!(view.iter().next(), None);
+            assert_eq!(view.iter().next_back(), None);
+            assert_eq!((view.first(), view.last()), (None, None));
+            assert!(!view.contains(&40));
+            assert!(view.view(..).is_empty());
+        }
+        assert!(Btree::<i32>::new(3).view(..).is_empty());
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut btree = Btree::new(2);
+        for key in 0..100 {
+            btree.insert(key);
+        }
+        // reads leave the generation as it is
+        let stamp = btree.generation();
+        assert!(btree.search(&50));
+        assert_eq!(btree.iter().count(), 100);
+        let mut cursor = btree.cursor();
+        cursor.seek(&10);
+        assert_eq!(btree.view(10..20).len(), 10);
+        assert_eq!(btree.page(None, 10).unwrap().0.len(), 10);
+        btree.search_with_finger(&mut Finger::new(), &70);
+        assert_eq!(btree.generation(), stamp);
+
+        let mut last = stamp;
+        let mut changed = |btree: &Btree<i32>| {
+            let changed = btree.generation() != last;
+            last = btree.generation();
+            changed
+        };
+        btree.insert(100);
+        assert!(changed(&btree));
+        btree.delete(&0);
+        assert!(changed(&btree));
+        btree.pop_last();
+        assert!(changed(&btree));
+        btree.rebuild();
+        assert!(changed(&btree));
+        let mut cursor = btree.cursor_mut();
+        cursor.seek(&50);
+        cursor.remove_current();
+        assert!(changed(&btree));
+        assert!(!changed(&btree));
+        assert_ne!(
+            btree.generation(),
+            btree.with_degree(3).unwrap().generation()
+        );
+    }
+
+    #[test]
+    fn test_page_tokens() {
+        let mut btree = Btree::new(2);
+        for key in 0..95 {
+            btree.insert(key);
+        }
+        let mut token = None;
+        let mut pages = Vec::new();
+        loop {
+            let (page, next) = btree.page(token, 10).unwrap();
+            pages.push(page.into_iter().copied().collect::<Vec<_>>());
+            match next {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(pages.len(), 10);
+        assert!(pages.concat().into_iter().eq(0..95));
+        assert_eq!(pages[9], (90..95).collect::<Vec<_>>());
+        assert_eq!(btree.page(None, 0), Ok((Vec::new(), None)));
+
+        // the same token keeps working as long as nothing changes
+        let (_, next) = btree.page(None, 30).unwrap();
+        let next = next.unwrap();
+        assert_eq!(btree.page(Some(next), 2).unwrap().0, [&30, &31]);
+        assert_eq!(btree.page(Some(next), 2).unwrap().0, [&30, &31]);
+
+        // a change in between makes it stale, so does another tree
+        let before = btree.generation();
+        btree.delete(&5);
+        assert_eq!(
+            btree.page(Some(next), 2),
+            Err(Stale {
+                expected: before,
+                found: btree.generation()
+            })
+        );
+        let other = btree.with_degree(2).unwrap();
+        assert!(other.iter().eq(btree.iter()));
+        let (_, next) = btree.page(None, 30).unwrap();
+        assert!(other.page(next, 2).is_err());
+        assert!(btree.page(next, 2).is_ok());
+    }
+
+    // run op on a fresh tree with the comparator set to panic on its n-th
+    // comparison, for n = 0, 1, .. until op gets through. after a panic the
+    // tree has to validate and hold the keys it started with, otherwise the
+    // keys in after
+    fn panic_at_every_comparison<C>(
+        fuse: &Cell<Option<usize>>,
+        build: impl Fn() -> Btree<i32, C>,
+        op: impl Fn(&mut Btree<i32, C>),
+        after: &[i32],
+    ) where
+        C: btree::compare::Compare<i32>,
+    {
+        for n in 0.. {
+            let mut btree = build();
+            let before: Vec<i32> = btree.iter().copied().collect();
+            fuse.set(Some(n));
+            let result = catch_unwind(AssertUnwindSafe(|| op(&mut btree)));
+            fuse.set(None);
+            assert_eq!(btree.validate(), Ok(()), "panic at comparison {}", n);
+            match result {
+                Err(_) => assert!(btree.iter().eq(&before), "panic at comparison {}", n),
+                Ok(()) => {
+                    assert!(btree.iter().eq(after));
+                    assert!(n > 0);
+                    return;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_panicking_comparator() {
+        // comparisons left until the comparator panics, None for never
+        let fuse = Rc::new(Cell::new(None));
+        let cmp = {
+            let fuse = Rc::clone(&fuse);
+            move |a: &i32, b: &i32| {
+                if let Some(left) = fuse.get() {
+                    assert!(left > 0, "comparator gave up");
+                    fuse.set(Some(left - 1));
+                }
+                a.cmp(b)
+            }
+        };
+        let mut keys: Vec<i32> = (0..300).map(|key| key * 2).collect();
+        Rng(0xc0ffee5).shuffle(&mut keys);
+        let build = |keys: &[i32]| {
+            let mut btree = Btree::with_comparator(2, cmp.clone());
+            for &key in keys {
+                btree.insert(key);
+            }
+            btree
+        };
+        let tree = || build(&keys);
+        let sorted = |keys: &mut dyn Iterator<Item = i32>| {
+            let mut keys: Vec<i32> = keys.collect();
+            keys.sort();
+            keys.dedup();
+            keys
+        };
+
+        // every node is full before the insert, the root included
+        let full = || {
+            build(&(0..15).map(|key| key * 2).collect::<Vec<_>>())
+                .with_degree(2)
+                .unwrap()
+        };
+        assert_eq!((full().node_count(), full().height()), (5, 2));
+        panic_at_every_comparison(
+            &fuse,
+            full,
+            |btree| {
+                btree.insert(13);
+            },
+            &sorted(&mut (0..15).map(|key| key * 2).chain([13])),
+        );
+
+        panic_at_every_comparison(
+            &fuse,
+            tree,
+            |btree| {
+                btree.insert(301);
+            },
+            &sorted(&mut keys.iter().copied().chain([301])),
+        );
+        panic_at_every_comparison(
+            &fuse,
+            tree,
+            |btree| {
+                btree.get_or_insert_with(&301, || 301);
+            },
+            &sorted(&mut keys.iter().copied().chain([301])),
+        );
+        panic_at_every_comparison(
+            &fuse,
+            || build(&[]),
+            |btree| {
+                btree.get_or_insert_with(&1, || 1);
+            },
+            &[1],
+        );
+        for delete in [0, 300, 598, keys[0]] {
+            panic_at_every_comparison(
+                &fuse,
+                tree,
+                |btree| {
+                    assert!(btree.delete(&delete));
+                },
+                &sorted(&mut keys.iter().copied().filter(|&key| key != delete)),
+            );
+        }
+        panic_at_every_comparison(
+            &fuse,
+            tree,
+            |btree| {
+                let mut batch: Vec<i32> = (0..200).map(|key| key * 3).collect();
+                btree.delete_many(&mut batch);
+            },
+            &sorted(&mut keys.iter().copied().filter(|key| key % 3 != 0)),
+        );
+        panic_at_every_comparison(
+            &fuse,
+            tree,
+            |btree| {
+                btree.extend_from_tree(build(&(0..200).map(|key| key * 3).collect::<Vec<_>>()));
+            },
+            &sorted(&mut keys.iter().copied().chain((0..200).map(|key| key * 3))),
+        );
+    }
+
+    #[cfg(feature = "check_ord")]
+    #[test]
+    #[should_panic(expected = "inconsistent ordering")]
+    fn test_check_ord_non_transitive() {
+        // rock, paper, scissors: keys in different classes mod 3 beat each
+        // other in a circle, so a < b < c < a happens
+        let mut btree = Btree::with_comparator(2, |a: &i32, b: &i32| match (a - b).rem_euclid(3) {
+            0 => a.cmp(b),
+            1 => std::cmp::Ordering::Greater,
+            _ => std::cmp::Ordering::Less,
+        });
+        for key in 0..100 {
+            btree.insert(key);
+        }
+    }
+
+    #[cfg(feature = "check_ord")]
+    #[test]
+    #[should_panic(expected = "inconsistent ordering")]
+    fn test_check_ord_unstable() {
+        // ascending for the first 500 comparisons, descending after that
+        let calls = Cell::new(0);
+        let mut btree = Btree::with_comparator(3, |a: &i32, b: &i32| {
+            calls.set(calls.get() + 1);
+            match calls.get() <= 500 {
+                true => a.cmp(b),
+                false => b.cmp(a),
+            }
+        });
+        let mut key = 0;
+        while calls.get() <= 500 {
+            btree.insert(key);
+            key += 1;
+        }
+        assert_eq!(btree.validate(), Ok(()));
+        btree.search(&(key / 2));
+    }
+
+    #[cfg(feature = "check_ord")]
+    #[test]
+    fn test_check_ord_consistent() {
+        let mut keys: Vec<u32> = (0..2000).collect();
+        Rng(0x0bd).shuffle(&mut keys);
+        let mut btree = Btree::new_reversed(2);
+        for &key in &keys {
+            btree.insert(key);
+        }
+        for key in &keys[..1000] {
+            assert!(btree.delete(key));
+        }
+        assert!(
+            btree
+                .iter()
+                .copied()
+                .eq((0..2000).rev().filter(|key| !keys[..1000].contains(key)))
+        );
+    }
+
+    #[test]
+    fn test_node_slices_and_chunks() {
+        // the key runs of every node, as the in-order walk finds them
+        struct Runs {
+            leaf: Vec<bool>,
+            runs: Vec<Vec<u32>>,
+        }
+        impl Visit<u32> for Runs {
+            fn enter_node(&mut self, info: &NodeInfo) {
+                self.leaf.push(info.is_leaf());
+                if info.is_leaf() {
+                    self.runs.push(Vec::new());
+                }
+            }
+            fn key(&mut self, key: &u32) {
+                match self.leaf.last() {
+                    Some(true) => self.runs.last_mut().unwrap().push(*key),
+                    _ => self.runs.push(vec![*key]),
+                }
+            }
+            fn leave_node(&mut self, _info: &NodeInfo) {
+                self.leaf.pop();
+            }
+        }
+
+        let empty: Btree<u32> = Btree::new(2);
+        assert_eq!(empty.iter_node_slices().next(), None);
+        assert_eq!(empty.iter_chunks(4).next(), None);
+
+        let mut rng = Rng(0x71c5e8a1d3b29f07);
+        for degree in 2..=6 {
+            let mut btree = Btree::new(degree);
+            for _ in 0..3_000 {
+                btree.insert(rng.below(10_000) as u32);
+            }
+            let mut runs = Runs {
+                leaf: Vec::new(),
+                runs: Vec::new(),
+            };
+            btree.walk(&mut runs, Order::InOrder);
+            let slices: Vec<&[u32]> = btree.iter_node_slices().collect();
+            assert!(slices.iter().map(|slice| slice.to_vec()).eq(runs.runs));
+            assert!(slices.concat().iter().eq(btree.iter()));
+
+            for size in [1, 2, 7, 64, 5_000] {
+                let chunks: Vec<Vec<&u32>> = btree.iter_chunks(size).collect();
+                assert!(chunks.iter().rev().skip(1).all(|chunk| chunk.len() == size));
+                assert!(chunks.last().is_some_and(|chunk| chunk.len() <= size));
+                assert!(chunks.into_iter().flatten().eq(btree.iter()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_join() {
+        // stream items are (key, position), the position only tells duplicates apart
+        type Item = (u32, usize);
+        // a join item as (key, 0 both | 1 left only | 2 right only, stream item)
+        type Flat = (u32, u8, Item);
+        fn by_key(key: &u32, item: &Item) -> std::cmp::Ordering {
+            key.cmp(&item.0)
+        }
+        fn flat(item: JoinItem<'_, u32, Item>) -> Flat {
+            match item {
+                JoinItem::Both(key, item) => (*key, 0, item),
+                JoinItem::LeftOnly(key) => (*key, 1, (0, 0)),
+                JoinItem::RightOnly(item) => (item.0, 2, item),
+            }
+        }
+        // every tree key with each stream item it equals, or alone, and every
+        // stream item no tree key equals, sorted by key
+        fn brute_force(keys: &[u32], stream: &[Item]) -> Vec<Flat> {
+            let mut events = Vec::new();
+            for &key in keys {
+                let equal: Vec<Item> = stream
+                    .iter()
+                    .filter(|item| item.0 == key)
+                    .copied()
+                    .collect();
+                if equal.is_empty() {
+                    events.push((key, 1, (0, 0)));
+                }
+                events.extend(equal.into_iter().map(|item| (key, 0, item)));
+            }
+            for &item in stream {
+                if !keys.contains(&item.0) {
+                    events.push((item.0, 2, item));
+                }
+            }
+            events.sort_by_key(|&(key, _, _)| key);
+            events
+        }
+
+        let empty: Btree<u32> = Btree::new(2);
+        assert_eq!(empty.merge_join(Vec::<Item>::new(), by_key).next(), None);
+        assert_eq!(
+            empty.merge_join(vec![(1, 0)], by_key).collect::<Vec<_>>(),
+            vec![JoinItem::RightOnly((1, 0))]
+        );
+
+        let mut rng = Rng(0x4be19c07a2f3d855);
+        for degree in 2..=5 {
+            for _ in 0..20 {
+                let mut btree = Btree::new(degree);
+                for _ in 0..rng.below(200) {
+                    btree.insert(rng.below(400) as u32);
+                }
+                // sparse or dense, with runs of equal items
+                let spread = 1 + rng.below(400);
+                let mut stream: Vec<u32> = (0..rng.below(60))
+                    .map(|_| rng.below(spread) as u32)
+                    .collect();
+                stream.sort();
+                let stream: Vec<Item> = stream
+                    .into_iter()
+                    .enumerate()
+                    .map(|(at, key)| (key, at))
+                    .collect();
+                let keys: Vec<u32> = btree.iter().copied().collect();
+
+                let joined: Vec<Flat> = btree
+                    .merge_join(stream.iter().copied(), by_key)
+                    .map(flat)
+                    .collect();
+                assert_eq!(joined, brute_force(&keys, &stream));
+
+                // matches_only keeps just the pairs, seeking over the gaps
+                let pairs: Vec<Flat> = btree
+                    .merge_join(stream.iter().copied(), by_key)
+                    .matches_only()
+                    .map(flat)
+                    .collect();
+                let expected: Vec<Flat> = brute_force(&keys, &stream)
+                    .into_iter()
+                    .filter(|&(_, side, _)| side == 0)
+                    .collect();
+                assert_eq!(pairs, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_keys() {
+        // ids have to stay below 1000
+        fn below_1000(id: &u32) -> Result<(), String> {
+            match *id < 1000 {
+                true => Ok(()),
+                false => Err(format!("id {} is out of range", id)),
+            }
+        }
+        fn invalid(index: usize, id: u32) -> InvalidKey {
+            InvalidKey {
+                index,
+                reason: format!("id {} is out of range", id),
+            }
+        }
+
+        // a key stored before the check was set is still found and deleted
+        let mut unchecked = Btree::new(2);
+        unchecked.insert(5_000u32);
+        let mut btree = unchecked.validate_keys(below_1000);
+        assert!(btree.search(&5_000));
+        assert!(btree.delete(&5_000));
+
+        for key in (0..100).map(|i| i * 7) {
+            assert_eq!(btree.try_insert(key), Ok(true));
+        }
+        assert_eq!(btree.try_insert(7), Ok(false));
+        assert_eq!(btree.try_insert(1_000), Err(invalid(0, 1_000)));
+        assert_eq!(
+            invalid(0, 1_000).to_string(),
+            "key 0 is invalid: id 1000 is out of range"
+        );
+        assert!(!btree.search(&1_000));
+        assert!(!btree.delete(&1_000));
+
+        // the calls that can't hand the reason back panic before touching the tree
+        let before: Vec<u32> = btree.iter().copied().collect();
+        fn panics<R>(btree: &mut Btree<u32>, f: impl FnOnce(&mut Btree<u32>) -> R) -> bool {
+            let payload = catch_unwind(AssertUnwindSafe(|| f(btree))).err();
+            payload.is_some_and(|payload| {
+                payload
+                    .downcast_ref::<String>()
+                    .is_some_and(|message| message.contains("out of range"))
+            })
+        }
+        assert!(panics(&mut btree, |btree| btree.insert(2_000)));
+        assert!(panics(&mut btree, |btree| btree.replace(2_001)));
+        assert!(panics(&mut btree, |btree| btree.insert_traced(2_002)));
+        assert!(panics(&mut btree, |btree| *btree
+            .get_or_insert_with(&2_003, || 2_003)));
+        assert!(panics(&mut btree, |btree| btree
+            .cursor_mut()
+            .insert_after(2_004)));
+        assert!(btree.iter().copied().eq(before.iter().copied()));
+        assert_eq!(btree.validate(), Ok(()));
+        assert_eq!(btree.replace(7), Some(7));
+        assert_eq!(*btree.get_or_insert_with(&8, || 8), 8);
+
+        // a bulk load names the first key turned down, in the order of the keys
+        let before: Vec<u32> = btree.iter().copied().collect();
+        let mut other = Btree::new(2);
+        for key in [1, 3, 1_500, 998, 4_000] {
+            other.insert(key);
+        }
+        assert_eq!(btree.try_extend_from_tree(other), Err(invalid(3, 1_500)));
+        assert!(btree.iter().copied().eq(before.iter().copied()));
+        let mut other = Btree::new(3);
+        for key in [1, 3, 998] {
+            other.insert(key);
+        }
+        assert_eq!(btree.try_extend_from_tree(other), Ok(()));
+        assert_eq!(btree.len(), before.len() + 3);
+
+        // a batch, small or merged, is turned down as a whole
+        let before: Vec<u32> = btree.iter().copied().collect();
+        for ops in [
+            vec![Op::Insert(2), Op::Delete(7), Op::Insert(9_999)],
+            (0..400)
+                .map(|i| match i % 3 {
+                    0 => Op::Delete(i * 3),
+                    _ => Op::Insert(i * 3),
+                })
+                .collect(),
+        ] {
+            let first = ops
+                .iter()
+                .position(|op| matches!(op, Op::Insert(key) if *key >= 1_000))
+                .unwrap();
+            let key = *ops[first].key();
+            assert_eq!(
+                btree.apply_batch(ops),
+                Err(BatchError::InvalidKey(invalid(first, key)))
+            );
+            assert!(btree.iter().copied().eq(before.iter().copied()));
+        }
+        let summary = btree.apply_batch([Op::Delete(7), Op::Insert(999)]).unwrap();
+        assert_eq!((summary.inserted, summary.deleted), (1, 1));
+
+        // trees made out of it keep the check
+        let mut wider = btree.with_degree(4).unwrap();
+        assert_eq!(wider.try_insert(1_000), Err(invalid(0, 1_000)));
+        for mut piece in btree.split_evenly(3) {
+            assert_eq!(piece.try_insert(1_000), Err(invalid(0, 1_000)));
+            let stored = piece.search(&1);
+            assert_eq!(piece.try_insert(1), Ok(!stored));
+        }
+    }
+
+    #[test]
+    fn test_extend_unsorted() {
+        use std::sync::{Arc, Mutex};
+
+        // batches on both sides of the size where merging takes over from
+        // inserting key by key, each checked against inserting key by key.
+        // tags tell which of equal keys was kept
+        let mut rng = Rng(0x8f1bbcdcca62c1d6);
+        for degree in 2..=5 {
+            for size in [0, 10, 200] {
+                for batch_size in [0, 1, 5, 30, 300] {
+                    let mut btree = tagged_tree(degree);
+                    for _ in 0..size {
+                        btree.insert((rng.below(400) as u32, 0));
+                    }
+                    let batch: Vec<Tagged> = (0..batch_size)
+                        .map(|at| (rng.below(400) as u32, at as u32 + 1))
+                        .collect();
+                    let mut expected = tagged_tree(degree);
+                    for key in btree.iter().chain(&batch) {
+                        expected.insert(*key);
+                    }
+
+                    let added = Arc::new(Mutex::new(0));
+                    let reported = Arc::clone(&added);
+                    btree.set_on_change(move |change: Change<&Tagged>| {
+                        assert!(matches!(change, Change::Inserted(_)));
+                        *reported.lock().unwrap() += 1;
+                    });
+                    let len = btree.len();
+                    assert_eq!(btree.extend_unsorted(batch), expected.len() - len);
+                    assert_eq!(btree.validate(), Ok(()));
+                    assert!(btree.iter().eq(expected.iter()));
+                    assert_eq!(*added.lock().unwrap(), expected.len() - len);
+                }
+            }
+        }
+
+        // the key check turns the batch down before any key goes in
+        let mut btree = Btree::new(3).validate_keys(|key: &u32| match *key < 100 {
+            true => Ok(()),
+            false => Err("too big".to_string()),
+        });
+        btree.extend_unsorted(vec![5, 1, 5, 3]);
+        for batch in [vec![7, 150], (0..90).rev().chain([100]).collect()] {
+            let result = catch_unwind(AssertUnwindSafe(|| btree.extend_unsorted(batch)));
+            assert!(result.is_err());
+            assert!(btree.iter().copied().eq([1, 3, 5]));
+        }
+    }
+
+    // keys tagged with a number the comparator ignores, so a replaced key can
+    // be told apart from the one it replaced
+    type Tagged = (u32, u32);
+
+    fn untagged(key: &Tagged) -> u32 {
+        key.0
+    }
+
+    type TaggedTree = Btree<Tagged, btree::compare::ByKey<fn(&Tagged) -> u32, u32>>;
+
+    fn tagged_tree(degree: usize) -> TaggedTree {
+        Btree::new_by_key(degree, untagged as fn(&Tagged) -> u32)
+    }
+
+    #[test]
+    fn test_on_change_reconciles() {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+
+        let mut rng = Rng(0x3c6ef372fe94f82b);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut btree = tagged_tree(3);
+        let events = Arc::clone(&log);
+        btree.set_on_change(move |change: Change<&Tagged>| {
+            events.lock().unwrap().push(match change {
+                Change::Inserted(key) => Change::Inserted(*key),
+                Change::Removed(key) => Change::Removed(*key),
+                Change::Replaced { old, new } => Change::Replaced {
+                    old: *old,
+                    new: *new,
+                },
+            });
+        });
+
+        // the log replayed onto a model has to end up where the tree is
+        let mut model: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut replay = |btree: &TaggedTree, expected: Option<usize>| {
+            let changes: Vec<_> = log.lock().unwrap().drain(..).collect();
+            if let Some(expected) = expected {
+                assert_eq!(changes.len(), expected);
+            }
+            for change in changes {
+                match change {
+                    Change::Inserted((key, tag)) => assert_eq!(model.insert(key, tag), None),
+                    Change::Removed((key, tag)) => assert_eq!(model.remove(&key), Some(tag)),
+                    Change::Replaced { old, new } => {
+                        assert_eq!(old.0, new.0);
+                        assert_eq!(model.insert(new.0, new.1), Some(old.1));
+                    }
+                }
+            }
+            assert!(
+                btree
+                    .iter()
+                    .copied()
+                    .eq(model.iter().map(|(&k, &v)| (k, v)))
+            );
+        };
+
+        for tag in 0..3_000 {
+            let key = rng.below(400) as u32;
+            match rng.below(10) {
+                0 | 1 => {
+                    let inserted = btree.insert((key, tag));
+                    replay(&btree, Some(usize::from(inserted)));
+                }
+                2 | 3 => {
+                    let deleted = btree.delete(&(key, 0));
+                    replay(&btree, Some(usize::from(deleted)));
+                }
+                4 => {
+                    btree.replace((key, tag));
+                    replay(&btree, Some(1));
+                }
+                5 => {
+                    let popped = match tag % 2 {
+                        0 => btree.pop_first(),
+                        _ => btree.pop_last(),
+                    };
+                    replay(&btree, Some(usize::from(popped.is_some())));
+                }
+                6 => {
+                    let had = btree.search(&(key, 0));
+                    btree.get_or_insert_with(&(key, 0), || (key, tag));
+                    replay(&btree, Some(usize::from(!had)));
+                }
+                7 => {
+                    // big batches go through a rebuild, small ones key by key
+                    let n = [3, 200][rng.below(2)];
+                    let mut keys: Vec<Tagged> =
+                        (0..n).map(|_| (rng.below(400) as u32, 0)).collect();
+                    let removed = btree.delete_many(&mut keys);
+                    replay(&btree, Some(removed));
+                }
+                8 => {
+                    let mut other = tagged_tree(2);
+                    for _ in 0..rng.below(150) {
+                        other.insert((rng.below(400) as u32, tag));
+                    }
+                    btree.extend_from_tree(other);
+                    replay(&btree, None);
+                    let mut ops: Vec<Op<Tagged>> = (0..rng.below(120))
+                        .map(|_| {
+                            let key = (rng.below(400) as u32, tag);
+                            match rng.below(2) {
+                                0 => Op::Insert(key),
+                                _ => Op::Delete(key),
+                            }
+                        })
+                        .collect();
+                    ops.sort_by_key(|op| op.key().0);
+                    btree.apply_batch(ops).unwrap();
+                    replay(&btree, None);
+                }
+                _ => {
+                    let drained = btree
+                        .drain_range((key, 0)..(key + rng.below(100) as u32, 0))
+                        .count();
+                    replay(&btree, Some(drained));
+                    let mut cursor = btree.cursor_mut();
+                    cursor.seek(&(key, 0));
+                    let removed = cursor.remove_current();
+                    replay(&btree, Some(usize::from(removed.is_some())));
+                }
+            }
+        }
+        assert_eq!(btree.validate(), Ok(()));
+
+        btree.clear_on_change();
+        btree.insert((1_000, 0));
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_explain_search() {
+        // [4]
+        // [2]         [6 8]
+        // [1] [3]     [5] [7] [9 10]
+        let mut btree = Btree::new(2);
+        for key in 1..=10 {
+            btree.insert(key);
+        }
+        let levels = |key| {
+            btree
+                .explain_search(&key)
+                .levels()
+                .iter()
+                .map(|level: &SearchLevel| {
+                    (
+                        level.key_count(),
+                        level.index(),
+                        level.comparisons(),
+                        level.matched(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(levels(4), vec![(1, 0, 1, true)]);
+        assert_eq!(levels(6), vec![(1, 1, 1, false), (2, 0, 2, true)]);
+        assert_eq!(
+            levels(10),
+            vec![(1, 1, 1, false), (2, 2, 2, false), (2, 1, 2, true)]
+        );
+        assert_eq!(
+            levels(0),
+            vec![(1, 0, 1, false), (1, 0, 1, false), (1, 0, 1, false)]
+        );
+
+        let explanation = btree.explain_search(&7);
+        assert!(explanation.found());
+        assert_eq!(explanation.depth(), 3);
+        assert_eq!(explanation.comparisons(), 4);
+        assert_eq!(
+            explanation.to_string(),
+            "0: 1 keys, down at 1, 1 cmp\n\
+             1: 2 keys, down at 1, 2 cmp\n\
+             2: 1 keys, match at 0, 1 cmp\n\
+             found after 3 levels, 4 cmp"
+        );
+        let explanation = btree.explain_search(&11);
+        assert!(!explanation.found());
+        assert!(explanation.to_string().starts_with("0: 1 keys, down at 1"));
+        assert!(
+            explanation
+                .to_string()
+                .contains("2: 2 keys, miss at 2, 2 cmp\n")
+        );
+
+        // the same descent search, path_to and depth_of make
+        for key in 0..=11 {
+            let explanation = btree.explain_search(&key);
+            assert_eq!(explanation.found(), btree.search(&key));
+            assert_eq!(explanation.depth(), btree.path_to(&key).len());
+            assert_eq!(
+                btree.depth_of(&key),
+                explanation.found().then(|| explanation.depth() - 1)
+            );
+        }
+
+        // a bigger degree is a shorter path
+        let wide = btree.with_degree(6).unwrap();
+        assert_eq!(wide.explain_search(&10).depth(), 1);
+        assert!(wide.explain_search(&10).found());
+
+        let empty: Btree<i32> = Btree::new(2);
+        let explanation = empty.explain_search(&1);
+        assert_eq!((explanation.depth(), explanation.comparisons()), (0, 0));
+        assert!(!explanation.found());
+        assert_eq!(explanation.to_string(), "not found after 0 levels, 0 cmp");
+    }
+
+    #[test]
+    fn test_traced_walkthrough() {
+        use TraceStep::*;
+
+        // the scenarios main.rs narrates
+        let mut btree = Btree::new(3);
+        for key in [1, 3, 7, 10, 16, 18, 23, 26, 30, 33, 35, 38, 41, 45] {
+            btree.insert(key);
+        }
+
+        // 3 sits in a leaf whose parent has to merge it with its sibling first
+        assert_eq!(
+            btree.delete_traced(&3),
+            vec![
+                Merge {
+                    depth: 1,
+                    left: 0,
+                    separator: 7
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![18, 30],
+                    child: 0
+                },
+                Found {
+                    depth: 1,
+                    node: vec![1, 3, 7, 10, 16],
+                    index: 1
+                },
+                Removed {
+                    depth: 1,
+                    node: vec![1, 3, 7, 10, 16],
+                    index: 1
+                },
+            ]
+        );
+        assert_eq!(
+            btree.delete_traced(&16),
+            vec![
+                Descend {
+                    depth: 0,
+                    node: vec![18, 30],
+                    child: 0
+                },
+                Found {
+                    depth: 1,
+                    node: vec![1, 7, 10, 16],
+                    index: 3
+                },
+                Removed {
+                    depth: 1,
+                    node: vec![1, 7, 10, 16],
+                    index: 3
+                },
+            ]
+        );
+
+        for key in [2, 4, 5, 6, 8, 9, 11, 12, 13, 14, 15, 17, 19, 20, 21, 22] {
+            btree.insert(key);
+        }
+
+        // borrowing
+        assert_eq!(
+            btree.delete_traced(&2),
+            vec![
+                BorrowRight {
+                    depth: 1,
+                    child: 0,
+                    separator: 10
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![13],
+                    child: 0
+                },
+                Merge {
+                    depth: 2,
+                    left: 0,
+                    separator: 4
+                },
+                Descend {
+                    depth: 1,
+                    node: vec![7, 10],
+                    child: 0
+                },
+                Found {
+                    depth: 2,
+                    node: vec![1, 2, 4, 5, 6],
+                    index: 1
+                },
+                Removed {
+                    depth: 2,
+                    node: vec![1, 2, 4, 5, 6],
+                    index: 1
+                },
+            ]
+        );
+        assert_eq!(
+            btree.delete_traced(&4)[0],
+            BorrowRight {
+                depth: 1,
+                child: 0,
+                separator: 13
+            }
+        );
+        assert_eq!(btree.delete_traced(&5).len(), 4);
+
+        // merging, down to the root giving up its last key
+        assert_eq!(
+            btree.delete_traced(&6)[1],
+            Merge {
+                depth: 2,
+                left: 0,
+                separator: 7
+            }
+        );
+        assert_eq!(
+            btree.delete_traced(&8),
+            vec![
+                Merge {
+                    depth: 1,
+                    left: 0,
+                    separator: 18
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![],
+                    child: 0
+                },
+                Descend {
+                    depth: 1,
+                    node: vec![10, 13, 18, 21, 30],
+                    child: 0
+                },
+                Found {
+                    depth: 2,
+                    node: vec![1, 7, 8, 9],
+                    index: 2
+                },
+                Removed {
+                    depth: 2,
+                    node: vec![1, 7, 8, 9],
+                    index: 2
+                },
+                RootShrank,
+            ]
+        );
+        assert_eq!(btree.height(), 2);
+        btree.delete_traced(&9);
+        btree.delete_traced(&11);
+
+        // a key of the root, both its children at the minimum
+        assert_eq!(
+            btree.delete_traced(&18),
+            vec![
+                Found {
+                    depth: 0,
+                    node: vec![10, 14, 18, 21, 30],
+                    index: 2
+                },
+                Merge {
+                    depth: 1,
+                    left: 2,
+                    separator: 18
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![10, 14, 21, 30],
+                    child: 2
+                },
+                Found {
+                    depth: 1,
+                    node: vec![15, 17, 18, 19, 20],
+                    index: 2
+                },
+                Removed {
+                    depth: 1,
+                    node: vec![15, 17, 18, 19, 20],
+                    index: 2
+                },
+            ]
+        );
+
+        // a missing key
+        assert_eq!(
+            btree.delete_traced(&100),
+            vec![
+                Descend {
+                    depth: 0,
+                    node: vec![10, 14, 21, 30],
+                    child: 4
+                },
+                NotFound {
+                    depth: 1,
+                    node: vec![33, 35, 38, 41, 45]
+                },
+            ]
+        );
+        assert!(btree.validate().is_ok());
+    }
+
+    #[test]
+    fn test_traced_split_and_replace() {
+        use TraceStep::*;
+
+        let mut btree = Btree::new(3);
+        for key in 1..=5 {
+            assert_eq!(
+                btree.insert_traced(key),
+                vec![Inserted {
+                    depth: 0,
+                    node: (1..=key).collect(),
+                    index: key as usize - 1
+                }]
+            );
+        }
+        let steps = btree.insert_traced(6);
+        assert_eq!(
+            steps,
+            vec![
+                RootGrew,
+                Split {
+                    depth: 1,
+                    node: vec![1, 2, 3, 4, 5],
+                    median: 3
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![3],
+                    child: 1
+                },
+                Inserted {
+                    depth: 1,
+                    node: vec![4, 5, 6],
+                    index: 2
+                },
+            ]
+        );
+        assert_eq!(
+            steps[1].to_string(),
+            "node [1 2 3 4 5] at depth 1 full, splitting: median 3 promoted"
+        );
+        assert_eq!(steps[2].to_string(), "descend into child 1 of node [3]");
+        assert_eq!(
+            btree.insert_traced(4),
+            vec![
+                Descend {
+                    depth: 0,
+                    node: vec![3],
+                    child: 1
+                },
+                Found {
+                    depth: 1,
+                    node: vec![4, 5, 6],
+                    index: 0
+                },
+            ]
+        );
+
+        // an internal key whose right child can spare one
+        let mut btree = Btree::new(3);
+        for key in [1, 3, 7, 10, 16, 18, 23, 26, 30, 33, 35, 38, 41, 45] {
+            btree.insert(key);
+        }
+        assert_eq!(
+            btree.delete_traced(&30),
+            vec![
+                Found {
+                    depth: 0,
+                    node: vec![7, 18, 30],
+                    index: 2
+                },
+                ReplaceWithSuccessor {
+                    depth: 0,
+                    node: vec![7, 18, 30],
+                    index: 2
+                },
+                Descend {
+                    depth: 0,
+                    node: vec![7, 18, 30],
+                    child: 3
+                },
+                Removed {
+                    depth: 1,
+                    node: vec![33, 35, 38, 41, 45],
+                    index: 0
+                },
+            ]
+        );
+        btree.insert(17);
+        let steps = btree.delete_traced(&18);
+        assert!(steps.contains(&ReplaceWithPredecessor {
+            depth: 0,
+            node: vec![7, 18, 33],
+            index: 1
+        }));
+        assert_eq!(
+            steps[1].to_string(),
+            "18 is in internal node [7 18 33], replacing it with its predecessor"
+        );
+    }
+
+    #[test]
+    fn test_traced_matches_untraced() {
+        let keys: Vec<u32> = (0..500).map(|i| (i * 7919) % 1000).collect();
+        let mut traced = Btree::new(2);
+        let mut plain = Btree::new(2);
+        for &key in &keys {
+            traced.insert_traced(key);
+            plain.insert(key);
+        }
+        assert!(traced.structurally_eq(&plain));
+        for &key in keys.iter().step_by(3) {
+            let steps = traced.delete_traced(&key);
+            assert!(matches!(
+                steps.last(),
+                Some(TraceStep::Removed { .. } | TraceStep::RootShrank)
+            ));
+            plain.delete(&key);
+            assert!(traced.structurally_eq(&plain));
+        }
+        assert!(traced.validate().is_ok());
+        assert!(matches!(
+            traced.delete_traced(&5000)[..],
+            [.., TraceStep::NotFound { .. }]
+        ));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics() {
+        use btree::btree::Metrics;
+
+        // the build-up from the demo: one root split and two leaf splits
+        let mut btree = Btree::new(3);
+        for key in [1, 3, 7, 10, 16, 18, 23, 26, 30, 33, 35, 38, 41, 45] {
+            btree.insert(key);
+        }
+        let metrics = btree.metrics();
+        assert_eq!(metrics.splits, 3);
+        assert_eq!(btree.node_count(), 5);
+        assert_eq!(
+            (metrics.merges, metrics.borrows_left, metrics.borrows_right),
+            (0, 0, 0)
+        );
+        assert!(metrics.comparisons > 0);
+
+        btree.reset_metrics();
+        assert_eq!(btree.metrics(), Metrics::default());
+        // root [7, 18, 30] over four leaves, a lookup goes through two nodes
+        assert!(btree.search(&26));
+        assert_eq!(btree.metrics().nodes_visited, 2);
+        assert!(btree.search(&18));
+        assert_eq!(btree.metrics().nodes_visited, 3);
+
+        // root [2, 4] over [1], [3] and [5, 6, 7]
+        let mut btree = Btree::new(2);
+        for key in 1..=7 {
+            btree.insert(key);
+        }
+        btree.reset_metrics();
+        let structural = |btree: &Btree<i32>| {
+            let metrics = btree.metrics();
+            (metrics.merges, metrics.borrows_left, metrics.borrows_right)
+        };
+        // [1] has no spare key and neither has [3]: they merge around 2
+        btree.delete(&1);
+        assert_eq!(structural(&btree), (1, 0, 0));
+        // root [4] over [2, 3] and [5, 6, 7], [2, 3] can give a key away
+        btree.delete(&2);
+        assert_eq!(structural(&btree), (1, 0, 0));
+        // [3] takes 4 from the root, which takes 5 from [5, 6, 7]
+        btree.delete(&3);
+        assert_eq!(structural(&btree), (1, 0, 1));
+        // root [5] over [4] and [6, 7], now the other way around
+        btree.insert(3);
+        btree.delete(&6);
+        btree.delete(&7);
+        assert_eq!(structural(&btree), (1, 1, 1));
+        assert_eq!(btree.validate(), Ok(()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_events() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit as FieldVisit};
+        use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+        // every event as "message depth=d", in the order they were emitted
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        struct Fields(String, Option<u64>);
+
+        impl FieldVisit for Fields {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                if field.name() == "depth" {
+                    self.1 = Some(value);
+                }
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl<S: tracing::Subscriber> Layer<S> for Recorder {
+            fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+                let mut fields = Fields(String::new(), None);
+                event.record(&mut fields);
+                let line = format!("{} depth={}", fields.0, fields.1.unwrap());
+                self.0.lock().unwrap().push(line);
+            }
+        }
+
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut btree = Btree::new(2);
+            for key in 1..=7 {
+                btree.insert(key);
+            }
+            // same script as the metrics test: merge, nothing, borrow right
+            for key in [1, 2, 3] {
+                btree.delete(&key);
+            }
+            // root [5] over [4] and [6, 7], grow it back to three levels
+            for key in 8..=13 {
+                btree.insert(key);
+            }
+        });
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                // 4 splits the root, 6 splits [3, 4, 5]
+                "split depth=0",
+                "split depth=1",
+                "merge depth=1",
+                "borrow right depth=1",
+                // 9 and 11 split full leaves, 13 finds the root full first
+                // and then its leaf, one level further down than before
+                "split depth=1",
+                "split depth=1",
+                "split depth=0",
+                "split depth=2",
+            ]
+        );
+    }
 }
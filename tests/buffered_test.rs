@@ -0,0 +1,153 @@
+mod common;
+
+use btree::buffered::BufferedBtree;
+use btree::compare::Desc;
+use common::Rng;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // from both ends in turn until they meet, put back in ascending order
+    fn both_ends(tree: &BufferedBtree<u64>) -> Vec<u64> {
+        let mut iter = tree.iter();
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        while let Some(&key) = iter.next() {
+            front.push(key);
+            match iter.next_back() {
+                Some(&key) => back.push(key),
+                None => break,
+            }
+        }
+        front.extend(back.into_iter().rev());
+        front
+    }
+
+    // random writes and reads against std's BTreeSet, checking the whole
+    // tree every so often while messages are still waiting in buffers
+    fn run(seed: u64, degree: usize, capacity: usize, steps: usize) {
+        let mut rng = Rng(seed);
+        let mut tree = BufferedBtree::with_buffer_capacity(degree, capacity);
+        let mut model = BTreeSet::new();
+        for step in 0..steps {
+            let key = rng.next() % 500;
+            match rng.next() % 8 {
+                0..=3 => {
+                    tree.insert(key);
+                    model.insert(key);
+                }
+                4..=5 => {
+                    tree.delete(key);
+                    model.remove(&key);
+                }
+                _ => assert_eq!(
+                    tree.search(&key),
+                    model.contains(&key),
+                    "search at step {}",
+                    step
+                ),
+            }
+            assert_eq!(tree.is_empty(), model.is_empty());
+            if tree.pending() == 0 {
+                assert_eq!(tree.len(), model.len(), "step {}", step);
+            }
+            if step % 97 == 0 {
+                assert_eq!(tree.validate(), Ok(()), "step {}", step);
+                assert!(tree.iter().eq(model.iter()), "step {}", step);
+                assert!(both_ends(&tree).iter().eq(model.iter()), "step {}", step);
+            }
+        }
+        assert!(tree.iter().rev().eq(model.iter().rev()));
+        tree.flush_all();
+        assert_eq!(tree.pending(), 0);
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.iter().eq(model.iter()));
+        assert_eq!(tree.len(), model.len());
+    }
+
+    #[test]
+    fn test_model() {
+        for (seed, degree, capacity) in [(1, 2, 1), (7, 2, 8), (42, 3, 4), (99, 4, 16), (5, 8, 32)]
+        {
+            run(seed, degree, capacity, 5_000);
+        }
+    }
+
+    #[test]
+    fn test_writes_wait_in_buffers() {
+        let mut tree = BufferedBtree::new(3);
+        for key in 0..1_000 {
+            tree.insert(key);
+        }
+        assert!(tree.height() > 1);
+        assert!(tree.pending() > 0);
+        // buffered keys are found before they reach a leaf
+        assert!((0..1_000).all(|key| tree.search(&key)));
+        for key in (0..1_000).step_by(2) {
+            tree.delete(key);
+        }
+        assert!(tree.pending() > 0);
+        assert!(!tree.search(&0));
+        tree.flush_all();
+        assert_eq!(tree.pending(), 0);
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), 500);
+        assert!(tree.iter().copied().eq((1..1_000).step_by(2)));
+        // deleting everything shrinks it back to a single leaf
+        for key in (1..1_000).step_by(2) {
+            tree.delete(key);
+        }
+        tree.flush_all();
+        assert!(tree.is_empty());
+        assert_eq!(tree.height(), 1);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_len_settles_at_flush() {
+        let mut tree = BufferedBtree::new(3);
+        // writes that turn out to change nothing once they reach the leaves
+        for key in (0..300).chain(0..300) {
+            tree.insert(key);
+        }
+        for key in 1_000..1_100 {
+            tree.delete(key);
+        }
+        assert!(tree.len() <= 300);
+        tree.flush_all();
+        assert_eq!(tree.len(), 300);
+        for key in (0..300).step_by(3) {
+            tree.delete(key);
+            tree.delete(key);
+        }
+        tree.flush_all();
+        assert_eq!(tree.len(), 200);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_iter_meets_in_the_middle() {
+        let mut tree = BufferedBtree::with_buffer_capacity(2, 8);
+        for key in 0..200 {
+            tree.insert(key);
+        }
+        assert!(tree.pending() > 0);
+        let mut iter = tree.iter();
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&199));
+        let rest: Vec<u64> = iter.copied().collect();
+        assert!(rest.into_iter().eq(1..199));
+        assert!(both_ends(&tree).into_iter().eq(0..200));
+    }
+
+    #[test]
+    fn test_comparator() {
+        let mut tree = BufferedBtree::with_comparator(2, 4, Desc);
+        for key in [5, 1, 9, 3, 7, 2, 8] {
+            tree.insert(key);
+        }
+        assert!(tree.iter().copied().eq([9, 8, 7, 5, 3, 2, 1]));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+}
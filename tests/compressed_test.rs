@@ -0,0 +1,142 @@
+use btree::btree::{Btree, CompressedBtree};
+use std::ops::Bound;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // urls the way a crawler would see them: a few hosts, deep shared paths
+    fn urls() -> Vec<String> {
+        let hosts = [
+            "https://www.example.com",
+            "https://docs.example.org",
+            "http://cdn.example.net",
+        ];
+        let sections = [
+            "products/catalog/electronics",
+            "blog/2024/engineering",
+            "static/assets/images",
+        ];
+        let mut urls = Vec::new();
+        for host in hosts {
+            for section in sections {
+                for item in 0..400 {
+                    urls.push(format!(
+                        "{}/{}/item-{:04}?ref=homepage",
+                        host,
+                        section,
+                        item * 7 % 400
+                    ));
+                }
+            }
+        }
+        urls
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let urls = urls();
+        for degree in [2, 3, 16] {
+            let mut compressed = CompressedBtree::new(degree);
+            let mut plain = Btree::new(degree);
+            for url in &urls {
+                assert_eq!(compressed.insert(url.clone()), plain.insert(url.clone()));
+            }
+            assert_eq!(compressed.validate(), Ok(()));
+            assert_eq!(compressed.len(), plain.len());
+            assert!(compressed.iter().eq(plain.iter().cloned()));
+            assert_eq!(compressed.first().as_ref(), plain.first());
+            assert_eq!(compressed.last().as_ref(), plain.last());
+
+            for url in urls.iter().step_by(7) {
+                assert!(compressed.contains(url));
+                assert!(!compressed.contains(&format!("{}#top", url)));
+                assert!(!compressed.contains(&url[..url.len() - 1]));
+            }
+            assert!(!compressed.contains("https://"));
+            assert!(!compressed.contains(""));
+
+            let from = "https://docs.example.org/blog".to_string();
+            let to = "https://docs.example.org/products/catalog/electronics/item-0100?ref=homepage"
+                .to_string();
+            assert!(
+                compressed
+                    .range(from.clone()..to.clone())
+                    .eq(plain.range(from.clone()..to.clone()).cloned())
+            );
+            let bounds = (
+                Bound::Excluded(to.clone()),
+                Bound::Included(from.clone() + "~"),
+            );
+            assert!(
+                compressed
+                    .range(bounds.clone())
+                    .eq(plain.range(bounds).cloned())
+            );
+            assert!(compressed.range(to.clone()..=to.clone()).eq([to.clone()]));
+
+            // remove every other key, the prefixes have to follow the merges
+            for url in urls.iter().step_by(2) {
+                assert_eq!(compressed.delete(url), plain.delete(url));
+            }
+            assert_eq!(compressed.validate(), Ok(()));
+            assert!(compressed.iter().eq(plain.iter().cloned()));
+            for url in &urls {
+                assert_eq!(compressed.delete(url), plain.delete(url));
+            }
+            assert!(compressed.is_empty());
+            assert_eq!(compressed.memory_usage(), 0);
+        }
+    }
+
+    #[test]
+    fn test_compressed_memory() {
+        let urls = urls();
+        let mut compressed = CompressedBtree::new(16);
+        let mut plain = Btree::new(16);
+        for url in &urls {
+            compressed.insert(url.clone());
+            plain.insert(url.clone());
+        }
+        // the plain tree's figure leaves out the bytes the strings own
+        let plain_total =
+            plain.memory_usage() + plain.iter().map(|url| url.capacity()).sum::<usize>();
+        let compressed_total = compressed.memory_usage();
+        assert!(
+            compressed_total * 2 < plain_total,
+            "compressed {} bytes, plain {} bytes",
+            compressed_total,
+            plain_total
+        );
+    }
+
+    #[test]
+    fn test_compressed_bytes() {
+        // keys that are prefixes of each other and bytes that aren't utf-8
+        let mut tree = CompressedBtree::new(2);
+        let keys: Vec<Vec<u8>> = vec![
+            vec![],
+            vec![0x00],
+            vec![0x00, 0x00],
+            vec![0x00, 0xff],
+            vec![0x01],
+            vec![0xff],
+            vec![0xff, 0xff],
+            vec![0xff, 0xff, 0x00],
+        ];
+        for key in keys.iter().rev() {
+            assert!(tree.insert(key.clone()));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(!tree.insert(vec![0xff, 0xff]));
+        assert!(tree.iter().eq(keys.iter().cloned()));
+        assert!(tree.contains(&[][..]));
+        assert!(!tree.contains(&[0xff, 0xfe][..]));
+        for key in &keys {
+            assert!(tree.delete(key));
+            assert!(!tree.delete(key));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.is_empty());
+    }
+}
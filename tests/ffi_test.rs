@@ -0,0 +1,138 @@
+mod common;
+
+#[cfg(all(test, feature = "ffi"))]
+mod tests {
+    use crate::common::Rng;
+    use btree::ffi::*;
+    use std::collections::BTreeSet;
+    use std::ptr;
+
+    #[test]
+    fn test_drive_ffi() {
+        assert!(btree_new(1).is_null());
+
+        let handle = btree_new(3);
+        assert!(!handle.is_null());
+        let mut model = BTreeSet::new();
+        let mut rng = Rng(0x5851f42d4c957f2d);
+        for _ in 0..2_000 {
+            let key = rng.below(500) as i64 - 250;
+            unsafe {
+                match rng.below(3) {
+                    0 => assert_eq!(btree_delete(handle, key), model.remove(&key)),
+                    _ => assert_eq!(btree_insert(handle, key), model.insert(key)),
+                }
+                assert_eq!(btree_contains(handle, key), model.contains(&key));
+            }
+        }
+        unsafe {
+            assert_eq!(btree_len(handle), model.len());
+            assert!(btree_contains(handle, *model.first().unwrap()));
+            assert!(!btree_contains(handle, 1_000));
+        }
+
+        // room for every key, and for only the smallest few
+        let mut out = vec![0i64; model.len() + 5];
+        let written = unsafe { btree_iter_collect(handle, out.as_mut_ptr(), out.len()) };
+        assert_eq!(written, model.len());
+        assert!(out[..written].iter().eq(model.iter()));
+        let mut few = [0i64; 4];
+        let written = unsafe { btree_iter_collect(handle, few.as_mut_ptr(), few.len()) };
+        assert_eq!(written, 4);
+        assert!(few.iter().eq(model.iter().take(4)));
+        assert_eq!(unsafe { btree_iter_collect(handle, ptr::null_mut(), 8) }, 0);
+
+        unsafe { btree_free(handle) };
+    }
+
+    #[test]
+    fn test_null_handles() {
+        let null = ptr::null_mut();
+        let mut out = [7i64; 2];
+        unsafe {
+            assert!(!btree_insert(null, 1));
+            assert!(!btree_delete(null, 1));
+            assert!(!btree_contains(null, 1));
+            assert_eq!(btree_len(null), 0);
+            assert_eq!(btree_iter_collect(null, out.as_mut_ptr(), out.len()), 0);
+            btree_free(null);
+        }
+        assert_eq!(out, [7, 7]);
+
+        // an empty tree writes nothing either
+        let handle = btree_new(2);
+        unsafe {
+            assert_eq!(btree_iter_collect(handle, out.as_mut_ptr(), out.len()), 0);
+            assert_eq!(btree_len(handle), 0);
+            btree_free(handle);
+        }
+    }
+
+    // the C type cbindgen writes for a rust type of the api
+    fn c_type(rust: &str) -> String {
+        let (pointer, pointee) = match rust.split_once(' ') {
+            Some(("*mut", pointee)) => ("", pointee),
+            Some(("*const", pointee)) => ("const ", pointee),
+            _ => {
+                return match rust {
+                    "usize" => "uintptr_t",
+                    "i64" => "int64_t",
+                    "bool" => "bool",
+                    "BtreeHandle" => "BtreeHandle",
+                    other => panic!("no C type for {}", other),
+                }
+                .to_string();
+            }
+        };
+        format!("{}{} *", pointer, c_type(pointee))
+    }
+
+    #[test]
+    fn test_header_matches() {
+        let source = include_str!("../src/ffi.rs");
+        let header = include_str!("../include/btree.h");
+        assert!(header.contains("typedef struct BtreeHandle BtreeHandle;"));
+
+        // every exported function, declared the way cbindgen would
+        let mut declared = 0;
+        let mut rest = source;
+        while let Some(at) = rest.find("extern \"C\" fn ") {
+            rest = &rest[at + "extern \"C\" fn ".len()..];
+            let (name, after) = rest.split_once('(').unwrap();
+            let (args, after) = after.split_once(')').unwrap();
+            let returns = after
+                .split_once('{')
+                .unwrap()
+                .0
+                .trim()
+                .strip_prefix("-> ")
+                .map_or("void".to_string(), c_type);
+            let args: Vec<String> = args
+                .split(',')
+                .map(str::trim)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| {
+                    let (arg, rust) = arg.split_once(": ").unwrap();
+                    let c = c_type(rust);
+                    match c.ends_with('*') {
+                        true => format!("{}{}", c, arg),
+                        false => format!("{} {}", c, arg),
+                    }
+                })
+                .collect();
+            let returns = match returns.ends_with('*') {
+                true => returns,
+                false => returns + " ",
+            };
+            let declaration = format!("{}{}({});", returns, name, args.join(", "));
+            assert!(
+                header.contains(&declaration),
+                "{} is not in the header",
+                declaration
+            );
+            declared += 1;
+        }
+        assert_eq!(declared, 7);
+        assert_eq!(header.matches(");").count(), declared);
+    }
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use btree::btree::{Btree, LazyBtree};
+use common::Rng;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rounds of deleting a random share of the keys and inserting them again,
+    // on a plain Btree and a LazyBtree side by side with std's BTreeSet
+    fn churn(mut lazy: LazyBtree<u64>, seed: u64) {
+        let mut rng = Rng(seed);
+        let mut eager = Btree::new(3);
+        let mut model = BTreeSet::new();
+        for key in 0..1_000 {
+            eager.insert(key);
+            lazy.insert(key);
+            model.insert(key);
+        }
+        for round in 0..20 {
+            let burst: Vec<u64> = (0..300).map(|_| rng.next() % 1_200).collect();
+            for key in &burst {
+                let removed = model.remove(key);
+                assert_eq!(eager.delete(key), removed, "round {}", round);
+                assert_eq!(lazy.delete(key), removed, "round {}", round);
+            }
+            assert_eq!(lazy.len(), model.len());
+            assert!(lazy.iter().eq(model.iter()));
+            for key in burst.iter().take(200) {
+                let inserted = model.insert(*key);
+                assert_eq!(eager.insert(*key), inserted, "round {}", round);
+                assert_eq!(lazy.insert(*key), inserted, "round {}", round);
+            }
+            for key in 0..1_200 {
+                assert_eq!(lazy.search(&key), model.contains(&key), "round {}", round);
+            }
+            assert!(eager.iter().eq(model.iter()));
+            assert!(lazy.iter().rev().eq(model.iter().rev()));
+            assert!(lazy.range(100..=400).eq(model.range(100..=400)));
+            assert_eq!(lazy.validate(), Ok(()), "round {}", round);
+        }
+        lazy.purge();
+        assert_eq!(lazy.tombstones(), 0);
+        assert_eq!(lazy.validate(), Ok(()));
+        assert!(lazy.iter().eq(model.iter()));
+    }
+
+    #[test]
+    fn test_churn() {
+        churn(LazyBtree::new(3), 7);
+        churn(LazyBtree::with_purge_threshold(3, 0.25), 7);
+        churn(LazyBtree::with_purge_threshold(2, 1.0), 42);
+    }
+
+    #[test]
+    fn test_tombstones() {
+        let mut tree = LazyBtree::new(2);
+        for key in 0..10 {
+            tree.insert(key);
+        }
+        assert!(tree.delete(&3));
+        assert!(!tree.delete(&3));
+        assert!(!tree.search(&3));
+        assert_eq!(tree.get(&4), Some(&4));
+        assert_eq!((tree.len(), tree.tombstones()), (9, 1));
+        // reinserting brings the slot back instead of adding one
+        assert!(tree.insert(3));
+        assert!(!tree.insert(3));
+        assert_eq!((tree.len(), tree.tombstones()), (10, 0));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let mut tree = LazyBtree::new(3);
+        for key in 0..1_000 {
+            tree.insert(key);
+        }
+        for key in (0..1_000).step_by(2) {
+            tree.delete(&key);
+        }
+        // readers on several threads at once need the tree to be Sync
+        let tree = &tree;
+        std::thread::scope(|scope| {
+            for start in 0..4 {
+                scope.spawn(move || {
+                    for key in (start..1_000).step_by(4) {
+                        assert_eq!(tree.search(&key), key % 2 == 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(tree.len(), 500);
+    }
+
+    #[test]
+    fn test_purge_reclaims_memory() {
+        let mut tree = LazyBtree::new(4);
+        for key in 0..10_000 {
+            tree.insert(key);
+        }
+        let full = tree.memory_usage();
+        for key in 0..9_000 {
+            tree.delete(&key);
+        }
+        // the dead keys still hold their slots
+        assert_eq!(tree.memory_usage(), full);
+        assert_eq!(tree.purge(), 9_000);
+        assert_eq!(tree.purge(), 0);
+        assert!(tree.memory_usage() < full / 5);
+        assert_eq!(tree.len(), 1_000);
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.iter().copied().eq(9_000..10_000));
+
+        // with a threshold the tree purges on its own
+        let mut tree = LazyBtree::with_purge_threshold(4, 0.5);
+        for key in 0..100 {
+            tree.insert(key);
+        }
+        for key in 0..49 {
+            tree.delete(&key);
+        }
+        assert_eq!(tree.tombstones(), 49);
+        tree.delete(&49);
+        assert_eq!(tree.tombstones(), 0);
+        assert_eq!(tree.len(), 50);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,320 @@
+mod common;
+
+use btree::btree::{Btree, BtreeMap};
+use btree::compare::ByKey;
+use common::Rng;
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model() {
+        for (seed, degree) in [(1, 2), (7, 3), (42, 5)] {
+            let mut rng = Rng(seed);
+            let mut map = BtreeMap::new(degree);
+            let mut model = BTreeMap::new();
+            for step in 0..5_000 {
+                let key = rng.next() % 300;
+                match rng.next() % 4 {
+                    0 | 1 => assert_eq!(map.insert(key, step), model.insert(key, step)),
+                    2 => assert_eq!(map.remove(&key), model.remove(&key)),
+                    _ => {
+                        if let Some(mut value) = map.get_mut(&key) {
+                            *value += 1;
+                        }
+                        if let Some(value) = model.get_mut(&key) {
+                            *value += 1;
+                        }
+                    }
+                }
+                assert_eq!(map.get(&key), model.get(&key));
+                assert_eq!(map.len(), model.len());
+            }
+            assert_eq!(map.validate(), Ok(()));
+            assert!(map.iter().eq(model.iter()));
+            assert!(map.range(50..150).rev().eq(model.range(50..150).rev()));
+            assert_eq!(map.first_key_value(), model.first_key_value());
+            assert_eq!(map.last_key_value(), model.last_key_value());
+        }
+    }
+
+    #[test]
+    fn test_get_key_value_returns_stored_key() {
+        let mut map = BtreeMap::new(2);
+        for i in 0..50 {
+            let mut key = String::with_capacity(100 + i);
+            key.push_str(&format!("key{:02}", i));
+            map.insert(key, i);
+        }
+        // an equal key with another capacity doesn't replace the stored one
+        assert_eq!(map.insert(String::from("key07"), 700), Some(7));
+        let probe = String::from("key07");
+        let (key, value) = map.get_key_value(&probe).unwrap();
+        assert_eq!((key.as_str(), *value), ("key07", 700));
+        assert_eq!(key.capacity(), 107);
+        assert_eq!(map.get_key_value("key99"), None);
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        // a set built by the same inserts has the same shape, it tells which
+        // keys sit in internal nodes
+        let mut shape = Btree::new(2);
+        let mut map = BtreeMap::new(2);
+        let mut model = BTreeMap::new();
+        for i in 0..200u64 {
+            let key = (i * 73) % 200;
+            shape.insert(key);
+            map.insert(key, key * 10 + 1);
+            model.insert(key, key * 10 + 1);
+        }
+        let height = shape.height();
+        let (internal, leaf): (Vec<u64>, Vec<u64>) = shape
+            .iter()
+            .partition(|key| shape.depth_of(*key).unwrap() + 1 < height);
+        assert!(!internal.is_empty() && !leaf.is_empty());
+
+        for key in internal.iter().chain(&leaf).step_by(3) {
+            let removed = map.remove_entry(key);
+            assert_eq!(removed, Some((*key, key * 10 + 1)));
+            assert_eq!(removed, model.remove_entry(key));
+            assert_eq!(map.remove_entry(key), None);
+            assert_eq!(map.validate(), Ok(()));
+        }
+        assert!(map.iter().eq(model.iter()));
+        // the stored key comes back, not the probe
+        let mut map = BtreeMap::new(2);
+        map.insert(String::with_capacity(64) + "a", 1);
+        let (key, value) = map.remove_entry("a").unwrap();
+        assert_eq!((key.capacity(), value), (64, 1));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_pops() {
+        let mut rng = Rng(99);
+        let mut map = BtreeMap::new(2);
+        let mut model = BTreeMap::new();
+        for _ in 0..1_000 {
+            let deadline = rng.next() % 5_000;
+            map.insert(deadline, format!("timer {}", deadline));
+            model.insert(deadline, format!("timer {}", deadline));
+        }
+        // drained from both ends until the two meet
+        for _ in 0..100 {
+            assert_eq!(map.pop_last(), model.pop_last());
+        }
+        assert_eq!(map.validate(), Ok(()));
+        let mut drained = Vec::new();
+        while let Some((deadline, timer)) = map.pop_first() {
+            assert_eq!(timer, format!("timer {}", deadline));
+            drained.push(deadline);
+            if drained.len() % 50 == 0 {
+                assert_eq!(map.validate(), Ok(()));
+            }
+        }
+        assert!(drained.iter().eq(model.keys()));
+        assert!(map.is_empty());
+        assert_eq!(map.first_key_value(), None);
+        assert_eq!(map.pop_last(), None);
+    }
+
+    #[test]
+    fn test_range_mut() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+        let mut map = BtreeMap::new(2);
+        let mut model = BTreeMap::new();
+        for key in 0..300u64 {
+            map.insert(key * 2, 0);
+            model.insert(key * 2, 0);
+        }
+        let ranges = [
+            (Included(100), Excluded(200)),
+            (Excluded(100), Included(200)),
+            (Included(101), Included(199)),
+            (Unbounded, Excluded(7)),
+            (Excluded(590), Unbounded),
+            (Included(1_000), Unbounded),
+            (Unbounded, Unbounded),
+        ];
+        for (version, range) in (1..).zip(ranges) {
+            let mut previous = None;
+            for (key, mut value) in map.range_mut(range) {
+                assert!(previous < Some(*key));
+                previous = Some(*key);
+                *value = version;
+            }
+            for (_, value) in model.range_mut(range) {
+                *value = version;
+            }
+            assert!(map.iter().eq(model.iter()));
+        }
+        // a range with nothing in it, and one whose start is past its end
+        assert_eq!(map.range_mut(101..102).count(), 0);
+        assert_eq!(map.range_mut((Excluded(300), Excluded(200))).count(), 0);
+        assert_eq!(BtreeMap::<u64, u64>::new(2).range_mut(..).count(), 0);
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_std_map_conversions() {
+        let model: BTreeMap<u32, String> = (0..10_000).map(|key| (key, key.to_string())).collect();
+        let map = BtreeMap::from(model.clone());
+        assert_eq!(map.len(), model.len());
+        assert_eq!(map.validate(), Ok(()));
+        assert!(map.iter().eq(model.iter()));
+        assert_eq!(BTreeMap::from(map), model);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: u32,
+        name: &'static str,
+    }
+
+    #[test]
+    fn test_get_key_value_ignored_fields() {
+        let mut map = BtreeMap::with_comparator(2, ByKey::new(|user: &User| user.id));
+        for (id, name) in [(3, "carol"), (1, "alice"), (2, "bob"), (4, "dave")] {
+            map.insert(User { id, name }, id * 10);
+        }
+        let probe = User { id: 2, name: "" };
+        assert_eq!(
+            map.get_key_value(&probe),
+            Some((&User { id: 2, name: "bob" }, &20))
+        );
+        assert!(!map.contains_key(&User { id: 5, name: "bob" }));
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut map = BtreeMap::new(2);
+        map.insert(1, "one");
+        map.insert(2, "two");
+        let stamp = map.generation();
+        assert_eq!(map.get(&1), Some(&"one"));
+        assert_eq!(map.iter().count(), 2);
+        assert_eq!(map.generation(), stamp);
+
+        *map.get_mut(&1).unwrap() = "uno";
+        let changed = map.generation();
+        assert_ne!(changed, stamp);
+        for (_, mut value) in map.range_mut(2..) {
+            *value = "dos";
+        }
+        assert_ne!(map.generation(), changed);
+        map.insert(1, "eins");
+        assert_ne!(map.generation(), changed);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut map = BtreeMap::new(2);
+        for key in 0..1_000 {
+            map.insert(key, key * 10);
+        }
+        let stamp = map.generation();
+        map.retain(|_, _| true);
+        assert_eq!(map.generation(), stamp);
+
+        let mut seen = Vec::new();
+        map.retain(|key, value| {
+            seen.push(*key);
+            *value % 30 != 0
+        });
+        assert!(seen.into_iter().eq(0..1_000));
+        assert_eq!(map.len(), 666);
+        assert!(
+            map.iter()
+                .all(|(key, value)| key % 3 != 0 && *value == key * 10)
+        );
+        assert_eq!(map.validate(), Ok(()));
+
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_weigher() {
+        let weight = |map: &BtreeMap<u64, String>| -> usize {
+            map.iter().map(|(_, value)| value.len()).sum()
+        };
+        for (seed, degree) in [(3, 2), (11, 4)] {
+            let mut rng = Rng(seed);
+            let mut map = BtreeMap::with_weigher(degree, |_: &u64, value: &String| value.len());
+            for step in 0..5_000 {
+                let key = rng.next() % 200;
+                match rng.next() % 8 {
+                    0..=2 => {
+                        let value = "x".repeat((rng.next() % 40) as usize);
+                        map.insert(key, value);
+                    }
+                    3 => {
+                        map.remove(&key);
+                    }
+                    4 => {
+                        map.remove_entry(&key);
+                    }
+                    5 => {
+                        map.pop_first();
+                    }
+                    6 => {
+                        map.pop_last();
+                    }
+                    _ if step % 50 == 0 => {
+                        let cut = rng.next() % 40;
+                        map.retain(|_, value| value.len() as u64 > cut);
+                    }
+                    _ if step % 3 == 0 => {
+                        // values changed in place are reweighed as they're let go
+                        let grow = (rng.next() % 5) as usize;
+                        for (_, mut value) in map.range_mut(key..key + 20) {
+                            value.push_str(&"y".repeat(grow));
+                        }
+                    }
+                    _ => {
+                        if let Some(mut value) = map.get_mut(&key) {
+                            let keep = value.len() / 2;
+                            value.truncate(keep);
+                        }
+                    }
+                }
+                assert_eq!(map.total_weight(), weight(&map), "step {}", step);
+                assert_eq!(
+                    map.weight(&key),
+                    map.get(&key).map(String::len),
+                    "step {}",
+                    step
+                );
+            }
+        }
+
+        // without a weigher every entry weighs 1
+        let mut map: BtreeMap<u64, String> = BtreeMap::new(3);
+        for key in 0..100 {
+            map.insert(key, key.to_string());
+        }
+        map.insert(5, "five".to_string());
+        assert_eq!(map.total_weight(), 100);
+        assert_eq!(map.weight(&5), Some(1));
+        assert_eq!(map.weight(&100), None);
+        map.get_mut(&5).unwrap().push_str("teen");
+        assert_eq!(map.total_weight(), 100);
+        map.retain(|key, _| key % 2 == 0);
+        assert_eq!(map.total_weight(), 50);
+        map.set_weigher(|_, value: &String| value.len());
+        assert_eq!(map.total_weight(), weight(&map));
+        // a guard kept around is only weighed once it goes
+        let before = map.total_weight();
+        let mut value = map.get_mut(&2).unwrap();
+        value.push_str("22");
+        drop(value);
+        assert_eq!(map.total_weight(), before + 2);
+        let map = BtreeMap::from(BTreeMap::from([(1, "a"), (2, "b")]));
+        assert_eq!(map.total_weight(), 2);
+    }
+}
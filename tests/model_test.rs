@@ -0,0 +1,143 @@
+mod common;
+
+use btree::btree::Btree;
+use common::Rng;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+     ** model based testing: the same random operations go to a Btree and to
+     ** std's BTreeSet, and everything observable has to match after every step
+     ** a small key range makes inserts collide and deletes hit, a large one
+     ** makes the tree grow. every failure message carries the seed and the step
+     ** so a case can be replayed by running just that config
+     */
+    struct Config {
+        seed: u64,
+        degree: usize,
+        key_range: usize,
+        steps: usize,
+        validate_every: usize,
+    }
+
+    fn bound(rng: &mut Rng, key_range: usize) -> Bound<u32> {
+        let key = rng.below(key_range + 2) as u32;
+        match rng.below(3) {
+            0 => Bound::Included(key),
+            1 => Bound::Excluded(key),
+            _ => Bound::Unbounded,
+        }
+    }
+
+    fn run(config: Config) {
+        let mut rng = Rng(config.seed);
+        let mut tree = Btree::new(config.degree);
+        let mut model = BTreeSet::new();
+
+        for step in 0..config.steps {
+            let at = || format!("seed {:#x}, step {}", config.seed, step);
+            let key = rng.below(config.key_range) as u32;
+            match rng.below(10) {
+                0..=3 => assert_eq!(tree.insert(key), model.insert(key), "insert: {}", at()),
+                4..=6 => assert_eq!(tree.delete(&key), model.remove(&key), "delete: {}", at()),
+                7 => {
+                    assert_eq!(tree.search(&key), model.contains(&key), "search: {}", at());
+                    assert_eq!(tree.get(&key), model.get(&key), "get: {}", at());
+                }
+                8 => {
+                    let bounds = (
+                        bound(&mut rng, config.key_range),
+                        bound(&mut rng, config.key_range),
+                    );
+                    // std panics on start > end, the tree gives an empty range
+                    let valid = match bounds {
+                        (
+                            Bound::Included(start) | Bound::Excluded(start),
+                            Bound::Included(end) | Bound::Excluded(end),
+                        ) => {
+                            start < end
+                                || (start == end
+                                    && matches!(bounds, (Bound::Included(_), Bound::Included(_))))
+                        }
+                        _ => true,
+                    };
+                    if valid {
+                        assert!(
+                            tree.range(bounds).take(50).eq(model.range(bounds).take(50)),
+                            "range: {}",
+                            at()
+                        );
+                        assert!(
+                            tree.range(bounds)
+                                .rev()
+                                .take(50)
+                                .eq(model.range(bounds).rev().take(50)),
+                            "range rev: {}",
+                            at()
+                        );
+                    } else {
+                        assert_eq!(tree.range(bounds).count(), 0, "empty range: {}", at());
+                    }
+                }
+                _ => match rng.below(4) {
+                    0 => assert_eq!(tree.pop_first(), model.pop_first(), "pop_first: {}", at()),
+                    1 => assert_eq!(tree.pop_last(), model.pop_last(), "pop_last: {}", at()),
+                    2 => assert_eq!(tree.first(), model.first(), "first: {}", at()),
+                    _ => assert_eq!(tree.last(), model.last(), "last: {}", at()),
+                },
+            }
+            assert_eq!(tree.len(), model.len(), "len: {}", at());
+
+            if step % config.validate_every == 0 {
+                assert_eq!(tree.validate(), Ok(()), "validate: {}", at());
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()), "seed {:#x}", config.seed);
+        assert!(tree.iter().eq(model.iter()), "seed {:#x}", config.seed);
+    }
+
+    #[test]
+    fn test_model_small_key_range() {
+        // keys collide all the time and the tree stays small, so the root
+        // keeps growing and shrinking through every deletion case
+        for degree in 2..=4 {
+            run(Config {
+                seed: 0x9e3779b97f4a7c15 + degree as u64,
+                degree,
+                key_range: 40,
+                steps: 20_000,
+                validate_every: 1,
+            });
+        }
+    }
+
+    #[test]
+    fn test_model_large_key_range() {
+        for degree in [2, 3, 5, 8] {
+            run(Config {
+                seed: 0xbf58476d1ce4e5b9 ^ degree as u64,
+                degree,
+                key_range: 5_000,
+                steps: 30_000,
+                validate_every: 500,
+            });
+        }
+    }
+
+    #[test]
+    fn test_model_many_seeds() {
+        for seed in 1..=40u64 {
+            run(Config {
+                seed: seed.wrapping_mul(0x94d049bb133111eb),
+                degree: 2 + seed as usize % 3,
+                key_range: 300,
+                steps: 2_000,
+                validate_every: 50,
+            });
+        }
+    }
+}
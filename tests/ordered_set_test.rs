@@ -0,0 +1,87 @@
+mod common;
+
+use btree::bplus::BPlusTree;
+use btree::btree::Btree;
+use btree::ordered_set::OrderedSet;
+use common::Rng;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the suite every implementation has to pass, written once against the trait
+    fn suite<S: OrderedSet<u32>>(mut make: impl FnMut() -> S) {
+        let mut set = make();
+        assert!(set.is_empty());
+        assert_eq!(set.iter().next(), None);
+        assert_eq!(set.range(0..10).next(), None);
+        assert!(!set.remove(&1));
+
+        for key in [5, 1, 9, 3, 7] {
+            assert!(set.insert(key));
+        }
+        assert!(!set.insert(3));
+        assert_eq!(set.len(), 5);
+        assert!(set.contains(&9));
+        assert!(!set.contains(&4));
+        assert!(set.iter().copied().eq([1, 3, 5, 7, 9]));
+        assert!(set.range(3..7).copied().eq([3, 5]));
+        assert!(set.range(3..=7).copied().eq([3, 5, 7]));
+        assert!(set.range(..4).copied().eq([1, 3]));
+        assert!(set.range(8..).copied().eq([9]));
+        assert!(set.remove(&5));
+        assert!(!set.contains(&5));
+
+        // the same random workload against a model
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let mut set = make();
+        let mut model = Vec::new();
+        for _ in 0..5_000 {
+            let key = (rng.next() % 500) as u32;
+            match rng.next() % 3 {
+                0 | 1 => {
+                    let missing = !model.contains(&key);
+                    assert_eq!(set.insert(key), missing);
+                    if missing {
+                        model.push(key);
+                    }
+                }
+                _ => {
+                    let stored = model.contains(&key);
+                    assert_eq!(set.remove(&key), stored);
+                    model.retain(|&other| other != key);
+                }
+            }
+        }
+        model.sort_unstable();
+        assert_eq!(set.len(), model.len());
+        assert!(set.iter().eq(model.iter()));
+        for start in (0..500).step_by(37) {
+            let expected = model
+                .iter()
+                .filter(|&&key| (start..start + 50).contains(&key));
+            assert!(set.range(start..start + 50).eq(expected));
+        }
+        assert!(model.iter().all(|key| set.contains(key)));
+    }
+
+    #[test]
+    fn test_btree() {
+        for degree in 2..=5 {
+            suite(|| Btree::new(degree));
+        }
+    }
+
+    #[test]
+    fn test_bplus_tree() {
+        for degree in 2..=5 {
+            suite(|| BPlusTree::new(degree));
+        }
+    }
+
+    #[test]
+    fn test_std_btree_set() {
+        suite(BTreeSet::new);
+    }
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use btree::btree::{Btree, PackedBtree};
+use common::Rng;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the same random operations against a PackedBtree and std's BTreeSet,
+    // keys drawn by pick so the widths inside nodes vary from run to run
+    fn run(seed: u64, degree: usize, steps: usize, pick: impl Fn(&mut Rng) -> u64) {
+        let mut rng = Rng(seed);
+        let mut tree = PackedBtree::new(degree);
+        let mut model = BTreeSet::new();
+        for step in 0..steps {
+            let key = pick(&mut rng);
+            match rng.next() % 8 {
+                0..=3 => assert_eq!(
+                    tree.insert(key),
+                    model.insert(key),
+                    "insert at step {}",
+                    step
+                ),
+                4..=5 => assert_eq!(
+                    tree.delete(&key),
+                    model.remove(&key),
+                    "delete at step {}",
+                    step
+                ),
+                6 => assert_eq!(
+                    tree.contains(&key),
+                    model.contains(&key),
+                    "contains at step {}",
+                    step
+                ),
+                _ => {
+                    let end = key.saturating_add(rng.next() % 64);
+                    assert!(
+                        tree.range(key..end).eq(model.range(key..end).copied()),
+                        "range at step {}",
+                        step
+                    );
+                }
+            }
+            if step % 100 == 0 {
+                assert_eq!(tree.validate(), Ok(()), "step {}", step);
+            }
+        }
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.len(), model.len());
+        assert!(tree.iter().eq(model.iter().copied()));
+        assert_eq!(tree.first(), model.first().copied());
+        assert_eq!(tree.last(), model.last().copied());
+    }
+
+    #[test]
+    fn test_packed_model() {
+        for degree in [2, 3, 8, 32] {
+            // dense keys, a handful of bits per node
+            run(0x9e3779b97f4a7c15 ^ degree as u64, degree, 6_000, |rng| {
+                rng.next() % 2_000
+            });
+            // sparse keys near the top of the range, full 64 bit distances
+            run(
+                0xbf58476d1ce4e5b9 ^ degree as u64,
+                degree,
+                3_000,
+                |rng| match rng.next() % 3 {
+                    0 => u64::MAX - rng.next() % 500,
+                    1 => rng.next() % 500,
+                    _ => rng.next(),
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_packed_edges() {
+        let mut tree = PackedBtree::new(2);
+        for key in [u32::MAX, 0, 1, u32::MAX - 1, 1 << 31] {
+            assert!(tree.insert(key));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(!tree.insert(0));
+        assert!(tree.iter().eq([0, 1, 1 << 31, u32::MAX - 1, u32::MAX]));
+        let bounds = (Bound::Excluded(1), Bound::Included(u32::MAX - 1));
+        assert!(tree.range(bounds).eq([1 << 31, u32::MAX - 1]));
+        assert!(tree.range(5..5).eq([]));
+        assert!(!tree.contains(&2));
+        for key in [0, u32::MAX, 1 << 31, 1, u32::MAX - 1] {
+            assert!(tree.delete(&key));
+            assert!(!tree.delete(&key));
+            assert_eq!(tree.validate(), Ok(()));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_packed_memory() {
+        // sequential keys leave a few bits per key, what's left is the nodes
+        // themselves, which weigh less the more keys they hold
+        for (degree, factor) in [(8, 1), (32, 3), (128, 8)] {
+            let mut packed = PackedBtree::new(degree);
+            let mut plain = Btree::new(degree);
+            for key in 0..50_000u64 {
+                packed.insert(key);
+                plain.insert(key);
+            }
+            assert!(packed.iter().eq(plain.iter().copied()));
+            assert!(
+                packed.memory_usage() * factor < plain.memory_usage(),
+                "degree {}: packed {} bytes, plain {} bytes",
+                degree,
+                packed.memory_usage(),
+                plain.memory_usage()
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use btree::btree::Btree;
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Insert(u16),
+        Delete(u16),
+    }
+
+    /*
+     ** a tree is generated as a degree plus the operations that built it, so a
+     ** failing case shrinks the way the operations do: proptest drops ops and
+     ** pulls keys and the degree down until the smallest sequence that still
+     ** fails is left
+     ** keys come from a narrow range most of the time so deletes actually hit
+     */
+    fn ops() -> impl Strategy<Value = Vec<Op>> {
+        let key = prop_oneof![3 => 0..64u16, 1 => any::<u16>()];
+        let op = prop_oneof![
+            3 => key.clone().prop_map(Op::Insert),
+            2 => key.prop_map(Op::Delete),
+        ];
+        prop::collection::vec(op, 0..400)
+    }
+
+    fn tree_and_model() -> impl Strategy<Value = (usize, Vec<Op>)> {
+        (2..=16usize, ops())
+    }
+
+    fn build(degree: usize, ops: &[Op]) -> (Btree<u16>, BTreeSet<u16>) {
+        let mut tree = Btree::new(degree);
+        let mut model = BTreeSet::new();
+        for op in ops {
+            match op {
+                Op::Insert(key) => {
+                    tree.insert(*key);
+                    model.insert(*key);
+                }
+                Op::Delete(key) => {
+                    tree.delete(key);
+                    model.remove(key);
+                }
+            }
+        }
+        (tree, model)
+    }
+
+    proptest! {
+        #[test]
+        fn prop_contents_match_model((degree, ops) in tree_and_model()) {
+            let mut tree = Btree::new(degree);
+            let mut model = BTreeSet::new();
+            // the return values have to agree at every step, not just the end
+            for op in &ops {
+                match op {
+                    Op::Insert(key) => prop_assert_eq!(tree.insert(*key), model.insert(*key)),
+                    Op::Delete(key) => prop_assert_eq!(tree.delete(key), model.remove(key)),
+                }
+            }
+            prop_assert_eq!(tree.len(), model.len());
+            prop_assert!(tree.iter().eq(model.iter()));
+        }
+
+        #[test]
+        fn prop_invariants_hold((degree, ops) in tree_and_model()) {
+            let (tree, _) = build(degree, &ops);
+            prop_assert_eq!(tree.validate(), Ok(()));
+        }
+
+        #[test]
+        fn prop_iteration_is_sorted((degree, ops) in tree_and_model()) {
+            let (tree, _) = build(degree, &ops);
+            let keys: Vec<u16> = tree.iter().copied().collect();
+            prop_assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+            let mut backwards: Vec<u16> = tree.iter().rev().copied().collect();
+            backwards.reverse();
+            prop_assert_eq!(backwards, keys);
+        }
+
+        #[test]
+        fn prop_deleted_keys_are_gone((degree, ops) in tree_and_model(), key in 0..64u16) {
+            let (mut tree, _) = build(degree, &ops);
+            tree.delete(&key);
+            prop_assert!(!tree.search(&key));
+            prop_assert!(!tree.delete(&key));
+            prop_assert_eq!(tree.validate(), Ok(()));
+        }
+    }
+}
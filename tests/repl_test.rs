@@ -0,0 +1,85 @@
+use btree::repl::{Command, Session, parse};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // feed a script through a session and hand back everything it wrote
+    fn run_script(script: &str) -> String {
+        let mut out = Vec::new();
+        Session::new(2)
+            .unwrap()
+            .run(script.as_bytes(), &mut out, false)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse("insert 1 -2 3"),
+            Ok(Some(Command::Insert(vec![1, -2, 3])))
+        );
+        assert_eq!(parse("  delete 7 "), Ok(Some(Command::Delete(vec![7]))));
+        assert_eq!(parse("search 4"), Ok(Some(Command::Search(4))));
+        assert_eq!(parse("range 1 9"), Ok(Some(Command::Range(1, 9))));
+        assert_eq!(parse("degree 5"), Ok(Some(Command::Degree(5))));
+        assert_eq!(
+            parse("load keys.txt"),
+            Ok(Some(Command::Load("keys.txt".to_string())))
+        );
+        assert_eq!(parse("stats"), Ok(Some(Command::Stats)));
+        assert_eq!(parse("exit"), Ok(Some(Command::Quit)));
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse("# a comment"), Ok(None));
+
+        assert!(parse("insert").is_err());
+        assert!(parse("insert 1 x").is_err());
+        assert!(parse("search 1 2").is_err());
+        assert!(parse("range 1").is_err());
+        assert!(parse("print now").is_err());
+        assert!(parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_session_script() {
+        let out = run_script(
+            "insert 1 2 3 4\n\
+             insert 2\n\
+             # comment\n\
+             search 3\n\
+             delete 3 10\n\
+             range 2 4\n\
+             pretty\n\
+             bogus\n\
+             validate\n\
+             quit\n\
+             insert 5\n",
+        );
+        let expected = "\
+inserted 1
+inserted 2
+inserted 3
+inserted 4
+2 is already in the tree
+found 3
+deleted 3
+10 is not in the tree
+[2, 4]
+[1 2 4]
+error: unknown command: bogus (try help)
+ok
+";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn test_session_degree_and_stats() {
+        let out = run_script("insert 1 2 3\ndegree 1\ndegree 4\nstats\nprint\n");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[3], "error: degree must be atleast 2, got 1");
+        assert_eq!(lines[4], "new empty tree with degree 4");
+        assert!(lines[5].starts_with("keys: 0, height: 0, nodes: 0, degree: 4"));
+        assert_eq!(lines[6], "Empty tree");
+    }
+}
@@ -0,0 +1,92 @@
+use btree::btree::{Btree, SharedBtree};
+use btree::error::Poisoned;
+use std::thread;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threads_hammering() {
+        let shared = SharedBtree::new(3);
+        let writers: Vec<_> = (0..12u32)
+            .map(|t| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    // every thread owns the keys congruent to it mod 12, and
+                    // ends up keeping the even ones of those
+                    for i in 0..2_000 {
+                        let key = i * 12 + t;
+                        assert_eq!(shared.insert(key), Ok(true));
+                        assert_eq!(shared.insert(key), Ok(false));
+                        if i % 2 == 1 {
+                            assert_eq!(shared.delete(&(key + 12)), Ok(false));
+                            assert_eq!(shared.delete(&key), Ok(true));
+                        }
+                        assert_eq!(shared.contains(&key), Ok(i % 2 == 0));
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let keys = shared.snapshot_vec().unwrap();
+                        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+                        let range = shared.range_vec(100..200).unwrap();
+                        assert!(range.iter().all(|key| (100..200).contains(key)));
+                        shared
+                            .with_read(|tree| assert_eq!(tree.validate(), Ok(())))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        let expected: Vec<u32> = (0..24_000).filter(|key| (key / 12) % 2 == 0).collect();
+        assert_eq!(shared.len(), Ok(12_000));
+        assert_eq!(shared.snapshot_vec(), Ok(expected));
+        assert_eq!(shared.range_vec(20..30), Ok(vec![24, 25, 26, 27, 28, 29]));
+        assert_eq!(shared.with_write(|tree| tree.pop_first()), Ok(Some(0)));
+        assert_eq!(shared.with_read(|tree| tree.validate()), Ok(Ok(())));
+    }
+
+    #[test]
+    fn test_poisoning() {
+        let shared = SharedBtree::from(Btree::new(2));
+        for key in 0..10 {
+            shared.insert(key).unwrap();
+        }
+        let handle = shared.clone();
+        let panicked = thread::spawn(move || {
+            handle
+                .with_write(|tree| {
+                    tree.delete(&3);
+                    panic!("half way through");
+                })
+                .unwrap();
+        })
+        .join();
+        assert!(panicked.is_err());
+
+        assert!(shared.is_poisoned());
+        assert_eq!(shared.insert(10), Err(Poisoned));
+        assert_eq!(shared.contains(&4), Err(Poisoned));
+        assert_eq!(shared.len(), Err(Poisoned));
+        assert_eq!(shared.snapshot_vec(), Err(Poisoned));
+        assert_eq!(shared.with_read(|_| ()), Err(Poisoned));
+
+        // the delete before the panic went through, the tree is fine
+        shared.clear_poison();
+        assert!(!shared.is_poisoned());
+        assert_eq!(shared.contains(&3), Ok(false));
+        assert_eq!(shared.len(), Ok(9));
+        assert_eq!(shared.insert(10), Ok(true));
+        assert_eq!(shared.with_read(|tree| tree.validate()), Ok(Ok(())));
+    }
+}
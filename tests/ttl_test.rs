@@ -0,0 +1,110 @@
+use btree::btree::TtlMap;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a clock reading the time the test sets by hand
+    fn clock(now: &Rc<Cell<Instant>>) -> impl Fn() -> Instant + use<> {
+        let now = Rc::clone(now);
+        move || now.get()
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_lazy_expiry() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let mut map = TtlMap::with_clock(2, clock(&now));
+        map.insert_with_ttl(1, "one", secs(10));
+        map.insert_with_ttl(2, "two", secs(20));
+        map.insert(3, "three");
+        assert_eq!(map.get(&1), Some(&"one"));
+        assert_eq!(map.expires_at(&3), Some(None));
+        assert_eq!(map.expires_at(&1), Some(Some(now.get() + secs(10))));
+
+        now.set(now.get() + secs(10));
+        assert_eq!(map.get(&1), None);
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.expires_at(&1), None);
+        assert_eq!(map.get(&2), Some(&"two"));
+        assert!(map.iter().eq([(&2, &"two"), (&3, &"three")]));
+        // still stored until something takes it out
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.get_or_evict(&1), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get_or_evict(&2), Some(&"two"));
+        assert_eq!(map.len(), 2);
+
+        now.set(now.get() + secs(1_000_000));
+        assert_eq!(map.get(&2), None);
+        assert_eq!(map.get(&3), Some(&"three"));
+        // an expired value isn't handed back by remove
+        assert_eq!(map.remove(&2), None);
+        assert_eq!(map.remove(&3), Some("three"));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let mut map = TtlMap::with_clock(3, clock(&now));
+        let start = now.get();
+        for key in 0..1_000u32 {
+            map.insert_with_ttl(key, "value", secs(u64::from(key % 10) + 1));
+        }
+        map.insert(5_000, "forever");
+        assert_eq!(map.purge_expired(start), 0);
+        assert_eq!(map.len(), 1_001);
+
+        assert_eq!(map.purge_expired(start + secs(3)), 300);
+        assert_eq!(map.len(), 701);
+        assert_eq!(map.validate(), Ok(()));
+        assert!(map.iter().all(|(key, _)| *key >= 5_000 || key % 10 >= 3));
+        assert_eq!(map.purge_expired(start + secs(3)), 0);
+
+        // purging goes by the instant passed, not the clock
+        now.set(start + secs(100));
+        assert_eq!(map.iter().count(), 1);
+        assert_eq!(map.purge_expired(start + secs(5)), 200);
+        assert_eq!(map.purge_expired(now.get()), 500);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&5_000), Some(&"forever"));
+        assert_eq!(map.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_reinsert_refreshes_ttl() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let mut map = TtlMap::with_clock(2, clock(&now));
+        let start = now.get();
+        assert_eq!(map.insert_with_ttl(1, "first", secs(10)), None);
+
+        now.set(start + secs(8));
+        assert_eq!(map.insert_with_ttl(1, "second", secs(10)), Some("first"));
+        now.set(start + secs(15));
+        assert_eq!(map.get(&1), Some(&"second"));
+        assert_eq!(map.purge_expired(now.get()), 0);
+
+        // replacing an expired value hands nothing back
+        now.set(start + secs(18));
+        assert_eq!(map.insert_with_ttl(1, "third", secs(10)), None);
+        assert_eq!(map.get(&1), Some(&"third"));
+
+        // insert without a ttl makes it stay
+        assert_eq!(map.insert(1, "fourth"), Some("third"));
+        assert_eq!(map.purge_expired(start + secs(1_000)), 0);
+        assert_eq!(map.get(&1), Some(&"fourth"));
+
+        // a ttl past what an Instant can hold never runs out
+        map.insert_with_ttl(2, "long", Duration::MAX);
+        assert_eq!(map.expires_at(&2), Some(None));
+        assert_eq!(map.len(), 2);
+    }
+}